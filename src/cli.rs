@@ -1,4 +1,5 @@
-use crate::error::{FluxError, Result};
+use crate::error::Result;
+use crate::helpers::user_input::prompt_select;
 use clap::Command;
 use colored::Colorize;
 
@@ -46,15 +47,14 @@ impl FluxCli {
                     .alias("wf")
                     .arg(
                         clap::Arg::new("name")
-                            .help("Workflow name")
-                            .required(true)
-                            .value_parser([
-                                "essential",
-                                "complete",
-                                "security",
-                                "development",
-                                "monitoring",
-                            ])
+                            .help("Workflow name (built-in or from [workflows.<name>])")
+                            .required_unless_present("list")
+                    )
+                    .arg(
+                        clap::Arg::new("list")
+                            .long("list")
+                            .help("List available workflows")
+                            .action(clap::ArgAction::SetTrue)
                     )
             )
             .subcommand(
@@ -125,39 +125,19 @@ impl FluxCli {
 
     /// Interactive module selection
     pub fn select_module(modules: &[String]) -> Result<String> {
-        use dialoguer::{theme::ColorfulTheme, Select};
-        
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select a module to load")
-            .items(modules)
-            .default(0)
-            .interact().map_err(|e| FluxError::system(format!("Selection failed: {}", e)))?;
+        let selection = prompt_select("Select a module to load", modules, 0)?;
         
         Ok(modules[selection].clone())
     }
 
-    /// Interactive workflow selection
-    pub fn select_workflow() -> Result<String> {
-        use dialoguer::{theme::ColorfulTheme, Select};
-        
-        let workflows = vec![
-            ("essential", "Basic system setup (update, certs, sysctl, ssh)"),
-            ("complete", "Full system configuration"),
-            ("security", "Security hardening workflow"),
-            ("development", "Development environment setup"),
-            ("monitoring", "Monitoring tools installation"),
-        ];
-        
+    /// Interactive workflow selection from `(name, description)` pairs
+    pub fn select_workflow(workflows: &[(&str, &str)]) -> Result<String> {
         let items: Vec<String> = workflows
             .iter()
             .map(|(name, desc)| format!("{:<12} - {}", name, desc))
             .collect();
         
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select a workflow to execute")
-            .items(&items)
-            .default(0)
-            .interact().map_err(|e| FluxError::system(format!("Selection failed: {}", e)))?;
+        let selection = prompt_select("Select a workflow to execute", &items, 0)?;
         
         Ok(workflows[selection].0.to_string())
     }
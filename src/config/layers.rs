@@ -0,0 +1,539 @@
+//! Layered configuration loading
+//!
+//! The effective configuration is merged from these layers, later ones
+//! overriding earlier ones key by key:
+//!
+//! 1. built-in defaults
+//! 2. `/etc/flux/flux.toml`
+//! 3. `/etc/flux/conf.d/*.toml`, in file name order
+//! 4. the user file, `~/.config/flux/flux.toml`
+//! 5. `/etc/flux/hosts/<hostname>.toml`
+//! 6. the profile selected with `--profile <name>`, `profiles/<name>.toml` in
+//!    `/etc/flux` or the user config directory
+//! 7. `FLUX_*` environment variables
+//!
+//! `--config <file>` replaces layers 2 to 5 with that single file. Tables are
+//! merged recursively; arrays and plain values are replaced as a whole.
+
+use super::Config;
+use crate::error::{FluxError, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the system-wide configuration
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/flux";
+
+/// Prefix of configuration environment variables
+pub const ENV_PREFIX: &str = "FLUX_";
+
+/// Sections that environment variables may address
+const SECTIONS: &[&str] = &["general", "modules", "workflows", "custom"];
+
+/// How the configuration is located
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Single file replacing the system, user and host files (`--config`)
+    pub file: Option<PathBuf>,
+    /// Profile applied on top of the files (`--profile`)
+    pub profile: Option<String>,
+}
+
+/// Where a layer came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum LayerSource {
+    Default,
+    System { path: PathBuf },
+    User { path: PathBuf },
+    Host { path: PathBuf },
+    Profile { name: String, path: PathBuf },
+    /// An explicit `--config` file
+    File { path: PathBuf },
+    Environment { variable: String },
+}
+
+impl LayerSource {
+    /// The file of a file layer
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            LayerSource::System { path }
+            | LayerSource::User { path }
+            | LayerSource::Host { path }
+            | LayerSource::Profile { path, .. }
+            | LayerSource::File { path } => Some(path),
+            LayerSource::Default | LayerSource::Environment { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LayerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerSource::Default => write!(f, "built-in default"),
+            LayerSource::System { path } => write!(f, "system file {}", path.display()),
+            LayerSource::User { path } => write!(f, "user file {}", path.display()),
+            LayerSource::Host { path } => write!(f, "host file {}", path.display()),
+            LayerSource::Profile { name, path } => {
+                write!(f, "profile '{}' ({})", name, path.display())
+            }
+            LayerSource::File { path } => write!(f, "file {}", path.display()),
+            LayerSource::Environment { variable } => write!(f, "environment variable {}", variable),
+        }
+    }
+}
+
+/// One source of configuration values
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub source: LayerSource,
+    pub values: toml::Table,
+}
+
+/// A value supplied for a key by one layer
+#[derive(Debug, Clone, Serialize)]
+pub struct Origin {
+    pub source: LayerSource,
+    pub value: toml::Value,
+}
+
+/// The configuration layers, lowest precedence first
+#[derive(Debug, Clone)]
+pub struct Layers {
+    layers: Vec<Layer>,
+}
+
+impl Layers {
+    /// Collect the layers from the standard locations and the process environment
+    pub fn discover(options: &LoadOptions) -> Result<Self> {
+        let mut layers = Self::defaults()?;
+        for source in file_sources(options)? {
+            layers.push_file(source)?;
+        }
+        layers.push_env(std::env::vars());
+        Ok(layers)
+    }
+
+    /// Collect the layers below the given directories
+    pub fn collect(
+        system_dir: &Path,
+        user_dir: Option<&Path>,
+        hostname: Option<&str>,
+        options: &LoadOptions,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        let mut layers = Self::defaults()?;
+        for source in sources_in(system_dir, user_dir, hostname, options)? {
+            layers.push_file(source)?;
+        }
+        layers.push_env(env);
+        Ok(layers)
+    }
+
+    fn defaults() -> Result<Self> {
+        match toml::Value::try_from(Config::default()) {
+            Ok(toml::Value::Table(values)) => Ok(Self {
+                layers: vec![Layer {
+                    source: LayerSource::Default,
+                    values,
+                }],
+            }),
+            _ => Err(FluxError::config("Failed to serialize the default configuration")),
+        }
+    }
+
+    fn push_file(&mut self, source: LayerSource) -> Result<()> {
+        let Some(path) = source.path() else {
+            return Ok(());
+        };
+
+        let contents = fs::read_to_string(path).map_err(|e| {
+            FluxError::config(format!("Failed to read config file {}: {}", path.display(), e))
+        })?;
+        // Each file must be valid on its own so errors point at the right file
+        toml::from_str::<Config>(&contents)
+            .map_err(|e| FluxError::config(format!("Invalid config file {}: {}", path.display(), e)))?;
+        let values = toml::from_str(&contents)
+            .map_err(|e| FluxError::config(format!("Invalid config file {}: {}", path.display(), e)))?;
+
+        self.layers.push(Layer { source, values });
+        Ok(())
+    }
+
+    /// Add a layer for every `FLUX_*` variable that names a setting, sorted by name
+    fn push_env(&mut self, env: impl IntoIterator<Item = (String, String)>) {
+        let mut env: Vec<(String, String)> = env
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        env.sort();
+
+        for (variable, value) in env {
+            if let Some(values) = self.env_layer(&variable, &value) {
+                self.layers.push(Layer {
+                    source: LayerSource::Environment { variable },
+                    values,
+                });
+            }
+        }
+    }
+
+    /// Turn `FLUX_<KEY>` into a layer, `None` for variables that are not settings
+    ///
+    /// `FLUX_DEFAULT_SSH_PORT` sets `general.default_ssh_port`; nested keys are
+    /// separated by double underscores, as in `FLUX_MODULES__SSH__PORT`.
+    fn env_layer(&self, variable: &str, value: &str) -> Option<toml::Table> {
+        let name = variable.strip_prefix(ENV_PREFIX)?.to_lowercase();
+        let mut path: Vec<String> = name.split("__").map(str::to_string).collect();
+
+        if path.len() == 1 {
+            // Other FLUX_* variables, such as FLUX_ROOT, are command-line options
+            if !super::GENERAL_KEYS.contains(&path[0].as_str()) {
+                return None;
+            }
+            path.insert(0, "general".to_string());
+        } else if !SECTIONS.contains(&path[0].as_str()) || path.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+
+        let key = path.join(".");
+        let value = match self.value(&key) {
+            // Strings stay strings, so FLUX_GITHUB_USER=1234 is not a number
+            Some(toml::Value::String(_)) => toml::Value::String(value.to_string()),
+            None if path[0] == "general" => toml::Value::String(value.to_string()),
+            _ => super::parse_value(value),
+        };
+
+        let mut values = toml::Table::new();
+        insert(&mut values, &key, value).ok()?;
+        Some(values)
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// All layers merged into one table
+    pub fn merged(&self) -> toml::Table {
+        let mut merged = toml::Table::new();
+        for layer in &self.layers {
+            merge(&mut merged, &layer.values);
+        }
+        merged
+    }
+
+    /// The effective configuration
+    pub fn config(&self) -> Result<Config> {
+        toml::Value::Table(self.merged()).try_into().map_err(|e| {
+            FluxError::config(format!(
+                "Invalid configuration after applying environment variables: {}",
+                e
+            ))
+        })
+    }
+
+    /// Merged value of a dotted key such as `general.mode` or `modules.ssh.port`
+    pub fn value(&self, key: &str) -> Option<toml::Value> {
+        self.explain(key).pop().map(|origin| origin.value)
+    }
+
+    /// Every layer that sets `key`, lowest precedence first
+    ///
+    /// The last entry supplied the effective value. Tables are reported per
+    /// layer, without the keys of other layers.
+    pub fn explain(&self, key: &str) -> Vec<Origin> {
+        self.layers
+            .iter()
+            .filter_map(|layer| {
+                lookup(&layer.values, key).map(|value| Origin {
+                    source: layer.source.clone(),
+                    value: value.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Contents of the configuration file at `path` with `key` set to `value`
+///
+/// Only that key changes, so values the file leaves to other layers stay
+/// unset. A missing file is treated as empty.
+pub fn file_with_key(path: &Path, key: &str, value: &str) -> Result<String> {
+    let mut table: toml::Table = if path.exists() {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            FluxError::config(format!("Failed to read config file {}: {}", path.display(), e))
+        })?;
+        toml::from_str(&contents)
+            .map_err(|e| FluxError::config(format!("Invalid config file {}: {}", path.display(), e)))?
+    } else {
+        toml::Table::new()
+    };
+
+    // Config::set checks and converts the value; copy back just the one key
+    let mut config: Config = toml::Value::Table(table.clone())
+        .try_into()
+        .map_err(|e| FluxError::config(format!("Invalid config file {}: {}", path.display(), e)))?;
+    config.set(key, value)?;
+    let updated = toml::Value::try_from(&config)
+        .map_err(|e| FluxError::config(format!("Failed to serialize config: {}", e)))?;
+
+    let key = Config::key_path(key);
+    let value = updated
+        .as_table()
+        .and_then(|t| lookup(t, &key))
+        .cloned()
+        .ok_or_else(|| FluxError::config(format!("Failed to set {}", key)))?;
+    insert(&mut table, &key, value)?;
+
+    toml::to_string_pretty(&table)
+        .map_err(|e| FluxError::config(format!("Failed to serialize config: {}", e)))
+}
+
+/// The configuration files that exist in the standard locations, lowest precedence first
+pub fn file_sources(options: &LoadOptions) -> Result<Vec<LayerSource>> {
+    let user_dir = Config::default_dir().ok();
+    let hostname = crate::helpers::system::get_hostname().ok();
+    sources_in(
+        Path::new(SYSTEM_CONFIG_DIR),
+        user_dir.as_deref(),
+        hostname.as_deref(),
+        options,
+    )
+}
+
+fn sources_in(
+    system_dir: &Path,
+    user_dir: Option<&Path>,
+    hostname: Option<&str>,
+    options: &LoadOptions,
+) -> Result<Vec<LayerSource>> {
+    let mut sources = Vec::new();
+
+    match &options.file {
+        Some(path) => sources.push(LayerSource::File { path: path.clone() }),
+        None => {
+            sources.push(LayerSource::System {
+                path: system_dir.join("flux.toml"),
+            });
+            for path in conf_d_files(&system_dir.join("conf.d"))? {
+                sources.push(LayerSource::System { path });
+            }
+            if let Some(dir) = user_dir {
+                sources.push(LayerSource::User {
+                    path: dir.join("flux.toml"),
+                });
+            }
+            if let Some(hostname) = hostname {
+                let short = hostname.split('.').next().unwrap_or(hostname);
+                sources.push(LayerSource::Host {
+                    path: system_dir.join("hosts").join(format!("{}.toml", short)),
+                });
+            }
+            // Only an explicit --config file has to exist
+            sources.retain(|source| source.path().is_some_and(Path::is_file));
+        }
+    }
+
+    if let Some(name) = &options.profile {
+        let mut dirs = vec![system_dir];
+        dirs.extend(user_dir);
+        let path = dirs
+            .iter()
+            .map(|dir| dir.join("profiles").join(format!("{}.toml", name)))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                FluxError::not_found(format!(
+                    "Profile '{}' not found in {}",
+                    name,
+                    dirs.iter()
+                        .map(|d| d.join("profiles").display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                ))
+            })?;
+        sources.push(LayerSource::Profile {
+            name: name.clone(),
+            path,
+        });
+    }
+
+    Ok(sources)
+}
+
+/// `*.toml` files in `dir`, sorted by name
+fn conf_d_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| FluxError::config(format!("Failed to read {}: {}", dir.display(), e)))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Merge `overlay` into `base`: tables recursively, everything else replaced
+fn merge(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+/// Set a dotted key, creating the tables on the way
+fn insert(table: &mut toml::Table, key: &str, value: toml::Value) -> Result<()> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap_or(key);
+
+    let mut table = table;
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| FluxError::config(format!("{} is not a table", part)))?;
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, name: &str, contents: &str) {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_layers_merge_in_order() {
+        let system = TempDir::new().unwrap();
+        let user = TempDir::new().unwrap();
+        let root = system.path();
+        write(root, "flux.toml", "[general]\ndefault_ssh_port = 2200\nlog_level = \"warn\"\n\n[modules.ssh]\nport = 2200\nciphers = [\"aes256-ctr\"]\n");
+        write(root, "conf.d/20-late.toml", "[modules.ssh]\nport = 2220\n");
+        write(root, "conf.d/10-early.toml", "[modules.ssh]\nport = 2210\nx11_forwarding = true\n");
+        write(root, "conf.d/notes.txt", "ignored");
+        write(root, "hosts/web1.toml", "[general]\ndefault_admin_user = \"webadmin\"\n");
+        write(user.path(), "profiles/prod.toml", "[general]\nmode = \"auto\"\n");
+
+        let options = LoadOptions {
+            file: None,
+            profile: Some("prod".to_string()),
+        };
+        let layers = Layers::collect(
+            root,
+            Some(user.path()),
+            Some("web1.example.com"),
+            &options,
+            env(&[
+                ("FLUX_DEFAULT_SSH_PORT", "2022"),
+                ("FLUX_MODULES__SSH__MAX_SESSIONS", "4"),
+                ("FLUX_GITHUB_USER", "1234"),
+                ("FLUX_ROOT", "/mnt"),
+                ("HOME", "/root"),
+            ]),
+        )
+        .unwrap();
+        let config = layers.config().unwrap();
+
+        assert_eq!(config.general.default_ssh_port, 2022);
+        assert_eq!(config.general.log_level, "warn");
+        assert_eq!(config.general.default_admin_user, "webadmin");
+        assert_eq!(config.general.github_user.as_deref(), Some("1234"));
+        assert_eq!(config.general.mode, crate::config::RunMode::Auto);
+
+        let ssh = &config.modules["ssh"];
+        assert_eq!(ssh["port"].as_integer(), Some(2220));
+        assert_eq!(ssh["x11_forwarding"].as_bool(), Some(true));
+        assert_eq!(ssh["max_sessions"].as_integer(), Some(4));
+        assert_eq!(ssh["ciphers"].as_array().unwrap().len(), 1);
+
+        let origins = layers.explain("modules.ssh.port");
+        let files: Vec<String> = origins
+            .iter()
+            .map(|o| o.source.path().unwrap().file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec!["flux.toml", "10-early.toml", "20-late.toml"]);
+
+        let port = layers.explain("general.default_ssh_port");
+        assert_eq!(port.first().unwrap().source, LayerSource::Default);
+        assert_eq!(
+            port.last().unwrap().source,
+            LayerSource::Environment {
+                variable: "FLUX_DEFAULT_SSH_PORT".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_explicit_file_replaces_file_layers() {
+        let system = TempDir::new().unwrap();
+        write(system.path(), "flux.toml", "[general]\ndefault_admin_user = \"system\"\n");
+        write(system.path(), "other.toml", "[general]\nlog_level = \"debug\"\n");
+
+        let options = LoadOptions {
+            file: Some(system.path().join("other.toml")),
+            profile: None,
+        };
+        let layers = Layers::collect(system.path(), None, None, &options, Vec::new()).unwrap();
+        let config = layers.config().unwrap();
+        assert_eq!(config.general.default_admin_user, "fluxadmin");
+        assert_eq!(config.general.log_level, "debug");
+
+        let missing = LoadOptions {
+            file: None,
+            profile: Some("nope".to_string()),
+        };
+        assert!(Layers::collect(system.path(), None, None, &missing, Vec::new()).is_err());
+
+        write(system.path(), "conf.d/bad.toml", "[general]\nport = 1\n");
+        let err = Layers::collect(system.path(), None, None, &LoadOptions::default(), Vec::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("bad.toml"));
+    }
+
+    #[test]
+    fn test_file_with_key_changes_one_key() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("flux.toml");
+        write(dir.path(), "flux.toml", "[general]\nlog_level = \"warn\"\n");
+
+        let contents = file_with_key(&path, "default_ssh_port", "2222").unwrap();
+        let table: toml::Table = toml::from_str(&contents).unwrap();
+        assert_eq!(table["general"]["default_ssh_port"].as_integer(), Some(2222));
+        assert_eq!(table["general"]["log_level"].as_str(), Some("warn"));
+        // Defaults are left to the default layer
+        assert!(table["general"].get("default_admin_user").is_none());
+
+        let contents = file_with_key(&dir.path().join("new.toml"), "modules.ssh.port", "2200").unwrap();
+        assert_eq!(contents.trim(), "[modules.ssh]\nport = 2200");
+
+        assert!(file_with_key(&path, "default_ssh_port", "ssh").is_err());
+    }
+}
@@ -0,0 +1,494 @@
+pub mod layers;
+pub mod secret;
+
+pub use layers::{Layers, LoadOptions};
+pub use secret::Secret;
+
+use crate::error::{FluxError, Result};
+use crate::helpers::schema::{self, ConfigSchema};
+use directories::ProjectDirs;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Keys of `[general]`
+const GENERAL_KEYS: &[&str] = &[
+    "mode",
+    "default_ssh_port",
+    "default_admin_user",
+    "default_admin_groups",
+    "github_user",
+    "log_level",
+    "modules_dir",
+    "colored_output",
+    "default_gateway",
+    "default_dns",
+    "secret_key",
+    "templates_dir",
+    "groups",
+];
+
+/// Main configuration structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// General settings
+    pub general: GeneralConfig,
+    
+    /// Module-specific configurations
+    pub modules: HashMap<String, toml::Value>,
+    
+    /// User-defined workflows, keyed by workflow name
+    pub workflows: HashMap<String, WorkflowConfig>,
+    
+    /// Custom key-value pairs
+    pub custom: HashMap<String, String>,
+    
+    #[serde(skip)]
+    config_path: Option<PathBuf>,
+}
+
+/// Execution mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunMode {
+    /// Prompt user for confirmations
+    #[default]
+    Interactive,
+    /// Run automatically with minimal prompts
+    Auto,
+    /// Record what would be done without making changes
+    DryRun,
+}
+
+impl RunMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunMode::Interactive => "interactive",
+            RunMode::Auto => "auto",
+            RunMode::DryRun => "dry-run",
+        }
+    }
+}
+
+impl std::str::FromStr for RunMode {
+    type Err = FluxError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "interactive" => Ok(RunMode::Interactive),
+            "auto" => Ok(RunMode::Auto),
+            "dry-run" => Ok(RunMode::DryRun),
+            _ => Err(FluxError::validation(format!(
+                "Invalid mode '{}': expected interactive, auto, or dry-run",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneralConfig {
+    /// Execution mode
+    pub mode: RunMode,
+    
+    /// Default SSH port
+    pub default_ssh_port: u16,
+    
+    /// Default admin username
+    pub default_admin_user: String,
+    
+    /// Default admin groups
+    pub default_admin_groups: Vec<String>,
+    
+    /// GitHub username for SSH key imports
+    pub github_user: Option<String>,
+    
+    /// Log level
+    pub log_level: String,
+    
+    /// Module directory
+    pub modules_dir: Option<PathBuf>,
+    
+    /// Enable colored output
+    pub colored_output: bool,
+    
+    /// Default gateway
+    pub default_gateway: Option<String>,
+    
+    /// Default DNS servers
+    pub default_dns: Vec<String>,
+    
+    /// age identity that decrypts `{ age = "..." }` secrets
+    pub secret_key: Option<PathBuf>,
+    
+    /// Directory of templates replacing the built-in ones
+    pub templates_dir: Option<PathBuf>,
+    
+    /// Inventory groups of this host, seen by workflow `when` conditions
+    pub groups: Vec<String>,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        Self {
+            mode: RunMode::default(),
+            default_ssh_port: 22,
+            default_admin_user: "fluxadmin".to_string(),
+            default_admin_groups: vec![
+                "sudo".to_string(),
+                "adm".to_string(),
+                "systemd-journal".to_string(),
+            ],
+            github_user: None,
+            log_level: "info".to_string(),
+            modules_dir: None,
+            colored_output: true,
+            default_gateway: None,
+            default_dns: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
+            secret_key: None,
+            templates_dir: None,
+            groups: Vec::new(),
+        }
+    }
+}
+
+impl ConfigSchema for GeneralConfig {
+    fn schema() -> serde_json::Value {
+        use schema::*;
+
+        object(
+            "General settings",
+            vec![
+                ("mode", one_of("Execution mode", &["interactive", "auto", "dry-run"])),
+                ("default_ssh_port", port("Default SSH port")),
+                ("default_admin_user", string("Default admin username")),
+                ("default_admin_groups", array("Default admin groups", string(""))),
+                ("github_user", string("GitHub username for SSH key imports")),
+                ("log_level", one_of("Log level", &["debug", "info", "warn", "error"])),
+                ("modules_dir", string("Directory of external plugin modules")),
+                ("colored_output", boolean("Enable colored output")),
+                ("default_gateway", string("Default gateway")),
+                ("default_dns", array("Default DNS servers", string(""))),
+                (
+                    "secret_key",
+                    string("age identity that decrypts encrypted secrets; defaults to /etc/flux/age.key"),
+                ),
+                (
+                    "templates_dir",
+                    string("Directory of templates replacing the built-in ones; defaults to /etc/flux/templates"),
+                ),
+                (
+                    "groups",
+                    array(
+                        "Inventory groups of this host, seen by workflow `when` conditions; set from the inventory with --hosts",
+                        string(""),
+                    ),
+                ),
+            ],
+        )
+    }
+}
+
+/// A workflow defined in `[workflows.<name>]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkflowConfig {
+    /// Shown by `flux workflow --list`
+    pub description: Option<String>,
+    
+    /// Modules to run, in order
+    pub modules: Vec<String>,
+    
+    /// Arguments passed to individual modules, e.g. `ssh = ["--harden"]`
+    pub args: HashMap<String, Vec<String>>,
+    
+    /// Keep going after a module fails; unset asks the user
+    pub continue_on_error: Option<bool>,
+    
+    /// Ask before running each module; unset asks
+    pub confirm_modules: Option<bool>,
+    
+    /// Conditions per module, e.g. `netdata = "facts.memory_mb > 1024"`
+    pub when: HashMap<String, String>,
+}
+
+impl ConfigSchema for WorkflowConfig {
+    fn schema() -> serde_json::Value {
+        use schema::*;
+
+        object(
+            "A workflow run with `flux workflow <name>`",
+            vec![
+                ("description", string("Shown by `flux workflow --list`")),
+                ("modules", array("Modules to run, in order", string(""))),
+                (
+                    "args",
+                    map("Arguments passed to individual modules", array("", string(""))),
+                ),
+                ("continue_on_error", boolean("Keep going after a module fails; unset asks the user")),
+                ("confirm_modules", boolean("Ask before running each module; unset asks")),
+                (
+                    "when",
+                    map("Conditions per module; the module is skipped when false", string("")),
+                ),
+            ],
+        )
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            general: GeneralConfig::default(),
+            modules: HashMap::new(),
+            workflows: HashMap::new(),
+            custom: HashMap::new(),
+            config_path: None,
+        }
+    }
+}
+
+impl Config {
+    /// Get the default config directory path
+    pub fn default_dir() -> Result<PathBuf> {
+        if let Some(proj_dirs) = ProjectDirs::from("com", "flux", "flux-framework") {
+            Ok(proj_dirs.config_dir().to_path_buf())
+        } else {
+            // Fallback to ~/.config/flux
+            let home = dirs::home_dir()
+                .ok_or_else(|| FluxError::config("Could not determine home directory"))?;
+            Ok(home.join(".config").join("flux"))
+        }
+    }
+
+    /// Get the default config file path
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Self::default_dir()?.join("flux.toml"))
+    }
+
+    /// Load configuration from the default locations or create default
+    pub fn load_or_default() -> Result<Self> {
+        Self::load(&LoadOptions::default())
+    }
+    
+    /// Merge the configuration layers selected by `options`
+    ///
+    /// See [`layers`] for the locations and their order. The result is saved to
+    /// the `--config` file if one was given, otherwise to `/etc/flux/flux.toml`
+    /// when running as root and to the user file otherwise.
+    pub fn load(options: &LoadOptions) -> Result<Self> {
+        let mut config = Layers::discover(options)?.config()?;
+        config.config_path = Some(Self::save_path(options)?);
+        Ok(config)
+    }
+    
+    /// The file `flux config <key> <value>` writes to
+    pub fn save_path(options: &LoadOptions) -> Result<PathBuf> {
+        match &options.file {
+            Some(path) => Ok(path.clone()),
+            None if crate::helpers::system::is_root() => {
+                Ok(Path::new(layers::SYSTEM_CONFIG_DIR).join("flux.toml"))
+            }
+            None => Self::default_path(),
+        }
+    }
+
+    /// Load configuration from a specific file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| FluxError::config(format!("Failed to read config file: {}", e)))?;
+        
+        let mut config: Config = toml::from_str(&contents)
+            .map_err(|e| FluxError::config(format!("Failed to parse config file: {}", e)))?;
+        
+        config.config_path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// The file this configuration was loaded from or will be saved to
+    pub fn path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// Save configuration to file
+    pub fn save(&self) -> Result<()> {
+        let path = self.config_path.as_ref()
+            .ok_or_else(|| FluxError::config("No config path set"))?;
+        
+        // Ensure directory exists
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| FluxError::config(format!("Failed to create config directory: {}", e)))?;
+        }
+        
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| FluxError::config(format!("Failed to serialize config: {}", e)))?;
+        
+        fs::write(path, contents)
+            .map_err(|e| FluxError::config(format!("Failed to write config file: {}", e)))?;
+        
+        Ok(())
+    }
+
+    /// Get a configuration value
+    pub fn get(&self, key: &str) -> Option<String> {
+        let key = key.strip_prefix("general.").unwrap_or(key);
+        if let Some((module, field)) = module_key(key) {
+            return match self.modules.get(module)?.get(field)? {
+                toml::Value::String(s) => Some(s.clone()),
+                value => Some(value.to_string()),
+            };
+        }
+        
+        // Check custom values first
+        if let Some(value) = self.custom.get(key) {
+            return Some(value.clone());
+        }
+        
+        // Check if it's a general config key
+        match key {
+            "mode" => Some(self.general.mode.as_str().to_string()),
+            "default_ssh_port" => Some(self.general.default_ssh_port.to_string()),
+            "default_admin_user" => Some(self.general.default_admin_user.clone()),
+            "github_user" => self.general.github_user.clone(),
+            "log_level" => Some(self.general.log_level.clone()),
+            "colored_output" => Some(self.general.colored_output.to_string()),
+            "secret_key" => self.general.secret_key.as_ref().map(|p| p.display().to_string()),
+            "templates_dir" => self.general.templates_dir.as_ref().map(|p| p.display().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Set a configuration value
+    ///
+    /// `modules.<module>.<key>` sets a key of a module's table; the value is read
+    /// as TOML, falling back to a plain string. Check the result against the
+    /// module's settings with `ModuleManager::validate_config`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let key = key.strip_prefix("general.").unwrap_or(key);
+        if let Some((module, field)) = module_key(key) {
+            let value = parse_value(value);
+            let table = self
+                .modules
+                .entry(module.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let Some(table) = table.as_table_mut() else {
+                return Err(FluxError::config(format!("[modules.{}] is not a table", module)));
+            };
+            table.insert(field.to_string(), value);
+            return Ok(());
+        }
+        
+        match key {
+            "mode" => {
+                self.general.mode = value.parse()?;
+            }
+            "default_ssh_port" => {
+                self.general.default_ssh_port = value.parse()
+                    .map_err(|_| FluxError::validation("Invalid port number"))?;
+            }
+            "default_admin_user" => {
+                self.general.default_admin_user = value.to_string();
+            }
+            "github_user" => {
+                self.general.github_user = Some(value.to_string());
+            }
+            "log_level" => {
+                self.general.log_level = value.to_string();
+            }
+            "colored_output" => {
+                self.general.colored_output = value.parse()
+                    .map_err(|_| FluxError::validation("Invalid boolean value"))?;
+            }
+            "secret_key" => {
+                self.general.secret_key = Some(PathBuf::from(value));
+            }
+            "templates_dir" => {
+                self.general.templates_dir = Some(PathBuf::from(value));
+            }
+            _ => {
+                // Store in custom values
+                self.custom.insert(key.to_string(), value.to_string());
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Get all configuration values
+    pub fn all(&self) -> Vec<(String, String)> {
+        let mut values = vec![
+            ("mode".to_string(), self.general.mode.as_str().to_string()),
+            ("default_ssh_port".to_string(), self.general.default_ssh_port.to_string()),
+            ("default_admin_user".to_string(), self.general.default_admin_user.clone()),
+            ("log_level".to_string(), self.general.log_level.clone()),
+            ("colored_output".to_string(), self.general.colored_output.to_string()),
+        ];
+        
+        if let Some(github_user) = &self.general.github_user {
+            values.push(("github_user".to_string(), github_user.clone()));
+        }
+        
+        // Add custom values
+        for (k, v) in &self.custom {
+            values.push((k.clone(), v.clone()));
+        }
+        
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values
+    }
+
+    /// Get module-specific configuration
+    pub fn get_module_config(&self, module: &str) -> Option<&toml::Value> {
+        self.modules.get(module)
+    }
+
+    /// Typed settings of `[modules.<module>]`, defaults when the table is missing
+    pub fn module_settings<T: DeserializeOwned + Default>(&self, module: &str) -> Result<T> {
+        match self.modules.get(module) {
+            Some(value) => value.clone().try_into().map_err(|e| {
+                FluxError::config(format!("Invalid [modules.{}] configuration: {}", module, e))
+            }),
+            None => Ok(T::default()),
+        }
+    }
+
+    /// Set module-specific configuration
+    pub fn set_module_config(&mut self, module: &str, config: toml::Value) {
+        self.modules.insert(module.to_string(), config);
+    }
+
+    /// Dotted path of the value behind a `flux config` key
+    ///
+    /// General settings may be named with or without `general.`; other keys
+    /// outside `modules.` and `workflows.` are custom values.
+    pub fn key_path(key: &str) -> String {
+        if ["general.", "modules.", "workflows."].iter().any(|p| key.starts_with(p)) {
+            key.to_string()
+        } else if GENERAL_KEYS.contains(&key) {
+            format!("general.{}", key)
+        } else {
+            format!("custom.{}", key)
+        }
+    }
+}
+
+/// Read a value as TOML, falling back to a plain string
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Split `modules.<module>.<key>` into module and key
+fn module_key(key: &str) -> Option<(&str, &str)> {
+    key.strip_prefix("modules.")?
+        .split_once('.')
+        .filter(|(module, field)| !module.is_empty() && !field.is_empty())
+}
@@ -0,0 +1,299 @@
+//! Secret settings
+//!
+//! A secret setting such as a password holds either the value itself or a
+//! reference to where the value is kept:
+//!
+//! ```toml
+//! admin_password = { file = "/run/secrets/admin-password" }
+//! admin_password = { env = "ADMIN_PASSWORD" }
+//! admin_password = { age = """
+//! -----BEGIN AGE ENCRYPTED FILE-----
+//! ...
+//! -----END AGE ENCRYPTED FILE-----
+//! """ }
+//! ```
+//!
+//! `age` values are decrypted with the `age` tool and the identity in
+//! `general.secret_key`; `flux secret encrypt` produces them. References are
+//! only resolved when a module needs the value, and every resolved value is
+//! registered with [`redact`] so it is masked in logs, dry-run output and run
+//! reports.
+
+use super::Config;
+use crate::error::{FluxError, Result};
+use crate::helpers::redact::{self, MASK};
+use crate::helpers::runner::{self, Invocation};
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Identity used when `general.secret_key` is unset
+pub const DEFAULT_SECRET_KEY: &str = "/etc/flux/age.key";
+
+/// Keys of a secret reference table
+const REFERENCE_KEYS: &[&str] = &["file", "env", "age"];
+
+/// A secret value or a reference to one
+#[derive(Clone, PartialEq, Eq)]
+pub enum Secret {
+    /// The value itself, written in the configuration
+    Plain(String),
+    /// Contents of a file on the machine flux runs on
+    File(PathBuf),
+    /// An environment variable of the flux process
+    Env(String),
+    /// An ASCII-armored age ciphertext
+    Age(String),
+}
+
+impl Secret {
+    /// The secret value, read or decrypted as needed
+    ///
+    /// A single trailing newline is dropped from files, variables and
+    /// decrypted values. The value is registered for redaction.
+    pub fn resolve(&self, config: &Config) -> Result<String> {
+        let value = match self {
+            Secret::Plain(value) => value.clone(),
+            Secret::File(path) => {
+                let contents = fs::read_to_string(path).map_err(|e| {
+                    FluxError::config(format!("Failed to read secret file {}: {}", path.display(), e))
+                })?;
+                strip_newline(contents)
+            }
+            Secret::Env(name) => {
+                let value = std::env::var(name).map_err(|_| {
+                    FluxError::not_found(format!("Secret variable {} is not set", name))
+                })?;
+                strip_newline(value)
+            }
+            Secret::Age(ciphertext) => strip_newline(decrypt(ciphertext, &secret_key(config))?),
+        };
+
+        redact::register(&value);
+        Ok(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Secret::Plain(_) => write!(f, "Plain({})", MASK),
+            Secret::File(path) => write!(f, "File({})", path.display()),
+            Secret::Env(name) => write!(f, "Env({})", name),
+            Secret::Age(_) => write!(f, "Age({})", MASK),
+        }
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let (key, value) = match self {
+            Secret::Plain(value) => return serializer.serialize_str(value),
+            Secret::File(path) => ("file", path.to_string_lossy()),
+            Secret::Env(name) => ("env", name.as_str().into()),
+            Secret::Age(ciphertext) => ("age", ciphertext.as_str().into()),
+        };
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(key, &value)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(SecretVisitor)
+    }
+}
+
+struct SecretVisitor;
+
+impl<'de> Visitor<'de> for SecretVisitor {
+    type Value = Secret;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string or a table with one of `file`, `env` or `age`")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Secret, E> {
+        Ok(Secret::Plain(value.to_string()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Secret, A::Error> {
+        let mut secret = None;
+        while let Some(key) = map.next_key::<String>()? {
+            let value: String = map.next_value()?;
+            if secret.is_some() {
+                return Err(de::Error::custom(
+                    "a secret reference takes exactly one of `file`, `env` or `age`",
+                ));
+            }
+            secret = Some(match key.as_str() {
+                "file" => Secret::File(PathBuf::from(value)),
+                "env" => Secret::Env(value),
+                "age" => Secret::Age(value),
+                _ => return Err(de::Error::unknown_field(&key, REFERENCE_KEYS)),
+            });
+        }
+
+        secret.ok_or_else(|| de::Error::custom("empty secret reference; set `file`, `env` or `age`"))
+    }
+}
+
+/// The age identity named by `general.secret_key`
+pub fn secret_key(config: &Config) -> PathBuf {
+    config
+        .general
+        .secret_key
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SECRET_KEY))
+}
+
+/// Decrypt an armored age `ciphertext` with the identity file `key`
+pub fn decrypt(ciphertext: &str, key: &Path) -> Result<String> {
+    if !key.exists() {
+        return Err(FluxError::config(format!(
+            "Cannot decrypt secret: age identity {} not found (set general.secret_key)",
+            key.display()
+        )));
+    }
+
+    let key = key.to_string_lossy();
+    let output = runner::run_on_host(
+        &Invocation::query("age", &["--decrypt", "--identity", &key]).stdin(ciphertext),
+    )?
+    .check("age")?;
+    Ok(output.stdout)
+}
+
+/// Encrypt `plaintext` to the given age recipients as armored text
+pub fn encrypt(plaintext: &str, recipients: &[String]) -> Result<String> {
+    if recipients.is_empty() {
+        return Err(FluxError::validation("No age recipients to encrypt to"));
+    }
+
+    let mut args = vec!["--encrypt", "--armor"];
+    for recipient in recipients {
+        args.extend(["--recipient", recipient.as_str()]);
+    }
+
+    let output = runner::run_on_host(&Invocation::query("age", &args).stdin(plaintext))?
+        .check("age")?;
+    Ok(output.stdout)
+}
+
+/// Public key (recipient) of the age identity file `key`
+pub fn recipient(key: &Path) -> Result<String> {
+    let key = key.to_string_lossy();
+    let output = runner::run_on_host(&Invocation::query("age-keygen", &["-y", &key]))?
+        .check("age-keygen")?;
+
+    output
+        .stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| FluxError::config(format!("No public key in age identity {}", key)))
+}
+
+fn strip_newline(mut value: String) -> String {
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::runner::{set_runner, CommandOutput, RecordingRunner};
+    use serial_test::serial;
+    use std::sync::Arc;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Settings {
+        password: Secret,
+    }
+
+    fn parse(toml: &str) -> std::result::Result<Secret, toml::de::Error> {
+        toml::from_str::<Settings>(toml).map(|s| s.password)
+    }
+
+    #[test]
+    fn test_parse_secret_forms() {
+        assert_eq!(parse("password = \"plain\"").unwrap(), Secret::Plain("plain".into()));
+        assert_eq!(
+            parse("password = { file = \"/run/secrets/pw\" }").unwrap(),
+            Secret::File(PathBuf::from("/run/secrets/pw"))
+        );
+        assert_eq!(parse("password = { env = \"PW\" }").unwrap(), Secret::Env("PW".into()));
+        assert_eq!(
+            parse("password = { age = \"\"\"\n-----BEGIN AGE ENCRYPTED FILE-----\n\"\"\" }").unwrap(),
+            Secret::Age("-----BEGIN AGE ENCRYPTED FILE-----\n".into())
+        );
+
+        assert!(parse("password = { path = \"/x\" }")
+            .unwrap_err()
+            .message()
+            .contains("unknown field `path`"));
+        assert!(parse("password = { file = \"/x\", env = \"X\" }").is_err());
+        assert!(parse("password = {}").is_err());
+        assert!(parse("password = 3").is_err());
+
+        let settings = Settings {
+            password: Secret::Env("PW".into()),
+        };
+        assert_eq!(toml::to_string(&settings).unwrap().trim(), "[password]\nenv = \"PW\"");
+        assert_eq!(format!("{:?}", Secret::Plain("hunter2".into())), "Plain(********)");
+    }
+
+    #[test]
+    #[serial]
+    fn test_resolve_file_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pw");
+        fs::write(&path, "from-file-secret\n").unwrap();
+        std::env::set_var("FLUX_TEST_SECRET_VALUE", "from-env-secret");
+
+        let config = Config::default();
+        let from_env = Secret::Env("FLUX_TEST_SECRET_VALUE".into()).resolve(&config);
+        std::env::remove_var("FLUX_TEST_SECRET_VALUE");
+
+        assert_eq!(Secret::File(path).resolve(&config).unwrap(), "from-file-secret");
+        assert_eq!(from_env.unwrap(), "from-env-secret");
+        assert!(Secret::Env("FLUX_TEST_SECRET_UNSET".into()).resolve(&config).is_err());
+        assert_eq!(redact::redact("pw from-file-secret"), "pw ********");
+    }
+
+    #[test]
+    #[serial]
+    fn test_resolve_age_uses_secret_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("age.key");
+        fs::write(&key, "AGE-SECRET-KEY-1TEST\n").unwrap();
+
+        let recorder = Arc::new(RecordingRunner::new());
+        recorder.respond("age --decrypt", CommandOutput::ok("decrypted-secret\n"));
+        let previous = set_runner(recorder.clone());
+
+        let mut config = Config::default();
+        config.general.secret_key = Some(key.clone());
+        let result = Secret::Age("ciphertext".into()).resolve(&config);
+        set_runner(previous);
+
+        assert_eq!(result.unwrap(), "decrypted-secret");
+        let calls = recorder.calls();
+        assert_eq!(
+            calls[0].command_line(),
+            format!("age --decrypt --identity {}", key.display())
+        );
+        assert_eq!(calls[0].stdin.as_deref(), Some("ciphertext"));
+    }
+}
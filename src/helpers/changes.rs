@@ -0,0 +1,154 @@
+//! Per-resource change tracking
+//!
+//! Module operations check whether a resource — a file, a service, a package —
+//! is already in the target state before touching it, and record the outcome
+//! here: `ok` when nothing had to be done, `changed` when it was modified and
+//! `failed` when converging it failed. [`track`] collects the outcomes of one
+//! module run; outside of it recording is a no-op.
+//!
+//! Tracking follows the tokio task, so modules running concurrently in a
+//! workflow each collect their own resources.
+
+use crate::helpers::logging::{log_debug, log_info, log_warn};
+use crate::helpers::redact::redact;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::future::Future;
+
+tokio::task_local! {
+    static TRACKED: RefCell<Vec<ResourceChange>>;
+}
+
+/// Whether a resource needed changing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceStatus {
+    /// Already in the target state
+    Ok,
+    Changed,
+    Failed,
+}
+
+/// Outcome for one resource a module manages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceChange {
+    /// What was checked, e.g. `/etc/ssh/sshd_config` or `service sshd`
+    pub resource: String,
+    pub status: ResourceStatus,
+    /// What was done, or why it failed
+    pub detail: Option<String>,
+}
+
+/// Run `operation`, collecting the resources it records
+pub async fn track<F: Future>(operation: F) -> (F::Output, Vec<ResourceChange>) {
+    TRACKED
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = operation.await;
+            let changes = TRACKED.with(|tracked| tracked.take());
+            (output, changes)
+        })
+        .await
+}
+
+/// Record that `resource` already was in the target state
+pub fn ok(resource: impl Into<String>) {
+    let resource = resource.into();
+    log_debug(format!("ok: {}", resource));
+    record(resource, ResourceStatus::Ok, None);
+}
+
+/// Record that `resource` was changed
+pub fn changed(resource: impl Into<String>, detail: impl Into<String>) {
+    let (resource, detail) = (resource.into(), detail.into());
+    log_info(format!("changed: {} ({})", resource, detail));
+    record(resource, ResourceStatus::Changed, Some(detail));
+}
+
+/// Record that converging `resource` failed
+pub fn failed(resource: impl Into<String>, error: impl Into<String>) {
+    let (resource, error) = (resource.into(), error.into());
+    log_warn(format!("failed: {} ({})", resource, error));
+    record(resource, ResourceStatus::Failed, Some(error));
+}
+
+/// Record `changed` when `did_change`, `ok` otherwise
+pub fn outcome(resource: impl Into<String>, did_change: bool, detail: impl Into<String>) {
+    if did_change {
+        changed(resource, detail);
+    } else {
+        ok(resource);
+    }
+}
+
+fn record(resource: String, status: ResourceStatus, detail: Option<String>) {
+    let change = ResourceChange {
+        resource: redact(&resource).into_owned(),
+        status,
+        detail: detail.map(|d| redact(&d).into_owned()),
+    };
+    let _ = TRACKED.try_with(|tracked| tracked.borrow_mut().push(change));
+}
+
+/// Number of resources with `status`
+pub fn count(changes: &[ResourceChange], status: ResourceStatus) -> usize {
+    changes.iter().filter(|c| c.status == status).count()
+}
+
+/// One-line summary such as `2 changed, 5 ok`
+pub fn summarize(changes: &[ResourceChange]) -> String {
+    let failed = count(changes, ResourceStatus::Failed);
+    let mut parts = vec![
+        format!("{} changed", count(changes, ResourceStatus::Changed)),
+        format!("{} ok", count(changes, ResourceStatus::Ok)),
+    ];
+    if failed > 0 {
+        parts.push(format!("{} failed", failed));
+    }
+    parts.join(", ")
+}
+
+/// Print every resource with its status
+pub fn print_changes(changes: &[ResourceChange]) {
+    if changes.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("{}", "=== Resources ===".cyan());
+    for change in changes {
+        let status = match change.status {
+            ResourceStatus::Ok => format!("{:<8}", "ok").green(),
+            ResourceStatus::Changed => format!("{:<8}", "changed").yellow(),
+            ResourceStatus::Failed => format!("{:<8}", "failed").red(),
+        };
+        match &change.detail {
+            Some(detail) => println!("  {} {} ({})", status, change.resource, detail),
+            None => println!("  {} {}", status, change.resource),
+        }
+    }
+    println!("{}", summarize(changes).white());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_track_collects_per_task() {
+        ok("outside");
+
+        let ((), changes) = track(async {
+            ok("/etc/hostname");
+            changed("/etc/hosts", "written");
+            let ((), inner) = track(async { failed("service sshd", "exit 1") }).await;
+            assert_eq!(inner.len(), 1);
+        })
+        .await;
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].status, ResourceStatus::Ok);
+        assert_eq!(changes[1].detail.as_deref(), Some("written"));
+        assert_eq!(summarize(&changes), "1 changed, 1 ok");
+    }
+}
@@ -0,0 +1,183 @@
+use crate::helpers::redact::redact;
+use colored::Colorize;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+tokio::task_local! {
+    /// Dry-run requested for a single module invocation
+    static SCOPED: bool;
+}
+
+lazy_static! {
+    static ref PLANNED_CHANGES: Mutex<Vec<PlannedChange>> = Mutex::new(Vec::new());
+}
+
+/// Kind of change recorded while running in dry-run mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    WriteFile,
+    AppendFile,
+    CopyFile,
+    Backup,
+    CreateDir,
+    RemovePath,
+    SetPermissions,
+    Command,
+    ServiceRestart,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ChangeKind::WriteFile => "write",
+            ChangeKind::AppendFile => "append",
+            ChangeKind::CopyFile => "copy",
+            ChangeKind::Backup => "backup",
+            ChangeKind::CreateDir => "mkdir",
+            ChangeKind::RemovePath => "remove",
+            ChangeKind::SetPermissions => "chmod",
+            ChangeKind::Command => "exec",
+            ChangeKind::ServiceRestart => "restart",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// A change that would have been performed outside of dry-run mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedChange {
+    pub kind: ChangeKind,
+    pub target: String,
+    pub detail: Option<String>,
+}
+
+impl fmt::Display for PlannedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "[{}] {} ({})", self.kind, self.target, detail),
+            None => write!(f, "[{}] {}", self.kind, self.target),
+        }
+    }
+}
+
+/// Enable or disable dry-run mode for the current process
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether dry-run mode is active, for the process or the current invocation
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst) || SCOPED.try_with(|scoped| *scoped).unwrap_or(false)
+}
+
+/// Run `operation` in dry-run mode without changing it for the rest of the process
+///
+/// Modules running concurrently or after `operation` are not affected.
+pub async fn scoped<F: Future>(operation: F) -> F::Output {
+    SCOPED.scope(true, operation).await
+}
+
+/// Record a planned change
+pub fn record(kind: ChangeKind, target: impl Into<String>, detail: Option<String>) {
+    PLANNED_CHANGES.lock().push(PlannedChange {
+        kind,
+        target: redact(&target.into()).into_owned(),
+        detail: detail.map(|d| redact(&d).into_owned()),
+    });
+}
+
+/// Record a command invocation if dry-run mode is active.
+///
+/// Returns `true` when the command was recorded and must not be executed.
+pub fn intercept_command(command: &str, args: &[&str]) -> bool {
+    if !is_dry_run() {
+        return false;
+    }
+
+    record_command(command, args);
+    true
+}
+
+/// Record a command invocation as a planned change
+pub fn record_command(command: &str, args: &[&str]) {
+    let target = if args.is_empty() {
+        command.to_string()
+    } else {
+        format!("{} {}", command, args.join(" "))
+    };
+    record(ChangeKind::Command, target, None);
+}
+
+/// Get a snapshot of all changes recorded so far
+pub fn planned_changes() -> Vec<PlannedChange> {
+    PLANNED_CHANGES.lock().clone()
+}
+
+/// Drain all recorded changes
+pub fn take_planned_changes() -> Vec<PlannedChange> {
+    std::mem::take(&mut *PLANNED_CHANGES.lock())
+}
+
+/// Print the list of planned changes and clear it
+pub fn print_planned_changes() {
+    let changes = take_planned_changes();
+
+    println!();
+    println!("{}", "=== Dry Run: Planned Changes ===".cyan().bold());
+
+    if changes.is_empty() {
+        println!("{}", "No changes would be made".green());
+        return;
+    }
+
+    for (i, change) in changes.iter().enumerate() {
+        println!("  {:>3}. {}", i + 1, change);
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("{} change(s) would be made; nothing was modified", changes.len()).yellow()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_intercept_command_only_in_dry_run() {
+        set_dry_run(false);
+        take_planned_changes();
+        assert!(!intercept_command("systemctl", &["restart", "sshd"]));
+        assert!(planned_changes().is_empty());
+
+        set_dry_run(true);
+        assert!(intercept_command("systemctl", &["restart", "sshd"]));
+        set_dry_run(false);
+
+        let changes = take_planned_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Command);
+        assert_eq!(changes[0].target, "systemctl restart sshd");
+        assert!(planned_changes().is_empty());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_scoped_dry_run_ends_with_operation() {
+        set_dry_run(false);
+
+        assert!(scoped(async { is_dry_run() }).await);
+        assert!(!is_dry_run());
+    }
+}
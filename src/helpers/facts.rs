@@ -0,0 +1,316 @@
+//! Facts about the managed host
+//!
+//! [`Facts`] collects what modules, templates and workflow conditions need to
+//! know about a host — OS, hardware, network and software — in one place. It is
+//! gathered the first time it is asked for and cached for the rest of the run;
+//! [`invalidate`] forces a fresh look after a change such as installing
+//! packages.
+//!
+//! With `--root`, OS, hostname and package facts describe the target tree;
+//! hardware and network facts always describe the machine flux runs on.
+
+use crate::helpers::network::{self, NetworkInterface, RouteEntry};
+use crate::helpers::paths;
+use crate::helpers::system::{self, command_output, Distribution};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use sysinfo::{CpuRefreshKind, Disks, MemoryRefreshKind, RefreshKind, System};
+
+lazy_static! {
+    static ref FACTS: RwLock<Option<Arc<Facts>>> = RwLock::new(None);
+}
+
+/// Everything flux knows about the host
+#[derive(Debug, Clone, Serialize)]
+pub struct Facts {
+    pub hostname: String,
+    pub os: OsFacts,
+    pub kernel: String,
+    pub architecture: String,
+    /// Hypervisor as reported by `systemd-detect-virt`, `none` on bare metal
+    pub virtualization: String,
+    /// Container runtime, `none` outside a container
+    pub container: String,
+    pub cpu_count: usize,
+    pub memory_mb: u64,
+    pub disks: Vec<DiskFacts>,
+    pub interfaces: Vec<NetworkInterface>,
+    pub routes: Vec<RouteEntry>,
+    pub primary_ip: Option<String>,
+    pub default_gateway: Option<String>,
+    /// PID 1, e.g. `systemd`
+    pub init_system: String,
+    /// `apt`, `dnf`, `yum` or `unknown`
+    pub package_manager: String,
+    /// Installed packages and their versions
+    pub packages: BTreeMap<String, String>,
+}
+
+/// The operating system, from `/etc/os-release`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OsFacts {
+    /// `ID`, e.g. `ubuntu`
+    pub id: String,
+    /// `NAME`, e.g. `Ubuntu`
+    pub name: String,
+    /// `VERSION_ID`, e.g. `24.04`
+    pub version: String,
+    /// `PRETTY_NAME`, e.g. `Ubuntu 24.04.1 LTS`
+    pub pretty_name: String,
+    /// `debian`, `redhat` or `unknown`
+    pub family: String,
+    #[serde(skip)]
+    pub distribution: Distribution,
+}
+
+/// A mounted filesystem
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskFacts {
+    pub mount_point: String,
+    pub filesystem: String,
+    pub total_mb: u64,
+    pub available_mb: u64,
+}
+
+impl Facts {
+    /// Look at the host now, bypassing the cache
+    pub fn gather() -> Self {
+        let os = os_facts();
+        let package_manager = os.distribution.package_manager().to_string();
+        let packages = installed_packages(&package_manager);
+
+        let sys = System::new_with_specifics(
+            RefreshKind::new()
+                .with_cpu(CpuRefreshKind::new())
+                .with_memory(MemoryRefreshKind::new().with_ram()),
+        );
+        let routes = network::get_routing_table().unwrap_or_default();
+
+        Self {
+            hostname: hostname(),
+            os,
+            kernel: System::kernel_version().unwrap_or_default(),
+            architecture: std::env::consts::ARCH.to_string(),
+            virtualization: detect_virt("--vm"),
+            container: detect_virt("--container"),
+            cpu_count: sys.cpus().len(),
+            memory_mb: sys.total_memory() / 1024 / 1024,
+            disks: disks(),
+            interfaces: network::get_network_interfaces().unwrap_or_default(),
+            primary_ip: system::get_primary_ip().ok(),
+            default_gateway: network::default_gateway(&routes),
+            routes,
+            init_system: init_system(),
+            package_manager,
+            packages,
+        }
+    }
+
+    /// Whether `package` is installed
+    pub fn has_package(&self, package: &str) -> bool {
+        self.packages.contains_key(package)
+    }
+}
+
+/// Facts about the host, gathered on first use and cached for the run
+pub fn get() -> Arc<Facts> {
+    if let Some(facts) = FACTS.read().as_ref() {
+        return Arc::clone(facts);
+    }
+
+    let mut cached = FACTS.write();
+    Arc::clone(cached.get_or_insert_with(|| Arc::new(Facts::gather())))
+}
+
+/// Drop the cached facts so the next [`get`] gathers them again
+pub fn invalidate() {
+    *FACTS.write() = None;
+}
+
+fn hostname() -> String {
+    if paths::root().is_some() {
+        if let Ok(name) = fs::read_to_string(paths::resolve("/etc/hostname")) {
+            return name.trim().to_string();
+        }
+    }
+    system::get_hostname().unwrap_or_default()
+}
+
+fn os_facts() -> OsFacts {
+    let contents = fs::read_to_string(paths::resolve("/etc/os-release")).unwrap_or_default();
+    let mut os = parse_os_release(&contents);
+
+    os.distribution = system::detect_distro().unwrap_or_default();
+    os.family = if os.distribution.is_debian_based() {
+        "debian"
+    } else if os.distribution.is_redhat_based() {
+        "redhat"
+    } else {
+        "unknown"
+    }
+    .to_string();
+    os
+}
+
+/// Read the fields of an os-release file
+fn parse_os_release(contents: &str) -> OsFacts {
+    let mut os = OsFacts::default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim() {
+            "ID" => os.id = value.to_lowercase(),
+            "NAME" => os.name = value,
+            "VERSION_ID" => os.version = value,
+            "PRETTY_NAME" => os.pretty_name = value,
+            _ => {}
+        }
+    }
+    os
+}
+
+/// `systemd-detect-virt` with `flag`, `none` when nothing is detected
+fn detect_virt(flag: &str) -> String {
+    let detected = command_output("systemd-detect-virt", &[flag])
+        .ok()
+        .map(|output| output.stdout.trim().to_string())
+        .filter(|kind| !kind.is_empty());
+
+    match detected {
+        Some(kind) => kind,
+        None if flag == "--container" && Path::new("/.dockerenv").exists() => "docker".to_string(),
+        None if flag == "--container" && Path::new("/run/.containerenv").exists() => {
+            "podman".to_string()
+        }
+        None => "none".to_string(),
+    }
+}
+
+fn init_system() -> String {
+    if Path::new("/run/systemd/system").is_dir() {
+        return "systemd".to_string();
+    }
+    fs::read_to_string("/proc/1/comm")
+        .map(|comm| comm.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn disks() -> Vec<DiskFacts> {
+    Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| DiskFacts {
+            mount_point: disk.mount_point().display().to_string(),
+            filesystem: disk.file_system().to_string_lossy().into_owned(),
+            total_mb: disk.total_space() / 1024 / 1024,
+            available_mb: disk.available_space() / 1024 / 1024,
+        })
+        .collect()
+}
+
+/// Installed packages by name, empty when the package manager is unknown
+fn installed_packages(package_manager: &str) -> BTreeMap<String, String> {
+    let output = match package_manager {
+        "apt" => command_output(
+            "dpkg-query",
+            &["-W", "-f", "${Package}\t${Version}\t${db:Status-Status}\n"],
+        ),
+        "dnf" | "yum" => command_output(
+            "rpm",
+            &[
+                "-qa",
+                "--queryformat",
+                "%{NAME}\t%{VERSION}-%{RELEASE}\tinstalled\n",
+            ],
+        ),
+        _ => return BTreeMap::new(),
+    };
+
+    output
+        .ok()
+        .filter(|output| output.success)
+        .map(|output| parse_packages(&output.stdout))
+        .unwrap_or_default()
+}
+
+/// Read `name<TAB>version<TAB>status` lines, keeping installed packages
+fn parse_packages(listing: &str) -> BTreeMap<String, String> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (name, version, status) = (fields.next()?, fields.next()?, fields.next()?);
+            (status == "installed" && !name.is_empty())
+                .then(|| (name.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::runner::{set_runner, CommandOutput, RecordingRunner};
+    use serial_test::serial;
+
+    #[test]
+    fn test_parse_os_release() {
+        let os = parse_os_release(
+            "PRETTY_NAME=\"Ubuntu 24.04.1 LTS\"\nNAME=\"Ubuntu\"\nVERSION_ID=\"24.04\"\nID=ubuntu\nID_LIKE=debian\n",
+        );
+        assert_eq!(os.id, "ubuntu");
+        assert_eq!(os.name, "Ubuntu");
+        assert_eq!(os.version, "24.04");
+        assert_eq!(os.pretty_name, "Ubuntu 24.04.1 LTS");
+    }
+
+    #[test]
+    #[serial]
+    fn test_installed_packages() {
+        let recorder = Arc::new(RecordingRunner::new());
+        recorder.respond(
+            "dpkg-query",
+            CommandOutput::ok(
+                "openssh-server\t1:9.6p1-3ubuntu13\tinstalled\nufw\t0.36.2-6\tconfig-files\n",
+            ),
+        );
+        let previous = set_runner(recorder.clone());
+        let packages = installed_packages("apt");
+        let unknown = installed_packages("unknown");
+        set_runner(previous);
+
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages["openssh-server"], "1:9.6p1-3ubuntu13");
+        assert!(unknown.is_empty());
+        assert_eq!(recorder.calls().len(), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalidate_gathers_again() {
+        let root = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::write(root.path().join("etc/hostname"), "alpha\n").unwrap();
+
+        let previous = set_runner(Arc::new(RecordingRunner::new()));
+        paths::set_root(Some(root.path().to_path_buf()));
+
+        let before = get().hostname.clone();
+        fs::write(root.path().join("etc/hostname"), "beta\n").unwrap();
+        let cached = get().hostname.clone();
+        invalidate();
+        let after = get().hostname.clone();
+
+        paths::set_root(None);
+        set_runner(previous);
+
+        assert_eq!(before, "alpha");
+        assert_eq!(cached, "alpha");
+        assert_eq!(after, "beta");
+    }
+}
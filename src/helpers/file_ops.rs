@@ -1,4 +1,8 @@
 use crate::error::{FluxError, Result};
+use crate::helpers::dry_run::{self, ChangeKind};
+use crate::helpers::paths::resolve;
+use crate::helpers::runner::{self, Invocation};
+use crate::helpers::{changes, journal, report};
 use crate::helpers::logging::{log_info, log_warn};
 use chrono::Local;
 use fs_extra::dir::CopyOptions;
@@ -6,11 +10,10 @@ use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 /// Create a timestamped backup of a file
 pub fn backup_file<P: AsRef<Path>>(file_path: P) -> Result<PathBuf> {
-    let file_path = file_path.as_ref();
+    let file_path = &resolve(file_path);
     
     if !file_path.exists() {
         return Err(FluxError::not_found(format!(
@@ -34,8 +37,18 @@ pub fn backup_file<P: AsRef<Path>>(file_path: P) -> Result<PathBuf> {
 
     let backup_path = parent_dir.join(backup_name);
     
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::Backup,
+            file_path.display().to_string(),
+            Some(format!("to {}", backup_path.display())),
+        );
+        return Ok(backup_path);
+    }
+    
     fs::copy(file_path, &backup_path)
         .map_err(|e| FluxError::Io(e))?;
+    journal::record_backup(file_path, &backup_path)?;
     
     log_info(format!("Backed up {} to {}", file_path.display(), backup_path.display()));
     
@@ -43,18 +56,42 @@ pub fn backup_file<P: AsRef<Path>>(file_path: P) -> Result<PathBuf> {
 }
 
 /// Safely write content to a file with backup
+///
+/// A file that already holds exactly `content` is neither backed up nor
+/// rewritten. Returns whether the file changed.
 pub fn safe_write_file<P: AsRef<Path>>(
     file_path: P,
     content: &str,
     backup: bool,
-) -> Result<()> {
-    let file_path = file_path.as_ref();
+) -> Result<bool> {
+    let file_path = &resolve(file_path);
+    
+    if fs::read(file_path).is_ok_and(|existing| existing == content.as_bytes()) {
+        changes::ok(file_path.display().to_string());
+        return Ok(false);
+    }
     
     // Backup existing file if requested
-    if backup && file_path.exists() {
+    let backed_up = backup && file_path.exists();
+    if backed_up {
         backup_file(file_path)?;
     }
     
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::WriteFile,
+            file_path.display().to_string(),
+            Some(format!("{} bytes", content.len())),
+        );
+        changes::changed(file_path.display().to_string(), "would write");
+        return Ok(true);
+    }
+    
+    // The backup already lets rollback restore the previous content
+    if !backed_up {
+        journal::record_write(file_path)?;
+    }
+    
     // Write to temporary file first
     let temp_path = file_path.with_extension("tmp");
     
@@ -70,25 +107,51 @@ pub fn safe_write_file<P: AsRef<Path>>(
     // Move temp file to final location
     fs::rename(&temp_path, file_path)
         .map_err(|e| FluxError::Io(e))?;
+    report::record_file(ChangeKind::WriteFile, file_path);
+    changes::changed(file_path.display().to_string(), "written");
     
     log_info(format!("Successfully wrote to {}", file_path.display()));
     
-    Ok(())
+    Ok(true)
 }
 
 /// Safely append content to a file with backup
+///
+/// Nothing is appended when the file already contains `content`. Returns
+/// whether the file changed.
 pub fn safe_append_file<P: AsRef<Path>>(
     file_path: P,
     content: &str,
     backup: bool,
-) -> Result<()> {
-    let file_path = file_path.as_ref();
+) -> Result<bool> {
+    let file_path = &resolve(file_path);
+    
+    if fs::read_to_string(file_path).is_ok_and(|existing| existing.contains(content)) {
+        changes::ok(file_path.display().to_string());
+        return Ok(false);
+    }
     
     // Backup existing file if requested
-    if backup && file_path.exists() {
+    let backed_up = backup && file_path.exists();
+    if backed_up {
         backup_file(file_path)?;
     }
     
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::AppendFile,
+            file_path.display().to_string(),
+            Some(format!("{} bytes", content.len())),
+        );
+        changes::changed(file_path.display().to_string(), "would append");
+        return Ok(true);
+    }
+    
+    // The backup already lets rollback restore the previous content
+    if !backed_up {
+        journal::record_write(file_path)?;
+    }
+    
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
@@ -97,15 +160,17 @@ pub fn safe_append_file<P: AsRef<Path>>(
     
     file.write_all(content.as_bytes())
         .map_err(|e| FluxError::Io(e))?;
+    report::record_file(ChangeKind::AppendFile, file_path);
+    changes::changed(file_path.display().to_string(), "appended");
     
     log_info(format!("Successfully appended to {}", file_path.display()));
     
-    Ok(())
+    Ok(true)
 }
 
 /// Read file content as string
 pub fn read_file_to_string<P: AsRef<Path>>(file_path: P) -> Result<String> {
-    let file_path = file_path.as_ref();
+    let file_path = &resolve(file_path);
     
     fs::read_to_string(file_path)
         .map_err(|e| FluxError::Io(e))
@@ -113,26 +178,60 @@ pub fn read_file_to_string<P: AsRef<Path>>(file_path: P) -> Result<String> {
 
 /// Check if file exists
 pub fn file_exists<P: AsRef<Path>>(file_path: P) -> bool {
-    file_path.as_ref().exists()
+    resolve(file_path).exists()
 }
 
 /// Create directory with parents
 pub fn create_dir_all<P: AsRef<Path>>(dir_path: P) -> Result<()> {
-    let dir_path = dir_path.as_ref();
+    let dir_path = &resolve(dir_path);
     
-    fs::create_dir_all(dir_path)
-        .map_err(|e| FluxError::Io(e))?;
+    if dir_path.is_dir() {
+        changes::ok(dir_path.display().to_string());
+        return Ok(());
+    }
+    
+    if dry_run::is_dry_run() {
+        dry_run::record(ChangeKind::CreateDir, dir_path.display().to_string(), None);
+        changes::changed(dir_path.display().to_string(), "would create");
+        return Ok(());
+    }
+    
+    fs::create_dir_all(dir_path)?;
+    report::record_file(ChangeKind::CreateDir, dir_path);
+    changes::changed(dir_path.display().to_string(), "created");
     
     Ok(())
 }
 
 /// Copy file with permissions
+///
+/// Only `dst` is resolved below `--root`; `src` is read from the host so that
+/// user-supplied files can be installed into the tree. Resolve `src` with
+/// [`resolve`] when it lives in the tree as well. Returns whether `dst`
+/// changed; a destination with the same content and mode is left alone.
 pub fn copy_file_with_perms<P: AsRef<Path>, Q: AsRef<Path>>(
     src: P,
     dst: Q,
-) -> Result<()> {
+) -> Result<bool> {
     let src = src.as_ref();
-    let dst = dst.as_ref();
+    let dst = &resolve(dst);
+    
+    if same_file_contents(src, dst) {
+        changes::ok(dst.display().to_string());
+        return Ok(false);
+    }
+    
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::CopyFile,
+            dst.display().to_string(),
+            Some(format!("from {}", src.display())),
+        );
+        changes::changed(dst.display().to_string(), "would copy");
+        return Ok(true);
+    }
+    
+    journal::record_write(dst)?;
     
     // Copy file
     fs::copy(src, dst)
@@ -144,20 +243,57 @@ pub fn copy_file_with_perms<P: AsRef<Path>, Q: AsRef<Path>>(
     
     fs::set_permissions(dst, metadata.permissions())
         .map_err(|e| FluxError::Io(e))?;
+    report::record_file(ChangeKind::CopyFile, dst);
+    changes::changed(dst.display().to_string(), format!("copied from {}", src.display()));
     
-    Ok(())
+    Ok(true)
+}
+
+/// Whether `dst` exists with the same content and mode as `src`
+fn same_file_contents(src: &Path, dst: &Path) -> bool {
+    let (Ok(src_meta), Ok(dst_meta)) = (fs::metadata(src), fs::metadata(dst)) else {
+        return false;
+    };
+    if src_meta.permissions().mode() != dst_meta.permissions().mode() {
+        return false;
+    }
+    
+    match (fs::read(src), fs::read(dst)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
 }
 
 /// Set file permissions (Unix mode)
-pub fn set_permissions<P: AsRef<Path>>(file_path: P, mode: u32) -> Result<()> {
-    let file_path = file_path.as_ref();
+///
+/// Returns whether the mode changed.
+pub fn set_permissions<P: AsRef<Path>>(file_path: P, mode: u32) -> Result<bool> {
+    let file_path = &resolve(file_path);
+    let resource = format!("{} mode", file_path.display());
+    
+    if fs::metadata(file_path).is_ok_and(|m| m.permissions().mode() & 0o7777 == mode) {
+        changes::ok(resource);
+        return Ok(false);
+    }
+    
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::SetPermissions,
+            file_path.display().to_string(),
+            Some(format!("mode {:o}", mode)),
+        );
+        changes::changed(resource, format!("would set {:o}", mode));
+        return Ok(true);
+    }
     
     let permissions = fs::Permissions::from_mode(mode);
     
     fs::set_permissions(file_path, permissions)
         .map_err(|e| FluxError::Io(e))?;
+    report::record_file(ChangeKind::SetPermissions, file_path);
+    changes::changed(resource, format!("set to {:o}", mode));
     
-    Ok(())
+    Ok(true)
 }
 
 /// Create temporary file
@@ -175,7 +311,7 @@ pub fn create_temp_file(prefix: &str) -> Result<(File, PathBuf)> {
 
 /// Find files matching pattern
 pub fn find_files(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
-    let glob_pattern = dir.join(pattern).to_string_lossy().to_string();
+    let glob_pattern = resolve(dir).join(pattern).to_string_lossy().to_string();
     
     let mut files = Vec::new();
     
@@ -193,7 +329,7 @@ pub fn find_files(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
 pub fn file_checksum<P: AsRef<Path>>(file_path: P) -> Result<String> {
     use sha2::{Digest, Sha256};
     
-    let mut file = File::open(file_path.as_ref())
+    let mut file = File::open(resolve(file_path))
         .map_err(|e| FluxError::Io(e))?;
     
     let mut hasher = Sha256::new();
@@ -215,7 +351,7 @@ pub fn file_checksum<P: AsRef<Path>>(file_path: P) -> Result<String> {
 
 /// Get file size
 pub fn file_size<P: AsRef<Path>>(file_path: P) -> Result<u64> {
-    let metadata = fs::metadata(file_path.as_ref())
+    let metadata = fs::metadata(resolve(file_path))
         .map_err(|e| FluxError::Io(e))?;
     
     Ok(metadata.len())
@@ -223,12 +359,27 @@ pub fn file_size<P: AsRef<Path>>(file_path: P) -> Result<u64> {
 
 /// Check if path is a directory
 pub fn is_directory<P: AsRef<Path>>(path: P) -> bool {
-    path.as_ref().is_dir()
+    resolve(path).is_dir()
 }
 
 /// Remove file or directory
-pub fn remove_path<P: AsRef<Path>>(path: P) -> Result<()> {
-    let path = path.as_ref();
+///
+/// Returns whether anything was removed; a missing path is left alone.
+pub fn remove_path<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = &resolve(path);
+    
+    if fs::symlink_metadata(path).is_err() {
+        changes::ok(path.display().to_string());
+        return Ok(false);
+    }
+    
+    if dry_run::is_dry_run() {
+        dry_run::record(ChangeKind::RemovePath, path.display().to_string(), None);
+        changes::changed(path.display().to_string(), "would remove");
+        return Ok(true);
+    }
+    
+    journal::record_removal(path)?;
     
     if path.is_dir() {
         fs::remove_dir_all(path)
@@ -237,19 +388,33 @@ pub fn remove_path<P: AsRef<Path>>(path: P) -> Result<()> {
         fs::remove_file(path)
             .map_err(|e| FluxError::Io(e))?;
     }
+    report::record_file(ChangeKind::RemovePath, path);
+    changes::changed(path.display().to_string(), "removed");
     
-    Ok(())
+    Ok(true)
 }
 
 /// Copy directory recursively
+///
+/// Like [`copy_file_with_perms`], only `dst` is resolved below `--root`.
 pub fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<()> {
     let src = src.as_ref();
-    let dst = dst.as_ref();
+    let dst = &resolve(dst);
+    
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::CopyFile,
+            dst.display().to_string(),
+            Some(format!("directory from {}", src.display())),
+        );
+        return Ok(());
+    }
     
     let options = CopyOptions::new();
     
     fs_extra::dir::copy(src, dst, &options)
         .map_err(|e| FluxError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+    report::record_file(ChangeKind::CopyFile, dst);
     
     Ok(())
 }
@@ -257,9 +422,11 @@ pub fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serial_test::serial;
     use tempfile::TempDir;
     
     #[test]
+    #[serial]
     fn test_backup_file() {
         let temp_dir = TempDir::new().unwrap();
         let test_file = temp_dir.path().join("test.txt");
@@ -279,6 +446,7 @@ mod tests {
     }
     
     #[test]
+    #[serial]
     fn test_safe_write_file() {
         let temp_dir = TempDir::new().unwrap();
         let test_file = temp_dir.path().join("test.txt");
@@ -300,6 +468,50 @@ mod tests {
         
         assert_eq!(backups.len(), 1);
     }
+    
+    #[test]
+    #[serial]
+    fn test_safe_write_file_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        
+        assert!(safe_write_file(&test_file, "content", true).unwrap());
+        
+        // Same content again: no backup, no write
+        assert!(!safe_write_file(&test_file, "content", true).unwrap());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        
+        // Appending content that is already there changes nothing either
+        assert!(!safe_append_file(&test_file, "tent", false).unwrap());
+        assert!(safe_append_file(&test_file, "\nmore", false).unwrap());
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "content\nmore");
+        
+        assert!(!remove_path(temp_dir.path().join("missing")).unwrap());
+    }
+    
+    #[test]
+    #[serial]
+    fn test_safe_write_file_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "original").unwrap();
+        
+        dry_run::take_planned_changes();
+        dry_run::set_dry_run(true);
+        let result = safe_write_file(&test_file, "changed", true);
+        dry_run::set_dry_run(false);
+        result.unwrap();
+        
+        // Nothing touched on disk, backup and write recorded
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "original");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        
+        let kinds: Vec<_> = dry_run::take_planned_changes()
+            .into_iter()
+            .map(|c| c.kind)
+            .collect();
+        assert_eq!(kinds, vec![ChangeKind::Backup, ChangeKind::WriteFile]);
+    }
 }
 
 
@@ -316,7 +528,7 @@ pub fn file_checksum_simple<P: AsRef<std::path::Path>>(file_path: P) -> Result<S
     use std::hash::{Hash, Hasher};
     use std::io::Read;
     
-    let mut file = std::fs::File::open(file_path.as_ref())
+    let mut file = std::fs::File::open(resolve(file_path))
         .map_err(|e| FluxError::Io(e))?;
     
     let mut buffer = Vec::new();
@@ -375,19 +587,8 @@ pub fn execute_command_with_timeout(
     // For production use, consider using tokio::process::Command with timeout
     let _ = timeout_secs; // Suppress unused warning
 
-    let output = Command::new(command)
-        .args(args)
-        .output()
-        .map_err(|e| FluxError::command_failed(format!("Command {} failed: {}", command, e)))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(FluxError::command_failed(format!(
-            "{} failed: {}", command, stderr
-        )));
-    }
-    
-    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    let output = runner::run(&Invocation::new(command, args))?.check(command)?;
+    Ok(output.stdout)
 }
 
 /// Check if running as root (enhanced version)
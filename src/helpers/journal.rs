@@ -0,0 +1,462 @@
+use crate::error::{FluxError, Result};
+use crate::helpers::dry_run::{self, ChangeKind};
+use crate::helpers::logging::{log_error, log_info, log_warn};
+use crate::helpers::paths;
+use crate::helpers::system::execute_command;
+use chrono::Local;
+use fs_extra::dir::CopyOptions;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory holding one sub-directory per journaled run
+pub const RUNS_DIR: &str = "/var/lib/flux/runs";
+
+const ENTRIES_FILE: &str = "journal.jsonl";
+const RUN_INFO_FILE: &str = "run.json";
+const SAVED_FILES_DIR: &str = "files";
+
+lazy_static! {
+    static ref ACTIVE: Mutex<Option<Journal>> = Mutex::new(None);
+}
+
+/// A single reversible change made during a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum JournalEntry {
+    /// A file that did not exist before the run
+    Created { path: PathBuf },
+    /// A file that was overwritten; `saved` holds its previous content
+    Modified { path: PathBuf, saved: PathBuf },
+    /// A file copied aside by `backup_file`
+    Backup { path: PathBuf, backup: PathBuf },
+    /// A file or directory that was deleted; `saved` holds a copy
+    Removed { path: PathBuf, saved: PathBuf },
+    /// A command whose effect is undone by running `inverse`
+    Command {
+        command: String,
+        args: Vec<String>,
+        inverse: Vec<String>,
+    },
+}
+
+/// Lifecycle state of a journaled run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+    RolledBack,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Completed => "completed",
+            RunStatus::Failed => "failed",
+            RunStatus::RolledBack => "rolled_back",
+        }
+    }
+}
+
+/// Metadata stored in `run.json` for each run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunInfo {
+    pub id: String,
+    /// Command line that started the run, e.g. `load ssh --harden`
+    pub command: String,
+    pub started: String,
+    pub finished: Option<String>,
+    pub status: RunStatus,
+}
+
+/// Open journal for the current process
+struct Journal {
+    dir: PathBuf,
+    next_saved: usize,
+}
+
+impl Journal {
+    fn append(&mut self, entry: &JournalEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| FluxError::system(format!("Failed to serialize journal entry: {}", e)))?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(ENTRIES_FILE))?;
+        writeln!(file, "{}", line)?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Copy a file or directory into the run directory before it changes
+    fn save(&mut self, path: &Path) -> Result<PathBuf> {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "root".to_string());
+        let saved = self
+            .dir
+            .join(SAVED_FILES_DIR)
+            .join(format!("{:04}-{}", self.next_saved, name));
+        self.next_saved += 1;
+
+        copy_path(path, &saved)?;
+        Ok(saved)
+    }
+}
+
+/// Copy a file (keeping permissions) or a directory tree
+fn copy_path(src: &Path, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if src.is_dir() {
+        let options = CopyOptions::new().copy_inside(true);
+        fs_extra::dir::copy(src, dst, &options)
+            .map_err(|e| FluxError::Io(std::io::Error::other(e)))?;
+    } else {
+        fs::copy(src, dst)?;
+    }
+
+    Ok(())
+}
+
+fn write_run_info(dir: &Path, info: &RunInfo) -> Result<()> {
+    let json = serde_json::to_string_pretty(info)
+        .map_err(|e| FluxError::system(format!("Failed to serialize run info: {}", e)))?;
+    fs::write(dir.join(RUN_INFO_FILE), json)?;
+    Ok(())
+}
+
+fn read_run_info(dir: &Path) -> Result<RunInfo> {
+    let json = fs::read_to_string(dir.join(RUN_INFO_FILE))?;
+    serde_json::from_str(&json)
+        .map_err(|e| FluxError::parse(format!("Invalid run info in {}: {}", dir.display(), e)))
+}
+
+/// Open a journal for this run under [`RUNS_DIR`]
+///
+/// Returns the run id, or `None` in dry-run mode where nothing is changed.
+pub fn begin(command: &str) -> Result<Option<String>> {
+    begin_in(&paths::resolve(RUNS_DIR), command)
+}
+
+/// Open a journal for this run under `root`
+pub fn begin_in(root: &Path, command: &str) -> Result<Option<String>> {
+    if dry_run::is_dry_run() {
+        return Ok(None);
+    }
+
+    let now = Local::now();
+    let mut id = now.format("%Y%m%d-%H%M%S").to_string();
+    let mut suffix = 1;
+    while root.join(&id).exists() {
+        suffix += 1;
+        id = format!("{}-{}", now.format("%Y%m%d-%H%M%S"), suffix);
+    }
+
+    let dir = root.join(&id);
+    fs::create_dir_all(dir.join(SAVED_FILES_DIR))?;
+
+    write_run_info(
+        &dir,
+        &RunInfo {
+            id: id.clone(),
+            command: command.to_string(),
+            started: now.to_rfc3339(),
+            finished: None,
+            status: RunStatus::Running,
+        },
+    )?;
+
+    *ACTIVE.lock() = Some(Journal { dir, next_saved: 0 });
+    Ok(Some(id))
+}
+
+/// Close the active journal and record whether the run succeeded
+pub fn finish(success: bool) -> Result<()> {
+    let Some(journal) = ACTIVE.lock().take() else {
+        return Ok(());
+    };
+
+    let mut info = read_run_info(&journal.dir)?;
+    info.finished = Some(Local::now().to_rfc3339());
+    info.status = if success {
+        RunStatus::Completed
+    } else {
+        RunStatus::Failed
+    };
+    write_run_info(&journal.dir, &info)
+}
+
+/// Check whether a journal is currently open
+pub fn is_active() -> bool {
+    ACTIVE.lock().is_some()
+}
+
+/// Record that `path` is about to be written
+///
+/// Saves the current content if the file exists so rollback can restore it.
+pub fn record_write(path: &Path) -> Result<()> {
+    let mut guard = ACTIVE.lock();
+    let Some(journal) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    let entry = if path.is_file() {
+        let saved = journal.save(path)?;
+        JournalEntry::Modified {
+            path: path.to_path_buf(),
+            saved,
+        }
+    } else {
+        JournalEntry::Created {
+            path: path.to_path_buf(),
+        }
+    };
+    journal.append(&entry)
+}
+
+/// Record a backup made by `backup_file`
+pub fn record_backup(path: &Path, backup: &Path) -> Result<()> {
+    let mut guard = ACTIVE.lock();
+    let Some(journal) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    journal.append(&JournalEntry::Backup {
+        path: path.to_path_buf(),
+        backup: backup.to_path_buf(),
+    })
+}
+
+/// Record that `path` is about to be removed, saving a copy first
+pub fn record_removal(path: &Path) -> Result<()> {
+    let mut guard = ACTIVE.lock();
+    let Some(journal) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let saved = journal.save(path)?;
+    journal.append(&JournalEntry::Removed {
+        path: path.to_path_buf(),
+        saved,
+    })
+}
+
+/// Record a successful command together with the command that reverses it
+pub fn record_command(command: &str, args: &[&str], inverse: &[&str]) -> Result<()> {
+    let mut guard = ACTIVE.lock();
+    let Some(journal) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    journal.append(&JournalEntry::Command {
+        command: command.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        inverse: inverse.iter().map(|a| a.to_string()).collect(),
+    })
+}
+
+/// Read the entries of a run in the order they were recorded
+pub fn read_entries(root: &Path, id: &str) -> Result<Vec<JournalEntry>> {
+    let dir = run_dir(root, id)?;
+    let path = dir.join(ENTRIES_FILE);
+
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    fs::read_to_string(&path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                FluxError::parse(format!("{}:{}: {}", path.display(), i + 1, e))
+            })
+        })
+        .collect()
+}
+
+/// List all journaled runs, oldest first
+pub fn list_runs(root: &Path) -> Result<Vec<RunInfo>> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut runs: Vec<RunInfo> = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| read_run_info(&entry.path()).ok())
+        .collect();
+    runs.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(runs)
+}
+
+fn run_dir(root: &Path, id: &str) -> Result<PathBuf> {
+    if id.is_empty() || id.contains('/') || id.contains("..") {
+        return Err(FluxError::validation(format!("Invalid run id: {}", id)));
+    }
+
+    let dir = root.join(id);
+    if !dir.join(RUN_INFO_FILE).exists() {
+        return Err(FluxError::not_found(format!("Run '{}' not found", id)));
+    }
+
+    Ok(dir)
+}
+
+/// Undo every change of a run in reverse order
+///
+/// Keeps going when a single step fails and returns an error listing the
+/// failed steps at the end. Honours dry-run mode.
+pub fn rollback(root: &Path, id: &str) -> Result<()> {
+    let dir = run_dir(root, id)?;
+    let mut info = read_run_info(&dir)?;
+
+    if info.status == RunStatus::RolledBack {
+        return Err(FluxError::validation(format!("Run '{}' was already rolled back", id)));
+    }
+
+    let entries = read_entries(root, id)?;
+    if entries.is_empty() {
+        log_info(format!("Run '{}' made no journaled changes", id));
+    }
+
+    let mut failures = Vec::new();
+    for entry in entries.iter().rev() {
+        if let Err(e) = undo(entry) {
+            log_error(format!("Rollback step failed: {}", e));
+            failures.push(e.to_string());
+        }
+    }
+
+    if dry_run::is_dry_run() {
+        return Ok(());
+    }
+
+    if !failures.is_empty() {
+        return Err(FluxError::system(format!(
+            "{} rollback step(s) failed for run '{}'",
+            failures.len(),
+            id
+        )));
+    }
+
+    info.status = RunStatus::RolledBack;
+    write_run_info(&dir, &info)
+}
+
+/// Restore `path` from a saved copy, replacing whatever is there now
+fn restore(saved: &Path, path: &Path) -> Result<()> {
+    if dry_run::is_dry_run() {
+        dry_run::record(
+            ChangeKind::CopyFile,
+            path.display().to_string(),
+            Some(format!("restore from {}", saved.display())),
+        );
+        return Ok(());
+    }
+
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    }
+    copy_path(saved, path)?;
+    log_info(format!("Restored {}", path.display()));
+    Ok(())
+}
+
+fn undo(entry: &JournalEntry) -> Result<()> {
+    match entry {
+        JournalEntry::Created { path } => {
+            if dry_run::is_dry_run() {
+                dry_run::record(ChangeKind::RemovePath, path.display().to_string(), None);
+            } else if path.exists() {
+                fs::remove_file(path)?;
+                log_info(format!("Removed {}", path.display()));
+            }
+            Ok(())
+        }
+        JournalEntry::Modified { path, saved } | JournalEntry::Removed { path, saved } => {
+            restore(saved, path)
+        }
+        JournalEntry::Backup { path, backup } => {
+            if !backup.exists() {
+                log_warn(format!("Backup {} is gone, cannot restore {}", backup.display(), path.display()));
+                return Ok(());
+            }
+            restore(backup, path)
+        }
+        JournalEntry::Command { inverse, .. } => {
+            let Some((command, args)) = inverse.split_first() else {
+                return Ok(());
+            };
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            execute_command(command, &args).map(|_| ())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::file_ops::{remove_path, safe_write_file};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_rollback_restores_files_in_reverse_order() {
+        let runs = TempDir::new().unwrap();
+        let work = TempDir::new().unwrap();
+        let existing = work.path().join("existing.conf");
+        let created = work.path().join("created.conf");
+        let removed = work.path().join("removed.conf");
+        fs::write(&existing, "original").unwrap();
+        fs::write(&removed, "keep me").unwrap();
+
+        let id = begin_in(runs.path(), "test").unwrap().unwrap();
+        safe_write_file(&existing, "first", false).unwrap();
+        safe_write_file(&existing, "second", false).unwrap();
+        safe_write_file(&created, "new", false).unwrap();
+        remove_path(&removed).unwrap();
+        finish(true).unwrap();
+
+        assert_eq!(read_entries(runs.path(), &id).unwrap().len(), 4);
+        assert_eq!(list_runs(runs.path()).unwrap()[0].status, RunStatus::Completed);
+
+        rollback(runs.path(), &id).unwrap();
+
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "original");
+        assert_eq!(fs::read_to_string(&removed).unwrap(), "keep me");
+        assert!(!created.exists());
+        assert_eq!(list_runs(runs.path()).unwrap()[0].status, RunStatus::RolledBack);
+        assert!(rollback(runs.path(), &id).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_no_journal_without_active_run() {
+        let work = TempDir::new().unwrap();
+        let file = work.path().join("file.conf");
+
+        assert!(!is_active());
+        safe_write_file(&file, "content", false).unwrap();
+        assert!(finish(true).is_ok());
+    }
+}
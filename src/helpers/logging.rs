@@ -1,7 +1,10 @@
 use crate::error::Result;
+use crate::helpers::{redact::redact, report};
 use clap::ValueEnum;
 use colored::Colorize;
+use std::cell::RefCell;
 use std::fs::{self, OpenOptions};
+use std::future::Future;
 use std::io::Write;
 use std::path::Path;
 use tracing::{debug, error, info, warn};
@@ -71,43 +74,155 @@ pub fn write_to_log_file(message: &str) -> Result<()> {
     Ok(())
 }
 
+tokio::task_local! {
+    /// Output of a module running alongside others, held until it finishes
+    static CAPTURED: RefCell<CapturedOutput>;
+}
+
+/// Print to stdout, or into the captured output of the current module
+#[macro_export]
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::helpers::logging::write_stdout(&format!($($arg)*))
+    };
+}
+
+/// Print a line to stdout, or into the captured output of the current module
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::helpers::logging::write_stdout("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::helpers::logging::write_stdout(&format!("{}\n", format!($($arg)*)))
+    };
+}
+
+/// Lines printed by a module while its output was captured
+#[derive(Debug, Default)]
+pub struct CapturedOutput {
+    lines: Vec<(bool, String)>,
+    partial: String,
+}
+
+impl CapturedOutput {
+    /// Captured lines in order, each flagged with whether it went to stderr
+    pub fn lines(&self) -> impl Iterator<Item = (bool, &str)> {
+        self.lines.iter().map(|(stderr, line)| (*stderr, line.as_str()))
+    }
+
+    /// Whether nothing was printed
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Print the captured lines to stdout and stderr
+    pub fn print(&self) {
+        for (stderr, line) in self.lines() {
+            if stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+
+    fn push_stdout(&mut self, text: &str) {
+        self.partial.push_str(text);
+        while let Some(end) = self.partial.find('\n') {
+            let line = self.partial[..end].to_string();
+            self.partial.drain(..=end);
+            self.lines.push((false, line));
+        }
+    }
+
+    fn push_stderr(&mut self, line: String) {
+        self.finish_partial();
+        self.lines.push((true, line));
+    }
+
+    fn finish_partial(&mut self) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.lines.push((false, line));
+        }
+    }
+}
+
+/// Run `operation` with everything it prints held back instead of written out
+///
+/// Used for modules running at the same time, so that each module's output
+/// can be printed as one block once it finishes.
+pub async fn capture<F: Future>(operation: F) -> (F::Output, CapturedOutput) {
+    CAPTURED
+        .scope(RefCell::new(CapturedOutput::default()), async {
+            let output = operation.await;
+            let captured = CAPTURED.with(|captured| {
+                let mut captured = captured.borrow_mut();
+                captured.finish_partial();
+                std::mem::take(&mut *captured)
+            });
+            (output, captured)
+        })
+        .await
+}
+
+/// Write `text` to stdout, or into the captured output of the current module
+pub fn write_stdout(text: &str) {
+    let captured = CAPTURED.try_with(|captured| captured.borrow_mut().push_stdout(text));
+    if captured.is_err() {
+        print!("{}", text);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Write a line to stderr, or into the captured output of the current module
+fn write_stderr_line(line: String) {
+    if CAPTURED.try_with(|_| ()).is_ok() {
+        CAPTURED.with(|captured| captured.borrow_mut().push_stderr(line));
+    } else {
+        eprintln!("{}", line);
+    }
+}
+
 /// Log debug message
 pub fn log_debug<S: AsRef<str>>(message: S) {
-    let msg = message.as_ref();
+    let msg = redact(message.as_ref());
     debug!("{}", msg);
-    println!("{} {}", "[DEBUG]".cyan(), msg);
+    outln!("{} {}", "[DEBUG]".cyan(), msg);
     let _ = write_to_log_file(&format!("[DEBUG] {}", msg));
 }
 
 /// Log info message
 pub fn log_info<S: AsRef<str>>(message: S) {
-    let msg = message.as_ref();
+    let msg = redact(message.as_ref());
     info!("{}", msg);
-    println!("{} {}", "[INFO]".green(), msg);
+    outln!("{} {}", "[INFO]".green(), msg);
     let _ = write_to_log_file(&format!("[INFO] {}", msg));
 }
 
 /// Log warning message
 pub fn log_warn<S: AsRef<str>>(message: S) {
-    let msg = message.as_ref();
+    let msg = redact(message.as_ref());
     warn!("{}", msg);
-    println!("{} {}", "[WARN]".yellow(), msg);
+    outln!("{} {}", "[WARN]".yellow(), msg);
     let _ = write_to_log_file(&format!("[WARN] {}", msg));
 }
 
 /// Log error message
 pub fn log_error<S: AsRef<str>>(message: S) {
-    let msg = message.as_ref();
+    let msg = redact(message.as_ref());
     error!("{}", msg);
-    eprintln!("{} {}", "[ERROR]".red(), msg);
+    write_stderr_line(format!("{} {}", "[ERROR]".red(), msg));
     let _ = write_to_log_file(&format!("[ERROR] {}", msg));
+    report::record_error(&msg);
 }
 
 /// Log success message
 pub fn log_success<S: AsRef<str>>(message: S) {
-    let msg = message.as_ref();
+    let msg = redact(message.as_ref());
     info!("{}", msg);
-    println!("{} {}", "[SUCCESS]".green().bold(), msg);
+    outln!("{} {}", "[SUCCESS]".green().bold(), msg);
     let _ = write_to_log_file(&format!("[SUCCESS] {}", msg));
 }
 
@@ -163,4 +278,23 @@ impl ProgressIndicator {
     pub fn finish_and_clear(&self) {
         self.pb.finish_and_clear();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_capture_holds_output_per_task() {
+        let ((), captured) = capture(async {
+            out!("Gateway: ");
+            outln!("ok");
+            write_stderr_line("failed".to_string());
+            out!("partial");
+        })
+        .await;
+
+        let lines: Vec<_> = captured.lines().collect();
+        assert_eq!(lines, vec![(false, "Gateway: ok"), (true, "failed"), (false, "partial")]);
+    }
+}
@@ -1,7 +1,18 @@
+pub mod changes;
+pub mod dry_run;
+pub mod facts;
 pub mod file_ops;
+pub mod journal;
 pub mod logging;
 pub mod network;
+pub mod output;
+pub mod paths;
+pub mod redact;
+pub mod report;
+pub mod runner;
+pub mod schema;
 pub mod system;
+pub mod template;
 pub mod user_input;
 pub mod validation;
 
@@ -1,11 +1,12 @@
 use crate::error::{FluxError, Result};
-use crate::helpers::system::execute_command;
+use crate::helpers::system::{execute_command, query_command};
 use pnet::datalink;
+use serde::Serialize;
 use std::net::{IpAddr, TcpStream, ToSocketAddrs};
 use std::time::Duration;
 
 /// Network interface information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NetworkInterface {
     pub name: String,
     pub mac: String,
@@ -100,22 +101,21 @@ pub fn get_dns_servers() -> Result<Vec<String>> {
 
 /// Get default gateway
 pub fn get_default_gateway() -> Result<String> {
-    let output = execute_command("ip", &["route", "show", "default"])?;
-    
-    // Parse: default via X.X.X.X dev ...
-    for line in output.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 3 && parts[0] == "default" && parts[1] == "via" {
-            return Ok(parts[2].to_string());
-        }
-    }
-    
-    Err(FluxError::network("No default gateway found"))
+    default_gateway(&get_routing_table()?)
+        .ok_or_else(|| FluxError::network("No default gateway found"))
+}
+
+/// Gateway of the default route in `routes`
+pub fn default_gateway(routes: &[RouteEntry]) -> Option<String> {
+    routes
+        .iter()
+        .find(|route| route.destination == "default")
+        .and_then(|route| route.gateway.clone())
 }
 
 /// Get routing table
 pub fn get_routing_table() -> Result<Vec<RouteEntry>> {
-    let output = execute_command("ip", &["route", "show"])?;
+    let output = query_command("ip", &["route", "show"])?;
     let mut routes = Vec::new();
     
     for line in output.lines() {
@@ -128,7 +128,7 @@ pub fn get_routing_table() -> Result<Vec<RouteEntry>> {
 }
 
 /// Routing table entry
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RouteEntry {
     pub destination: String,
     pub gateway: Option<String>,
@@ -0,0 +1,70 @@
+use crate::error::{FluxError, Result};
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::FromRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The original stdout, reserved for JSON documents
+    static ref JSON_OUT: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// Output formats for command results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable text
+    #[default]
+    Text,
+    /// JSON documents on stdout; progress and logs go to stderr
+    Json,
+}
+
+/// Select the output format for the rest of the process
+///
+/// In JSON mode, stdout is redirected to stderr so that module output and
+/// logging cannot corrupt the JSON stream; [`emit`] writes to the original
+/// stdout instead.
+pub fn set_output_format(format: OutputFormat) -> Result<()> {
+    if format != OutputFormat::Json || JSON.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    std::io::stdout().flush()?;
+    let saved = nix::unistd::dup(1).map_err(redirect_failed)?;
+    nix::unistd::dup2(2, 1).map_err(redirect_failed)?;
+
+    // SAFETY: `saved` is a freshly duplicated descriptor owned by nothing else
+    *JSON_OUT.lock() = Some(unsafe { File::from_raw_fd(saved) });
+    Ok(())
+}
+
+fn redirect_failed(e: nix::Error) -> FluxError {
+    FluxError::system(format!("Failed to redirect stdout for JSON output: {}", e))
+}
+
+/// Check if JSON output was requested
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// Write `value` as a pretty-printed JSON document to stdout
+pub fn emit<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| FluxError::parse(format!("Failed to serialize output: {}", e)))?;
+
+    let mut out = JSON_OUT.lock();
+    match out.as_mut() {
+        Some(file) => {
+            writeln!(file, "{}", json)?;
+            file.flush()?;
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
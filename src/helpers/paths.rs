@@ -0,0 +1,82 @@
+//! Filesystem root override
+//!
+//! With `--root <dir>` (or `FLUX_ROOT`), every absolute system path flux reads
+//! or writes is resolved below `<dir>` instead of `/`, so modules can be run
+//! against a chroot, an unpacked image tree or a test directory.
+//!
+//! `/proc`, `/sys` and `/dev` describe the running kernel and are never
+//! redirected.
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::path::{Component, Path, PathBuf};
+
+/// Paths that always refer to the running system
+const HOST_ONLY: &[&str] = &["/proc", "/sys", "/dev"];
+
+lazy_static! {
+    static ref ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// Resolve system paths below `root` for the rest of the process
+///
+/// `None` or `/` restores the real filesystem root. Cached facts are dropped
+/// since they describe the previous root.
+pub fn set_root(root: Option<PathBuf>) {
+    *ROOT.write() = root.filter(|r| r.as_path() != Path::new("/"));
+    crate::helpers::facts::invalidate();
+}
+
+/// The root override, if one is set
+pub fn root() -> Option<PathBuf> {
+    ROOT.read().clone()
+}
+
+/// Map an absolute system path into the current root
+///
+/// Relative paths, paths already below the root and kernel interfaces are
+/// returned unchanged, so resolving twice is harmless.
+pub fn resolve<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let guard = ROOT.read();
+    let Some(root) = guard.as_ref() else {
+        return path.to_path_buf();
+    };
+
+    if !path.is_absolute()
+        || path.starts_with(root)
+        || HOST_ONLY.iter().any(|p| path.starts_with(p))
+    {
+        return path.to_path_buf();
+    }
+
+    let relative: PathBuf = path
+        .components()
+        .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)))
+        .collect();
+    root.join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_resolve_under_root() {
+        set_root(Some(PathBuf::from("/tmp/image")));
+
+        let sshd = resolve("/etc/ssh/sshd_config");
+        let again = resolve(&sshd);
+        let relative = resolve("motd.tmp");
+        let proc = resolve("/proc/loadavg");
+        set_root(None);
+
+        assert_eq!(sshd, PathBuf::from("/tmp/image/etc/ssh/sshd_config"));
+        assert_eq!(again, sshd);
+        assert_eq!(relative, PathBuf::from("motd.tmp"));
+        assert_eq!(proc, PathBuf::from("/proc/loadavg"));
+        assert_eq!(resolve("/etc/hosts"), PathBuf::from("/etc/hosts"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Keeps secret values out of logs and run reports
+//!
+//! Every secret resolved from the configuration is registered here, and the
+//! logging, dry-run and report helpers pass their text through [`redact`]
+//! before printing or storing it.
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::borrow::Cow;
+
+/// Shown in place of a secret value
+pub const MASK: &str = "********";
+
+/// Shorter values would mask unrelated text
+const MIN_LENGTH: usize = 4;
+
+lazy_static! {
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+/// Mask `value` in everything logged or reported from now on
+///
+/// Each line of a multi-line value (e.g. a private key) is masked on its own
+/// as well, since output is often split by lines.
+pub fn register(value: &str) {
+    let mut secrets = SECRETS.write();
+    for candidate in std::iter::once(value.trim()).chain(value.lines().map(str::trim)) {
+        if candidate.chars().count() >= MIN_LENGTH && !secrets.iter().any(|s| s == candidate) {
+            secrets.push(candidate.to_string());
+        }
+    }
+
+    // Longest first, so a value containing another one is masked as a whole
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+}
+
+/// `text` with every registered secret replaced by [`MASK`]
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let secrets = SECRETS.read();
+    if !secrets.iter().any(|s| text.contains(s.as_str())) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut redacted = text.to_string();
+    for secret in secrets.iter() {
+        redacted = redacted.replace(secret.as_str(), MASK);
+    }
+    Cow::Owned(redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_redact_registered_values() {
+        assert_eq!(redact("nothing to hide"), "nothing to hide");
+
+        register("hunter2-redact-test");
+        register("abc");
+        register("line-one-redact-test\nline-two-redact-test\n");
+
+        assert_eq!(
+            redact("chpasswd failed for hunter2-redact-test"),
+            "chpasswd failed for ********"
+        );
+        assert_eq!(redact("abc stays"), "abc stays");
+        assert_eq!(redact("got line-two-redact-test"), "got ********");
+        assert_eq!(
+            redact("line-one-redact-test\nline-two-redact-test"),
+            "********"
+        );
+    }
+}
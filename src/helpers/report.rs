@@ -0,0 +1,352 @@
+use crate::error::{FluxError, Result};
+use crate::helpers::dry_run::{self, ChangeKind};
+use crate::helpers::paths;
+use crate::helpers::redact::redact;
+use crate::helpers::system::{get_system_status_enhanced, EnhancedSystemStatus};
+use crate::modules::{ModuleResult, ModuleStatus};
+use chrono::Local;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory holding `<id>.json` and `<id>.md` for every reported run
+pub const REPORTS_DIR: &str = "/var/lib/flux/reports";
+
+lazy_static! {
+    static ref ACTIVE: Mutex<Option<(PathBuf, RunReport)>> = Mutex::new(None);
+}
+
+/// A file touched during a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub kind: ChangeKind,
+    pub path: String,
+}
+
+/// Everything that happened during one `load`, `apply` or `workflow` run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Same as the rollback journal's run id when one was opened
+    pub id: String,
+    /// Command line that started the run, e.g. `workflow security`
+    pub command: String,
+    pub started: String,
+    pub finished: Option<String>,
+    pub success: bool,
+    /// Changes and commands were only planned, not performed
+    pub dry_run: bool,
+    /// Host facts taken when the run started
+    pub host: Option<EnhancedSystemStatus>,
+    pub modules: Vec<ModuleResult>,
+    pub files_changed: Vec<FileChange>,
+    pub commands: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Start collecting a report for this run under [`REPORTS_DIR`]
+///
+/// `id` reuses the rollback journal's run id; a new one is generated when the
+/// run is not journaled (e.g. in dry-run mode).
+pub fn begin(command: &str, id: Option<&str>) -> Result<String> {
+    begin_in(
+        &paths::resolve(REPORTS_DIR),
+        command,
+        id,
+        get_system_status_enhanced().ok(),
+    )
+}
+
+/// Start collecting a report under `root` with the given host facts
+pub fn begin_in(
+    root: &Path,
+    command: &str,
+    id: Option<&str>,
+    host: Option<EnhancedSystemStatus>,
+) -> Result<String> {
+    fs::create_dir_all(root)?;
+
+    let now = Local::now();
+    let id = match id {
+        Some(id) => id.to_string(),
+        None => {
+            let mut id = now.format("%Y%m%d-%H%M%S").to_string();
+            let mut suffix = 1;
+            while report_path(root, &id, "json").exists() {
+                suffix += 1;
+                id = format!("{}-{}", now.format("%Y%m%d-%H%M%S"), suffix);
+            }
+            id
+        }
+    };
+
+    let report = RunReport {
+        id: id.clone(),
+        command: command.to_string(),
+        started: now.to_rfc3339(),
+        finished: None,
+        success: false,
+        dry_run: dry_run::is_dry_run(),
+        host,
+        modules: Vec::new(),
+        files_changed: Vec::new(),
+        commands: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    *ACTIVE.lock() = Some((root.to_path_buf(), report));
+    Ok(id)
+}
+
+/// Record a file created, modified or removed by the active run
+pub fn record_file(kind: ChangeKind, path: &Path) {
+    if let Some((_, report)) = ACTIVE.lock().as_mut() {
+        report.files_changed.push(FileChange {
+            kind,
+            path: path.display().to_string(),
+        });
+    }
+}
+
+/// Record a command executed by the active run
+pub fn record_command(command: &str, args: &[&str]) {
+    if let Some((_, report)) = ACTIVE.lock().as_mut() {
+        report.commands.push(redact(&command_line(command, args)).into_owned());
+    }
+}
+
+/// Record module outcomes of the active run
+pub fn record_modules(results: &[ModuleResult]) {
+    if let Some((_, report)) = ACTIVE.lock().as_mut() {
+        report.modules.extend(results.iter().cloned().map(|mut result| {
+            result.message = result.message.map(|m| redact(&m).into_owned());
+            result
+        }));
+    }
+}
+
+/// Record an error logged during the active run
+pub fn record_error(message: &str) {
+    if let Some((_, report)) = ACTIVE.lock().as_mut() {
+        report.errors.push(redact(message).into_owned());
+    }
+}
+
+/// Close the active report and write it as JSON and Markdown
+///
+/// `error` is the error the run ended with, if any. Returns the report id, or
+/// `None` when no report was open.
+pub fn finish(error: Option<String>) -> Result<Option<String>> {
+    let Some((root, mut report)) = ACTIVE.lock().take() else {
+        return Ok(None);
+    };
+
+    report.finished = Some(Local::now().to_rfc3339());
+    report.success = error.is_none();
+    if let Some(error) = error.map(|e| redact(&e).into_owned()) {
+        if !report.errors.contains(&error) {
+            report.errors.push(error);
+        }
+    }
+
+    // Nothing was performed in dry-run mode, so report what would have been
+    if report.dry_run {
+        for change in dry_run::planned_changes() {
+            match change.kind {
+                ChangeKind::Command => report.commands.push(change.target),
+                ChangeKind::ServiceRestart => {
+                    report.commands.push(format!("systemctl restart {}", change.target))
+                }
+                kind => report.files_changed.push(FileChange {
+                    kind,
+                    path: change.target,
+                }),
+            }
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|e| FluxError::system(format!("Failed to serialize run report: {}", e)))?;
+    fs::write(report_path(&root, &report.id, "json"), json)?;
+    fs::write(report_path(&root, &report.id, "md"), render_markdown(&report))?;
+
+    Ok(Some(report.id))
+}
+
+/// Load a stored report
+pub fn load(root: &Path, id: &str) -> Result<RunReport> {
+    if id.is_empty() || id.contains('/') || id.contains("..") {
+        return Err(FluxError::validation(format!("Invalid report id: {}", id)));
+    }
+
+    let path = report_path(root, id, "json");
+    if !path.exists() {
+        return Err(FluxError::not_found(format!("Report '{}' not found", id)));
+    }
+
+    let json = fs::read_to_string(&path)?;
+    serde_json::from_str(&json)
+        .map_err(|e| FluxError::parse(format!("Invalid report {}: {}", path.display(), e)))
+}
+
+/// List all stored reports, oldest first
+pub fn list(root: &Path) -> Result<Vec<RunReport>> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut reports: Vec<RunReport> = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            load(root, &path.file_stem()?.to_string_lossy()).ok()
+        })
+        .collect();
+    reports.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(reports)
+}
+
+/// Render a report as Markdown
+pub fn render_markdown(report: &RunReport) -> String {
+    let mut md = String::new();
+
+    let _ = writeln!(md, "# Flux run report {}", report.id);
+    let _ = writeln!(md);
+    let _ = writeln!(md, "- **Command:** `flux {}`", report.command);
+    if let Some(host) = &report.host {
+        let _ = writeln!(md, "- **Host:** {} ({})", host.basic.hostname, host.basic.os_info);
+    }
+    let _ = writeln!(md, "- **Started:** {}", report.started);
+    let _ = writeln!(md, "- **Finished:** {}", report.finished.as_deref().unwrap_or("-"));
+    let _ = writeln!(
+        md,
+        "- **Result:** {}{}",
+        if report.success { "success" } else { "failed" },
+        if report.dry_run { " (dry run, nothing was changed)" } else { "" }
+    );
+
+    if !report.modules.is_empty() {
+        let _ = writeln!(md, "\n## Modules\n");
+        let _ = writeln!(md, "| Module | Status | Duration | Message |");
+        let _ = writeln!(md, "|--------|--------|----------|---------|");
+        for result in &report.modules {
+            let status = match result.status {
+                ModuleStatus::Completed => "✓ completed",
+                ModuleStatus::Failed => "✗ failed",
+                ModuleStatus::Skipped => "○ skipped",
+                ModuleStatus::SkippedByCondition => "– skipped by condition",
+            };
+            let _ = writeln!(
+                md,
+                "| {} | {} | {:.1}s | {} |",
+                result.module,
+                status,
+                result.duration_ms as f64 / 1000.0,
+                result.message.as_deref().unwrap_or("").replace('|', "\\|")
+            );
+        }
+    }
+
+    let _ = writeln!(md, "\n## Files changed\n");
+    if report.files_changed.is_empty() {
+        let _ = writeln!(md, "None");
+    }
+    for change in &report.files_changed {
+        let _ = writeln!(md, "- `{}` {}", change.kind, change.path);
+    }
+
+    let _ = writeln!(md, "\n## Commands executed\n");
+    if report.commands.is_empty() {
+        let _ = writeln!(md, "None");
+    }
+    for command in &report.commands {
+        let _ = writeln!(md, "- `{}`", command);
+    }
+
+    if !report.errors.is_empty() {
+        let _ = writeln!(md, "\n## Errors\n");
+        for error in &report.errors {
+            let _ = writeln!(md, "- {}", error);
+        }
+    }
+
+    if let Some(host) = &report.host {
+        let _ = writeln!(md, "\n## Host facts\n");
+        let _ = writeln!(md, "| Fact | Value |");
+        let _ = writeln!(md, "|------|-------|");
+        let facts = [
+            ("Kernel", host.kernel_version.clone()),
+            ("Installed kernel", host.installed_kernel.clone()),
+            ("Architecture", host.basic.architecture.clone()),
+            ("Primary IP", host.basic.primary_ip.clone()),
+            ("Gateway", host.basic.gateway.clone()),
+            ("Memory", host.basic.memory_usage.clone()),
+            ("Disk (/)", host.basic.disk_usage.clone()),
+            ("Load (1 min)", format!("{:.2}", host.system_load_1min)),
+            ("Swap", format!("{:.0}%", host.swap_usage.percentage)),
+            ("Security updates", host.security_updates.to_string()),
+            ("Reboot required", host.reboot_required.to_string()),
+            ("Failed services", host.failed_services.join(", ")),
+        ];
+        for (fact, value) in facts {
+            let _ = writeln!(md, "| {} | {} |", fact, value);
+        }
+    }
+
+    md
+}
+
+fn report_path(root: &Path, id: &str, extension: &str) -> PathBuf {
+    root.join(format!("{}.{}", id, extension))
+}
+
+fn command_line(command: &str, args: &[&str]) -> String {
+    if args.is_empty() {
+        command.to_string()
+    } else {
+        format!("{} {}", command, args.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_report_round_trip() {
+        let root = TempDir::new().unwrap();
+
+        let id = begin_in(root.path(), "workflow test", Some("20250101-120000"), None).unwrap();
+        record_file(ChangeKind::WriteFile, Path::new("/etc/ssh/sshd_config"));
+        record_command("systemctl", &["restart", "ssh"]);
+        record_modules(&[
+            ModuleResult::new("ssh", &Ok(()), Duration::from_millis(1500)),
+            ModuleResult::skipped("netdata", "not available on this system"),
+        ]);
+        record_error("firewall: ufw not installed");
+        assert_eq!(finish(Some("Workflow failed".to_string())).unwrap(), Some(id.clone()));
+        assert_eq!(finish(None).unwrap(), None);
+
+        let report = load(root.path(), &id).unwrap();
+        assert!(!report.success);
+        assert_eq!(report.modules.len(), 2);
+        assert_eq!(report.commands, vec!["systemctl restart ssh"]);
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(list(root.path()).unwrap().len(), 1);
+
+        let md = fs::read_to_string(root.path().join("20250101-120000.md")).unwrap();
+        assert!(md.contains("| ssh | ✓ completed | 1.5s |  |"));
+        assert!(md.contains("- `write` /etc/ssh/sshd_config"));
+        assert!(load(root.path(), "../etc").is_err());
+    }
+}
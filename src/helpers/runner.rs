@@ -0,0 +1,379 @@
+//! Pluggable execution of external commands
+//!
+//! Every external command flux runs goes through the process-wide
+//! [`CommandRunner`]. [`SystemRunner`] spawns real processes, [`DryRunRunner`]
+//! records changing commands instead of running them, [`RootRunner`] confines
+//! them to the `--root` tree, and [`RecordingRunner`]
+//! (or `MockCommandRunner` in unit tests) lets module logic be tested without
+//! root.
+
+use crate::error::{FluxError, Result};
+use crate::helpers::dry_run;
+use crate::helpers::logging::log_warn;
+use crate::helpers::paths;
+use crate::helpers::report;
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, RwLock};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Package managers hold a system-wide lock, so modules running concurrently
+/// must not invoke them at the same time
+const PACKAGE_MANAGERS: &[&str] = &["apt", "apt-get", "dpkg", "dnf", "yum", "rpm", "zypper", "pacman"];
+
+lazy_static! {
+    static ref RUNNER: RwLock<Arc<dyn CommandRunner>> = RwLock::new(Arc::new(SystemRunner));
+    static ref PACKAGE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A command to run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Written to the command's stdin
+    pub stdin: Option<String>,
+    /// Extra environment variables
+    pub env: Vec<(String, String)>,
+    /// The command only inspects the system and also runs in dry-run mode
+    pub read_only: bool,
+}
+
+impl Invocation {
+    /// A command that changes the system
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// A command that only inspects the system
+    pub fn query(program: &str, args: &[&str]) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(program, args)
+        }
+    }
+
+    pub fn stdin(mut self, input: impl Into<String>) -> Self {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// The command line as it would be typed, e.g. `ufw allow 22/tcp`
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Captured result of a finished command
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub success: bool,
+    /// Exit code, `None` when the command was killed by a signal
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    /// A successful run printing `stdout`
+    pub fn ok(stdout: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            code: Some(0),
+            stdout: stdout.into(),
+            stderr: String::new(),
+        }
+    }
+
+    /// A failed run with exit `code` printing `stderr`
+    pub fn failed(code: i32, stderr: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            code: Some(code),
+            stdout: String::new(),
+            stderr: stderr.into(),
+        }
+    }
+
+    /// Turn a non-zero exit status into an error
+    pub fn check(self, program: &str) -> Result<Self> {
+        if self.success {
+            Ok(self)
+        } else {
+            Err(FluxError::command_failed(format!("{} failed: {}", program, self.stderr)))
+        }
+    }
+}
+
+/// Executes external commands
+#[cfg_attr(test, mockall::automock)]
+pub trait CommandRunner: Send + Sync {
+    /// Run `invocation` to completion
+    ///
+    /// A non-zero exit status is not an error; only failing to start the
+    /// command is.
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput>;
+}
+
+/// Runs commands as real processes
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput> {
+        let program = invocation.program.as_str();
+        let _package_lock = (!invocation.read_only && PACKAGE_MANAGERS.contains(&program))
+            .then(|| PACKAGE_LOCK.lock());
+
+        let mut command = Command::new(program);
+        command
+            .args(&invocation.args)
+            .envs(invocation.env.iter().map(|(k, v)| (k, v)))
+            .stdin(if invocation.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| FluxError::command_failed(format!("Failed to execute {}: {}", program, e)))?;
+
+        if let (Some(input), Some(mut stdin)) = (&invocation.stdin, child.stdin.take()) {
+            stdin.write_all(input.as_bytes()).map_err(|e| {
+                FluxError::command_failed(format!("Failed to write to {}: {}", program, e))
+            })?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| FluxError::command_failed(format!("Failed to wait for {}: {}", program, e)))?;
+
+        if output.status.success() && !invocation.read_only {
+            let args: Vec<&str> = invocation.args.iter().map(String::as_str).collect();
+            report::record_command(program, &args);
+        }
+
+        Ok(CommandOutput {
+            success: output.status.success(),
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Records commands that change the system instead of running them
+///
+/// Read-only commands are passed to the wrapped runner.
+pub struct DryRunRunner {
+    inner: Arc<dyn CommandRunner>,
+}
+
+impl DryRunRunner {
+    pub fn new(inner: Arc<dyn CommandRunner>) -> Self {
+        Self { inner }
+    }
+}
+
+impl CommandRunner for DryRunRunner {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput> {
+        if invocation.read_only {
+            return self.inner.run(invocation);
+        }
+
+        let args: Vec<&str> = invocation.args.iter().map(String::as_str).collect();
+        dry_run::record_command(&invocation.program, &args);
+        Ok(CommandOutput::ok(""))
+    }
+}
+
+/// Keeps commands inside the `--root` tree
+///
+/// When the root holds a complete system (it has `/bin/sh`), every command is
+/// run inside it with `chroot`. Otherwise read-only commands inspect the host
+/// and changing commands are skipped, since they would act on the host.
+pub struct RootRunner {
+    root: PathBuf,
+    inner: Arc<dyn CommandRunner>,
+}
+
+impl RootRunner {
+    pub fn new(root: PathBuf, inner: Arc<dyn CommandRunner>) -> Self {
+        Self { root, inner }
+    }
+}
+
+impl CommandRunner for RootRunner {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput> {
+        if self.root.join("bin/sh").exists() {
+            // Paths already resolved into the tree are absolute inside the chroot
+            let root = self.root.to_string_lossy().into_owned();
+            let in_tree = invocation.args.iter().map(|arg| match arg.strip_prefix(&root) {
+                Some(rest) if rest.starts_with('/') => rest.to_string(),
+                _ => arg.clone(),
+            });
+
+            let mut chrooted = invocation.clone();
+            chrooted.program = "chroot".to_string();
+            chrooted.args = [root.clone(), invocation.program.clone()]
+                .into_iter()
+                .chain(in_tree)
+                .collect();
+            return self.inner.run(&chrooted);
+        }
+
+        if invocation.read_only {
+            return self.inner.run(invocation);
+        }
+
+        log_warn(format!(
+            "Not running '{}': {} is not a chroot",
+            invocation.command_line(),
+            self.root.display()
+        ));
+        Ok(CommandOutput::ok(""))
+    }
+}
+
+/// Records every invocation and answers with canned output
+///
+/// Commands without a canned response succeed with empty output.
+#[derive(Default)]
+pub struct RecordingRunner {
+    calls: Mutex<Vec<Invocation>>,
+    responses: Mutex<Vec<(String, CommandOutput)>>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer commands whose command line starts with `prefix`
+    ///
+    /// Later responses take precedence over earlier ones.
+    pub fn respond(&self, prefix: &str, output: CommandOutput) {
+        self.responses.lock().push((prefix.to_string(), output));
+    }
+
+    /// Every invocation so far, in order
+    pub fn calls(&self) -> Vec<Invocation> {
+        self.calls.lock().clone()
+    }
+
+    /// Command lines of every invocation so far, in order
+    pub fn command_lines(&self) -> Vec<String> {
+        self.calls.lock().iter().map(Invocation::command_line).collect()
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, invocation: &Invocation) -> Result<CommandOutput> {
+        self.calls.lock().push(invocation.clone());
+
+        let line = invocation.command_line();
+        let output = self
+            .responses
+            .lock()
+            .iter()
+            .rev()
+            .find(|(prefix, _)| line.starts_with(prefix.as_str()))
+            .map(|(_, output)| output.clone())
+            .unwrap_or_else(|| CommandOutput::ok(""));
+        Ok(output)
+    }
+}
+
+/// Replace the process-wide runner, returning the previous one
+pub fn set_runner(runner: Arc<dyn CommandRunner>) -> Arc<dyn CommandRunner> {
+    std::mem::replace(&mut *RUNNER.write(), runner)
+}
+
+/// The runner to use for the next command, honouring dry-run mode and `--root`
+pub fn runner() -> Arc<dyn CommandRunner> {
+    let mut runner = Arc::clone(&*RUNNER.read());
+    if let Some(root) = paths::root() {
+        runner = Arc::new(RootRunner::new(root, runner));
+    }
+    if dry_run::is_dry_run() {
+        Arc::new(DryRunRunner::new(runner))
+    } else {
+        runner
+    }
+}
+
+/// Run `invocation` with the current runner
+pub fn run(invocation: &Invocation) -> Result<CommandOutput> {
+    runner().run(invocation)
+}
+
+/// Run a read-only `invocation` on the machine flux runs on, even with `--root`
+///
+/// For flux's own work rather than inspecting the managed system, e.g.
+/// decrypting secrets with a local key.
+pub fn run_on_host(invocation: &Invocation) -> Result<CommandOutput> {
+    debug_assert!(invocation.read_only);
+    let runner = Arc::clone(&*RUNNER.read());
+    runner.run(invocation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn test_recording_runner_responses() {
+        let runner = RecordingRunner::new();
+        runner.respond("ufw status", CommandOutput::ok("Status: active"));
+        runner.respond("ufw", CommandOutput::failed(1, "denied"));
+
+        let status = runner.run(&Invocation::query("ufw", &["status"])).unwrap();
+        let allow = runner.run(&Invocation::new("ufw", &["allow", "22"])).unwrap();
+        let other = runner.run(&Invocation::new("true", &[])).unwrap();
+
+        // The later, broader response wins
+        assert!(!status.success);
+        assert_eq!(allow.stderr, "denied");
+        assert!(other.success);
+        assert_eq!(runner.command_lines(), vec!["ufw status", "ufw allow 22", "true"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_dry_run_only_passes_queries_through() {
+        let mut mock = MockCommandRunner::new();
+        mock.expect_run()
+            .withf(|inv| inv.read_only && inv.program == "sysctl")
+            .times(1)
+            .returning(|_| Ok(CommandOutput::ok("1\n")));
+
+        dry_run::take_planned_changes();
+        let runner = DryRunRunner::new(Arc::new(mock));
+
+        let value = runner.run(&Invocation::query("sysctl", &["-n", "net.ipv4.ip_forward"])).unwrap();
+        let applied = runner.run(&Invocation::new("sysctl", &["-p"])).unwrap();
+
+        assert_eq!(value.stdout, "1\n");
+        assert!(applied.success);
+        let changes = dry_run::take_planned_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].target, "sysctl -p");
+    }
+}
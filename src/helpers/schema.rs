@@ -0,0 +1,310 @@
+//! Typed configuration schemas
+//!
+//! Every table of flux.toml with a fixed set of keys is described twice: by the
+//! serde struct that reads it and by a JSON Schema for editors. [`ConfigSchema`]
+//! ties the two together, and [`check_section`] re-reads a module's table from
+//! the original file text so that errors carry line numbers.
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+/// JSON Schema dialect understood by the common TOML editor plugins
+pub const JSON_SCHEMA_DIALECT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// A configuration table that can describe itself as JSON Schema
+pub trait ConfigSchema: Serialize + DeserializeOwned + Default {
+    /// Schema of the table, usually built with [`object`]
+    fn schema() -> Value;
+
+    /// [`ConfigSchema::schema`] with each property's default filled in from `Default`
+    fn json_schema() -> Value {
+        with_defaults(Self::schema(), &Self::default())
+    }
+}
+
+/// A module's typed `[modules.<name>]` settings
+#[derive(Clone, Copy)]
+pub struct SectionSchema {
+    schema: fn() -> Value,
+    check: fn(&str, &str) -> std::result::Result<(), toml::de::Error>,
+}
+
+impl SectionSchema {
+    pub fn of<T: ConfigSchema>() -> Self {
+        Self {
+            schema: T::json_schema,
+            check: check_section::<T>,
+        }
+    }
+
+    /// JSON Schema of the table
+    pub fn json_schema(&self) -> Value {
+        (self.schema)()
+    }
+
+    /// Check `[modules.<module>]` in the configuration text `contents`
+    pub fn check(&self, contents: &str, module: &str) -> std::result::Result<(), toml::de::Error> {
+        (self.check)(contents, module)
+    }
+}
+
+/// A problem found while validating a configuration file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    /// 1-based line, 0 when the location is unknown
+    pub line: usize,
+    /// 1-based column, 0 when the location is unknown
+    pub column: usize,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    /// Locate a TOML error within the text it was read from
+    pub fn from_toml(contents: &str, error: &toml::de::Error, context: Option<&str>) -> Self {
+        let (line, column) = error
+            .span()
+            .map(|span| position(contents, span))
+            .unwrap_or((0, 0));
+        let message = match context {
+            Some(context) => format!("{} {}", context, error.message()),
+            None => error.message().to_string(),
+        };
+
+        Self {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+/// 1-based line and column of the start of `span`
+fn position(contents: &str, span: Range<usize>) -> (usize, usize) {
+    let before = &contents[..span.start.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+/// Deserialize `[modules.<module>]` of `contents` as `T`, ignoring everything else
+///
+/// Going through the document deserializer rather than a parsed `toml::Value`
+/// keeps the spans, so the error points at the offending key or value.
+pub fn check_section<T: DeserializeOwned>(
+    contents: &str,
+    module: &str,
+) -> std::result::Result<(), toml::de::Error> {
+    check_table::<T>(contents, "modules", module)
+}
+
+/// Location of `[<table>.<name>]` in `contents`, `(0, 0)` if it is missing
+pub fn section_position(contents: &str, table: &str, name: &str) -> (usize, usize) {
+    match check_table::<Marker>(contents, table, name) {
+        Err(e) => e.span().map(|span| position(contents, span)).unwrap_or((0, 0)),
+        Ok(()) => (0, 0),
+    }
+}
+
+fn check_table<T: DeserializeOwned>(
+    contents: &str,
+    table: &str,
+    name: &str,
+) -> std::result::Result<(), toml::de::Error> {
+    let seed = TableSeed {
+        key: table,
+        inner: TableSeed {
+            key: name,
+            inner: PhantomData::<T>,
+        },
+    };
+    seed.deserialize(toml::Deserializer::new(contents))
+}
+
+/// Fails on purpose, so the error carries the span of the value it was read from
+struct Marker;
+
+impl<'de> Deserialize<'de> for Marker {
+    fn deserialize<D: Deserializer<'de>>(_deserializer: D) -> std::result::Result<Self, D::Error> {
+        Err(de::Error::custom(""))
+    }
+}
+
+/// Walks a table, handing the value under `key` to `inner` and skipping the rest
+#[derive(Clone, Copy)]
+struct TableSeed<'a, S> {
+    key: &'a str,
+    inner: S,
+}
+
+impl<'de, S: DeserializeSeed<'de> + Copy> DeserializeSeed<'de> for TableSeed<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de> + Copy> Visitor<'de> for TableSeed<'_, S> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a table containing `{}`", self.key)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.key {
+                map.next_value_seed(self.inner)?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An object with a fixed set of properties
+pub fn object(description: &str, properties: Vec<(&str, Value)>) -> Value {
+    let properties: Map<String, Value> = properties
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    described(
+        json!({ "type": "object", "properties": properties, "additionalProperties": false }),
+        description,
+    )
+}
+
+/// An object with arbitrary keys whose values all match `values`
+pub fn map(description: &str, values: Value) -> Value {
+    described(json!({ "type": "object", "additionalProperties": values }), description)
+}
+
+pub fn array(description: &str, items: Value) -> Value {
+    described(json!({ "type": "array", "items": items }), description)
+}
+
+pub fn boolean(description: &str) -> Value {
+    described(json!({ "type": "boolean" }), description)
+}
+
+pub fn string(description: &str) -> Value {
+    described(json!({ "type": "string" }), description)
+}
+
+/// A string limited to `values`
+pub fn one_of(description: &str, values: &[&str]) -> Value {
+    described(json!({ "type": "string", "enum": values }), description)
+}
+
+/// A secret: the value itself or a `file`, `env` or `age` reference
+pub fn secret(description: &str) -> Value {
+    let reference = |key: &str| {
+        json!({
+            "type": "object",
+            "properties": { key: { "type": "string" } },
+            "required": [key],
+            "additionalProperties": false,
+        })
+    };
+    described(
+        json!({
+            "oneOf": [
+                { "type": "string" },
+                reference("file"),
+                reference("env"),
+                reference("age"),
+            ]
+        }),
+        description,
+    )
+}
+
+/// A non-negative integer
+pub fn unsigned(description: &str) -> Value {
+    described(json!({ "type": "integer", "minimum": 0 }), description)
+}
+
+/// A TCP or UDP port number
+pub fn port(description: &str) -> Value {
+    described(json!({ "type": "integer", "minimum": 0, "maximum": 65535 }), description)
+}
+
+fn described(mut schema: Value, description: &str) -> Value {
+    if !description.is_empty() {
+        schema["description"] = Value::from(description);
+    }
+    schema
+}
+
+/// Copy the values of `defaults` into the matching properties of `schema`
+pub fn with_defaults<T: Serialize>(mut schema: Value, defaults: &T) -> Value {
+    let Ok(Value::Object(defaults)) = serde_json::to_value(defaults) else {
+        return schema;
+    };
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        for (name, property) in properties.iter_mut() {
+            match defaults.get(name) {
+                Some(Value::Null) | None => {}
+                Some(default) => property["default"] = default.clone(),
+            }
+        }
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct Example {
+        port: u16,
+        users: Vec<String>,
+    }
+
+    impl ConfigSchema for Example {
+        fn schema() -> Value {
+            object(
+                "Example",
+                vec![
+                    ("port", port("Listening port")),
+                    ("users", array("", string(""))),
+                ],
+            )
+        }
+    }
+
+    const CONTENTS: &str = "[general]\nmode = \"auto\"\n\n[modules.good]\nport = 22\n\n[modules.typo]\nprot = 22\n\n[modules.bad]\nusers = [\"a\", 1]\n";
+
+    #[test]
+    fn test_check_section_reports_position() {
+        assert!(check_section::<Example>(CONTENTS, "good").is_ok());
+        assert!(check_section::<Example>(CONTENTS, "missing").is_ok());
+
+        let typo = check_section::<Example>(CONTENTS, "typo").unwrap_err();
+        let issue = ConfigIssue::from_toml(CONTENTS, &typo, Some("[modules.typo]"));
+        assert_eq!((issue.line, issue.column), (8, 1));
+        assert!(issue.message.starts_with("[modules.typo] unknown field `prot`"));
+
+        let bad = check_section::<Example>(CONTENTS, "bad").unwrap_err();
+        let issue = ConfigIssue::from_toml(CONTENTS, &bad, None);
+        assert_eq!((issue.line, issue.column), (11, 15));
+
+        assert_eq!(section_position(CONTENTS, "modules", "bad").0, 10);
+    }
+
+    #[test]
+    fn test_json_schema_includes_defaults() {
+        let schema = Example::json_schema();
+        assert_eq!(schema["properties"]["port"]["default"], json!(0));
+        assert_eq!(schema["properties"]["port"]["maximum"], json!(65535));
+        assert_eq!(schema["properties"]["users"]["items"]["type"], json!("string"));
+        assert_eq!(schema["additionalProperties"], json!(false));
+    }
+}
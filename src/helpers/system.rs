@@ -1,11 +1,17 @@
 use crate::error::{FluxError, Result};
+use crate::helpers::changes;
+use crate::helpers::dry_run::{self, ChangeKind};
+use crate::helpers::facts;
 use crate::helpers::logging::{log_info, log_warn};
+use crate::helpers::network;
+use crate::helpers::paths;
+use crate::helpers::runner::{self, CommandOutput, Invocation};
 use crate::helpers::user_input::prompt_yes_no;
+use crate::outln;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::process::Command;
 use sysinfo::System;
 
 /// Linux distribution types
@@ -23,6 +29,12 @@ pub enum Distribution {
     Unknown(String),
 }
 
+impl Default for Distribution {
+    fn default() -> Self {
+        Distribution::Unknown("unknown".to_string())
+    }
+}
+
 impl Distribution {
     /// Check if this is a Debian-based distribution
     pub fn is_debian_based(&self) -> bool {
@@ -80,10 +92,10 @@ pub struct SystemStatus {
     pub updates_available: u32,
 }
 
-/// Detect the Linux distribution
+/// Detect the Linux distribution, of the `--root` tree when one is set
 pub fn detect_distro() -> Result<Distribution> {
     // Try /etc/os-release first (most modern systems)
-    if let Ok(contents) = fs::read_to_string("/etc/os-release") {
+    if let Ok(contents) = fs::read_to_string(paths::resolve("/etc/os-release")) {
         for line in contents.lines() {
             if line.starts_with("ID=") {
                 let id = line.trim_start_matches("ID=").trim_matches('"').to_lowercase();
@@ -104,11 +116,11 @@ pub fn detect_distro() -> Result<Distribution> {
     }
     
     // Fallback to other methods
-    if fs::read_to_string("/etc/debian_version").is_ok() {
+    if fs::read_to_string(paths::resolve("/etc/debian_version")).is_ok() {
         return Ok(Distribution::Debian);
     }
     
-    if fs::read_to_string("/etc/redhat-release").is_ok() {
+    if fs::read_to_string(paths::resolve("/etc/redhat-release")).is_ok() {
         return Ok(Distribution::RHEL);
     }
     
@@ -194,7 +206,7 @@ pub fn get_system_status() -> Result<SystemStatus> {
     
     // Network information
     let primary_ip = get_primary_ip().unwrap_or_else(|_| "Unknown".to_string());
-    let gateway = get_default_gateway().unwrap_or_else(|_| "Unknown".to_string());
+    let gateway = network::get_default_gateway().unwrap_or_else(|_| "Unknown".to_string());
     
     // Check services
     let mut services = HashMap::new();
@@ -230,25 +242,21 @@ pub fn get_system_status() -> Result<SystemStatus> {
 /// Get primary IP address
 pub fn get_primary_ip() -> Result<String> {
     // Try to get IP from hostname command first
-    if let Ok(output) = Command::new("hostname").arg("-I").output() {
-        if output.status.success() {
-            if let Ok(ips) = String::from_utf8(output.stdout) {
-                if let Some(ip) = ips.split_whitespace().next() {
-                    return Ok(ip.to_string());
-                }
+    if let Ok(output) = command_output("hostname", &["-I"]) {
+        if output.success {
+            if let Some(ip) = output.stdout.split_whitespace().next() {
+                return Ok(ip.to_string());
             }
         }
     }
     
     // Fallback: get IP from default route
-    if let Ok(output) = Command::new("ip").args(&["route", "get", "1"]).output() {
-        if output.status.success() {
-            if let Ok(route) = String::from_utf8(output.stdout) {
-                for part in route.split_whitespace() {
-                    if let Ok(addr) = part.parse::<std::net::IpAddr>() {
-                        if !addr.is_loopback() {
-                            return Ok(addr.to_string());
-                        }
+    if let Ok(output) = command_output("ip", &["route", "get", "1"]) {
+        if output.success {
+            for part in output.stdout.split_whitespace() {
+                if let Ok(addr) = part.parse::<std::net::IpAddr>() {
+                    if !addr.is_loopback() {
+                        return Ok(addr.to_string());
                     }
                 }
             }
@@ -258,75 +266,46 @@ pub fn get_primary_ip() -> Result<String> {
     Err(FluxError::network("Could not determine primary IP address"))
 }
 
-/// Get default gateway
-pub fn get_default_gateway() -> Result<String> {
-    if let Ok(output) = Command::new("ip").args(&["route", "show", "default"]).output() {
-        if output.status.success() {
-            if let Ok(route) = String::from_utf8(output.stdout) {
-                // Parse: default via X.X.X.X dev ...
-                let parts: Vec<&str> = route.split_whitespace().collect();
-                if parts.len() >= 3 && parts[0] == "default" && parts[1] == "via" {
-                    return Ok(parts[2].to_string());
-                }
-            }
-        }
-    }
-    
-    Err(FluxError::network("Could not determine default gateway"))
-}
-
 /// Check if a service is active
 pub fn is_service_active(service: &str) -> Result<bool> {
     if !has_systemd() {
         return Err(FluxError::unsupported("systemd not available"));
     }
     
-    let output = Command::new("systemctl")
-        .args(&["is-active", service])
-        .output()?;
+    let output = command_output("systemctl", &["is-active", service])?;
     
-    Ok(output.status.success())
+    Ok(output.success)
 }
 
 /// Check for available updates
 pub fn check_updates_available() -> Result<u32> {
-    let distro = detect_distro()?;
+    let distro = facts::get().os.distribution.clone();
     
     if distro.is_debian_based() {
         // Check apt for updates
-        if let Ok(output) = Command::new("apt")
-            .args(&["list", "--upgradable"])
-            .output()
-        {
-            if output.status.success() {
-                if let Ok(list) = String::from_utf8(output.stdout) {
-                    // Count lines that contain "upgradable"
-                    let count = list.lines()
-                        .filter(|line| line.contains("upgradable"))
-                        .count();
-                    return Ok(count as u32);
-                }
+        if let Ok(output) = command_output("apt", &["list", "--upgradable"]) {
+            if output.success {
+                // Count lines that contain "upgradable"
+                let count = output.stdout.lines()
+                    .filter(|line| line.contains("upgradable"))
+                    .count();
+                return Ok(count as u32);
             }
         }
     } else if distro.is_redhat_based() {
         // Check yum/dnf for updates
         let pkg_manager = if which::which("dnf").is_ok() { "dnf" } else { "yum" };
         
-        if let Ok(output) = Command::new(pkg_manager)
-            .args(&["check-update"])
-            .output()
-        {
+        if let Ok(output) = command_output(pkg_manager, &["check-update"]) {
             // yum/dnf returns 100 when updates are available
-            if output.status.code() == Some(100) {
-                if let Ok(list) = String::from_utf8(output.stdout) {
-                    // Count non-empty lines after the header
-                    let count = list.lines()
-                        .skip_while(|line| !line.is_empty())
-                        .skip(1)
-                        .filter(|line| !line.is_empty() && !line.starts_with(' '))
-                        .count();
-                    return Ok(count as u32);
-                }
+            if output.code == Some(100) {
+                // Count non-empty lines after the header
+                let count = output.stdout.lines()
+                    .skip_while(|line| !line.is_empty())
+                    .skip(1)
+                    .filter(|line| !line.is_empty() && !line.starts_with(' '))
+                    .count();
+                return Ok(count as u32);
             }
         }
     }
@@ -335,21 +314,27 @@ pub fn check_updates_available() -> Result<u32> {
 }
 
 /// Execute a command with error handling
+///
+/// Runs through the current [`runner`], so in dry-run mode the command is
+/// recorded instead of executed and an empty output is returned. Use
+/// [`query_command`] for read-only inspection.
 pub fn execute_command(command: &str, args: &[&str]) -> Result<String> {
-    let output = Command::new(command)
-        .args(args)
-        .output()
-        .map_err(|e| FluxError::command_failed(format!("Failed to execute {}: {}", command, e)))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(FluxError::command_failed(format!(
-            "{} failed: {}",
-            command, stderr
-        )));
-    }
-    
-    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    let output = runner::run(&Invocation::new(command, args))?.check(command)?;
+    Ok(output.stdout)
+}
+
+/// Execute a read-only command that never modifies the system
+///
+/// Unlike [`execute_command`] this always runs, even in dry-run mode.
+pub fn query_command(command: &str, args: &[&str]) -> Result<String> {
+    Ok(command_output(command, args)?.check(command)?.stdout)
+}
+
+/// Run a read-only command and return its output whatever the exit status
+///
+/// Fails only when the command cannot be started.
+pub fn command_output(command: &str, args: &[&str]) -> Result<CommandOutput> {
+    runner::run(&Invocation::query(command, args))
 }
 
 /// Check if a command exists
@@ -372,25 +357,33 @@ pub fn get_os_info() -> Result<String> {
 }
 
 /// Restart a systemd service
+///
+/// Callers only restart after a change to the service's configuration, so the
+/// restart is always reported as a change.
 pub fn restart_service(service: &str) -> Result<()> {
     if !has_systemd() {
         return Err(FluxError::unsupported("systemd not available"));
     }
 
+    let resource = format!("service {}", service);
+    if dry_run::is_dry_run() {
+        dry_run::record(ChangeKind::ServiceRestart, service, None);
+        changes::changed(resource, "would restart");
+        return Ok(());
+    }
+
     log_info(&format!("Restarting service: {}", service));
 
-    let output = Command::new("systemctl")
-        .args(&["restart", service])
-        .output()
-        .map_err(|e| FluxError::command_failed(format!("Failed to restart {}: {}", service, e)))?;
+    let output = runner::run(&Invocation::new("systemctl", &["restart", service]))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.success {
+        changes::failed(resource, output.stderr.trim());
         return Err(FluxError::command_failed(format!(
             "Failed to restart {}: {}",
-            service, stderr
+            service, output.stderr
         )));
     }
+    changes::changed(resource, "restarted");
 
     Ok(())
 }
@@ -426,14 +419,16 @@ pub fn check_reboot_needed() -> Result<()> {
     
     if reboot_required {
         log_warn("System reboot is required");
-        println!("{}", "⚠ System reboot is required".yellow());
+        outln!("{}", "⚠ System reboot is required".yellow());
         
-        if prompt_yes_no("Reboot system now?", false)? {
+        if dry_run::is_dry_run() {
+            dry_run::record(ChangeKind::Command, "reboot", None);
+        } else if prompt_yes_no("Reboot system now?", false)? {
             log_info("Initiating system reboot");
             execute_reboot()?;
         } else {
             log_info("Reboot postponed by user");
-            println!("{}", "Remember to reboot later to complete the configuration".yellow());
+            outln!("{}", "Remember to reboot later to complete the configuration".yellow());
         }
     } else {
         log_info("No reboot required");
@@ -450,8 +445,8 @@ fn is_reboot_required() -> Result<bool> {
     }
     
     // Check for Red Hat/CentOS kernel updates
-    if let Ok(output) = Command::new("needs-restarting").arg("-r").output() {
-        if output.status.code() == Some(1) {
+    if let Ok(output) = command_output("needs-restarting", &["-r"]) {
+        if output.code == Some(1) {
             return Ok(true);
         }
     }
@@ -473,12 +468,10 @@ fn get_installed_kernel_version() -> Result<String> {
     // Try different methods based on distribution
     
     // Method 1: dpkg (Debian/Ubuntu)
-    if let Ok(output) = Command::new("dpkg")
-        .args(&["-l", "linux-image-*"])
-        .output() 
+    if let Ok(output) = command_output("dpkg", &["-l", "linux-image-*"]) 
     {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
+        if output.success {
+            let stdout = &output.stdout;
             // Parse dpkg output for latest kernel version
             // This is a simplified implementation
             for line in stdout.lines() {
@@ -492,12 +485,9 @@ fn get_installed_kernel_version() -> Result<String> {
     }
     
     // Method 2: rpm (Red Hat/CentOS)
-    if let Ok(output) = Command::new("rpm")
-        .args(&["-q", "kernel", "--last"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
+    if let Ok(output) = command_output("rpm", &["-q", "kernel", "--last"]) {
+        if output.success {
+            let stdout = &output.stdout;
             if let Some(first_line) = stdout.lines().next() {
                 if let Some(version) = extract_kernel_version_from_rpm_line(first_line) {
                     return Ok(version);
@@ -539,14 +529,12 @@ fn extract_kernel_version_from_rpm_line(line: &str) -> Option<String> {
 /// Execute system reboot
 fn execute_reboot() -> Result<()> {
     log_info("Executing system reboot in 10 seconds...");
-    println!("{}", "System will reboot in 10 seconds...".red());
+    outln!("{}", "System will reboot in 10 seconds...".red());
     
     // Give users a chance to cancel
     std::thread::sleep(std::time::Duration::from_secs(10));
     
-    Command::new("reboot")
-        .output()
-        .map_err(|e| FluxError::command_failed(format!("Failed to execute reboot: {}", e)))?;
+    runner::run(&Invocation::new("reboot", &[]))?;
     
     Ok(())
 }
@@ -560,12 +548,9 @@ pub fn is_service_active_enhanced(service: &str) -> Result<bool> {
     }
     
     // Use systemctl to check service status
-    match Command::new("systemctl")
-        .args(&["is-active", service])
-        .output()
-    {
+    match command_output("systemctl", &["is-active", service]) {
         Ok(output) => {
-            let status = String::from_utf8_lossy(&output.stdout).trim().to_lowercase();
+            let status = output.stdout.trim().to_lowercase();
             Ok(status == "active")
         }
         Err(e) => Err(FluxError::system(format!(
@@ -577,21 +562,15 @@ pub fn is_service_active_enhanced(service: &str) -> Result<bool> {
 /// Check service status using SysV init (fallback for non-systemd systems)
 fn check_service_sysvinit(service: &str) -> Result<bool> {
     // Try service command
-    if let Ok(output) = Command::new("service")
-        .args(&[service, "status"])
-        .output()
-    {
-        return Ok(output.status.success());
+    if let Ok(output) = command_output("service", &[service, "status"]) {
+        return Ok(output.success);
     }
     
     // Try init.d script directly
     let init_script = format!("/etc/init.d/{}", service);
     if std::path::Path::new(&init_script).exists() {
-        if let Ok(output) = Command::new(&init_script)
-            .arg("status")
-            .output()
-        {
-            return Ok(output.status.success());
+        if let Ok(output) = command_output(&init_script, &["status"]) {
+            return Ok(output.success);
         }
     }
     
@@ -620,7 +599,7 @@ pub fn get_system_status_enhanced() -> Result<EnhancedSystemStatus> {
 }
 
 /// Enhanced system status structure
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct EnhancedSystemStatus {
     pub basic: crate::helpers::system::SystemStatus,
     pub kernel_version: String,
@@ -634,7 +613,7 @@ pub struct EnhancedSystemStatus {
     pub zombie_processes: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SwapUsage {
     pub total: u64,
     pub used: u64,
@@ -642,7 +621,7 @@ pub struct SwapUsage {
     pub percentage: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InodesUsage {
     pub total: u64,
     pub used: u64,
@@ -665,16 +644,13 @@ fn get_running_kernel_version() -> Result<String> {
 
 /// Count available security updates
 fn count_security_updates() -> Result<u32> {
-    let distro = crate::helpers::system::detect_distro()?;
+    let distro = facts::get().os.distribution.clone();
     
     if distro.is_debian_based() {
         // Check for security updates in apt
-        if let Ok(output) = Command::new("apt")
-            .args(&["list", "--upgradable"])
-            .output()
-        {
-            if output.status.success() {
-                let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Ok(output) = command_output("apt", &["list", "--upgradable"]) {
+            if output.success {
+                let stdout = &output.stdout;
                 let count = stdout.lines()
                     .filter(|line| line.contains("-security"))
                     .count();
@@ -685,12 +661,9 @@ fn count_security_updates() -> Result<u32> {
         // Check for security updates with yum/dnf
         let pkg_manager = if which::which("dnf").is_ok() { "dnf" } else { "yum" };
         
-        if let Ok(output) = Command::new(pkg_manager)
-            .args(&["updateinfo", "list", "security"])
-            .output()
-        {
-            if output.status.success() {
-                let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Ok(output) = command_output(pkg_manager, &["updateinfo", "list", "security"]) {
+            if output.success {
+                let stdout = &output.stdout;
                 let count = stdout.lines()
                     .filter(|line| !line.trim().is_empty() && !line.starts_with("Last metadata"))
                     .count();
@@ -708,16 +681,14 @@ fn get_failed_services() -> Result<Vec<String>> {
         return Ok(Vec::new());
     }
     
-    let output = Command::new("systemctl")
-        .args(&["--failed", "--no-legend", "--no-pager"])
-        .output()
+    let output = command_output("systemctl", &["--failed", "--no-legend", "--no-pager"])
         .map_err(|e| FluxError::system(format!("Failed to get failed services: {}", e)))?;
     
-    if !output.status.success() {
+    if !output.success {
         return Ok(Vec::new());
     }
     
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = &output.stdout;
     let failed_services: Vec<String> = stdout
         .lines()
         .filter_map(|line| {
@@ -789,16 +760,14 @@ fn parse_meminfo_value(line: &str) -> Result<u64> {
 
 /// Get inode usage for all mounted filesystems
 fn get_inodes_usage() -> Result<HashMap<String, InodesUsage>> {
-    let output = Command::new("df")
-        .args(&["-i"])
-        .output()
+    let output = command_output("df", &["-i"])
         .map_err(|e| FluxError::system(format!("Failed to get inode usage: {}", e)))?;
     
-    if !output.status.success() {
+    if !output.success {
         return Err(FluxError::command_failed("df command failed"));
     }
     
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = &output.stdout;
     let mut usage_map = HashMap::new();
     
     for line in stdout.lines().skip(1) { // Skip header
@@ -0,0 +1,211 @@
+//! Templates for generated configuration files
+//!
+//! Files such as the sshd hardening drop-in, `netdata.conf` or the MOTD
+//! scripts are rendered from [MiniJinja](https://docs.rs/minijinja) templates.
+//! The built-in templates are compiled into flux; a file with the same name
+//! below the templates directory (`general.templates_dir`,
+//! `/etc/flux/templates` by default) replaces one.
+//!
+//! Templates see the variables the module passes, which include its
+//! `[modules.<name>]` settings, plus `facts` about the host. Facts are only
+//! gathered when a template reads one.
+
+use crate::error::{FluxError, Result};
+use crate::helpers::facts;
+use lazy_static::lazy_static;
+use minijinja::value::{Object, Value};
+use minijinja::{context, AutoEscape, Environment, UndefinedBehavior};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Directory of template overrides when `general.templates_dir` is unset
+pub const TEMPLATES_DIR: &str = "/etc/flux/templates";
+
+/// Built-in templates by name
+const BUILTIN: &[(&str, &str)] = &[
+    ("motd/10-flux-header", include_str!("../../templates/motd/10-flux-header")),
+    ("motd/20-flux-sysinfo", include_str!("../../templates/motd/20-flux-sysinfo")),
+    ("motd/30-flux-security", include_str!("../../templates/motd/30-flux-security")),
+    ("motd/90-flux-footer", include_str!("../../templates/motd/90-flux-footer")),
+    ("netdata/health.d/cpu_usage.conf", include_str!("../../templates/netdata/health.d/cpu_usage.conf")),
+    ("netdata/health.d/disk_space.conf", include_str!("../../templates/netdata/health.d/disk_space.conf")),
+    ("netdata/health.d/ram_usage.conf", include_str!("../../templates/netdata/health.d/ram_usage.conf")),
+    ("netdata/netdata.conf", include_str!("../../templates/netdata/netdata.conf")),
+    ("network/netplan.yaml", include_str!("../../templates/network/netplan.yaml")),
+    ("ssh/50-flux-hardening.conf", include_str!("../../templates/ssh/50-flux-hardening.conf")),
+    ("sysctl/99-flux-hardening.conf", include_str!("../../templates/sysctl/99-flux-hardening.conf")),
+];
+
+lazy_static! {
+    static ref DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// Set the directory of template overrides, `None` for [`TEMPLATES_DIR`]
+pub fn set_dir(dir: Option<PathBuf>) {
+    *DIR.write() = dir;
+}
+
+/// The directory of template overrides
+pub fn dir() -> PathBuf {
+    DIR.read()
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(TEMPLATES_DIR))
+}
+
+/// A template and where its source comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    /// Override file replacing the built-in template
+    pub overridden_by: Option<PathBuf>,
+}
+
+/// Every built-in template, with the overrides found in the templates directory
+pub fn list() -> Vec<TemplateInfo> {
+    let dir = dir();
+    BUILTIN
+        .iter()
+        .map(|(name, _)| TemplateInfo {
+            name: name.to_string(),
+            overridden_by: Some(dir.join(name)).filter(|path| path.is_file()),
+        })
+        .collect()
+}
+
+/// Source of the template `name`, the override if there is one
+pub fn source(name: &str) -> Result<String> {
+    load(&dir(), name)?.ok_or_else(|| FluxError::not_found(format!("Unknown template '{}'", name)))
+}
+
+/// Render the template `name` with `vars`
+pub fn render<S: Serialize>(name: &str, vars: &S) -> Result<String> {
+    render_in(&dir(), name, vars)
+}
+
+/// Render the template `name` with overrides taken from `dir`
+pub fn render_in<S: Serialize>(dir: &Path, name: &str, vars: &S) -> Result<String> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env.add_filter("yes_no", |value: bool| if value { "yes" } else { "no" });
+
+    let dir = dir.to_path_buf();
+    env.set_loader(move |name| {
+        load(&dir, name).map_err(|e| {
+            minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, e.to_string())
+        })
+    });
+
+    let template = env.get_template(name).map_err(|e| template_error(name, e))?;
+    let ctx = context! {
+        facts => facts_value(),
+        ..Value::from_serialize(vars)
+    };
+    template.render(ctx).map_err(|e| template_error(name, e))
+}
+
+/// Value of the `generated` variable of built-in templates
+///
+/// It names the flux version instead of a timestamp, so rendering the same
+/// settings twice gives the same file and nothing is rewritten.
+pub fn generated_by() -> String {
+    format!("Flux Framework {}", crate::VERSION)
+}
+
+/// Host facts for a template or expression context, gathered when first read
+pub fn facts_value() -> Value {
+    Value::from_object(LazyFacts::default())
+}
+
+/// Source of `name` from `dir`, falling back to the built-in template
+fn load(dir: &Path, name: &str) -> Result<Option<String>> {
+    let relative = Path::new(name);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(FluxError::validation(format!("Invalid template name '{}'", name)));
+    }
+
+    let path = dir.join(relative);
+    if path.is_file() {
+        let source = fs::read_to_string(&path).map_err(|e| {
+            FluxError::config(format!("Failed to read template {}: {}", path.display(), e))
+        })?;
+        return Ok(Some(source));
+    }
+
+    Ok(BUILTIN
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, source)| source.to_string()))
+}
+
+fn template_error(name: &str, error: minijinja::Error) -> FluxError {
+    match error.kind() {
+        minijinja::ErrorKind::TemplateNotFound => {
+            FluxError::not_found(format!("Unknown template '{}'", name))
+        }
+        _ => FluxError::config(format!("Failed to render template {}: {}", name, error)),
+    }
+}
+
+/// [`facts::get`], only gathered when a template reads a fact
+#[derive(Debug, Default)]
+struct LazyFacts(OnceLock<Value>);
+
+impl Object for LazyFacts {
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        let facts = self.0.get_or_init(|| Value::from_serialize(&*facts::get()));
+        facts.get_item(key).ok().filter(|value| !value.is_undefined())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn test_builtin_templates_parse() {
+        let mut env = Environment::new();
+        env.add_filter("yes_no", |value: bool| if value { "yes" } else { "no" });
+        for (name, source) in BUILTIN {
+            assert!(env.template_from_named_str(name, source).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_override_replaces_builtin() {
+        let dir = tempfile::tempdir().unwrap();
+        let vars = serde_json::json!({ "web_port": 8080 });
+
+        let builtin = render_in(dir.path(), "netdata/netdata.conf", &vars).unwrap();
+        assert!(builtin.contains("default port = 8080\n"));
+
+        fs::create_dir_all(dir.path().join("netdata")).unwrap();
+        fs::write(
+            dir.path().join("netdata/netdata.conf"),
+            "{% if web_port > 1024 %}port {{ web_port }}{% endif %} on {{ facts.architecture }}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            render_in(dir.path(), "netdata/netdata.conf", &vars).unwrap(),
+            format!("port 8080 on {}\n", std::env::consts::ARCH)
+        );
+
+        assert!(render_in(dir.path(), "netdata/missing.conf", &vars).is_err());
+        assert!(render_in(dir.path(), "../etc/passwd", &vars).is_err());
+
+        // Typos in variable names are errors rather than empty output
+        fs::write(dir.path().join("netdata/netdata.conf"), "{{ web_prot }}\n").unwrap();
+        assert!(render_in(dir.path(), "netdata/netdata.conf", &vars).is_err());
+    }
+}
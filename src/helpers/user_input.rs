@@ -2,10 +2,172 @@ use crate::error::{FluxError, Result};
 use crate::helpers::validation;
 use colored::Colorize;
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::HashMap;
 use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref ANSWERS: RwLock<HashMap<String, toml::Value>> = RwLock::new(HashMap::new());
+}
+
+/// Enable or disable non-interactive mode for the current process
+///
+/// In non-interactive mode prompts never touch the terminal: confirmations
+/// answer yes, prompts with a default take it, and anything else must come
+/// from the answers file.
+pub fn set_non_interactive(enabled: bool) {
+    NON_INTERACTIVE.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether non-interactive mode is active
+pub fn is_non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::SeqCst)
+}
+
+/// Load an answers file: a TOML table keyed by prompt id
+pub fn load_answers<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        FluxError::config(format!("Failed to read answers file {}: {}", path.display(), e))
+    })?;
+    let table: toml::Table = toml::from_str(&content).map_err(|e| {
+        FluxError::config(format!("Invalid answers file {}: {}", path.display(), e))
+    })?;
+
+    set_answers(table.into_iter().collect());
+    Ok(())
+}
+
+/// Replace the loaded answers
+pub fn set_answers(answers: HashMap<String, toml::Value>) {
+    *ANSWERS.write() = answers;
+}
+
+/// Stable id of a prompt, used as its key in the answers file
+///
+/// Lowercases the prompt text and joins its words with underscores, so
+/// "Enter new hostname" becomes `enter_new_hostname`.
+pub fn prompt_id(prompt: &str) -> String {
+    let mut id = String::new();
+    for c in prompt.chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c.to_ascii_lowercase());
+        } else if !id.is_empty() && !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    id.trim_end_matches('_').to_string()
+}
+
+/// Find the answer for a prompt by id or by its exact text
+fn lookup_answer(prompt: &str) -> Option<toml::Value> {
+    let answers = ANSWERS.read();
+    answers
+        .get(&prompt_id(prompt))
+        .or_else(|| answers.get(prompt))
+        .cloned()
+}
+
+/// Consume the next answer for a menu
+///
+/// Menus usually loop, so an answer is used once; an array answers the menu
+/// several times in order. Returns `Some(None)` once all answers are used.
+fn take_menu_answer(title: &str) -> Option<Option<toml::Value>> {
+    let mut answers = ANSWERS.write();
+    let key = [prompt_id(title), title.to_string()]
+        .into_iter()
+        .find(|key| answers.contains_key(key))?;
+
+    let mut remaining = match answers.remove(&key)? {
+        toml::Value::Array(values) => values,
+        single => vec![single],
+    };
+    let next = (!remaining.is_empty()).then(|| remaining.remove(0));
+    answers.insert(prompt_id(title), toml::Value::Array(remaining));
+
+    Some(next)
+}
+
+fn missing_answer(prompt: &str) -> FluxError {
+    FluxError::validation(format!(
+        "Prompt '{}' needs an answer in non-interactive mode; add `{} = ...` to the answers file",
+        prompt,
+        prompt_id(prompt)
+    ))
+}
+
+fn invalid_answer(prompt: &str, expected: &str) -> FluxError {
+    FluxError::validation(format!(
+        "Invalid answer for '{}': expected {}",
+        prompt_id(prompt),
+        expected
+    ))
+}
+
+/// Echo an automatic answer so logs show what was chosen
+fn print_answer(prompt: &str, answer: &str) {
+    println!("{} {} {}", "?".green(), prompt, answer.bold());
+}
+
+fn answer_as_string(prompt: &str, value: &toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(invalid_answer(prompt, "a string")),
+    }
+}
+
+fn answer_as_bool(prompt: &str, value: &toml::Value) -> Result<bool> {
+    match value {
+        toml::Value::Boolean(b) => Ok(*b),
+        toml::Value::String(s) => match s.to_ascii_lowercase().as_str() {
+            "y" | "yes" | "true" => Ok(true),
+            "n" | "no" | "false" => Ok(false),
+            _ => Err(invalid_answer(prompt, "yes or no")),
+        },
+        _ => Err(invalid_answer(prompt, "yes or no")),
+    }
+}
+
+/// Resolve an answer to an item index: item text or 1-based position
+fn answer_as_index<T: AsRef<str>>(prompt: &str, value: &toml::Value, items: &[T]) -> Result<usize> {
+    let index = match value {
+        toml::Value::Integer(n) if *n >= 1 && (*n as usize) <= items.len() => Some(*n as usize - 1),
+        toml::Value::String(s) => {
+            let s = s.to_lowercase();
+            items
+                .iter()
+                .position(|item| item.as_ref().to_lowercase() == s)
+                .or_else(|| items.iter().position(|item| item.as_ref().to_lowercase().starts_with(&s)))
+        }
+        _ => None,
+    };
+
+    index.ok_or_else(|| {
+        let options: Vec<&str> = items.iter().map(|i| i.as_ref()).collect();
+        invalid_answer(prompt, &format!("one of: {}", options.join(", ")))
+    })
+}
 
 /// Prompt for yes/no confirmation
 pub fn prompt_yes_no(prompt: &str, default: bool) -> Result<bool> {
+    if let Some(value) = lookup_answer(prompt) {
+        let answer = answer_as_bool(prompt, &value)?;
+        print_answer(prompt, if answer { "yes" } else { "no" });
+        return Ok(answer);
+    }
+    if is_non_interactive() {
+        print_answer(prompt, "yes");
+        return Ok(true);
+    }
+
     Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .default(default)
@@ -15,6 +177,16 @@ pub fn prompt_yes_no(prompt: &str, default: bool) -> Result<bool> {
 
 /// Prompt for input with default value
 pub fn prompt_with_default(prompt: &str, default: &str) -> Result<String> {
+    if let Some(value) = lookup_answer(prompt) {
+        let answer = answer_as_string(prompt, &value)?;
+        print_answer(prompt, &answer);
+        return Ok(answer);
+    }
+    if is_non_interactive() {
+        print_answer(prompt, default);
+        return Ok(default.to_string());
+    }
+
     Input::<String>::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .default(default.to_string())
@@ -24,6 +196,15 @@ pub fn prompt_with_default(prompt: &str, default: &str) -> Result<String> {
 
 /// Prompt for input without default
 pub fn prompt_input(prompt: &str) -> Result<String> {
+    if let Some(value) = lookup_answer(prompt) {
+        let answer = answer_as_string(prompt, &value)?;
+        print_answer(prompt, &answer);
+        return Ok(answer);
+    }
+    if is_non_interactive() {
+        return Err(missing_answer(prompt));
+    }
+
     Input::<String>::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .interact()
@@ -32,6 +213,10 @@ pub fn prompt_input(prompt: &str) -> Result<String> {
 
 /// Prompt for password
 pub fn prompt_password(prompt: &str) -> Result<String> {
+    if let Some(answer) = answered_password(prompt)? {
+        return Ok(answer);
+    }
+
     Password::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .interact()
@@ -40,6 +225,10 @@ pub fn prompt_password(prompt: &str) -> Result<String> {
 
 /// Prompt for password with confirmation
 pub fn prompt_password_confirm(prompt: &str) -> Result<String> {
+    if let Some(answer) = answered_password(prompt)? {
+        return Ok(answer);
+    }
+
     Password::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .with_confirmation("Confirm password", "Passwords do not match")
@@ -47,6 +236,19 @@ pub fn prompt_password_confirm(prompt: &str) -> Result<String> {
         .map_err(|e| FluxError::External(e.into()))
 }
 
+/// Password from the answers file, without echoing it
+fn answered_password(prompt: &str) -> Result<Option<String>> {
+    if let Some(value) = lookup_answer(prompt) {
+        let answer = answer_as_string(prompt, &value)?;
+        print_answer(prompt, "********");
+        return Ok(Some(answer));
+    }
+    if is_non_interactive() {
+        return Err(missing_answer(prompt));
+    }
+    Ok(None)
+}
+
 /// Prompt with validation
 pub fn prompt_with_validation<F>(
     prompt: &str,
@@ -68,6 +270,15 @@ where
             return Ok(input);
         }
         
+        // An automatic answer would fail the same way every time
+        if is_non_interactive() || lookup_answer(prompt).is_some() {
+            return Err(FluxError::validation(format!(
+                "Invalid answer for '{}': {}",
+                prompt_id(prompt),
+                error_msg
+            )));
+        }
+        
         eprintln!("{} {}", "[ERROR]".red(), error_msg);
     }
 }
@@ -133,13 +344,13 @@ pub fn prompt_interface(prompt: &str, show_list: bool) -> Result<String> {
             return Err(FluxError::network("No network interfaces found"));
         }
         
+        // Picking an interface for the user could cut the host off the network
+        if is_non_interactive() && lookup_answer(prompt).is_none() {
+            return Err(missing_answer(prompt));
+        }
+        
         // Show selection menu
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt(prompt)
-            .items(&interfaces)
-            .default(0)
-            .interact()
-            .map_err(|e| FluxError::External(e.into()))?;
+        let selection = prompt_select(prompt, &interfaces, 0)?;
         
         Ok(interfaces[selection].clone())
     } else {
@@ -154,6 +365,17 @@ pub fn prompt_interface(prompt: &str, show_list: bool) -> Result<String> {
 
 /// Prompt for selection from list
 pub fn prompt_select(prompt: &str, items: &[String], default: usize) -> Result<usize> {
+    if let Some(value) = lookup_answer(prompt) {
+        let index = answer_as_index(prompt, &value, items)?;
+        print_answer(prompt, &items[index]);
+        return Ok(index);
+    }
+    if is_non_interactive() {
+        let item = items.get(default).ok_or_else(|| missing_answer(prompt))?;
+        print_answer(prompt, item);
+        return Ok(default);
+    }
+
     Select::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .items(items)
@@ -166,6 +388,24 @@ pub fn prompt_select(prompt: &str, items: &[String], default: usize) -> Result<u
 pub fn prompt_multi_select(prompt: &str, items: &[String]) -> Result<Vec<usize>> {
     use dialoguer::MultiSelect;
     
+    if let Some(value) = lookup_answer(prompt) {
+        let values = match value {
+            toml::Value::Array(values) => values,
+            single => vec![single],
+        };
+        let indices = values
+            .iter()
+            .map(|v| answer_as_index(prompt, v, items))
+            .collect::<Result<Vec<_>>>()?;
+        let chosen: Vec<&str> = indices.iter().map(|&i| items[i].as_str()).collect();
+        print_answer(prompt, &chosen.join(", "));
+        return Ok(indices);
+    }
+    if is_non_interactive() {
+        print_answer(prompt, "(none)");
+        return Ok(Vec::new());
+    }
+    
     MultiSelect::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .items(items)
@@ -176,6 +416,9 @@ pub fn prompt_multi_select(prompt: &str, items: &[String]) -> Result<Vec<usize>>
 /// Prompt to continue or abort
 pub fn prompt_continue(message: &str) -> Result<()> {
     println!("{}", message);
+    if is_non_interactive() {
+        return Ok(());
+    }
     println!("Press Enter to continue...");
     
     let mut input = String::new();
@@ -286,7 +529,18 @@ impl Menu {
         println!("{}. Exit", display_items.len() + 1);
         println!();
 
-        let selection = prompt_input("Select option")?;
+        // Menus are answered by title since every menu shares the same prompt
+        let selection = match take_menu_answer(&self.title) {
+            Some(Some(value)) => {
+                let answer = answer_as_string(&self.title, &value)?;
+                print_answer("Select option", &answer);
+                answer
+            }
+            // Every answer was used up, leave the menu
+            Some(None) => return Err(FluxError::UserCancelled),
+            None if is_non_interactive() => return Err(missing_answer(&self.title)),
+            None => prompt_input("Select option")?,
+        };
 
         if let Ok(num) = selection.parse::<usize>() {
             if num > 0 && num <= self.items.len() {
@@ -317,4 +571,78 @@ pub fn select_from_menu<T: AsRef<str>>(prompt: &str, items: &[T]) -> Result<usiz
 pub fn multi_select_menu<T: AsRef<str>>(prompt: &str, items: &[T]) -> Result<Vec<usize>> {
     let string_items: Vec<String> = items.iter().map(|s| s.as_ref().to_string()).collect();
     prompt_multi_select(prompt, &string_items)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn test_prompt_id() {
+        assert_eq!(prompt_id("Enter new hostname"), "enter_new_hostname");
+        assert_eq!(
+            prompt_id("Sysctl configuration already exists. Overwrite?"),
+            "sysctl_configuration_already_exists_overwrite"
+        );
+        assert_eq!(prompt_id("  SSH port (1-65535): "), "ssh_port_1_65535");
+    }
+
+    #[test]
+    #[serial]
+    fn test_non_interactive_answers() {
+        let answers: toml::Table = toml::from_str(
+            r#"
+            enter_new_hostname = "web01"
+            "Restart SSH now?" = "no"
+            select_profile = 2
+            ssh_port = "99999"
+            "#,
+        )
+        .unwrap();
+        set_answers(answers.into_iter().collect());
+        set_non_interactive(true);
+
+        let items = vec!["modern".to_string(), "intermediate".to_string()];
+        let results = (
+            prompt_input("Enter new hostname"),
+            prompt_yes_no("Restart SSH now?", true),
+            prompt_yes_no("Continue?", false),
+            prompt_with_default("Timezone", "UTC"),
+            prompt_select("Select profile", &items, 0),
+            prompt_input("Admin username"),
+            prompt_port("SSH port", Some("22")),
+        );
+
+        set_non_interactive(false);
+        set_answers(HashMap::new());
+
+        assert_eq!(results.0.unwrap(), "web01");
+        assert!(!results.1.unwrap());
+        assert!(results.2.unwrap());
+        assert_eq!(results.3.unwrap(), "UTC");
+        assert_eq!(results.4.unwrap(), 1);
+        assert!(matches!(results.5, Err(FluxError::Validation(_))));
+        assert!(matches!(results.6, Err(FluxError::Validation(_))));
+    }
+
+    #[test]
+    #[serial]
+    fn test_menu_answers_are_consumed() {
+        let answers: toml::Table = toml::from_str(r#"update_menu = ["check", 2]"#).unwrap();
+        set_answers(answers.into_iter().collect());
+        set_non_interactive(true);
+
+        let menu = Menu::new("Update Menu")
+            .add_item("check", "Check for updates")
+            .add_item("upgrade", "Upgrade packages");
+        let results = (menu.show(), menu.show(), menu.show());
+
+        set_non_interactive(false);
+        set_answers(HashMap::new());
+
+        assert_eq!(results.0.unwrap(), "check");
+        assert_eq!(results.1.unwrap(), "upgrade");
+        assert!(matches!(results.2, Err(FluxError::UserCancelled)));
+    }
+}
@@ -174,6 +174,8 @@ pub fn validate_ssh_key(key: &str) -> Result<()> {
         "ecdsa-sha2-nistp256",
         "ecdsa-sha2-nistp384",
         "ecdsa-sha2-nistp521",
+        "sk-ssh-ed25519@openssh.com",
+        "sk-ecdsa-sha2-nistp256@openssh.com",
     ];
     
     if !valid_types.contains(&parts[0]) {
@@ -7,6 +7,7 @@ pub mod config;
 pub mod error;
 pub mod helpers;
 pub mod modules;
+pub mod remote;
 pub mod workflows;
 
 // Re-export commonly used types
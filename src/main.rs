@@ -2,11 +2,20 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use colored::Colorize;
 use flux_framework::{
-    config::Config,
-    helpers::logging::{init_logging, LogLevel},
-    modules::ModuleManager,
+    config::{self, secret, Config, Layers, LoadOptions, RunMode},
+    helpers::{
+        changes, dry_run, facts, journal,
+        logging::{init_logging, log_warn, LogLevel},
+        output::{self, OutputFormat},
+        paths,
+        report, template, user_input,
+    },
+    modules::{ssh, ModuleManager, ModuleResult},
+    remote::{self, HostStatus, Inventory},
     workflows::WorkflowManager,
 };
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
 use std::process;
 use tracing::{error, info};
 
@@ -21,10 +30,42 @@ struct Cli {
     #[arg(short = 'L', long, value_enum, default_value = "info")]
     log_level: LogLevel,
 
-    /// Configuration file path
+    /// Configuration file path (replaces the system, user and host files)
     #[arg(short, long, value_name = "FILE")]
     config: Option<String>,
 
+    /// Apply profiles/<NAME>.toml from /etc/flux or the user config directory
+    #[arg(long, value_name = "NAME", env = "FLUX_PROFILE", global = true)]
+    profile: Option<String>,
+
+    /// Show what would be changed without modifying the system
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Never prompt: confirm with yes, take defaults, read other answers from --answers
+    #[arg(short = 'y', long = "yes", visible_alias = "non-interactive", global = true)]
+    non_interactive: bool,
+
+    /// TOML file of prompt answers keyed by prompt id
+    #[arg(long, value_name = "FILE", global = true)]
+    answers: Option<String>,
+
+    /// Output format for command results (json keeps stdout machine-readable)
+    #[arg(short, long, value_enum, default_value = "text", global = true)]
+    output: OutputFormat,
+
+    /// Apply changes to the filesystem tree at DIR instead of / (e.g. a chroot or image)
+    #[arg(long, value_name = "DIR", env = "FLUX_ROOT", global = true)]
+    root: Option<PathBuf>,
+
+    /// Run the command on the hosts of an inventory file over SSH
+    #[arg(long, value_name = "FILE", global = true)]
+    hosts: Option<String>,
+
+    /// Only run on these hosts or groups of the inventory (comma-separated)
+    #[arg(long, value_name = "NAMES", value_delimiter = ',', global = true, requires = "hosts")]
+    limit: Vec<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -37,6 +78,9 @@ enum Commands {
     /// Show system status
     Status,
 
+    /// Show the facts gathered about the host (all of them with --output json)
+    Facts,
+
     /// Load and execute a specific module
     Load {
         /// Module name
@@ -45,20 +89,74 @@ enum Commands {
         args: Vec<String>,
     },
 
-    /// Execute a predefined workflow
+    /// Converge the system to the desired state in a configuration file
+    Apply {
+        /// Configuration file describing the desired state
+        file: String,
+    },
+
+    /// Report drift between the desired state and the live system
+    ///
+    /// Exits with status 2 when drift is found and 1 when a module could not be checked.
+    Plan {
+        /// Configuration file describing the desired state (defaults to the loaded configuration)
+        file: Option<String>,
+    },
+
+    /// Execute a built-in or configured workflow
     Workflow {
-        /// Workflow name (essential, complete, security, development, monitoring)
-        name: String,
+        /// Workflow name (see --list)
+        name: Option<String>,
+        /// List available workflows
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// Undo the changes journaled by a previous run
+    Rollback {
+        /// Run id printed at the end of a run (lists recent runs when omitted)
+        run_id: Option<String>,
+    },
+
+    /// Confirm or revert an SSH change made with safe apply
+    ///
+    /// Without confirmation the change is reverted when its timeout expires.
+    Ssh {
+        #[command(subcommand)]
+        action: SshAction,
+    },
+
+    /// Inspect the reports written for each run
+    Report {
+        #[command(subcommand)]
+        action: ReportAction,
     },
 
-    /// Get or set configuration values
+    /// Get or set configuration values, or check the configuration file
+    ///
+    /// Module settings are addressed as `modules.<module>.<key>`.
+    #[command(args_conflicts_with_subcommands = true)]
     Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
         /// Configuration key
         key: Option<String>,
         /// Configuration value (if setting)
         value: Option<String>,
     },
 
+    /// Encrypt values for secret settings such as passwords
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+
+    /// List, show or preview the templates of generated configuration files
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+
     /// Generate shell completions
     Completions {
         /// Shell type
@@ -67,6 +165,76 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum ReportAction {
+    /// List stored run reports
+    List,
+    /// Show a run report as Markdown (or JSON with --output json)
+    Show {
+        /// Report id, or `latest`
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SshAction {
+    /// Restart sshd with the tested configuration and cancel the revert
+    Confirm,
+    /// Restore the configuration from before the unconfirmed change
+    Revert,
+    /// Show the change waiting for confirmation
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Check configuration files against the module settings schemas
+    ///
+    /// Exits with status 1 when problems are found.
+    Validate {
+        /// File to check (defaults to every file the configuration is loaded from)
+        file: Option<String>,
+    },
+    /// Show the value of a key and which configuration layer supplied it
+    Explain {
+        /// Key such as `default_ssh_port` or `modules.ssh.port`
+        key: String,
+    },
+    /// Print the JSON Schema of flux.toml for editor completion
+    Schema,
+}
+
+#[derive(Subcommand)]
+enum SecretAction {
+    /// Encrypt a value with age and print it as a `{ age = ... }` setting
+    ///
+    /// The value is read from FILE, or from stdin (prompted for on a terminal).
+    Encrypt {
+        /// age recipient to encrypt to (defaults to the public key of general.secret_key)
+        #[arg(short, long = "recipient", value_name = "KEY")]
+        recipients: Vec<String>,
+        /// Read the value from this file
+        #[arg(short, long, value_name = "FILE")]
+        file: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateAction {
+    /// List the templates and the overrides found in the templates directory
+    List,
+    /// Print the source of a template, the override if there is one
+    Show {
+        /// Template name, e.g. `ssh/50-flux-hardening.conf`
+        name: String,
+    },
+    /// Render a template with the current configuration and host facts
+    Render {
+        /// Template name, e.g. `ssh/50-flux-hardening.conf`
+        name: String,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
@@ -78,40 +246,107 @@ async fn main() {
 async fn run() -> Result<()> {
     let cli = Cli::parse();
 
+    // Redirect human-readable output before anything is printed
+    output::set_output_format(cli.output)?;
+
     // Initialize logging
     init_logging(cli.log_level)?;
 
-    // Load configuration
-    let config = match cli.config {
-        Some(path) => Config::from_file(&path)?,
-        None => Config::load_or_default()?,
+    // With --hosts, --root names a directory on the remote hosts
+    if let Some(root) = cli.root.as_ref().filter(|_| cli.hosts.is_none()) {
+        if !root.is_dir() {
+            anyhow::bail!("--root {} is not a directory", root.display());
+        }
+        paths::set_root(Some(root.canonicalize()?));
+    }
+
+    let load_options = LoadOptions {
+        file: cli.config.as_ref().map(PathBuf::from),
+        profile: cli.profile.clone(),
     };
 
+    // Validation must not depend on the configuration loading cleanly
+    if let Commands::Config { action: Some(action), .. } = &cli.command {
+        return handle_config_action(action, &load_options);
+    }
+
+    // Load configuration
+    let mut config = Config::load(&load_options)?;
+    template::set_dir(config.general.templates_dir.clone());
+
+    if cli.dry_run {
+        config.general.mode = RunMode::DryRun;
+    }
+    if config.general.mode == RunMode::DryRun {
+        dry_run::set_dry_run(true);
+    }
+
+    if cli.non_interactive || config.general.mode == RunMode::Auto {
+        user_input::set_non_interactive(true);
+    }
+    if let Some(path) = &cli.answers {
+        user_input::load_answers(path)?;
+    }
+
     info!(
         "Flux Framework v{} initialized",
         env!("CARGO_PKG_VERSION")
     );
 
+    if let Some(inventory) = &cli.hosts {
+        return run_remote(inventory, &cli.limit, &cli.command, &config).await;
+    }
+
     // Check if running with appropriate privileges
     check_privileges(&cli.command)?;
 
     // Execute command
     match cli.command {
         Commands::List => {
-            list_modules().await?;
+            list_modules(&config).await?;
         }
         Commands::Status => {
             show_system_status().await?;
         }
+        Commands::Facts => {
+            show_facts()?;
+        }
         Commands::Load { module, args } => {
             load_module(&module, args, &config).await?;
         }
-        Commands::Workflow { name } => {
+        Commands::Apply { file } => {
+            apply_config(&file, cli.dry_run || config.general.mode == RunMode::DryRun).await?;
+        }
+        Commands::Plan { file } => {
+            let code = plan_config(file.as_deref(), &config).await?;
+            if code != 0 {
+                process::exit(code);
+            }
+        }
+        Commands::Workflow { name: Some(name), list: false } => {
             execute_workflow(&name, &config).await?;
         }
-        Commands::Config { key, value } => {
+        Commands::Workflow { .. } => {
+            list_workflows(&config)?;
+        }
+        Commands::Rollback { run_id } => {
+            rollback_run(run_id.as_deref())?;
+        }
+        Commands::Ssh { action } => {
+            handle_ssh(action)?;
+        }
+        Commands::Report { action } => {
+            handle_report(action)?;
+        }
+        Commands::Config { key, value, .. } => {
             handle_config(key, value, &config)?;
         }
+        Commands::Secret { action } => {
+            handle_secret(action, &config)?;
+        }
+        Commands::Template { action } => {
+            handle_template(action, &config)?;
+        }
         Commands::Completions { shell } => {
             generate_completions(shell);
         }
@@ -120,14 +355,51 @@ async fn run() -> Result<()> {
     Ok(())
 }
 
+async fn run_remote(inventory: &str, limit: &[String], command: &Commands, config: &Config) -> Result<()> {
+    if matches!(
+        command,
+        Commands::Apply { .. }
+            | Commands::Plan { .. }
+            | Commands::Config { .. }
+            | Commands::Secret { .. }
+            | Commands::Completions { .. }
+    ) {
+        anyhow::bail!("This command cannot be run with --hosts; use a workflow or 'load' instead");
+    }
+
+    let inventory = Inventory::from_file(inventory)?;
+    let hosts = inventory.select(limit)?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = remote::remote_args(&args)?;
+
+    println!(
+        "{}",
+        format!("Running 'flux {}' on {} host(s)", args.join(" "), hosts.len()).cyan()
+    );
+    let results = remote::run_on_hosts(&inventory, hosts, args, config).await;
+    remote::print_summary(&results)?;
+
+    if results.iter().any(|r| r.status != HostStatus::Ok) {
+        process::exit(1);
+    }
+    Ok(())
+}
+
 fn check_privileges(command: &Commands) -> Result<()> {
     // Some commands require root privileges
     let requires_root = matches!(
         command,
-        Commands::Load { .. } | Commands::Workflow { .. } | Commands::Status
+        Commands::Load { .. }
+            | Commands::Apply { .. }
+            | Commands::Plan { .. }
+            | Commands::Rollback { .. }
+            | Commands::Ssh { .. }
+            | Commands::Workflow { name: Some(_), list: false }
+            | Commands::Status
     );
 
-    if requires_root && !nix::unistd::Uid::effective().is_root() {
+    // Under --root only the target tree is modified
+    if requires_root && paths::root().is_none() && !nix::unistd::Uid::effective().is_root() {
         eprintln!(
             "{}",
             "This command requires root privileges. Please run with sudo.".red()
@@ -138,13 +410,18 @@ fn check_privileges(command: &Commands) -> Result<()> {
     Ok(())
 }
 
-async fn list_modules() -> Result<()> {
+async fn list_modules(config: &Config) -> Result<()> {
+    let manager = ModuleManager::with_config(config)?;
+    let modules = manager.discover_modules()?;
+
+    if output::is_json() {
+        output::emit(&modules)?;
+        return Ok(());
+    }
+
     println!("{}", "=== Available Flux Modules ===".cyan());
     println!();
 
-    let manager = ModuleManager::new()?;
-    let modules = manager.discover_modules()?;
-
     if modules.is_empty() {
         println!("{}", "No modules found".red());
     } else {
@@ -171,6 +448,12 @@ async fn list_modules() -> Result<()> {
 }
 
 async fn show_system_status() -> Result<()> {
+    if output::is_json() {
+        let status = flux_framework::helpers::system::get_system_status_enhanced()?;
+        output::emit(&status)?;
+        return Ok(());
+    }
+
     println!("{}", "=== System Status Check ===".cyan());
     println!();
 
@@ -218,20 +501,403 @@ async fn show_system_status() -> Result<()> {
     Ok(())
 }
 
+fn show_facts() -> Result<()> {
+    let facts = facts::get();
+    if output::is_json() {
+        output::emit(&*facts)?;
+        return Ok(());
+    }
+
+    println!("{}", "System:".white());
+    println!("  Hostname: {}", facts.hostname);
+    println!("  OS: {} ({} family)", facts.os.pretty_name, facts.os.family);
+    println!("  Kernel: {}", facts.kernel);
+    println!("  Architecture: {}", facts.architecture);
+    println!("  Virtualization: {}", facts.virtualization);
+    println!("  Container: {}", facts.container);
+    println!("  Init system: {}", facts.init_system);
+
+    println!();
+    println!("{}", "Hardware:".white());
+    println!("  CPUs: {}", facts.cpu_count);
+    println!("  Memory: {} MB", facts.memory_mb);
+    for disk in &facts.disks {
+        println!(
+            "  Disk {}: {} MB free of {} MB ({})",
+            disk.mount_point, disk.available_mb, disk.total_mb, disk.filesystem
+        );
+    }
+
+    println!();
+    println!("{}", "Network:".white());
+    println!("  Primary IP: {}", facts.primary_ip.as_deref().unwrap_or("unknown"));
+    println!("  Gateway: {}", facts.default_gateway.as_deref().unwrap_or("none"));
+    for interface in facts.interfaces.iter().filter(|i| !i.is_loopback) {
+        let ips: Vec<String> = interface.ips.iter().map(|ip| ip.to_string()).collect();
+        println!(
+            "  {}: {} ({})",
+            interface.name,
+            if ips.is_empty() { "no address".to_string() } else { ips.join(", ") },
+            if interface.is_up { "up" } else { "down" }
+        );
+    }
+
+    println!();
+    println!("{}", "Software:".white());
+    println!("  Package manager: {}", facts.package_manager);
+    println!("  Installed packages: {}", facts.packages.len());
+
+    Ok(())
+}
+
 async fn load_module(name: &str, args: Vec<String>, config: &Config) -> Result<()> {
     info!("Loading module: {}", name);
 
-    let manager = ModuleManager::new()?;
-    manager.load_module(name, args, config).await?;
+    let command = std::iter::once(format!("load {}", name))
+        .chain(args.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // The whole run is this one module, so its --dry-run covers the journal and report
+    if args.iter().any(|arg| arg == "--dry-run") {
+        dry_run::set_dry_run(true);
+    }
+
+    let manager = ModuleManager::with_config(config)?;
+    let mut module_result = None;
+    let result = journaled(&command, async {
+        let started = std::time::Instant::now();
+        let (result, resources) = manager.run_module(name, args, config).await;
+        let outcome = ModuleResult::new(name, &result, started.elapsed()).with_resources(resources);
+        report::record_modules(std::slice::from_ref(&outcome));
+        module_result = Some(outcome);
+        result
+    })
+    .await;
 
+    if dry_run::is_dry_run() {
+        dry_run::print_planned_changes();
+    }
+
+    match &module_result {
+        Some(outcome) if output::is_json() => output::emit(outcome)?,
+        Some(outcome) => changes::print_changes(&outcome.resources),
+        None => {}
+    }
+
+    result?;
     Ok(())
 }
 
+/// Run `operation` with a rollback journal and run report open, printing their ids at the end
+async fn journaled<F>(command: &str, operation: F) -> flux_framework::Result<()>
+where
+    F: std::future::Future<Output = flux_framework::Result<()>>,
+{
+    let run_id = match journal::begin(command) {
+        Ok(id) => id,
+        Err(e) => {
+            log_warn(format!("Could not open rollback journal, continuing without it: {}", e));
+            None
+        }
+    };
+
+    if let Err(e) = report::begin(command, run_id.as_deref()) {
+        log_warn(format!("Could not start run report, continuing without it: {}", e));
+    }
+
+    let result = operation.await;
+
+    match report::finish(result.as_ref().err().map(|e| e.to_string())) {
+        Ok(Some(id)) => {
+            println!();
+            println!(
+                "{}",
+                format!("Report: {} (view with 'flux report show {}')", id, id).white()
+            );
+        }
+        Ok(None) => {}
+        Err(e) => log_warn(format!("Could not write run report: {}", e)),
+    }
+
+    if let Some(id) = run_id {
+        if let Err(e) = journal::finish(result.is_ok()) {
+            log_warn(format!("Could not close rollback journal: {}", e));
+        }
+        println!(
+            "{}",
+            format!("Run id: {} (undo with 'flux rollback {}')", id, id).white()
+        );
+    }
+
+    result
+}
+
+async fn apply_config(file: &str, dry_run: bool) -> Result<()> {
+    info!("Applying configuration: {}", file);
+
+    let mut config = Config::from_file(file)?;
+    if dry_run {
+        config.general.mode = RunMode::DryRun;
+    }
+    if config.general.mode == RunMode::DryRun {
+        dry_run::set_dry_run(true);
+    }
+
+    let manager = ModuleManager::with_config(&config)?;
+    let result = journaled(&format!("apply {}", file), manager.apply_config(&config)).await;
+
+    if dry_run::is_dry_run() {
+        dry_run::print_planned_changes();
+    }
+
+    result?;
+    Ok(())
+}
+
+/// Print per-module drift and return the process exit code
+async fn plan_config(file: Option<&str>, config: &Config) -> Result<i32> {
+    let config = match file {
+        Some(path) => {
+            info!("Planning configuration: {}", path);
+            Config::from_file(path)?
+        }
+        None => config.clone(),
+    };
+
+    let manager = ModuleManager::with_config(&config)?;
+    let plans = manager.plan_config(&config).await;
+
+    let errors = plans.iter().filter(|p| p.error.is_some()).count();
+    let drifted = plans.iter().filter(|p| !p.drift.is_empty()).count();
+    let code = if errors > 0 {
+        1
+    } else if drifted > 0 {
+        2
+    } else {
+        0
+    };
+
+    if output::is_json() {
+        output::emit(&plans)?;
+        return Ok(code);
+    }
+
+    if plans.is_empty() {
+        println!("{}", "No plannable modules configured under [modules.*]".yellow());
+        return Ok(0);
+    }
+
+    for plan in &plans {
+        println!("{}", format!("=== {} ===", plan.module).cyan());
+
+        if let Some(err) = &plan.error {
+            println!("  {}", format!("! {}", err).red());
+        } else if let Some(reason) = &plan.skipped {
+            println!("  {}", format!("- skipped: {}", reason).bright_black());
+        } else if plan.drift.is_empty() {
+            println!("  {}", "No drift".green());
+        } else {
+            for drift in &plan.drift {
+                let line = drift.to_string();
+                if drift.actual.is_some() {
+                    println!("  {}", line.yellow());
+                } else {
+                    println!("  {}", line.green());
+                }
+            }
+        }
+        println!();
+    }
+
+    let total_drift: usize = plans.iter().map(|p| p.drift.len()).sum();
+    println!("{}", "=== Plan Summary ===".cyan());
+    let checked = plans.iter().filter(|p| p.skipped.is_none()).count();
+    println!("  Modules checked: {}", checked);
+    println!("  Modules drifted: {}", drifted.to_string().yellow());
+    println!("  Differences:     {}", total_drift.to_string().yellow());
+    println!("  Errors:          {}", errors.to_string().red());
+
+    Ok(code)
+}
+
 async fn execute_workflow(name: &str, config: &Config) -> Result<()> {
     info!("Executing workflow: {}", name);
 
-    let manager = WorkflowManager::new()?;
-    manager.execute_workflow(name, config).await?;
+    let manager = WorkflowManager::with_config(config)?;
+    let result = journaled(
+        &format!("workflow {}", name),
+        manager.execute_workflow(name, config),
+    )
+    .await;
+
+    // Modules given --dry-run in their args record changes in a live run too
+    if dry_run::is_dry_run() || !dry_run::planned_changes().is_empty() {
+        dry_run::print_planned_changes();
+    }
+
+    result?;
+    Ok(())
+}
+
+fn list_workflows(config: &Config) -> Result<()> {
+    let manager = WorkflowManager::with_config(config)?;
+
+    if output::is_json() {
+        let workflows: Vec<serde_json::Value> = manager
+            .list_workflows()
+            .into_iter()
+            .map(|(name, description)| {
+                let modules = manager
+                    .get_workflow(name)
+                    .map(|w| w.modules())
+                    .unwrap_or_default();
+                serde_json::json!({
+                    "name": name,
+                    "description": description,
+                    "modules": modules,
+                })
+            })
+            .collect();
+        output::emit(&workflows)?;
+        return Ok(());
+    }
+
+    println!("{}", "=== Available Workflows ===".cyan());
+    println!();
+
+    for (name, description) in manager.list_workflows() {
+        println!("{:<16} {}", name.white(), description);
+        if let Some(workflow) = manager.get_workflow(name) {
+            println!("{:<16} {}", "", workflow.modules().join(" → ").bright_black());
+        }
+    }
+
+    Ok(())
+}
+
+fn rollback_run(run_id: Option<&str>) -> Result<()> {
+    let root = &paths::resolve(journal::RUNS_DIR);
+
+    let Some(id) = run_id else {
+        let runs = journal::list_runs(root)?;
+        if runs.is_empty() {
+            println!("{}", "No journaled runs found".yellow());
+            return Ok(());
+        }
+
+        println!("{}", "=== Journaled Runs ===".cyan());
+        for run in runs.iter().rev() {
+            println!(
+                "{:<20} {:<12} {}",
+                run.id.white(),
+                run.status.as_str(),
+                run.command
+            );
+        }
+        return Ok(());
+    };
+
+    info!("Rolling back run: {}", id);
+    let result = journal::rollback(root, id);
+
+    if dry_run::is_dry_run() {
+        dry_run::print_planned_changes();
+    }
+
+    result?;
+    if !dry_run::is_dry_run() {
+        println!("{}", format!("Run '{}' rolled back", id).green());
+    }
+    Ok(())
+}
+
+fn handle_ssh(action: SshAction) -> Result<()> {
+    match action {
+        SshAction::Confirm => ssh::confirm_change()?,
+        SshAction::Revert => ssh::revert_change()?,
+        SshAction::Status => {
+            let pending = ssh::PendingChange::load()?;
+            if output::is_json() {
+                output::emit(&pending)?;
+                return Ok(());
+            }
+
+            let Some(pending) = pending else {
+                println!("{}", "No SSH change is waiting for confirmation".green());
+                return Ok(());
+            };
+            println!("{}", "=== Unconfirmed SSH Change ===".cyan());
+            println!("  Started:   {}", pending.started);
+            println!("  Reverts:   {}", pending.deadline.yellow());
+            println!("  Test port: {}", pending.test_port);
+            for file in &pending.files {
+                let state = if file.content.is_some() { "saved" } else { "new" };
+                println!("  {:<9}  {}", state, file.path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_report(action: ReportAction) -> Result<()> {
+    let root = &paths::resolve(report::REPORTS_DIR);
+
+    match action {
+        ReportAction::List => {
+            let reports = report::list(root)?;
+
+            if output::is_json() {
+                let ids: Vec<serde_json::Value> = reports
+                    .iter()
+                    .map(|r| {
+                        serde_json::json!({
+                            "id": r.id,
+                            "command": r.command,
+                            "started": r.started,
+                            "success": r.success,
+                            "dry_run": r.dry_run,
+                        })
+                    })
+                    .collect();
+                output::emit(&ids)?;
+                return Ok(());
+            }
+
+            if reports.is_empty() {
+                println!("{}", "No run reports found".yellow());
+                return Ok(());
+            }
+
+            println!("{}", "=== Run Reports ===".cyan());
+            for r in reports.iter().rev() {
+                let result = match (r.success, r.dry_run) {
+                    (true, true) => "dry-run".yellow(),
+                    (true, false) => "success".green(),
+                    (false, _) => "failed".red(),
+                };
+                println!("{:<20} {:<10} {}", r.id.white(), result, r.command);
+            }
+        }
+        ReportAction::Show { id } => {
+            let id = if id == "latest" {
+                report::list(root)?
+                    .pop()
+                    .map(|r| r.id)
+                    .ok_or_else(|| anyhow::anyhow!("No run reports found"))?
+            } else {
+                id
+            };
+
+            let run = report::load(root, &id)?;
+            if output::is_json() {
+                output::emit(&run)?;
+            } else {
+                print!("{}", report::render_markdown(&run));
+            }
+        }
+    }
 
     Ok(())
 }
@@ -239,11 +905,21 @@ async fn execute_workflow(name: &str, config: &Config) -> Result<()> {
 fn handle_config(key: Option<String>, value: Option<String>, config: &Config) -> Result<()> {
     match (key, value) {
         (Some(k), Some(v)) => {
-            // Set configuration value
-            let mut config = config.clone();
-            config.set(&k, &v)?;
-            config.save()?;
-            println!("Set {} = {}", k, v);
+            // Set the value in the file the configuration is saved to, leaving
+            // whatever the other layers supply alone
+            let path = config
+                .path()
+                .ok_or_else(|| anyhow::anyhow!("No config path set"))?;
+            let contents = config::layers::file_with_key(path, &k, &v)?;
+            let manager = ModuleManager::with_config(config)?;
+            if let Some(issue) = manager.validate_config(&contents).first() {
+                anyhow::bail!("Invalid value for {}: {}", k, issue.message);
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, contents)?;
+            println!("Set {} = {} in {}", k, v, path.display());
         }
         (Some(k), None) => {
             // Get configuration value
@@ -264,6 +940,175 @@ fn handle_config(key: Option<String>, value: Option<String>, config: &Config) ->
     Ok(())
 }
 
+fn handle_secret(action: SecretAction, config: &Config) -> Result<()> {
+    match action {
+        SecretAction::Encrypt { recipients, file } => {
+            let recipients = if recipients.is_empty() {
+                vec![secret::recipient(&secret::secret_key(config))?]
+            } else {
+                recipients
+            };
+
+            let plaintext = match file {
+                Some(path) => std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?,
+                None if std::io::stdin().is_terminal() => {
+                    user_input::prompt_password_confirm("Secret value")?
+                }
+                None => {
+                    let mut plaintext = String::new();
+                    std::io::stdin().read_to_string(&mut plaintext)?;
+                    plaintext
+                }
+            };
+
+            let armored = secret::encrypt(&plaintext, &recipients)?;
+            if output::is_json() {
+                output::emit(&serde_json::json!({ "age": armored }))?;
+            } else {
+                print!("{{ age = \"\"\"\n{}\"\"\" }}\n", armored);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_template(action: TemplateAction, config: &Config) -> Result<()> {
+    match action {
+        TemplateAction::List => {
+            let templates = template::list();
+            if output::is_json() {
+                output::emit(&templates)?;
+                return Ok(());
+            }
+
+            println!("{} ({})", "Templates:".white(), template::dir().display());
+            for info in templates {
+                match info.overridden_by {
+                    Some(path) => println!(
+                        "  {:<36} {}",
+                        info.name.cyan(),
+                        format!("overridden by {}", path.display()).yellow()
+                    ),
+                    None => println!("  {:<36} built-in", info.name.cyan()),
+                }
+            }
+        }
+        TemplateAction::Show { name } => {
+            print!("{}", template::source(&name)?);
+        }
+        TemplateAction::Render { name } => {
+            let manager = ModuleManager::with_config(config)?;
+            let rendered = manager.render_template(&name, config)?;
+            if output::is_json() {
+                output::emit(&serde_json::json!({ "name": name, "output": rendered }))?;
+            } else {
+                print!("{}", rendered);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_config_action(action: &ConfigAction, options: &LoadOptions) -> Result<()> {
+    match action {
+        ConfigAction::Validate { file } => validate_config_files(file.as_deref(), options),
+        ConfigAction::Explain { key } => explain_config_key(key, options),
+        ConfigAction::Schema => {
+            let manager = ModuleManager::new()?;
+            println!("{}", serde_json::to_string_pretty(&manager.config_schema())?);
+            Ok(())
+        }
+    }
+}
+
+/// Validate `file`, or every configuration file that would be loaded
+fn validate_config_files(file: Option<&str>, options: &LoadOptions) -> Result<()> {
+    let paths: Vec<PathBuf> = match file {
+        Some(path) => vec![PathBuf::from(path)],
+        None => config::layers::file_sources(options)?
+            .iter()
+            .filter_map(|source| source.path().map(PathBuf::from))
+            .collect(),
+    };
+    if paths.is_empty() {
+        anyhow::bail!("No configuration files found; pass one to validate");
+    }
+
+    let mut results = Vec::new();
+    for path in &paths {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+
+        // Plugins from modules_dir can only be checked once the file parses
+        let manager = match toml::from_str::<Config>(&contents) {
+            Ok(config) => ModuleManager::with_config(&config)?,
+            Err(_) => ModuleManager::new()?,
+        };
+        let issues = manager.validate_config(&contents);
+
+        if !output::is_json() {
+            for issue in &issues {
+                if issue.line == 0 {
+                    eprintln!("{}: {}", path.display(), issue.message);
+                } else {
+                    eprintln!("{}:{}:{}: {}", path.display(), issue.line, issue.column, issue.message);
+                }
+            }
+            if issues.is_empty() {
+                println!("{}", format!("✓ {} is valid", path.display()).green());
+            } else {
+                eprintln!(
+                    "{}",
+                    format!("✗ {} problem(s) in {}", issues.len(), path.display()).red()
+                );
+            }
+        }
+        results.push(serde_json::json!({
+            "file": path,
+            "valid": issues.is_empty(),
+            "issues": issues,
+        }));
+    }
+
+    if output::is_json() {
+        output::emit(&results)?;
+    }
+    if results.iter().any(|r| r["valid"] == false) {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Show which layer supplied a configuration value
+fn explain_config_key(key: &str, options: &LoadOptions) -> Result<()> {
+    let layers = Layers::discover(options)?;
+    let path = Config::key_path(key);
+    let origins = layers.explain(&path);
+
+    if output::is_json() {
+        output::emit(&serde_json::json!({
+            "key": path,
+            "value": origins.last().map(|o| &o.value),
+            "layers": origins,
+        }))?;
+        return Ok(());
+    }
+
+    let Some((effective, overridden)) = origins.split_last() else {
+        println!("{} is not set by any layer", path);
+        return Ok(());
+    };
+    println!("{} = {}", path.white(), effective.value);
+    println!("  {} {}", "from".green(), effective.source);
+    for origin in overridden.iter().rev() {
+        println!("  {} {} = {}", "overrides".dimmed(), origin.source, origin.value);
+    }
+    Ok(())
+}
+
 fn generate_completions(shell: clap_complete::Shell) {
     use clap::CommandFactory;
     use clap_complete::generate;
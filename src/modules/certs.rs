@@ -4,15 +4,18 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
+    facts,
+    file_ops::{copy_file_with_perms, create_dir_all, remove_path, set_permissions},
     logging::{log_debug, log_error, log_info, log_success, log_warn},
-    system::{check_command, execute_command},
+    paths::resolve,
+    system::{check_command, command_output, execute_command},
     user_input::{prompt_input, prompt_yes_no, select_from_menu},
 };
 use crate::modules::{Module, ModuleBase, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 const DEBIAN_CERT_DIR: &str = "/usr/local/share/ca-certificates";
 const RHEL_CERT_DIR: &str = "/etc/pki/ca-trust/source/anchors";
@@ -31,6 +34,8 @@ impl CertsModule {
             tags: vec!["security".to_string(), "ssl".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
         Self {
             base: ModuleBase { info },
@@ -39,12 +44,12 @@ impl CertsModule {
 
     /// Detect certificate directory based on distribution
     fn get_cert_dir(&self) -> Result<PathBuf> {
-        let distro = crate::helpers::system::detect_distro()?;
+        let distro = facts::get().os.distribution.clone();
 
         if distro.is_debian_based() {
-            Ok(PathBuf::from(DEBIAN_CERT_DIR))
+            Ok(resolve(DEBIAN_CERT_DIR))
         } else if distro.is_redhat_based() {
-            Ok(PathBuf::from(RHEL_CERT_DIR))
+            Ok(resolve(RHEL_CERT_DIR))
         } else {
             Err(FluxError::Module(
                 "Unsupported distribution for certificate management".to_string()
@@ -64,21 +69,14 @@ impl CertsModule {
         }
 
         // Check if it's a valid certificate using openssl
-        let output = Command::new("openssl")
-            .arg("x509")
-            .arg("-in")
-            .arg(cert_path)
-            .arg("-noout")
-            .arg("-text")
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to validate certificate: {}", e)))?;
-
-        if output.status.success() {
+        let cert = cert_path.to_string_lossy();
+        let output = command_output("openssl", &["x509", "-in", &cert, "-noout", "-text"])?;
+
+        if output.success {
             log_debug("Certificate validation successful");
             Ok(true)
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            log_warn(&format!("Certificate validation failed: {}", stderr));
+            log_warn(format!("Certificate validation failed: {}", output.stderr));
             Ok(false)
         }
     }
@@ -87,25 +85,18 @@ impl CertsModule {
     async fn show_cert_info(&self, cert_path: &Path) -> Result<()> {
         log_info(&format!("Certificate information for: {:?}", cert_path));
 
-        let output = Command::new("openssl")
-            .arg("x509")
-            .arg("-in")
-            .arg(cert_path)
-            .arg("-noout")
-            .arg("-subject")
-            .arg("-issuer")
-            .arg("-dates")
-            .arg("-fingerprint")
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to read certificate info: {}", e)))?;
-
-        if output.status.success() {
-            println!("\n{}", String::from_utf8_lossy(&output.stdout));
+        let cert = cert_path.to_string_lossy();
+        let output = command_output(
+            "openssl",
+            &["x509", "-in", &cert, "-noout", "-subject", "-issuer", "-dates", "-fingerprint"],
+        )?;
+
+        if output.success {
+            outln!("\n{}", output.stdout);
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(FluxError::command_failed(format!(
                 "Failed to get certificate info: {}",
-                stderr
+                output.stderr
             )));
         }
 
@@ -134,7 +125,7 @@ impl CertsModule {
         }
 
         let cert_dir = self.get_cert_dir()?;
-        fs::create_dir_all(&cert_dir)?;
+        create_dir_all(&cert_dir)?;
 
         // Determine certificate filename
         let filename = if let Some(name) = cert_name {
@@ -160,16 +151,10 @@ impl CertsModule {
         let dest_path = cert_dir.join(&filename);
 
         // Copy certificate to system directory
-        fs::copy(cert_path, &dest_path)?;
+        copy_file_with_perms(cert_path, &dest_path)?;
 
         // Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&dest_path)?.permissions();
-            perms.set_mode(0o644);
-            fs::set_permissions(&dest_path, perms)?;
-        }
+        set_permissions(&dest_path, 0o644)?;
 
         log_success(&format!(
             "Certificate copied to: {}",
@@ -187,7 +172,7 @@ impl CertsModule {
     async fn update_trust_store(&self) -> Result<()> {
         log_info("Updating system certificate trust store");
 
-        let distro = crate::helpers::system::detect_distro()?;
+        let distro = facts::get().os.distribution.clone();
 
         if distro.is_debian_based() {
             execute_command("update-ca-certificates", &[])?;
@@ -217,8 +202,8 @@ impl CertsModule {
         let entries = fs::read_dir(&cert_dir)
             .map_err(|e| FluxError::system(format!("Failed to read certificate directory: {}", e)))?;
 
-        println!("\n{:<40} {:<15} {:<30}", "Certificate", "Size", "Modified");
-        println!("{}", "-".repeat(85));
+        outln!("\n{:<40} {:<15} {:<30}", "Certificate", "Size", "Modified");
+        outln!("{}", "-".repeat(85));
 
         let mut count = 0;
         for entry in entries {
@@ -242,7 +227,7 @@ impl CertsModule {
                             })
                             .unwrap_or_else(|| "Unknown".to_string());
 
-                        println!(
+                        outln!(
                             "{:<40} {:<15} {:<30}",
                             filename.to_string_lossy(),
                             format!("{} bytes", size),
@@ -255,9 +240,9 @@ impl CertsModule {
         }
 
         if count == 0 {
-            println!("No custom certificates installed");
+            outln!("No custom certificates installed");
         } else {
-            println!("\nTotal: {} certificate(s)", count);
+            outln!("\nTotal: {} certificate(s)", count);
         }
 
         Ok(())
@@ -293,7 +278,7 @@ impl CertsModule {
         }
 
         // Remove the certificate file
-        fs::remove_file(&cert_path)?;
+        remove_path(&cert_path)?;
         log_success(&format!("Certificate file removed: {}", filename));
 
         // Update trust store
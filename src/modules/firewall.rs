@@ -4,15 +4,19 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
+    changes, dry_run, facts,
+    file_ops::{create_dir_all, safe_write_file},
+    journal,
     logging::{log_debug, log_error, log_info, log_success, log_warn},
-    system::{check_command, execute_command},
+    schema::{self, ConfigSchema, SectionSchema},
+    system::{check_command, command_output, execute_command, query_command},
     user_input::{prompt_input, prompt_with_default, prompt_yes_no, select_from_menu},
 };
-use crate::modules::{Module, ModuleBase, ModuleInfo};
+use crate::modules::{parse_spec, Drift, Module, ModuleBase, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
-use std::process::Command;
 
 const UFW_BACKUP_DIR: &str = "/var/backups/flux/firewall";
 const FIREWALLD_BACKUP_DIR: &str = "/var/backups/flux/firewall";
@@ -25,6 +29,215 @@ pub enum FirewallType {
     None,
 }
 
+/// Desired firewall state, read from `[modules.firewall]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FirewallSpec {
+    pub enable_firewall: bool,
+    /// "ufw", "firewalld" or "auto"
+    pub backend: String,
+    pub allow_ssh: bool,
+    pub ssh_port: u16,
+    pub allow_http: bool,
+    pub allow_https: bool,
+    /// Presets whose ports should be open (see `--preset`)
+    pub presets: Vec<String>,
+    pub rules: Vec<FirewallRule>,
+}
+
+impl Default for FirewallSpec {
+    fn default() -> Self {
+        Self {
+            enable_firewall: true,
+            backend: "auto".to_string(),
+            allow_ssh: true,
+            ssh_port: 22,
+            allow_http: false,
+            allow_https: false,
+            presets: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl ConfigSchema for FirewallSpec {
+    fn schema() -> serde_json::Value {
+        use schema::*;
+
+        let mut rule = object(
+            "A port to open",
+            vec![
+                ("port", port("")),
+                ("protocol", one_of("", &["tcp", "udp"])),
+                ("action", one_of("Only allow rules are supported", &["allow"])),
+                ("description", string("")),
+            ],
+        );
+        rule["required"] = serde_json::json!(["port"]);
+
+        object(
+            "Firewall state applied by `flux apply`",
+            vec![
+                ("enable_firewall", boolean("Enable the firewall")),
+                ("backend", one_of("Firewall to configure", &["auto", "ufw", "firewalld"])),
+                ("allow_ssh", boolean("Open ssh_port")),
+                ("ssh_port", port("SSH port to open")),
+                ("allow_http", boolean("Open port 80")),
+                ("allow_https", boolean("Open port 443")),
+                (
+                    "presets",
+                    array(
+                        "Presets whose ports should be open",
+                        one_of(
+                            "",
+                            &["minimal", "web-server", "database", "mail-server", "docker-host", "kubernetes"],
+                        ),
+                    ),
+                ),
+                ("rules", array("Additional ports to open", rule)),
+            ],
+        )
+    }
+}
+
+/// A single port rule from `[[modules.firewall.rules]]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FirewallRule {
+    pub port: u16,
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    #[serde(default = "default_action")]
+    pub action: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+fn default_protocol() -> String {
+    "tcp".to_string()
+}
+
+fn default_action() -> String {
+    "allow".to_string()
+}
+
+impl FirewallSpec {
+    /// All ports that should be open, as (port, protocol, comment)
+    pub fn desired_ports(&self) -> Result<Vec<(u16, String, String)>> {
+        let mut ports = Vec::new();
+
+        if self.allow_ssh {
+            ports.push((self.ssh_port, "tcp".to_string(), "SSH".to_string()));
+        }
+        if self.allow_http {
+            ports.push((80, "tcp".to_string(), "HTTP".to_string()));
+        }
+        if self.allow_https {
+            ports.push((443, "tcp".to_string(), "HTTPS".to_string()));
+        }
+
+        for preset in &self.presets {
+            for (port, protocol) in preset_ports(preset)? {
+                ports.push((port, protocol.to_string(), format!("{} preset", preset)));
+            }
+        }
+
+        for rule in &self.rules {
+            if rule.action != "allow" {
+                return Err(FluxError::validation(format!(
+                    "Unsupported firewall rule action '{}' for port {} (only \"allow\" is supported)",
+                    rule.action, rule.port
+                )));
+            }
+            if rule.protocol != "tcp" && rule.protocol != "udp" {
+                return Err(FluxError::validation(format!(
+                    "Invalid protocol '{}' for port {}: expected tcp or udp",
+                    rule.protocol, rule.port
+                )));
+            }
+            let comment = rule
+                .description
+                .clone()
+                .unwrap_or_else(|| "flux rule".to_string());
+            ports.push((rule.port, rule.protocol.clone(), comment));
+        }
+
+        // Keep the first occurrence of each port/protocol pair
+        let mut seen = std::collections::HashSet::new();
+        ports.retain(|(port, protocol, _)| seen.insert((*port, protocol.clone())));
+
+        Ok(ports)
+    }
+}
+
+/// Ports allowed by `ufw status` output; `None` protocol means both tcp and udp
+fn parse_ufw_allowed(status: &str) -> Vec<(u16, Option<String>)> {
+    status
+        .lines()
+        .filter(|line| line.contains("ALLOW"))
+        .filter_map(|line| {
+            let target = line.split_whitespace().next()?;
+            match target.split_once('/') {
+                Some((port, protocol)) => Some((port.parse().ok()?, Some(protocol.to_string()))),
+                None => Some((target.parse().ok()?, None)),
+            }
+        })
+        .collect()
+}
+
+/// Check whether `port/protocol` is covered by a list of allowed ports
+fn is_port_allowed(allowed: &[(u16, Option<String>)], port: u16, protocol: &str) -> bool {
+    allowed
+        .iter()
+        .any(|(p, proto)| *p == port && proto.as_deref().is_none_or(|proto| proto == protocol))
+}
+
+/// Ports opened by a named security preset
+fn preset_ports(preset: &str) -> Result<Vec<(u16, &'static str)>> {
+    let ports = match preset {
+        "web-server" => vec![
+            (80, "tcp"),
+            (443, "tcp"),
+        ],
+        "database" => vec![
+            (3306, "tcp"),  // MySQL
+            (5432, "tcp"),  // PostgreSQL
+        ],
+        "mail-server" => vec![
+            (25, "tcp"),    // SMTP
+            (465, "tcp"),   // SMTPS
+            (587, "tcp"),   // Submission
+            (143, "tcp"),   // IMAP
+            (993, "tcp"),   // IMAPS
+            (110, "tcp"),   // POP3
+            (995, "tcp"),   // POP3S
+        ],
+        "docker-host" => vec![
+            (2376, "tcp"),  // Docker daemon
+            (2377, "tcp"),  // Swarm management
+            (7946, "tcp"),  // Container network discovery
+            (7946, "udp"),
+            (4789, "udp"),  // Overlay network
+        ],
+        "kubernetes" => vec![
+            (6443, "tcp"),  // API server
+            (2379, "tcp"),  // etcd
+            (2380, "tcp"),  // etcd
+            (10250, "tcp"), // Kubelet
+            (10251, "tcp"), // Scheduler
+            (10252, "tcp"), // Controller
+        ],
+        "minimal" => vec![
+            (22, "tcp"),    // SSH only
+        ],
+        _ => {
+            return Err(FluxError::Module(format!("Unknown preset: {}", preset)));
+        }
+    };
+
+    Ok(ports)
+}
+
 pub struct FirewallModule {
     base: ModuleBase,
 }
@@ -39,6 +252,8 @@ impl FirewallModule {
             tags: vec!["security".to_string(), "network".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: vec!["update".to_string(), "network".to_string()],
         };
         Self {
             base: ModuleBase { info },
@@ -51,13 +266,8 @@ impl FirewallModule {
 
         // Check UFW
         if check_command("ufw").is_ok() {
-            let output = Command::new("ufw")
-                .arg("status")
-                .output()
-                .ok();
-
-            if let Some(out) = output {
-                let status = String::from_utf8_lossy(&out.stdout);
+            if let Ok(out) = command_output("ufw", &["status"]) {
+                let status = out.stdout;
                 if status.contains("Status: active") {
                     log_info("Detected active UFW firewall");
                     return Ok(FirewallType::UFW);
@@ -67,13 +277,8 @@ impl FirewallModule {
 
         // Check firewalld
         if check_command("firewall-cmd").is_ok() {
-            let output = Command::new("firewall-cmd")
-                .arg("--state")
-                .output()
-                .ok();
-
-            if let Some(out) = output {
-                let status = String::from_utf8_lossy(&out.stdout);
+            if let Ok(out) = command_output("firewall-cmd", &["--state"]) {
+                let status = out.stdout;
                 if status.trim() == "running" {
                     log_info("Detected active firewalld");
                     return Ok(FirewallType::Firewalld);
@@ -93,7 +298,7 @@ impl FirewallModule {
 
     /// Install firewall based on distribution
     async fn install_firewall(&self, fw_type: &FirewallType) -> Result<()> {
-        let distro = crate::helpers::system::detect_distro()?;
+        let distro = facts::get().os.distribution.clone();
 
         match fw_type {
             FirewallType::UFW => {
@@ -108,6 +313,7 @@ impl FirewallModule {
                     return Err(FluxError::Module("Unsupported distribution for UFW".to_string()));
                 }
 
+                facts::invalidate();
                 log_success("UFW installed successfully");
                 Ok(())
             }
@@ -123,6 +329,7 @@ impl FirewallModule {
                     return Err(FluxError::Module("Unsupported distribution for firewalld".to_string()));
                 }
 
+                facts::invalidate();
                 log_success("firewalld installed successfully");
                 Ok(())
             }
@@ -139,16 +346,9 @@ impl FirewallModule {
         execute_command("ufw", &["allow", "ssh"])?;
 
         // Enable UFW
-        let output = Command::new("ufw")
-            .arg("--force")
-            .arg("enable")
-            .output()
+        execute_command("ufw", &["--force", "enable"])
             .map_err(|e| FluxError::command_failed(format!("Failed to enable UFW: {}", e)))?;
 
-        if !output.status.success() {
-            return Err(FluxError::command_failed("Failed to enable UFW".to_string()));
-        }
-
         // Enable UFW service
         if check_command("systemctl").is_ok() {
             execute_command("systemctl", &["enable", "ufw"])?;
@@ -176,35 +376,74 @@ impl FirewallModule {
         Ok(())
     }
 
-    /// Add UFW rule
+    /// Add UFW rule, unless ufw already allows the port
     async fn add_ufw_rule(&self, port: u16, protocol: &str, comment: Option<&str>) -> Result<()> {
-        log_info(&format!("Adding UFW rule: {}/{}", port, protocol));
+        let rule = format!("{}/{}", port, protocol);
+        let resource = format!("ufw allow {}", rule);
+
+        let allowed = parse_ufw_allowed(&query_command("ufw", &["status"]).unwrap_or_default());
+        if is_port_allowed(&allowed, port, protocol) {
+            changes::ok(resource);
+            return Ok(());
+        }
+
+        log_info(&format!("Adding UFW rule: {}", rule));
 
         let mut args = vec!["allow".to_string()];
         if let Some(cmt) = comment {
             args.push("comment".to_string());
             args.push(cmt.to_string());
         }
-        args.push(format!("{}/{}", port, protocol));
+        args.push(rule.clone());
 
         let args_str: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-        execute_command("ufw", &args_str)?;
+        let output = execute_command("ufw", &args_str)?;
+
+        // An inactive ufw lists no rules, but still skips those that exist;
+        // those must survive a rollback
+        if output.contains("Skipping") {
+            changes::ok(resource);
+            return Ok(());
+        }
+        journal::record_command("ufw", &args_str, &["ufw", "delete", "allow", &rule])?;
+        let detail = if dry_run::is_dry_run() { "would add" } else { "added" };
+        changes::changed(resource, detail);
 
-        log_success(&format!("UFW rule added: {}/{}", port, protocol));
+        log_success(&format!("UFW rule added: {}", rule));
         Ok(())
     }
 
-    /// Add firewalld rule
+    /// Add firewalld rule, unless the zone already has the port
     async fn add_firewalld_rule(&self, port: u16, protocol: &str, zone: &str) -> Result<()> {
-        log_info(&format!("Adding firewalld rule: {}/{} to zone {}", port, protocol, zone));
+        let zone_arg = format!("--zone={}", zone);
+        let port_arg = format!("{}/{}", port, protocol);
+        let resource = format!("firewalld {} port {}", zone, port_arg);
 
-        execute_command(
+        let existed = query_command(
             "firewall-cmd",
-            &["--permanent", &format!("--zone={}", zone), &format!("--add-port={}/{}", port, protocol)]
-        )?;
+            &["--permanent", &zone_arg, &format!("--query-port={}", port_arg)],
+        )
+        .is_ok();
+        if existed {
+            changes::ok(resource);
+            return Ok(());
+        }
+
+        log_info(&format!("Adding firewalld rule: {} to zone {}", port_arg, zone));
+
+        let add_args = ["--permanent", zone_arg.as_str(), &format!("--add-port={}", port_arg)];
+        execute_command("firewall-cmd", &add_args)?;
         execute_command("firewall-cmd", &["--reload"])?;
 
-        log_success(&format!("firewalld rule added: {}/{}", port, protocol));
+        let undo = format!(
+            "firewall-cmd --permanent {} --remove-port={} && firewall-cmd --reload",
+            zone_arg, port_arg
+        );
+        journal::record_command("firewall-cmd", &add_args, &["sh", "-c", &undo])?;
+        let detail = if dry_run::is_dry_run() { "would add" } else { "added" };
+        changes::changed(resource, detail);
+
+        log_success(&format!("firewalld rule added: {}", port_arg));
         Ok(())
     }
 
@@ -218,12 +457,9 @@ impl FirewallModule {
             vec!["status", "numbered"]
         };
 
-        let output = Command::new("ufw")
-            .args(&args)
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to list UFW rules: {}", e)))?;
+        let output = command_output("ufw", &args)?;
 
-        println!("\n{}", String::from_utf8_lossy(&output.stdout));
+        outln!("\n{}", output.stdout);
         Ok(())
     }
 
@@ -231,13 +467,9 @@ impl FirewallModule {
     async fn list_firewalld_rules(&self, zone: &str) -> Result<()> {
         log_info(&format!("Listing firewalld rules for zone: {}", zone));
 
-        let output = Command::new("firewall-cmd")
-            .arg(&format!("--zone={}", zone))
-            .arg("--list-all")
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to list firewalld rules: {}", e)))?;
+        let output = command_output("firewall-cmd", &[&format!("--zone={}", zone), "--list-all"])?;
 
-        println!("\n{}", String::from_utf8_lossy(&output.stdout));
+        outln!("\n{}", output.stdout);
         Ok(())
     }
 
@@ -273,46 +505,7 @@ impl FirewallModule {
 
         let fw_type = self.detect_firewall().await?;
 
-        let ports_to_open: Vec<(u16, &str)> = match preset {
-            "web-server" => vec![
-                (80, "tcp"),
-                (443, "tcp"),
-            ],
-            "database" => vec![
-                (3306, "tcp"),  // MySQL
-                (5432, "tcp"),  // PostgreSQL
-            ],
-            "mail-server" => vec![
-                (25, "tcp"),    // SMTP
-                (465, "tcp"),   // SMTPS
-                (587, "tcp"),   // Submission
-                (143, "tcp"),   // IMAP
-                (993, "tcp"),   // IMAPS
-                (110, "tcp"),   // POP3
-                (995, "tcp"),   // POP3S
-            ],
-            "docker-host" => vec![
-                (2376, "tcp"),  // Docker daemon
-                (2377, "tcp"),  // Swarm management
-                (7946, "tcp"),  // Container network discovery
-                (7946, "udp"),
-                (4789, "udp"),  // Overlay network
-            ],
-            "kubernetes" => vec![
-                (6443, "tcp"),  // API server
-                (2379, "tcp"),  // etcd
-                (2380, "tcp"),  // etcd
-                (10250, "tcp"), // Kubelet
-                (10251, "tcp"), // Scheduler
-                (10252, "tcp"), // Controller
-            ],
-            "minimal" => vec![
-                (22, "tcp"),    // SSH only
-            ],
-            _ => {
-                return Err(FluxError::Module(format!("Unknown preset: {}", preset)));
-            }
-        };
+        let ports_to_open = preset_ports(preset)?;
 
         for (port, protocol) in ports_to_open {
             match fw_type {
@@ -333,6 +526,145 @@ impl FirewallModule {
         Ok(())
     }
 
+    /// Make sure the requested firewall backend is installed and enabled
+    async fn ensure_firewall(&self, backend: &str) -> Result<FirewallType> {
+        let active = self.detect_firewall().await?;
+
+        let wanted = match backend {
+            "ufw" => FirewallType::UFW,
+            "firewalld" => FirewallType::Firewalld,
+            "auto" => {
+                if active == FirewallType::UFW || active == FirewallType::Firewalld {
+                    changes::ok("firewall");
+                    return Ok(active);
+                }
+                if check_command("ufw").is_ok() {
+                    FirewallType::UFW
+                } else if check_command("firewall-cmd").is_ok() {
+                    FirewallType::Firewalld
+                } else if facts::get().os.distribution.is_debian_based() {
+                    FirewallType::UFW
+                } else {
+                    FirewallType::Firewalld
+                }
+            }
+            other => {
+                return Err(FluxError::validation(format!(
+                    "Unknown firewall backend '{}': expected ufw, firewalld or auto",
+                    other
+                )));
+            }
+        };
+
+        if active == wanted {
+            changes::ok("firewall");
+            return Ok(wanted);
+        }
+
+        let command = if wanted == FirewallType::UFW { "ufw" } else { "firewall-cmd" };
+        if check_command(command).is_err() {
+            self.install_firewall(&wanted).await?;
+        }
+
+        match wanted {
+            FirewallType::UFW => self.enable_ufw().await?,
+            _ => self.enable_firewalld().await?,
+        }
+        let name = if wanted == FirewallType::UFW { "ufw" } else { "firewalld" };
+        let detail = if dry_run::is_dry_run() { "would enable" } else { "enabled" };
+        changes::changed("firewall", format!("{} {}", detail, name));
+
+        Ok(wanted)
+    }
+
+    /// Converge firewall rules to the given spec without prompting
+    async fn apply_spec(&self, spec: &FirewallSpec) -> Result<()> {
+        if !spec.enable_firewall {
+            log_info("Firewall disabled in configuration, nothing to apply");
+            return Ok(());
+        }
+
+        let ports = spec.desired_ports()?;
+        let fw_type = self.ensure_firewall(&spec.backend).await?;
+
+        for (port, protocol, comment) in ports {
+            match fw_type {
+                FirewallType::UFW => self.add_ufw_rule(port, &protocol, Some(&comment)).await?,
+                _ => self.add_firewalld_rule(port, &protocol, "public").await?,
+            }
+        }
+
+        log_success("Firewall configuration converged");
+        Ok(())
+    }
+
+    /// Ports currently allowed in the public zone of firewalld
+    fn firewalld_allowed(&self) -> Result<Vec<(u16, Option<String>)>> {
+        let mut allowed = Vec::new();
+
+        let ports = query_command("firewall-cmd", &["--zone=public", "--list-ports"])?;
+        for entry in ports.split_whitespace() {
+            if let Some((port, protocol)) = entry.split_once('/') {
+                if let Ok(port) = port.parse() {
+                    allowed.push((port, Some(protocol.to_string())));
+                }
+            }
+        }
+
+        let services = query_command("firewall-cmd", &["--zone=public", "--list-services"])?;
+        let service_ports = self.get_service_ports();
+        for service in services.split_whitespace() {
+            if let Some((port, protocol)) = service_ports.get(service) {
+                allowed.push((*port, Some(protocol.clone())));
+            }
+        }
+
+        Ok(allowed)
+    }
+
+    /// Compare the active firewall rules against the given spec
+    async fn plan_spec(&self, spec: &FirewallSpec) -> Result<Vec<Drift>> {
+        if !spec.enable_firewall {
+            return Ok(Vec::new());
+        }
+
+        let ports = spec.desired_ports()?;
+        let active = self.detect_firewall().await?;
+
+        let expected_backend = match spec.backend.as_str() {
+            "ufw" => Some(FirewallType::UFW),
+            "firewalld" => Some(FirewallType::Firewalld),
+            _ => None,
+        };
+
+        let allowed = match active {
+            FirewallType::UFW | FirewallType::Firewalld
+                if expected_backend.as_ref().is_none_or(|wanted| *wanted == active) =>
+            {
+                if active == FirewallType::UFW {
+                    parse_ufw_allowed(&query_command("ufw", &["status"])?)
+                } else {
+                    self.firewalld_allowed()?
+                }
+            }
+            _ => {
+                return Ok(vec![Drift::new(
+                    "firewall",
+                    format!("{} active", spec.backend),
+                    Some(format!("{:?}", active).to_lowercase()),
+                )]);
+            }
+        };
+
+        Ok(ports
+            .into_iter()
+            .filter(|(port, protocol, _)| !is_port_allowed(&allowed, *port, protocol))
+            .map(|(port, protocol, comment)| {
+                Drift::missing(format!("{}/{}", port, protocol), format!("allow ({})", comment))
+            })
+            .collect())
+    }
+
     /// Backup firewall configuration
     async fn backup_config(&self) -> Result<String> {
         log_info("Backing up firewall configuration");
@@ -342,23 +674,19 @@ impl FirewallModule {
 
         match fw_type {
             FirewallType::UFW => {
-                fs::create_dir_all(UFW_BACKUP_DIR)?;
+                create_dir_all(UFW_BACKUP_DIR)?;
                 let backup_path = format!("{}/ufw-rules-{}.backup", UFW_BACKUP_DIR, timestamp);
 
                 // Export UFW rules
-                let output = Command::new("ufw")
-                    .arg("status")
-                    .arg("numbered")
-                    .output()
-                    .map_err(|e| FluxError::command_failed(format!("Failed to backup UFW: {}", e)))?;
+                let output = command_output("ufw", &["status", "numbered"])?;
 
-                fs::write(&backup_path, output.stdout)?;
+                safe_write_file(&backup_path, &output.stdout, false)?;
 
                 log_success(&format!("UFW configuration backed up to: {}", backup_path));
                 Ok(backup_path)
             }
             FirewallType::Firewalld => {
-                fs::create_dir_all(FIREWALLD_BACKUP_DIR)?;
+                create_dir_all(FIREWALLD_BACKUP_DIR)?;
                 let backup_path = format!("{}/firewalld-{}.backup", FIREWALLD_BACKUP_DIR, timestamp);
 
                 // Copy firewalld configuration
@@ -386,13 +714,8 @@ impl FirewallModule {
             }
             FirewallType::Iptables => {
                 log_info("iptables Status:");
-                let output = Command::new("iptables")
-                    .arg("-L")
-                    .arg("-n")
-                    .arg("-v")
-                    .output()
-                    .map_err(|e| FluxError::command_failed(format!("Failed to list iptables: {}", e)))?;
-                println!("\n{}", String::from_utf8_lossy(&output.stdout));
+                let output = command_output("iptables", &["-L", "-n", "-v"])?;
+                outln!("\n{}", output.stdout);
             }
             FirewallType::None => {
                 log_warn("No firewall is currently active");
@@ -405,7 +728,7 @@ impl FirewallModule {
     /// Interactive setup wizard
     async fn setup_wizard(&self) -> Result<()> {
         log_info("Firewall Setup Wizard");
-        println!("\nThis wizard will help you configure your firewall.\n");
+        outln!("\nThis wizard will help you configure your firewall.\n");
 
         let fw_type = self.detect_firewall().await?;
 
@@ -417,7 +740,7 @@ impl FirewallModule {
                 return Ok(());
             }
 
-            let distro = crate::helpers::system::detect_distro()?;
+            let distro = facts::get().os.distribution.clone();
             let default_fw = if distro.is_debian_based() {
                 "UFW"
             } else {
@@ -608,6 +931,10 @@ impl Module for FirewallModule {
         &self.base.info.version
     }
 
+    fn after(&self) -> &[String] {
+        &self.base.info.after
+    }
+
     fn is_available(&self) -> bool {
         check_command("ufw").is_ok()
             || check_command("firewall-cmd").is_ok()
@@ -660,6 +987,24 @@ EXAMPLES:
         )
     }
 
+    fn supports_apply(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> Option<SectionSchema> {
+        Some(SectionSchema::of::<FirewallSpec>())
+    }
+
+    async fn apply(&self, spec: &toml::Value, _config: &Config) -> Result<()> {
+        let spec: FirewallSpec = parse_spec(self.name(), spec)?;
+        self.apply_spec(&spec).await
+    }
+
+    async fn plan(&self, spec: &toml::Value, _config: &Config) -> Result<Vec<Drift>> {
+        let spec: FirewallSpec = parse_spec(self.name(), spec)?;
+        self.plan_spec(&spec).await
+    }
+
     async fn execute(&self, args: Vec<String>, _config: &Config) -> Result<()> {
         if args.is_empty() || args.contains(&"--menu".to_string()) {
             return self.show_menu().await;
@@ -744,3 +1089,78 @@ EXAMPLES:
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::changes::ResourceStatus;
+    use crate::helpers::runner::{set_runner, CommandOutput, RecordingRunner};
+    use serial_test::serial;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_ufw_allowed() {
+        let status = "Status: active\n\n\
+To                         Action      From\n\
+--                         ------      ----\n\
+22/tcp                     ALLOW       Anywhere\n\
+80                         ALLOW       Anywhere\n\
+OpenSSH                    ALLOW       Anywhere\n\
+3306/tcp                   DENY        Anywhere\n\
+22/tcp (v6)                ALLOW       Anywhere (v6)\n";
+
+        let allowed = parse_ufw_allowed(status);
+
+        assert!(is_port_allowed(&allowed, 22, "tcp"));
+        assert!(!is_port_allowed(&allowed, 22, "udp"));
+        assert!(is_port_allowed(&allowed, 80, "udp"));
+        assert!(!is_port_allowed(&allowed, 3306, "tcp"));
+        assert!(!is_port_allowed(&allowed, 443, "tcp"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_add_rules_skip_existing() {
+        let recorder = Arc::new(RecordingRunner::new());
+        recorder.respond("ufw status", CommandOutput::ok("Status: active\n\n22/tcp ALLOW Anywhere\n"));
+        recorder.respond(
+            "firewall-cmd --permanent --zone=public --query-port=443/tcp",
+            CommandOutput::failed(1, "no"),
+        );
+        let previous = set_runner(recorder.clone());
+
+        let module = FirewallModule::new();
+        let (result, resources) = changes::track(async {
+            module.add_ufw_rule(22, "tcp", None).await?;
+            module.add_ufw_rule(80, "tcp", None).await?;
+            module.add_firewalld_rule(8080, "tcp", "public").await?;
+            module.add_firewalld_rule(443, "tcp", "public").await
+        })
+        .await;
+        set_runner(previous);
+
+        result.unwrap();
+        assert_eq!(
+            recorder.command_lines(),
+            vec![
+                "ufw status",
+                "ufw status",
+                "ufw allow 80/tcp",
+                "firewall-cmd --permanent --zone=public --query-port=8080/tcp",
+                "firewall-cmd --permanent --zone=public --query-port=443/tcp",
+                "firewall-cmd --permanent --zone=public --add-port=443/tcp",
+                "firewall-cmd --reload",
+            ]
+        );
+        let statuses: Vec<_> = resources.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ResourceStatus::Ok,
+                ResourceStatus::Changed,
+                ResourceStatus::Ok,
+                ResourceStatus::Changed,
+            ]
+        );
+    }
+}
@@ -1,13 +1,16 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    file_ops::{backup_file, safe_write_file},
+    changes, facts,
+    file_ops::{file_exists, read_file_to_string, safe_write_file},
     logging::log_info,
-    system::{execute_command, get_hostname},
+    paths,
+    system::{execute_command, get_hostname, query_command, restart_service},
     user_input::{prompt_input, prompt_yes_no},
     validation::validate_hostname,
 };
 use crate::modules::{Module, ModuleBase, ModuleContext, ModuleInfo};
+use crate::{out, outln};
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::Colorize;
@@ -27,6 +30,8 @@ impl HostnameModule {
             tags: vec!["system".to_string(), "network".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
         
         Self {
@@ -102,20 +107,20 @@ impl HostnameModule {
     }
     
     async fn show_hostname_config(&self) -> Result<()> {
-        println!("{}", "=== Current Hostname Configuration ===".cyan());
+        outln!("{}", "=== Current Hostname Configuration ===".cyan());
         
         let info = self.get_hostname_info()?;
         
-        println!("Hostname: {}", info.hostname);
-        println!("FQDN: {}", info.fqdn.as_ref().unwrap_or(&"Not set".to_string()));
-        println!("Domain: {}", info.domain.as_ref().unwrap_or(&"Not set".to_string()));
-        println!("Short: {}", info.short_name);
+        outln!("Hostname: {}", info.hostname);
+        outln!("FQDN: {}", info.fqdn.as_ref().unwrap_or(&"Not set".to_string()));
+        outln!("Domain: {}", info.domain.as_ref().unwrap_or(&"Not set".to_string()));
+        outln!("Short: {}", info.short_name);
         
         // Show hostnamectl output if available
         if crate::helpers::system::command_exists("hostnamectl") {
-            println!("\n{}", "Hostnamectl output:".white());
-            let output = execute_command("hostnamectl", &["status"])?;
-            println!("{}", output);
+            outln!("\n{}", "Hostnamectl output:".white());
+            let output = query_command("hostnamectl", &["status"])?;
+            outln!("{}", output);
         }
         
         Ok(())
@@ -127,12 +132,22 @@ impl HostnameModule {
         // Validate hostname
         validate_hostname(new_hostname)?;
         
-        // Backup configuration files
-        backup_file("/etc/hostname")?;
-        backup_file("/etc/hosts")?;
+        // Files are backed up by the writes below when they change
+        let configured = read_file_to_string("/etc/hostname").unwrap_or_default();
+        let live = if paths::root().is_some() {
+            configured.trim().to_string()
+        } else {
+            get_hostname().unwrap_or_default()
+        };
+        let mut changed = false;
         
         // Set hostname using hostnamectl if available
-        if crate::helpers::system::command_exists("hostnamectl") {
+        if configured.trim() == new_hostname && live == new_hostname {
+            changes::ok(format!("hostname {}", new_hostname));
+        } else if paths::root().is_some() {
+            // The kernel hostname belongs to the host, only the tree is updated
+            changed |= safe_write_file("/etc/hostname", &format!("{}\n", new_hostname), true)?;
+        } else if crate::helpers::system::command_exists("hostnamectl") {
             log_info("Using hostnamectl to set hostname");
             
             execute_command("hostnamectl", &["set-hostname", new_hostname, "--static"])?;
@@ -142,21 +157,25 @@ impl HostnameModule {
             if !new_hostname.contains('.') {
                 execute_command("hostnamectl", &["set-hostname", new_hostname, "--pretty"])?;
             }
+            changes::changed(format!("hostname {}", new_hostname), format!("was {}", live));
+            changed = true;
         } else {
             // Fallback method
             log_info("Using traditional method to set hostname");
             
-            safe_write_file("/etc/hostname", new_hostname, false)?;
+            safe_write_file("/etc/hostname", &format!("{}\n", new_hostname), true)?;
             execute_command("hostname", &[new_hostname])?;
+            changes::changed(format!("hostname {}", new_hostname), format!("was {}", live));
+            changed = true;
         }
         
         // Update hosts file
-        self.update_hosts_file(new_hostname, None).await?;
+        changed |= self.update_hosts_file(new_hostname, None).await?;
         
         // Update machine-info if it exists
         let machine_info_path = "/etc/machine-info";
-        if std::path::Path::new(machine_info_path).exists() {
-            let content = std::fs::read_to_string(machine_info_path).unwrap_or_default();
+        if file_exists(machine_info_path) {
+            let content = read_file_to_string(machine_info_path).unwrap_or_default();
             let updated = if content.contains("PRETTY_HOSTNAME=") {
                 content
                     .lines()
@@ -169,13 +188,20 @@ impl HostnameModule {
                     })
                     .collect::<Vec<_>>()
                     .join("\n")
+                    + if content.ends_with('\n') { "\n" } else { "" }
             } else {
                 format!("{}\nPRETTY_HOSTNAME=\"{}\"", content, new_hostname)
             };
             
-            safe_write_file(machine_info_path, &updated, true)?;
+            changed |= safe_write_file(machine_info_path, &updated, true)?;
+        }
+        
+        if !changed {
+            log_info("Hostname already set");
+            return Ok(());
         }
         
+        facts::invalidate();
         log_info("Hostname set successfully");
         
         // Check if services need restart
@@ -213,8 +239,8 @@ impl HostnameModule {
         
         // Set domain in resolv.conf if not already set
         let resolv_conf = "/etc/resolv.conf";
-        if std::path::Path::new(resolv_conf).exists() {
-            let content = std::fs::read_to_string(resolv_conf)?;
+        if file_exists(resolv_conf) {
+            let content = read_file_to_string(resolv_conf)?;
             if !content.contains("domain ") && !content.contains("search ") {
                 let updated = format!("{}\ndomain {}", content, new_domain);
                 safe_write_file(resolv_conf, &updated, true)?;
@@ -226,22 +252,22 @@ impl HostnameModule {
     }
     
     async fn configure_interactive(&self) -> Result<()> {
-        println!("{}", "=== Hostname Configuration ===".cyan());
+        outln!("{}", "=== Hostname Configuration ===".cyan());
         
         // Show current configuration
         let info = self.get_hostname_info()?;
-        println!("\n{}", "Current Configuration:".white());
-        println!("  Hostname: {}", info.hostname);
-        println!("  FQDN: {}", info.fqdn.as_ref().unwrap_or(&"Not set".to_string()));
-        println!("  Domain: {}", info.domain.as_ref().unwrap_or(&"Not set".to_string()));
-        println!();
+        outln!("\n{}", "Current Configuration:".white());
+        outln!("  Hostname: {}", info.hostname);
+        outln!("  FQDN: {}", info.fqdn.as_ref().unwrap_or(&"Not set".to_string()));
+        outln!("  Domain: {}", info.domain.as_ref().unwrap_or(&"Not set".to_string()));
+        outln!();
         
         // Ask what to configure
-        println!("What would you like to configure?");
-        println!("  1) Simple hostname only");
-        println!("  2) Fully Qualified Domain Name (FQDN)");
-        println!("  3) Cancel");
-        println!();
+        outln!("What would you like to configure?");
+        outln!("  1) Simple hostname only");
+        outln!("  2) Fully Qualified Domain Name (FQDN)");
+        outln!("  3) Cancel");
+        outln!();
         
         let choice = prompt_input("Select option [1-3]")?;
         
@@ -257,14 +283,14 @@ impl HostnameModule {
                 };
                 
                 self.set_hostname(&new_hostname).await?;
-                println!("\n{}", format!("Hostname set to: {}", new_hostname).green());
+                outln!("\n{}", format!("Hostname set to: {}", new_hostname).green());
             }
             
             "2" => {
                 // FQDN
-                println!("\n{}", "Note: FQDN should be in format: hostname.domain.tld".yellow());
-                println!("{}", "Example: server01.example.com".yellow());
-                println!();
+                outln!("\n{}", "Note: FQDN should be in format: hostname.domain.tld".yellow());
+                outln!("{}", "Example: server01.example.com".yellow());
+                outln!();
                 
                 let new_fqdn = loop {
                     let input = prompt_input("Enter new FQDN")?;
@@ -285,7 +311,7 @@ impl HostnameModule {
                 };
                 
                 self.set_fqdn(&new_fqdn).await?;
-                println!("\n{}", format!("FQDN set to: {}", new_fqdn).green());
+                outln!("\n{}", format!("FQDN set to: {}", new_fqdn).green());
             }
             
             "3" => {
@@ -299,106 +325,107 @@ impl HostnameModule {
         }
         
         // Show new configuration
-        println!("\n{}", "New Configuration:".white());
+        outln!("\n{}", "New Configuration:".white());
         let new_info = self.get_hostname_info()?;
-        println!("  Hostname: {}", new_info.hostname);
-        println!("  FQDN: {}", new_info.fqdn.as_ref().unwrap_or(&"Not set".to_string()));
-        println!("  Domain: {}", new_info.domain.as_ref().unwrap_or(&"Not set".to_string()));
+        outln!("  Hostname: {}", new_info.hostname);
+        outln!("  FQDN: {}", new_info.fqdn.as_ref().unwrap_or(&"Not set".to_string()));
+        outln!("  Domain: {}", new_info.domain.as_ref().unwrap_or(&"Not set".to_string()));
         
-        println!("\n{}", "Note: You may need to reconnect SSH sessions for changes to take effect".yellow());
+        outln!("\n{}", "Note: You may need to reconnect SSH sessions for changes to take effect".yellow());
         
         Ok(())
     }
     
     async fn verify_configuration(&self) -> Result<()> {
-        println!("{}", "=== Hostname Configuration Verification ===".cyan());
+        outln!("{}", "=== Hostname Configuration Verification ===".cyan());
         
         let mut all_good = true;
         let info = self.get_hostname_info()?;
         
         // Check hostname command
-        print!("Hostname command: ");
+        out!("Hostname command: ");
         if !info.hostname.is_empty() && info.hostname != "localhost" {
-            println!("{}", info.hostname.green());
+            outln!("{}", info.hostname.green());
         } else {
-            println!("{}", "Not set properly".red());
+            outln!("{}", "Not set properly".red());
             all_good = false;
         }
         
         // Check /etc/hostname
-        print!("/etc/hostname: ");
-        if let Ok(file_hostname) = std::fs::read_to_string("/etc/hostname") {
+        out!("/etc/hostname: ");
+        if let Ok(file_hostname) = read_file_to_string("/etc/hostname") {
             let file_hostname = file_hostname.trim();
             if file_hostname == info.hostname {
-                println!("{}", file_hostname.green());
+                outln!("{}", file_hostname.green());
             } else {
-                println!("{} (mismatch)", file_hostname.yellow());
+                outln!("{} (mismatch)", file_hostname.yellow());
                 all_good = false;
             }
         } else {
-            println!("{}", "File not found".red());
+            outln!("{}", "File not found".red());
             all_good = false;
         }
         
         // Check FQDN resolution
-        print!("FQDN resolution: ");
+        out!("FQDN resolution: ");
         if let Some(fqdn) = &info.fqdn {
-            println!("{}", fqdn.green());
+            outln!("{}", fqdn.green());
         } else {
-            println!("{}", "Not set".yellow());
+            outln!("{}", "Not set".yellow());
         }
         
         // Check /etc/hosts
-        print!("/etc/hosts entries: ");
-        let hosts_content = std::fs::read_to_string("/etc/hosts").unwrap_or_default();
+        out!("/etc/hosts entries: ");
+        let hosts_content = read_file_to_string("/etc/hosts").unwrap_or_default();
         if hosts_content.contains(&info.hostname) {
-            println!("{}", "Found".green());
+            outln!("{}", "Found".green());
             
             // Show relevant entries
-            println!("  Entries containing hostname:");
+            outln!("  Entries containing hostname:");
             for line in hosts_content.lines() {
                 if line.contains(&info.hostname) {
-                    println!("    {}", line);
+                    outln!("    {}", line);
                 }
             }
         } else {
-            println!("{}", "Not found".red());
+            outln!("{}", "Not found".red());
             all_good = false;
         }
         
         // DNS resolution test
-        print!("DNS resolution test: ");
-        if let Ok(output) = execute_command("host", &[&info.hostname]) {
+        out!("DNS resolution test: ");
+        if let Ok(output) = query_command("host", &[&info.hostname]) {
             if output.contains("has address") {
                 let ip = output
                     .lines()
                     .find(|l| l.contains("has address"))
                     .and_then(|l| l.split_whitespace().last())
                     .unwrap_or("unknown");
-                println!("{}", format!("OK ({})", ip).green());
+                outln!("{}", format!("OK ({})", ip).green());
             } else {
-                println!("{}", "Cannot resolve (this is normal for local hostnames)".yellow());
+                outln!("{}", "Cannot resolve (this is normal for local hostnames)".yellow());
             }
         } else {
-            println!("{}", "host command not available".yellow());
+            outln!("{}", "host command not available".yellow());
         }
         
         // Overall status
-        println!();
+        outln!();
         if all_good {
-            println!("{}", "✓ Hostname configuration is correct".green());
+            outln!("{}", "✓ Hostname configuration is correct".green());
         } else {
-            println!("{}", "⚠ Some issues detected with hostname configuration".yellow());
+            outln!("{}", "⚠ Some issues detected with hostname configuration".yellow());
         }
         
         Ok(())
     }
     
-    async fn update_hosts_file(&self, hostname: &str, fqdn: Option<&str>) -> Result<()> {
+    /// Point `hostname` at this host in /etc/hosts, returning whether the file changed
+    async fn update_hosts_file(&self, hostname: &str, fqdn: Option<&str>) -> Result<bool> {
         log_info("Updating /etc/hosts");
         
         let hosts_path = "/etc/hosts";
-        let content = std::fs::read_to_string(hosts_path).unwrap_or_default();
+        let content = read_file_to_string(hosts_path).unwrap_or_default();
         
         // Get primary IP address
         let primary_ip = crate::helpers::network::get_network_interfaces()?
@@ -408,13 +435,10 @@ impl HostnameModule {
             .map(|ip| ip.to_string())
             .unwrap_or_default();
         
-        // Filter out old hostname entries
+        // Filter out old hostname entries, so they are not added twice
         let mut new_lines: Vec<String> = content
             .lines()
-            .filter(|line| {
-                !line.contains(&format!("127.0.1.1.*{}", hostname)) &&
-                !line.contains(&format!("{}.*{}", primary_ip, hostname))
-            })
+            .filter(|line| !is_hostname_entry(line, hostname, &primary_ip))
             .map(String::from)
             .collect();
         
@@ -444,29 +468,31 @@ impl HostnameModule {
         }
         
         let new_content = new_lines.join("\n") + "\n";
-        safe_write_file(hosts_path, &new_content, false)?;
+        let changed = safe_write_file(hosts_path, &new_content, true)?;
         
-        log_info("Hosts file updated");
-        Ok(())
+        if changed {
+            log_info("Hosts file updated");
+        }
+        Ok(changed)
     }
     
     fn get_hostname_info(&self) -> Result<HostnameInfo> {
         let hostname = get_hostname()?;
         
         // Try to get FQDN
-        let fqdn = execute_command("hostname", &["-f"])
+        let fqdn = query_command("hostname", &["-f"])
             .ok()
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty() && s != &hostname);
         
         // Try to get domain
-        let domain = execute_command("hostname", &["-d"])
+        let domain = query_command("hostname", &["-d"])
             .ok()
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty());
         
         // Get short name
-        let short_name = execute_command("hostname", &["-s"])
+        let short_name = query_command("hostname", &["-s"])
             .ok()
             .map(|s| s.trim().to_string())
             .unwrap_or_else(|| hostname.clone());
@@ -494,21 +520,30 @@ impl HostnameModule {
         }
         
         if !services_needing_restart.is_empty() {
-            println!("\n{}", "The following services may need to be restarted:".yellow());
+            outln!("\n{}", "The following services may need to be restarted:".yellow());
             for service in &services_needing_restart {
-                println!("  - {}", service);
+                outln!("  - {}", service);
             }
             
             if let Ok(true) = prompt_yes_no("Restart these services now?", false) {
                 for service in services_needing_restart {
                     log_info(format!("Restarting {}", service));
-                    let _ = execute_command("systemctl", &["restart", service]);
+                    let _ = restart_service(service);
                 }
             }
         }
     }
 }
 
+/// Whether a hosts file line maps `127.0.1.1` or `primary_ip` to `hostname`
+fn is_hostname_entry(line: &str, hostname: &str, primary_ip: &str) -> bool {
+    let mut fields = line.split_whitespace();
+    let Some(address) = fields.next() else {
+        return false;
+    };
+    (address == "127.0.1.1" || address == primary_ip) && fields.any(|name| name == hostname)
+}
+
 #[derive(Debug)]
 struct HostnameInfo {
     hostname: String,
@@ -4,6 +4,7 @@ pub mod hostname;
 pub mod motd;
 pub mod netdata;
 pub mod network;
+pub mod plugin;
 pub mod ssh;
 pub mod sysctl;
 pub mod timezone;
@@ -11,11 +12,20 @@ pub mod update;
 pub mod user;
 pub mod zsh;
 
-use crate::config::Config;
+use crate::config::{Config, RunMode};
 use crate::error::{FluxError, Result};
+use crate::helpers::changes::{self, ResourceChange, ResourceStatus};
+use crate::helpers::dry_run;
+use crate::helpers::schema::{self, ConfigIssue, ConfigSchema, SectionSchema};
+use crate::helpers::{output, report, template};
+use crate::helpers::logging::{log_error, log_warn};
 use async_trait::async_trait;
+use colored::Colorize;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 /// Module trait that all modules must implement
 #[async_trait]
@@ -37,10 +47,234 @@ pub trait Module: Send + Sync {
     
     /// Execute module with arguments
     async fn execute(&self, args: Vec<String>, config: &Config) -> Result<()>;
+    
+    /// Modules that must be in the same workflow and succeed before this one
+    fn requires(&self) -> &[String] {
+        &[]
+    }
+    
+    /// Modules that run before this one when they are in the same workflow
+    fn after(&self) -> &[String] {
+        &[]
+    }
+    
+    /// Converge the system to the desired state in `spec` without prompting
+    ///
+    /// `spec` is the module's `[modules.<name>]` table from the configuration.
+    async fn apply(&self, _spec: &toml::Value, _config: &Config) -> Result<()> {
+        Err(FluxError::unsupported(format!(
+            "Module '{}' does not support declarative apply",
+            self.name()
+        )))
+    }
+    
+    /// Whether this module implements [`Module::apply`] and [`Module::plan`]
+    fn supports_apply(&self) -> bool {
+        false
+    }
+    
+    /// Typed settings read from `[modules.<name>]`
+    ///
+    /// Modules returning `None` receive their table unchecked.
+    fn settings(&self) -> Option<SectionSchema> {
+        None
+    }
+    
+    /// Variables of each template this module renders, as they would be for `config`
+    ///
+    /// `flux template render` previews templates with these.
+    fn template_vars(&self, _config: &Config) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        Ok(Vec::new())
+    }
+    
+    /// Compare the desired state in `spec` against the live system
+    ///
+    /// Returns every difference that [`Module::apply`] would fix. Never modifies
+    /// the system.
+    async fn plan(&self, _spec: &toml::Value, _config: &Config) -> Result<Vec<Drift>> {
+        Err(FluxError::unsupported(format!(
+            "Module '{}' does not support planning",
+            self.name()
+        )))
+    }
 }
 
-/// Module metadata
+/// A difference between desired and live state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Drift {
+    /// What drifted, e.g. `PermitRootLogin` or `user alice`
+    pub resource: String,
+    /// Desired value
+    pub expected: String,
+    /// Live value, `None` when the resource is missing entirely
+    pub actual: Option<String>,
+}
+
+impl Drift {
+    pub fn new(
+        resource: impl Into<String>,
+        expected: impl Into<String>,
+        actual: Option<String>,
+    ) -> Self {
+        Self {
+            resource: resource.into(),
+            expected: expected.into(),
+            actual,
+        }
+    }
+    
+    /// Shorthand for a resource that does not exist at all
+    pub fn missing(resource: impl Into<String>, expected: impl Into<String>) -> Self {
+        Self::new(resource, expected, None)
+    }
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(f, "~ {}: {} -> {}", self.resource, actual, self.expected),
+            None => write!(f, "+ {}: {} (missing)", self.resource, self.expected),
+        }
+    }
+}
+
+/// Result of planning a single module
+#[derive(Debug, Clone, Serialize)]
+pub struct ModulePlan {
+    pub module: String,
+    pub drift: Vec<Drift>,
+    /// Set when the module could not be planned, e.g. its tool is missing
+    pub error: Option<String>,
+    /// Set for configured modules without declarative apply, which are not checked
+    pub skipped: Option<String>,
+}
+
+impl ModulePlan {
+    fn new(module: &str, drift: Vec<Drift>) -> Self {
+        Self {
+            module: module.to_string(),
+            drift,
+            error: None,
+            skipped: None,
+        }
+    }
+
+    fn failed(module: &str, error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Self::new(module, Vec::new())
+        }
+    }
+}
+
+/// How a module run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleStatus {
+    Completed,
+    Failed,
+    Skipped,
+    /// The workflow step's `when` condition did not hold
+    SkippedByCondition,
+}
+
+impl ModuleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModuleStatus::Completed => "completed",
+            ModuleStatus::Failed => "failed",
+            ModuleStatus::Skipped => "skipped",
+            ModuleStatus::SkippedByCondition => "skipped_by_condition",
+        }
+    }
+}
+
+/// Structured outcome of running a single module
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleResult {
+    pub module: String,
+    pub status: ModuleStatus,
+    /// Why the module failed or was skipped
+    pub message: Option<String>,
+    pub duration_ms: u64,
+    /// Resources the module checked, with whether they needed changing
+    #[serde(default)]
+    pub resources: Vec<ResourceChange>,
+}
+
+impl ModuleResult {
+    pub fn new(module: impl Into<String>, result: &Result<()>, elapsed: Duration) -> Self {
+        let (status, message) = match result {
+            Ok(()) => (ModuleStatus::Completed, None),
+            Err(e) => (ModuleStatus::Failed, Some(e.to_string())),
+        };
+        
+        Self {
+            module: module.into(),
+            status,
+            message,
+            duration_ms: elapsed.as_millis() as u64,
+            resources: Vec::new(),
+        }
+    }
+    
+    /// Attach the resources collected while the module ran
+    pub fn with_resources(mut self, resources: Vec<ResourceChange>) -> Self {
+        self.resources = resources;
+        self
+    }
+    
+    /// Whether the module changed anything
+    pub fn changed(&self) -> bool {
+        self.resources.iter().any(|r| r.status == ResourceStatus::Changed)
+    }
+    
+    pub fn skipped(module: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            module: module.into(),
+            status: ModuleStatus::Skipped,
+            message: Some(reason.into()),
+            duration_ms: 0,
+            resources: Vec::new(),
+        }
+    }
+    
+    pub fn skipped_by_condition(module: impl Into<String>, condition: &str) -> Self {
+        Self {
+            module: module.into(),
+            status: ModuleStatus::SkippedByCondition,
+            message: Some(format!("condition not met: {}", condition)),
+            duration_ms: 0,
+            resources: Vec::new(),
+        }
+    }
+}
+
+/// Result of `flux apply`
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplySummary {
+    pub applied: usize,
+    /// Applied modules that had to change something
+    pub changed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub modules: Vec<ModuleResult>,
+}
+
+/// Deserialize a module's `[modules.<name>]` table into its typed spec
+pub fn parse_spec<T: DeserializeOwned>(module: &str, value: &toml::Value) -> Result<T> {
+    value
+        .clone()
+        .try_into()
+        .map_err(|e| FluxError::config(format!("Invalid [modules.{}] configuration: {}", module, e)))
+}
+
+/// Module metadata
+///
+/// Missing fields default to empty, so plugins only need to describe what
+/// they use.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModuleInfo {
     pub name: String,
     pub description: String,
@@ -49,17 +283,24 @@ pub struct ModuleInfo {
     pub tags: Vec<String>,
     pub requires_root: bool,
     pub supported_distros: Vec<String>,
+    /// Modules that must be in the same workflow and succeed before this one
+    pub requires: Vec<String>,
+    /// Modules that run before this one when they are in the same workflow
+    pub after: Vec<String>,
 }
 
 /// Module manager for discovering and loading modules
 pub struct ModuleManager {
     modules: HashMap<String, Box<dyn Module>>,
+    /// Module names in registration order
+    order: Vec<String>,
 }
 
 impl ModuleManager {
     /// Create new module manager
     pub fn new() -> Result<Self> {
         let mut modules: HashMap<String, Box<dyn Module>> = HashMap::new();
+        let mut order = Vec::new();
         
         // Register all modules
         let all_modules: Vec<Box<dyn Module>> = vec![
@@ -78,10 +319,155 @@ impl ModuleManager {
         ];
         
         for module in all_modules {
+            order.push(module.name().to_string());
             modules.insert(module.name().to_string(), module);
         }
         
-        Ok(Self { modules })
+        Ok(Self { modules, order })
+    }
+    
+    /// Create a module manager including the plugins in `general.modules_dir`
+    pub fn with_config(config: &Config) -> Result<Self> {
+        let mut manager = Self::new()?;
+        
+        if let Some(dir) = &config.general.modules_dir {
+            if !dir.as_os_str().is_empty() {
+                manager.load_plugins(dir)?;
+            }
+        }
+        
+        Ok(manager)
+    }
+    
+    /// Register every plugin in `dir`, returning how many were added
+    ///
+    /// Plugins cannot replace a module that is already registered.
+    pub fn load_plugins(&mut self, dir: &Path) -> Result<usize> {
+        let mut added = 0;
+        
+        for plugin in plugin::discover_plugins(dir)? {
+            let name = plugin.name().to_string();
+            if self.modules.contains_key(&name) {
+                log_warn(format!(
+                    "Plugin {} conflicts with existing module '{}', skipping",
+                    plugin.path().display(),
+                    name
+                ));
+                continue;
+            }
+            
+            self.order.push(name.clone());
+            self.modules.insert(name, Box::new(plugin));
+            added += 1;
+        }
+        
+        Ok(added)
+    }
+    
+    /// Check the text of a configuration file against the typed settings
+    ///
+    /// Returns every problem found, each with its line in `contents`. Syntax
+    /// errors and errors outside `[modules.*]` stop validation at the first one.
+    pub fn validate_config(&self, contents: &str) -> Vec<ConfigIssue> {
+        let config: Config = match toml::from_str(contents) {
+            Ok(config) => config,
+            Err(e) => return vec![ConfigIssue::from_toml(contents, &e, None)],
+        };
+        
+        let mut issues = Vec::new();
+        for name in config.modules.keys() {
+            let context = format!("[modules.{}]", name);
+            match self.modules.get(name) {
+                None => {
+                    let (line, column) = schema::section_position(contents, "modules", name);
+                    issues.push(ConfigIssue {
+                        line,
+                        column,
+                        message: format!("{} unknown module '{}'", context, name),
+                    });
+                }
+                Some(module) => {
+                    if let Some(Err(e)) = module.settings().map(|s| s.check(contents, name)) {
+                        issues.push(ConfigIssue::from_toml(contents, &e, Some(&context)));
+                    }
+                }
+            }
+        }
+        
+        for (name, workflow) in &config.workflows {
+            for module in &workflow.modules {
+                if !self.modules.contains_key(module) {
+                    let (line, column) = schema::section_position(contents, "workflows", name);
+                    issues.push(ConfigIssue {
+                        line,
+                        column,
+                        message: format!("[workflows.{}] unknown module '{}'", name, module),
+                    });
+                }
+            }
+            for (module, source) in &workflow.when {
+                if let Err(e) = crate::workflows::condition::Condition::parse(source) {
+                    let (line, column) = schema::section_position(contents, "workflows", name);
+                    issues.push(ConfigIssue {
+                        line,
+                        column,
+                        message: format!("[workflows.{}] when.{}: {}", name, module, e),
+                    });
+                }
+            }
+        }
+        
+        issues.sort_by_key(|issue| (issue.line == 0, issue.line, issue.column));
+        issues
+    }
+    
+    /// JSON Schema of flux.toml, including every module with typed settings
+    pub fn config_schema(&self) -> serde_json::Value {
+        let modules: Vec<(&str, serde_json::Value)> = self
+            .order
+            .iter()
+            .filter_map(|name| {
+                let settings = self.modules[name].settings()?;
+                Some((name.as_str(), settings.json_schema()))
+            })
+            .collect();
+        
+        let mut modules = schema::object("Module settings, keyed by module name", modules);
+        // Modules without typed settings, including plugins, take any table
+        modules["additionalProperties"] = serde_json::json!({ "type": "object" });
+        
+        let mut root = schema::object(
+            "Flux Framework configuration (flux.toml)",
+            vec![
+                ("general", crate::config::GeneralConfig::json_schema()),
+                ("modules", modules),
+                (
+                    "workflows",
+                    schema::map(
+                        "User-defined workflows, keyed by workflow name",
+                        crate::config::WorkflowConfig::json_schema(),
+                    ),
+                ),
+                ("custom", schema::map("Custom key-value pairs", schema::string(""))),
+            ],
+        );
+        root["$schema"] = serde_json::Value::from(schema::JSON_SCHEMA_DIALECT);
+        root["title"] = serde_json::Value::from("flux.toml");
+        root
+    }
+    
+    /// Render the template `name` the way the module using it would for `config`
+    pub fn render_template(&self, name: &str, config: &Config) -> Result<String> {
+        for module in self.order.iter().map(|name| &self.modules[name]) {
+            let templates = module.template_vars(config)?;
+            if let Some((_, vars)) = templates.into_iter().find(|(template, _)| *template == name) {
+                return template::render(name, &vars);
+            }
+        }
+        
+        // Distinguish unknown templates from ones no module renders
+        template::source(name)?;
+        Err(FluxError::not_found(format!("No module renders template '{}'", name)))
     }
     
     /// Discover available modules
@@ -130,12 +516,154 @@ impl ModuleManager {
             return Ok(());
         }
         
-        module.execute(args, config).await
+        // Dry-run can be requested by config mode or per invocation; the latter
+        // only applies to this call, not to other modules of a workflow
+        let (dry_run_flags, args): (Vec<String>, Vec<String>) =
+            args.into_iter().partition(|arg| arg == "--dry-run");
+        if config.general.mode == RunMode::DryRun {
+            dry_run::set_dry_run(true);
+        }
+        
+        if dry_run_flags.is_empty() {
+            module.execute(args, config).await
+        } else {
+            dry_run::scoped(module.execute(args, config)).await
+        }
+    }
+    
+    /// Load and execute a module, collecting the resources it checked or changed
+    pub async fn run_module(
+        &self,
+        name: &str,
+        args: Vec<String>,
+        config: &Config,
+    ) -> (Result<()>, Vec<ResourceChange>) {
+        changes::track(self.load_module(name, args, config)).await
+    }
+    
+    /// Converge every module configured under `[modules.*]` without prompting
+    ///
+    /// Modules are applied in registration order. A failing module does not stop
+    /// the remaining ones; an error is returned at the end if any module failed.
+    pub async fn apply_config(&self, config: &Config) -> Result<()> {
+        for name in config.modules.keys() {
+            if !self.modules.contains_key(name) {
+                log_warn(format!("Unknown module in configuration: [modules.{}]", name));
+            }
+        }
+        
+        let mut results = Vec::new();
+        
+        for name in &self.order {
+            let Some(spec) = config.get_module_config(name) else {
+                continue;
+            };
+            let module = &self.modules[name];
+            
+            if !module.supports_apply() {
+                log_warn(format!("Module '{}' does not support declarative apply, skipping", name));
+                results.push(ModuleResult::skipped(name, "declarative apply not supported"));
+                continue;
+            }
+            
+            if !module.is_available() {
+                log_warn(format!("Module '{}' is not available on this system, skipping", name));
+                results.push(ModuleResult::skipped(name, "not available on this system"));
+                continue;
+            }
+            
+            println!("{}", format!("=== Applying: {} ===", name).cyan());
+            let started = Instant::now();
+            let (result, resources) = changes::track(module.apply(spec, config)).await;
+            if let Err(e) = &result {
+                log_error(format!("Module '{}' failed: {}", name, e));
+            }
+            let outcome = ModuleResult::new(name, &result, started.elapsed()).with_resources(resources);
+            if result.is_ok() {
+                println!("  {}", changes::summarize(&outcome.resources).white());
+            }
+            results.push(outcome);
+        }
+        
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        let summary = ApplySummary {
+            applied: count(ModuleStatus::Completed),
+            changed: results
+                .iter()
+                .filter(|r| r.status == ModuleStatus::Completed && r.changed())
+                .count(),
+            skipped: count(ModuleStatus::Skipped),
+            failed: count(ModuleStatus::Failed),
+            modules: results,
+        };
+        report::record_modules(&summary.modules);
+        
+        if output::is_json() {
+            output::emit(&summary)?;
+        } else {
+            println!();
+            println!("{}", "=== Apply Summary ===".cyan());
+            println!("  Applied: {}", summary.applied.to_string().green());
+            println!("  Changed: {}", summary.changed.to_string().yellow());
+            println!("  Skipped: {}", summary.skipped.to_string().yellow());
+            println!("  Failed:  {}", summary.failed.to_string().red());
+        }
+        
+        let failed: Vec<&str> = summary
+            .modules
+            .iter()
+            .filter(|r| r.status == ModuleStatus::Failed)
+            .map(|r| r.module.as_str())
+            .collect();
+        
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(FluxError::module(format!(
+                "Apply failed for: {}",
+                failed.join(", ")
+            )))
+        }
+    }
+    
+    /// Diff every module configured under `[modules.*]` against the live system
+    pub async fn plan_config(&self, config: &Config) -> Vec<ModulePlan> {
+        let mut plans = Vec::new();
+        
+        for name in &self.order {
+            let Some(spec) = config.get_module_config(name) else {
+                continue;
+            };
+            let module = &self.modules[name];
+            
+            // Listed like `flux apply` skips them, so nothing configured goes unmentioned
+            if !module.supports_apply() {
+                plans.push(ModulePlan {
+                    skipped: Some("declarative apply not supported".to_string()),
+                    ..ModulePlan::new(name, Vec::new())
+                });
+                continue;
+            }
+            
+            // A module whose tool is missing cannot be compliant
+            if !module.is_available() {
+                plans.push(ModulePlan::failed(name, "not available on this system"));
+                continue;
+            }
+            
+            let plan = match module.plan(spec, config).await {
+                Ok(drift) => ModulePlan::new(name, drift),
+                Err(e) => ModulePlan::failed(name, e.to_string()),
+            };
+            plans.push(plan);
+        }
+        
+        plans
     }
 }
 
 /// Module descriptor for listing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ModuleDescriptor {
     pub name: String,
     pub description: String,
@@ -180,7 +708,7 @@ pub struct ModuleContext<'a> {
 
 impl<'a> ModuleContext<'a> {
     pub fn new(config: &'a Config, args: Vec<String>) -> Self {
-        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        let dry_run = dry_run::is_dry_run() || args.iter().any(|arg| arg == "--dry-run");
         let verbose = args.iter().any(|arg| arg == "--verbose" || arg == "-v");
         
         Self {
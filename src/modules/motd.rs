@@ -4,16 +4,18 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    file_ops::safe_write_file,
+    file_ops::{create_dir_all, remove_path, safe_write_file, set_permissions},
     logging::{log_debug, log_error, log_info, log_success, log_warn},
+    paths::resolve,
+    system::command_output,
+    template,
     user_input::{prompt_input, prompt_yes_no, select_from_menu},
 };
 use crate::modules::{Module, ModuleBase, ModuleInfo};
+use crate::{out, outln};
 use async_trait::async_trait;
+use serde_json::json;
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use std::process::Command;
 
 const MOTD_DIR: &str = "/etc/update-motd.d";
 const MOTD_STATIC: &str = "/etc/motd";
@@ -32,6 +34,8 @@ impl MotdModule {
             tags: vec!["system".to_string(), "ux".to_string()],
             requires_root: true,
             supported_distros: vec!["debian".to_string(), "ubuntu".to_string()],
+            requires: Vec::new(),
+            after: vec!["hostname".to_string()],
         };
         Self {
             base: ModuleBase { info },
@@ -84,41 +88,20 @@ impl MotdModule {
     async fn create_header_script(&self, banner_style: &str, organization: Option<&str>) -> Result<()> {
         log_info("Creating MOTD header script");
 
-        let banner = self.get_banner(banner_style);
-        let org_text = if let Some(org) = organization {
-            format!("  Organization: {}\n", org)
-        } else {
-            String::new()
-        };
-
-        let script = format!(
-            r#"#!/bin/bash
-# Flux Framework - MOTD Header
-# 10-flux-header
-
-CYAN='\033[0;36m'
-RESET='\033[0m'
-
-cat << 'EOF'
-{}
-EOF
-
-echo -e "${{CYAN}}{}  Generated: $(date)${{RESET}}"
-echo
-"#,
-            banner, org_text
-        );
+        let vars = json!({
+            "banner": self.get_banner(banner_style),
+            "organization": organization,
+        });
+        let script = template::render("motd/10-flux-header", &vars)?;
 
-        let script_path = PathBuf::from(MOTD_DIR).join("10-flux-header");
+        let script_path = resolve(MOTD_DIR).join("10-flux-header");
         let script_path_str = script_path
             .to_str()
             .ok_or_else(|| FluxError::system("Invalid UTF-8 in path"))?;
         safe_write_file(script_path_str, &script, true)?;
 
         // Make executable
-        let mut perms = fs::metadata(&script_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&script_path, perms)?;
+        set_permissions(&script_path, 0o755)?;
 
         log_success("Header script created");
         Ok(())
@@ -128,64 +111,16 @@ echo
     async fn create_sysinfo_script(&self) -> Result<()> {
         log_info("Creating system info script");
 
-        let script = r#"#!/bin/bash
-# Flux Framework - System Information
-# 20-flux-sysinfo
-
-GREEN='\033[0;32m'
-YELLOW='\033[0;33m'
-BLUE='\033[0;34m'
-RESET='\033[0m'
-
-# System info
-HOSTNAME=$(hostname)
-KERNEL=$(uname -r)
-UPTIME=$(uptime -p)
-USERS=$(who | wc -l)
-
-# CPU info
-CPU_LOAD=$(uptime | awk -F'load average:' '{print $2}' | cut -d, -f1 | xargs)
-
-# Memory info
-MEM_TOTAL=$(free -m | awk 'NR==2{print $2}')
-MEM_USED=$(free -m | awk 'NR==2{print $3}')
-MEM_PERCENT=$(free | awk 'NR==2{printf "%.0f", $3*100/$2}')
-
-# Disk info
-DISK_TOTAL=$(df -h / | awk 'NR==2{print $2}')
-DISK_USED=$(df -h / | awk 'NR==2{print $3}')
-DISK_PERCENT=$(df -h / | awk 'NR==2{print $5}')
-
-# Network
-IP_ADDR=$(hostname -I | awk '{print $1}')
-
-echo -e "${GREEN}System Information:${RESET}"
-echo "─────────────────────────────────────────"
-echo -e "  ${BLUE}Hostname:${RESET}    $HOSTNAME"
-echo -e "  ${BLUE}Kernel:${RESET}      $KERNEL"
-echo -e "  ${BLUE}Uptime:${RESET}      $UPTIME"
-echo -e "  ${BLUE}Users:${RESET}       $USERS active"
-echo
-
-echo -e "${GREEN}Resource Usage:${RESET}"
-echo "─────────────────────────────────────────"
-echo -e "  ${BLUE}CPU Load:${RESET}    $CPU_LOAD"
-echo -e "  ${BLUE}Memory:${RESET}      ${MEM_USED}MB / ${MEM_TOTAL}MB (${MEM_PERCENT}%)"
-echo -e "  ${BLUE}Disk:${RESET}        ${DISK_USED} / ${DISK_TOTAL} (${DISK_PERCENT})"
-echo -e "  ${BLUE}IP Address:${RESET}  $IP_ADDR"
-echo
-"#;
-
-        let script_path = PathBuf::from(MOTD_DIR).join("20-flux-sysinfo");
+        let script = template::render("motd/20-flux-sysinfo", &json!({}))?;
+
+        let script_path = resolve(MOTD_DIR).join("20-flux-sysinfo");
         let script_path_str = script_path
             .to_str()
             .ok_or_else(|| FluxError::system("Invalid UTF-8 in path"))?;
-        safe_write_file(script_path_str, script, true)?;
+        safe_write_file(script_path_str, &script, true)?;
 
         // Make executable
-        let mut perms = fs::metadata(&script_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&script_path, perms)?;
+        set_permissions(&script_path, 0o755)?;
 
         log_success("System info script created");
         Ok(())
@@ -195,58 +130,16 @@ echo
     async fn create_security_script(&self) -> Result<()> {
         log_info("Creating security status script");
 
-        let script = r#"#!/bin/bash
-# Flux Framework - Security Status
-# 30-flux-security
-
-RED='\033[0;31m'
-GREEN='\033[0;32m'
-YELLOW='\033[0;33m'
-RESET='\033[0m'
-
-echo -e "${GREEN}Security Status:${RESET}"
-echo "─────────────────────────────────────────"
-
-# Check firewall
-if systemctl is-active --quiet ufw; then
-    echo -e "  ${GREEN}✓${RESET} UFW Firewall: Active"
-elif systemctl is-active --quiet firewalld; then
-    echo -e "  ${GREEN}✓${RESET} firewalld: Active"
-else
-    echo -e "  ${RED}✗${RESET} Firewall: Inactive"
-fi
-
-# Check fail2ban
-if systemctl is-active --quiet fail2ban; then
-    BANNED=$(fail2ban-client status sshd 2>/dev/null | grep "Currently banned" | awk '{print $NF}' || echo "0")
-    echo -e "  ${GREEN}✓${RESET} fail2ban: Active (${BANNED} banned)"
-else
-    echo -e "  ${YELLOW}○${RESET} fail2ban: Not installed"
-fi
-
-# Check for updates
-if command -v apt-get &> /dev/null; then
-    UPDATES=$(apt-get -s upgrade 2>/dev/null | grep -P '^\d+ upgraded' | cut -d" " -f1)
-    if [ "$UPDATES" -gt 0 ]; then
-        echo -e "  ${YELLOW}⚠${RESET}  System updates: $UPDATES available"
-    else
-        echo -e "  ${GREEN}✓${RESET} System updates: Up to date"
-    fi
-fi
-
-echo
-"#;
-
-        let script_path = PathBuf::from(MOTD_DIR).join("30-flux-security");
+        let script = template::render("motd/30-flux-security", &json!({}))?;
+
+        let script_path = resolve(MOTD_DIR).join("30-flux-security");
         let script_path_str = script_path
             .to_str()
             .ok_or_else(|| FluxError::system("Invalid UTF-8 in path"))?;
-        safe_write_file(script_path_str, script, true)?;
+        safe_write_file(script_path_str, &script, true)?;
 
         // Make executable
-        let mut perms = fs::metadata(&script_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&script_path, perms)?;
+        set_permissions(&script_path, 0o755)?;
 
         log_success("Security status script created");
         Ok(())
@@ -256,38 +149,16 @@ echo
     async fn create_footer_script(&self, message: Option<&str>) -> Result<()> {
         log_info("Creating footer script");
 
-        let custom_msg = if let Some(msg) = message {
-            format!("echo -e \"  {}\"\necho", msg)
-        } else {
-            String::new()
-        };
+        let script = template::render("motd/90-flux-footer", &json!({ "message": message }))?;
 
-        let script = format!(
-            r#"#!/bin/bash
-# Flux Framework - MOTD Footer
-# 90-flux-footer
-
-CYAN='\033[0;36m'
-RESET='\033[0m'
-
-{}
-
-echo -e "${{CYAN}}For help, type: flux help${{RESET}}"
-echo
-"#,
-            custom_msg
-        );
-
-        let script_path = PathBuf::from(MOTD_DIR).join("90-flux-footer");
+        let script_path = resolve(MOTD_DIR).join("90-flux-footer");
         let script_path_str = script_path
             .to_str()
             .ok_or_else(|| FluxError::system("Invalid UTF-8 in path"))?;
         safe_write_file(script_path_str, &script, true)?;
 
         // Make executable
-        let mut perms = fs::metadata(&script_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&script_path, perms)?;
+        set_permissions(&script_path, 0o755)?;
 
         log_success("Footer script created");
         Ok(())
@@ -297,7 +168,7 @@ echo
     async fn disable_default_scripts(&self) -> Result<()> {
         log_info("Disabling default MOTD scripts");
 
-        let motd_dir = PathBuf::from(MOTD_DIR);
+        let motd_dir = resolve(MOTD_DIR);
         if !motd_dir.exists() {
             log_warn("MOTD directory not found");
             return Ok(());
@@ -318,9 +189,7 @@ echo
         for script in default_scripts {
             let script_path = motd_dir.join(script);
             if script_path.exists() {
-                let mut perms = fs::metadata(&script_path)?.permissions();
-                perms.set_mode(0o644); // Remove execute permission
-                fs::set_permissions(&script_path, perms)?;
+                set_permissions(&script_path, 0o644)?; // Remove execute permission
                 log_debug(&format!("Disabled: {}", script));
             }
         }
@@ -333,7 +202,7 @@ echo
     async fn enable_default_scripts(&self) -> Result<()> {
         log_info("Enabling default MOTD scripts");
 
-        let motd_dir = PathBuf::from(MOTD_DIR);
+        let motd_dir = resolve(MOTD_DIR);
         if !motd_dir.exists() {
             return Ok(());
         }
@@ -348,9 +217,7 @@ echo
         for script in default_scripts {
             let script_path = motd_dir.join(script);
             if script_path.exists() {
-                let mut perms = fs::metadata(&script_path)?.permissions();
-                perms.set_mode(0o755); // Add execute permission
-                fs::set_permissions(&script_path, perms)?;
+                set_permissions(&script_path, 0o755)?; // Add execute permission
                 log_debug(&format!("Enabled: {}", script));
             }
         }
@@ -364,7 +231,7 @@ echo
         log_info("Installing dynamic MOTD");
 
         // Create MOTD directory if it doesn't exist
-        fs::create_dir_all(MOTD_DIR)?;
+        create_dir_all(MOTD_DIR)?;
 
         // Disable default scripts
         self.disable_default_scripts().await?;
@@ -395,11 +262,11 @@ echo
             "90-flux-footer",
         ];
 
-        let motd_dir = PathBuf::from(MOTD_DIR);
+        let motd_dir = resolve(MOTD_DIR);
         for script in flux_scripts {
             let script_path = motd_dir.join(script);
             if script_path.exists() {
-                fs::remove_file(&script_path)?;
+                remove_path(&script_path)?;
                 log_debug(&format!("Removed: {}", script));
             }
         }
@@ -415,10 +282,10 @@ echo
     async fn preview_motd(&self) -> Result<()> {
         log_info("Previewing MOTD:");
 
-        println!("\n{}", "=".repeat(70));
+        outln!("\n{}", "=".repeat(70));
 
         // Execute MOTD scripts
-        let motd_dir = PathBuf::from(MOTD_DIR);
+        let motd_dir = resolve(MOTD_DIR);
         if motd_dir.exists() {
             let mut scripts: Vec<_> = fs::read_dir(&motd_dir)?
                 .filter_map(|e| e.ok())
@@ -435,18 +302,14 @@ echo
             scripts.sort_by_key(|e| e.file_name());
 
             for script in scripts {
-                let output = Command::new("bash")
-                    .arg(script.path())
-                    .output()
-                    .ok();
-
-                if let Some(out) = output {
-                    print!("{}", String::from_utf8_lossy(&out.stdout));
+                let path = script.path();
+                if let Ok(out) = command_output("bash", &[&path.to_string_lossy()]) {
+                    out!("{}", out.stdout);
                 }
             }
         }
 
-        println!("{}", "=".repeat(70));
+        outln!("{}", "=".repeat(70));
         Ok(())
     }
 
@@ -521,8 +384,24 @@ impl Module for MotdModule {
         &self.base.info.version
     }
 
+    fn after(&self) -> &[String] {
+        &self.base.info.after
+    }
+
+    fn template_vars(&self, _config: &Config) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        Ok(vec![
+            (
+                "motd/10-flux-header",
+                json!({ "banner": self.get_banner("flux"), "organization": null }),
+            ),
+            ("motd/20-flux-sysinfo", json!({})),
+            ("motd/30-flux-security", json!({})),
+            ("motd/90-flux-footer", json!({ "message": null })),
+        ])
+    }
+
     fn is_available(&self) -> bool {
-        PathBuf::from(MOTD_DIR).exists()
+        resolve(MOTD_DIR).exists()
     }
 
     fn help(&self) -> String {
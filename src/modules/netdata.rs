@@ -4,20 +4,28 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    file_ops::safe_write_file,
+    changes, dry_run, facts,
+    file_ops::{create_dir_all, safe_write_file},
     logging::{log_info, log_success, log_warn},
-    system::{check_command, execute_command},
+    paths::resolve,
+    runner::{self, Invocation},
+    system::{check_command, command_output, execute_command, is_service_active, restart_service},
+    template,
     user_input::{prompt_input, prompt_with_default, prompt_yes_no, select_from_menu},
 };
 use crate::modules::{Module, ModuleBase, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
+use serde_json::json;
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
 
 const NETDATA_CONFIG_DIR: &str = "/etc/netdata";
 const NETDATA_INSTALL_URL: &str = "https://get.netdata.cloud/kickstart.sh";
 
+/// Health alarms installed below `health.d`, one template each
+const HEALTH_ALARMS: &[&str] = &["cpu_usage", "ram_usage", "disk_space"];
+
 pub struct NetdataModule {
     base: ModuleBase,
 }
@@ -32,6 +40,8 @@ impl NetdataModule {
             tags: vec!["monitoring".to_string(), "observability".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: vec!["update".to_string(), "firewall".to_string()],
         };
         Self {
             base: ModuleBase { info },
@@ -40,7 +50,7 @@ impl NetdataModule {
 
     /// Check if Netdata is installed
     fn is_netdata_installed(&self) -> bool {
-        check_command("netdata").is_ok() || PathBuf::from("/usr/sbin/netdata").exists()
+        check_command("netdata").is_ok() || resolve("/usr/sbin/netdata").exists()
     }
 
     /// Check system requirements
@@ -48,13 +58,10 @@ impl NetdataModule {
         log_info("Checking system requirements");
 
         // Check available memory (minimum 512MB)
-        let output = Command::new("free")
-            .arg("-m")
-            .output()
-            .ok();
+        let output = command_output("free", &["-m"]).ok();
 
         if let Some(out) = output {
-            let mem_info = String::from_utf8_lossy(&out.stdout);
+            let mem_info = out.stdout;
             if let Some(line) = mem_info.lines().nth(1) {
                 let parts: Vec<&str> = line.split_whitespace().collect();
                 if parts.len() > 1 {
@@ -68,14 +75,10 @@ impl NetdataModule {
         }
 
         // Check disk space (minimum 100MB)
-        let output = Command::new("df")
-            .arg("-m")
-            .arg("/")
-            .output()
-            .ok();
+        let output = command_output("df", &["-m", "/"]).ok();
 
         if let Some(out) = output {
-            let disk_info = String::from_utf8_lossy(&out.stdout);
+            let disk_info = out.stdout;
             if let Some(line) = disk_info.lines().nth(1) {
                 let parts: Vec<&str> = line.split_whitespace().collect();
                 if parts.len() > 3 {
@@ -106,9 +109,14 @@ impl NetdataModule {
         // Check requirements
         self.check_requirements().await?;
 
+        if dry_run::intercept_command("bash", &[NETDATA_INSTALL_URL, "--dont-wait"]) {
+            return Ok(());
+        }
+
         // Download installer
         log_info("Downloading Netdata installer");
-        let temp_dir = std::env::temp_dir();
+        // Keep the installer inside --root so a chroot can run it
+        let temp_dir = resolve(std::env::temp_dir());
         let installer_path = temp_dir.join("netdata-kickstart.sh");
 
         let client = reqwest::Client::new();
@@ -135,26 +143,25 @@ impl NetdataModule {
         // Execute installer
         log_info("Running Netdata installer (this may take a few minutes)");
 
-        let mut cmd = Command::new("bash");
-        cmd.arg(&installer_path);
-        cmd.args(&install_args);
+        let installer = installer_path.to_string_lossy();
+        let mut args = vec![installer.as_ref()];
+        args.extend(&install_args);
 
         if let Some(token) = claim_token {
-            cmd.arg("--claim-token").arg(token);
+            args.extend(["--claim-token", token]);
         }
 
-        let output = cmd
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to execute installer: {}", e)))?;
+        let output = runner::run(&Invocation::new("bash", &args))?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.success {
             return Err(FluxError::command_failed(format!(
                 "Netdata installation failed: {}",
-                stderr
+                output.stderr
             )));
         }
 
+        facts::invalidate();
+
         // Clean up installer
         fs::remove_file(&installer_path).ok();
 
@@ -162,120 +169,42 @@ impl NetdataModule {
         Ok(())
     }
 
-    /// Configure Netdata
-    async fn configure_netdata(&self, web_port: u16, enable_cloud: bool) -> Result<()> {
+    /// Configure Netdata, returning whether `netdata.conf` changed
+    async fn configure_netdata(&self, web_port: u16, enable_cloud: bool) -> Result<bool> {
         log_info("Configuring Netdata");
 
         let config_path = PathBuf::from(NETDATA_CONFIG_DIR).join("netdata.conf");
 
-        // Generate configuration
-        let config = format!(
-            r#"# Flux Framework - Netdata Configuration
-
-[global]
-    # Web server configuration
-    bind to = *
-    default port = {}
-
-    # Performance tuning
-    update every = 1
-    history = 3600
-    memory mode = ram
-
-    # Security
-    run as user = netdata
-
-[web]
-    enable gzip compression = yes
-
-[plugins]
-    # Enable/disable plugins
-    proc = yes
-    diskspace = yes
-    cgroups = yes
-    tc = no
-    idlejitter = no
-
-[health]
-    enabled = yes
-    default repeat warning = 300
-    default repeat critical = 60
-"#,
-            web_port
-        );
+        let config = template::render("netdata/netdata.conf", &json!({ "web_port": web_port }))?;
 
         let config_path_str = config_path
             .to_str()
             .ok_or_else(|| FluxError::system("Invalid UTF-8 in config path"))?;
-        safe_write_file(config_path_str, &config, true)?;
+        let changed = safe_write_file(config_path_str, &config, true)?;
 
         log_success("Netdata configured");
-        Ok(())
+        Ok(changed)
     }
 
-    /// Setup health alarms
-    async fn setup_health_alarms(&self) -> Result<()> {
+    /// Setup health alarms, returning whether any alarm file changed
+    async fn setup_health_alarms(&self) -> Result<bool> {
         log_info("Setting up health alarms");
 
         let health_dir = PathBuf::from(NETDATA_CONFIG_DIR).join("health.d");
-        fs::create_dir_all(&health_dir)?;
-
-        // CPU alarm
-        let cpu_alarm = r#"
-alarm: cpu_usage
-on: system.cpu
-lookup: average -3m percentage of user,system
-units: %
-every: 60s
-warn: $this > 80
-crit: $this > 95
-info: CPU usage is high
-"#;
-
-        let cpu_alarm_path = health_dir.join("cpu_usage.conf");
-        let cpu_alarm_path_str = cpu_alarm_path
-            .to_str()
-            .ok_or_else(|| FluxError::system("Invalid UTF-8 in alarm path"))?;
-        safe_write_file(cpu_alarm_path_str, cpu_alarm, true)?;
-
-        // Memory alarm
-        let mem_alarm = r#"
-alarm: ram_usage
-on: system.ram
-lookup: average -3m percentage of used
-units: %
-every: 60s
-warn: $this > 80
-crit: $this > 90
-info: RAM usage is high
-"#;
-
-        let mem_alarm_path = health_dir.join("ram_usage.conf");
-        let mem_alarm_path_str = mem_alarm_path
-            .to_str()
-            .ok_or_else(|| FluxError::system("Invalid UTF-8 in alarm path"))?;
-        safe_write_file(mem_alarm_path_str, mem_alarm, true)?;
-
-        // Disk alarm
-        let disk_alarm = r#"
-alarm: disk_space
-on: disk.space
-lookup: average -1m percentage of used
-units: %
-every: 60s
-warn: $this > 80
-crit: $this > 90
-info: Disk space usage is high
-"#;
-
-        let disk_alarm_path = health_dir.join("disk_space.conf");
-        let disk_alarm_path_str = disk_alarm_path
-            .to_str()
-            .ok_or_else(|| FluxError::system("Invalid UTF-8 in alarm path"))?;
-        safe_write_file(disk_alarm_path_str, disk_alarm, true)?;
+        create_dir_all(&health_dir)?;
+
+        let mut changed = false;
+        for alarm in HEALTH_ALARMS {
+            let contents = template::render(&format!("netdata/health.d/{}.conf", alarm), &json!({}))?;
+            let alarm_path = health_dir.join(format!("{}.conf", alarm));
+            let alarm_path_str = alarm_path
+                .to_str()
+                .ok_or_else(|| FluxError::system("Invalid UTF-8 in alarm path"))?;
+            changed |= safe_write_file(alarm_path_str, &contents, true)?;
+        }
 
         log_success("Health alarms configured");
-        Ok(())
+        Ok(changed)
     }
 
     /// Configure firewall for Netdata
@@ -284,13 +213,10 @@ info: Disk space usage is high
 
         // Check which firewall is active
         if check_command("ufw").is_ok() {
-            let output = Command::new("ufw")
-                .arg("status")
-                .output()
-                .ok();
+            let output = command_output("ufw", &["status"]).ok();
 
             if let Some(out) = output {
-                let status = String::from_utf8_lossy(&out.stdout);
+                let status = out.stdout;
                 if status.contains("Status: active") {
                     execute_command("ufw", &["allow", &port.to_string()])?;
                     log_success(&format!("UFW rule added for port {}", port));
@@ -311,12 +237,24 @@ info: Disk space usage is high
     }
 
     /// Start Netdata service
-    async fn start_service(&self) -> Result<()> {
-        log_info("Starting Netdata service");
-
+    ///
+    /// A running service is left alone unless `config_changed`, in which case
+    /// it is restarted to pick up the new configuration.
+    async fn start_service(&self, config_changed: bool) -> Result<()> {
         if check_command("systemctl").is_ok() {
+            if is_service_active("netdata").unwrap_or(false) {
+                if config_changed {
+                    restart_service("netdata")?;
+                } else {
+                    changes::ok("service netdata");
+                }
+                return Ok(());
+            }
+
+            log_info("Starting Netdata service");
             execute_command("systemctl", &["start", "netdata"])?;
             execute_command("systemctl", &["enable", "netdata"])?;
+            changes::changed("service netdata", "started and enabled");
             log_success("Netdata service started and enabled");
         } else {
             log_warn("systemctl not found. You may need to start Netdata manually.");
@@ -352,7 +290,7 @@ info: Disk space usage is high
 
         // Run uninstaller if it exists
         let uninstaller_path = "/usr/libexec/netdata/netdata-uninstaller.sh";
-        if PathBuf::from(uninstaller_path).exists() {
+        if resolve(uninstaller_path).exists() {
             execute_command("bash", &[uninstaller_path, "--yes", "--force"])?;
         } else {
             log_warn("Netdata uninstaller not found. Manual cleanup may be required.");
@@ -372,29 +310,22 @@ info: Disk space usage is high
         log_info("Netdata Status:");
 
         if check_command("systemctl").is_ok() {
-            let output = Command::new("systemctl")
-                .arg("status")
-                .arg("netdata")
-                .output()
-                .ok();
+            let output = command_output("systemctl", &["status", "netdata"]).ok();
 
             if let Some(out) = output {
-                println!("\n{}", String::from_utf8_lossy(&out.stdout));
+                outln!("\n{}", out.stdout);
             }
         }
 
         // Show access URL
-        let output = Command::new("hostname")
-            .arg("-I")
-            .output()
-            .ok();
+        let output = command_output("hostname", &["-I"]).ok();
 
         if let Some(out) = output {
-            let stdout = String::from_utf8_lossy(&out.stdout);
+            let stdout = out.stdout;
             let ip = stdout.trim().split_whitespace().next().unwrap_or("localhost");
-            println!("\n{}", "=".repeat(70));
-            println!("Netdata Web Interface: http://{}:19999", ip);
-            println!("{}", "=".repeat(70));
+            outln!("\n{}", "=".repeat(70));
+            outln!("Netdata Web Interface: http://{}:19999", ip);
+            outln!("{}", "=".repeat(70));
         }
 
         Ok(())
@@ -403,7 +334,7 @@ info: Disk space usage is high
     /// Full setup wizard
     async fn setup_wizard(&self) -> Result<()> {
         log_info("Netdata Setup Wizard");
-        println!("\nThis wizard will guide you through Netdata installation and configuration.\n");
+        outln!("\nThis wizard will guide you through Netdata installation and configuration.\n");
 
         // Install Netdata
         let disable_telemetry = prompt_yes_no("Disable telemetry?", true)?;
@@ -421,12 +352,12 @@ info: Disk space usage is high
         let web_port_str = prompt_with_default("Enter web interface port", "19999")?;
         let web_port = web_port_str.parse::<u16>().unwrap_or(19999);
 
-        self.configure_netdata(web_port, use_cloud).await?;
+        let mut changed = self.configure_netdata(web_port, use_cloud).await?;
 
         // Setup health alarms
         let setup_alarms = prompt_yes_no("Setup health alarms?", true)?;
         if setup_alarms {
-            self.setup_health_alarms().await?;
+            changed |= self.setup_health_alarms().await?;
         }
 
         // Configure firewall
@@ -436,7 +367,7 @@ info: Disk space usage is high
         }
 
         // Start service
-        self.start_service().await?;
+        self.start_service(changed).await?;
 
         log_success("Netdata setup complete!");
         self.show_status().await?;
@@ -481,7 +412,7 @@ info: Disk space usage is high
                     self.show_status().await?;
                 }
                 5 => {
-                    self.start_service().await?;
+                    self.start_service(false).await?;
                 }
                 6 => {
                     self.stop_service().await?;
@@ -515,6 +446,19 @@ impl Module for NetdataModule {
         &self.base.info.version
     }
 
+    fn after(&self) -> &[String] {
+        &self.base.info.after
+    }
+
+    fn template_vars(&self, _config: &Config) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        Ok(vec![
+            ("netdata/netdata.conf", json!({ "web_port": 19999 })),
+            ("netdata/health.d/cpu_usage.conf", json!({})),
+            ("netdata/health.d/ram_usage.conf", json!({})),
+            ("netdata/health.d/disk_space.conf", json!({})),
+        ])
+    }
+
     fn is_available(&self) -> bool {
         check_command("curl").is_ok() || check_command("wget").is_ok()
     }
@@ -633,7 +577,7 @@ WEB INTERFACE:
                     i += 1;
                 }
                 "--start" => {
-                    self.start_service().await?;
+                    self.start_service(false).await?;
                     i += 1;
                 }
                 "--stop" => {
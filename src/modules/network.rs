@@ -1,19 +1,23 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    file_ops::{backup_file, safe_write_file},
+    facts,
+    file_ops::{read_file_to_string, safe_write_file},
     logging::{log_info, log_warn},
     network::get_network_interfaces,
-    system::{detect_distro, execute_command},
+    paths::resolve,
+    system::{execute_command, query_command, restart_service},
+    template,
     user_input::{prompt_input, prompt_ip, prompt_subnet, prompt_yes_no, prompt_select},
     validation::{validate_interface, validate_ip, validate_vlan},
 };
 use crate::modules::{Module, ModuleBase, ModuleContext, ModuleInfo};
+use crate::{out, outln};
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use serde_json::json;
 
 /// Network configuration module
 pub struct NetworkModule {
@@ -43,6 +47,8 @@ impl NetworkModule {
             tags: vec!["network".to_string(), "configuration".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
         
         Self {
@@ -144,13 +150,13 @@ impl NetworkModule {
     }
     
     async fn list_interfaces(&self) -> Result<()> {
-        println!("{}", "=== Network Interfaces ===".cyan());
-        println!();
+        outln!("{}", "=== Network Interfaces ===".cyan());
+        outln!();
         
         let interfaces = get_network_interfaces()?;
         
         // Physical interfaces
-        println!("{}", "Physical Interfaces:".white());
+        outln!("{}", "Physical Interfaces:".white());
         for iface in interfaces.iter().filter(|i| !i.is_loopback) {
             let status = if iface.is_up {
                 "UP".green()
@@ -158,23 +164,23 @@ impl NetworkModule {
                 "DOWN".red()
             };
             
-            println!("  {:<15} {}", format!("{}:", iface.name).white(), status);
-            println!("    MAC: {}", iface.mac);
+            outln!("  {:<15} {}", format!("{}:", iface.name).white(), status);
+            outln!("    MAC: {}", iface.mac);
             
             if !iface.ips.is_empty() {
                 let ips: Vec<String> = iface.ips.iter().map(|ip| ip.to_string()).collect();
-                println!("    IPs: {}", ips.join(", "));
+                outln!("    IPs: {}", ips.join(", "));
             }
             
-            println!("    MTU: {}", iface.mtu);
-            println!();
+            outln!("    MTU: {}", iface.mtu);
+            outln!();
         }
         
         // Show routing table
-        println!("{}", "Routing Table:".white());
+        outln!("{}", "Routing Table:".white());
         let routes = crate::helpers::network::get_routing_table()?;
         for route in routes.iter().take(10) {
-            println!("  {} via {} dev {}", 
+            outln!("  {} via {} dev {}", 
                 route.destination,
                 route.gateway.as_ref().unwrap_or(&"*".to_string()),
                 route.interface
@@ -182,10 +188,10 @@ impl NetworkModule {
         }
         
         // DNS configuration
-        println!("\n{}", "DNS Configuration:".white());
+        outln!("\n{}", "DNS Configuration:".white());
         let dns_servers = crate::helpers::network::get_dns_servers()?;
         for dns in dns_servers {
-            println!("  {}", dns);
+            outln!("  {}", dns);
         }
         
         Ok(())
@@ -207,11 +213,11 @@ impl NetworkModule {
         }
         
         // Show interfaces
-        println!("{}", "Available network interfaces:".cyan());
+        outln!("{}", "Available network interfaces:".cyan());
         for (i, name) in iface_names.iter().enumerate() {
-            println!("  {}. {}", i + 1, name);
+            outln!("  {}. {}", i + 1, name);
         }
-        println!();
+        outln!();
         
         // Select interface
         let selection = prompt_select("Select interface to configure", &iface_names, 0)?;
@@ -264,8 +270,11 @@ impl NetworkModule {
             }
         }
         
-        // Apply configuration
-        self.apply_network_config(&config).await?;
+        // Apply configuration; an unchanged configuration needs no restart
+        if !self.apply_network_config(&config).await? {
+            log_info("Network configuration already up to date");
+            return Ok(());
+        }
         
         // Restart networking
         if prompt_yes_no("Restart networking to apply changes?", true)? {
@@ -275,149 +284,109 @@ impl NetworkModule {
         Ok(())
     }
     
-    async fn apply_network_config(&self, config: &NetworkConfig) -> Result<()> {
-        let distro = detect_distro()?;
+    /// Write the configuration for `config.interface`, returning whether it changed
+    async fn apply_network_config(&self, config: &NetworkConfig) -> Result<bool> {
+        let distro = facts::get().os.distribution.clone();
         let net_manager = self.detect_network_manager()?;
         
         log_info(format!("Applying network configuration using {}", net_manager));
         
-        match net_manager.as_str() {
-            "interfaces" => self.configure_interface_debian(config).await,
-            "netplan" => self.configure_interface_netplan(config).await,
-            "networkmanager" => self.configure_interface_networkmanager(config).await,
-            _ => Err(FluxError::unsupported("Unknown network management system")),
+        let changed = match net_manager.as_str() {
+            "interfaces" => self.configure_interface_debian(config).await?,
+            "netplan" => self.configure_interface_netplan(config).await?,
+            "networkmanager" => self.configure_interface_networkmanager(config).await?,
+            _ => return Err(FluxError::unsupported("Unknown network management system")),
+        };
+        if changed {
+            facts::invalidate();
         }
+        Ok(changed)
     }
     
-    async fn configure_interface_debian(&self, config: &NetworkConfig) -> Result<()> {
+    async fn configure_interface_debian(&self, config: &NetworkConfig) -> Result<bool> {
         let interfaces_file = "/etc/network/interfaces";
         
-        // Backup interfaces file
-        backup_file(interfaces_file)?;
-        
-        // Read existing content
-        let mut content = std::fs::read_to_string(interfaces_file).unwrap_or_default();
-        
-        // Add new configuration
-        content.push_str(&format!("\n\nauto {}\n", config.interface));
+        let existing = read_file_to_string(interfaces_file).unwrap_or_default();
+        let content = replace_interface_stanza(&existing, &config.interface, &interfaces_stanza(config));
         
-        if config.dhcp {
-            content.push_str(&format!("iface {} inet dhcp\n", config.interface));
-        } else {
-            content.push_str(&format!("iface {} inet static\n", config.interface));
-            if let Some(addr) = &config.address {
-                content.push_str(&format!("    address {}\n", addr));
-            }
-            if let Some(mask) = &config.netmask {
-                content.push_str(&format!("    netmask {}\n", mask));
-            }
-            if let Some(gw) = &config.gateway {
-                content.push_str(&format!("    gateway {}\n", gw));
-            }
-            if !config.dns.is_empty() {
-                content.push_str(&format!("    dns-nameservers {}\n", config.dns.join(" ")));
-            }
+        let changed = safe_write_file(interfaces_file, &content, true)?;
+        if changed {
+            log_info("Network configuration updated in /etc/network/interfaces");
         }
         
-        safe_write_file(interfaces_file, &content, false)?;
-        log_info("Network configuration updated in /etc/network/interfaces");
-        
-        Ok(())
+        Ok(changed)
     }
     
-    async fn configure_interface_netplan(&self, config: &NetworkConfig) -> Result<()> {
+    /// Netplan configuration for `config`
+    fn render_netplan(&self, config: &NetworkConfig) -> Result<String> {
+        let mut vars = json!(config);
+        vars["prefix"] = json!(self.netmask_to_prefix(config.netmask.as_deref().unwrap_or("255.255.255.0")));
+        template::render("network/netplan.yaml", &vars)
+    }
+    
+    async fn configure_interface_netplan(&self, config: &NetworkConfig) -> Result<bool> {
         let netplan_file = format!("/etc/netplan/50-flux-{}.yaml", config.interface);
         
-        // Generate netplan configuration
-        let mut yaml = String::from("network:\n  version: 2\n  renderer: networkd\n");
-        
-        if let Some(vlan_id) = config.vlan_id {
-            yaml.push_str(&format!("  vlans:\n    {}.{}:\n", config.interface, vlan_id));
-            yaml.push_str(&format!("      id: {}\n", vlan_id));
-            yaml.push_str(&format!("      link: {}\n", config.interface));
-        } else {
-            yaml.push_str(&format!("  ethernets:\n    {}:\n", config.interface));
-        }
+        let yaml = self.render_netplan(config)?;
         
-        if config.dhcp {
-            yaml.push_str("      dhcp4: true\n");
-            yaml.push_str("      dhcp6: false\n");
-        } else {
-            yaml.push_str("      dhcp4: false\n");
-            yaml.push_str("      dhcp6: false\n");
-            
-            if let Some(addr) = &config.address {
-                let prefix = self.netmask_to_prefix(config.netmask.as_deref().unwrap_or("255.255.255.0"));
-                yaml.push_str(&format!("      addresses:\n        - {}/{}\n", addr, prefix));
-            }
-            
-            if let Some(gw) = &config.gateway {
-                yaml.push_str("      routes:\n");
-                yaml.push_str(&format!("        - to: default\n          via: {}\n", gw));
-            }
-            
-            if !config.dns.is_empty() {
-                yaml.push_str(&format!("      nameservers:\n        addresses: [{}]\n", 
-                    config.dns.join(", ")));
-            }
+        if !safe_write_file(&netplan_file, &yaml, true)? {
+            return Ok(false);
         }
-        
-        safe_write_file(&netplan_file, &yaml, true)?;
         log_info(format!("Network configuration written to {}", netplan_file));
         
         // Apply netplan configuration
         execute_command("netplan", &["apply"])?;
         
-        Ok(())
+        Ok(true)
     }
     
-    async fn configure_interface_networkmanager(&self, config: &NetworkConfig) -> Result<()> {
+    async fn configure_interface_networkmanager(&self, config: &NetworkConfig) -> Result<bool> {
         // NetworkManager configuration would use nmcli commands
         log_warn("NetworkManager configuration not yet implemented");
         Err(FluxError::unsupported("NetworkManager support coming soon"))
     }
     
     async fn run_diagnostics(&self) -> Result<()> {
-        println!("{}", "=== Network Diagnostics ===".cyan());
+        outln!("{}", "=== Network Diagnostics ===".cyan());
         
         let connectivity = crate::helpers::network::test_connectivity();
         
-        println!("\n{}", "Connectivity Tests:".white());
+        outln!("\n{}", "Connectivity Tests:".white());
         
         // Gateway
-        print!("  Gateway ({}): ", connectivity.gateway);
+        out!("  Gateway ({}): ", connectivity.gateway);
         if connectivity.gateway_reachable {
-            println!("{}", "OK".green());
+            outln!("{}", "OK".green());
         } else {
-            println!("{}", "FAILED".red());
+            outln!("{}", "FAILED".red());
         }
         
         // DNS
-        print!("  DNS ({}): ", connectivity.dns_server);
+        out!("  DNS ({}): ", connectivity.dns_server);
         if connectivity.dns_reachable {
-            println!("{}", "OK".green());
+            outln!("{}", "OK".green());
         } else {
-            println!("{}", "FAILED".red());
+            outln!("{}", "FAILED".red());
         }
         
         // DNS resolution
-        print!("  DNS Resolution: ");
+        out!("  DNS Resolution: ");
         if connectivity.dns_resolution_working {
-            println!("{}", "OK".green());
+            outln!("{}", "OK".green());
         } else {
-            println!("{}", "FAILED".red());
+            outln!("{}", "FAILED".red());
         }
         
         // Internet
-        print!("  Internet (8.8.8.8): ");
+        out!("  Internet (8.8.8.8): ");
         if connectivity.internet_reachable {
-            println!("{}", "OK".green());
+            outln!("{}", "OK".green());
         } else {
-            println!("{}", "FAILED".red());
+            outln!("{}", "FAILED".red());
         }
         
         // Port checks
-        println!("\n{}", "Common Ports:".white());
+        outln!("\n{}", "Common Ports:".white());
         let ports = vec![
             (22, "SSH"),
             (80, "HTTP"),
@@ -426,17 +395,17 @@ impl NetworkModule {
         ];
         
         for (port, name) in ports {
-            print!("  {} (port {}): ", name, port);
+            out!("  {} (port {}): ", name, port);
             if crate::helpers::network::is_port_open("localhost", port, 1) {
-                println!("{}", "LISTENING".green());
+                outln!("{}", "LISTENING".green());
             } else {
-                println!("{}", "NOT LISTENING".yellow());
+                outln!("{}", "NOT LISTENING".yellow());
             }
         }
         
         // Network manager
         let net_manager = self.detect_network_manager()?;
-        println!("\n{}: {}", "Network Manager".white(), net_manager);
+        outln!("\n{}: {}", "Network Manager".white(), net_manager);
         
         Ok(())
     }
@@ -448,16 +417,17 @@ impl NetworkModule {
         
         match net_manager.as_str() {
             "interfaces" => {
-                execute_command("systemctl", &["restart", "networking"])?;
+                restart_service("networking")?;
             }
             "netplan" => {
                 execute_command("netplan", &["apply"])?;
             }
             "networkmanager" => {
-                execute_command("systemctl", &["restart", "NetworkManager"])?;
+                restart_service("NetworkManager")?;
             }
             _ => return Err(FluxError::unsupported("Unknown network system")),
         }
+        facts::invalidate();
         
         log_info("Networking restarted");
         Ok(())
@@ -469,8 +439,8 @@ impl NetworkModule {
         let vlan_id = validate_vlan(vlan_id)?;
         
         // Check if 802.1Q module is loaded
-        if execute_command("lsmod", &[]).is_ok() {
-            if !execute_command("lsmod", &[])?.contains("8021q") {
+        if query_command("lsmod", &[]).is_ok() {
+            if !query_command("lsmod", &[])?.contains("8021q") {
                 log_info("Loading 802.1Q VLAN module");
                 execute_command("modprobe", &["8021q"])?;
                 
@@ -511,7 +481,8 @@ impl NetworkModule {
             vlan_id: None,
         };
         
-        self.apply_network_config(&config).await
+        self.apply_network_config(&config).await?;
+        Ok(())
     }
     
     async fn configure_static(&self, interface: &str, ip_address: &str) -> Result<()> {
@@ -529,7 +500,8 @@ impl NetworkModule {
             vlan_id: None,
         };
 
-        self.apply_network_config(&config).await
+        self.apply_network_config(&config).await?;
+        Ok(())
     }
     
     async fn show_network_menu(&self) -> Result<()> {
@@ -575,11 +547,11 @@ impl NetworkModule {
     }
     
     fn detect_network_manager(&self) -> Result<String> {
-        if Path::new("/etc/netplan").exists() && crate::helpers::system::command_exists("netplan") {
+        if resolve("/etc/netplan").exists() && crate::helpers::system::command_exists("netplan") {
             Ok("netplan".to_string())
-        } else if Path::new("/etc/network/interfaces").exists() {
+        } else if resolve("/etc/network/interfaces").exists() {
             Ok("interfaces".to_string())
-        } else if Path::new("/etc/NetworkManager").exists() 
+        } else if resolve("/etc/NetworkManager").exists() 
             && crate::helpers::system::is_service_active("NetworkManager").unwrap_or(false) {
             Ok("networkmanager".to_string())
         } else {
@@ -665,6 +637,78 @@ impl NetworkModule {
     }
 }
 
+/// `/etc/network/interfaces` stanza for `config`
+fn interfaces_stanza(config: &NetworkConfig) -> String {
+    let mut stanza = format!("auto {}\n", config.interface);
+    
+    if config.dhcp {
+        stanza.push_str(&format!("iface {} inet dhcp\n", config.interface));
+    } else {
+        stanza.push_str(&format!("iface {} inet static\n", config.interface));
+        if let Some(addr) = &config.address {
+            stanza.push_str(&format!("    address {}\n", addr));
+        }
+        if let Some(mask) = &config.netmask {
+            stanza.push_str(&format!("    netmask {}\n", mask));
+        }
+        if let Some(gw) = &config.gateway {
+            stanza.push_str(&format!("    gateway {}\n", gw));
+        }
+        if !config.dns.is_empty() {
+            stanza.push_str(&format!("    dns-nameservers {}\n", config.dns.join(" ")));
+        }
+    }
+    
+    stanza
+}
+
+/// Replace the `auto`, `allow-hotplug` and `iface <name> inet` stanzas of `interface` with `stanza`
+///
+/// Other interfaces are kept as they are, as is an `inet6` stanza of the same
+/// interface. `auto` and `allow-hotplug` lines naming several interfaces only
+/// lose `interface`. Writing the same stanza twice gives the same file.
+fn replace_interface_stanza(existing: &str, interface: &str, stanza: &str) -> String {
+    let mut kept: Vec<String> = Vec::new();
+    let mut in_stanza = false;
+    
+    for line in existing.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [keyword @ ("auto" | "allow-hotplug"), names @ ..] if names.contains(&interface) => {
+                in_stanza = false;
+                let others: Vec<&str> = names.iter().copied().filter(|name| *name != interface).collect();
+                if !others.is_empty() {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    kept.push(format!("{}{} {}", indent, keyword, others.join(" ")));
+                }
+                continue;
+            }
+            ["iface", name, "inet", ..] if *name == interface => {
+                in_stanza = true;
+                continue;
+            }
+            _ => {}
+        }
+        // Options of the removed `iface` are indented below it
+        if in_stanza && line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            continue;
+        }
+        in_stanza = false;
+        kept.push(line.to_string());
+    }
+    
+    while kept.last().is_some_and(|line| line.trim().is_empty()) {
+        kept.pop();
+    }
+    
+    let mut content = kept.join("\n");
+    if !content.is_empty() {
+        content.push_str("\n\n");
+    }
+    content.push_str(stanza);
+    content
+}
+
 #[async_trait]
 impl Module for NetworkModule {
     fn name(&self) -> &str {
@@ -810,4 +854,71 @@ mod tests {
             assert_eq!(computed_prefix, prefix, "Roundtrip failed for prefix {}", prefix);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_replace_interface_stanza() {
+        let existing = "auto lo\niface lo inet loopback\n\nauto eth0\niface eth0 inet static\n    address 10.0.0.5\n    netmask 255.255.255.0\n";
+        let config = NetworkConfig {
+            interface: "eth0".to_string(),
+            dhcp: true,
+            address: None,
+            netmask: None,
+            gateway: None,
+            dns: vec![],
+            mtu: None,
+            vlan_id: None,
+        };
+        let stanza = interfaces_stanza(&config);
+        
+        let once = replace_interface_stanza(existing, "eth0", &stanza);
+        assert_eq!(once, "auto lo\niface lo inet loopback\n\nauto eth0\niface eth0 inet dhcp\n");
+        
+        // Writing it again leaves the file as it is
+        assert_eq!(replace_interface_stanza(&once, "eth0", &stanza), once);
+        assert_eq!(replace_interface_stanza("", "eth0", &stanza), stanza);
+    }
+
+    #[test]
+    fn test_replace_interface_stanza_keeps_other_names() {
+        let existing = "auto lo eth0\nallow-hotplug eth0 eth1\niface lo inet loopback\n";
+        let stanza = "auto eth0\niface eth0 inet dhcp\n";
+        
+        assert_eq!(
+            replace_interface_stanza(existing, "eth0", stanza),
+            "auto lo\nallow-hotplug eth1\niface lo inet loopback\n\nauto eth0\niface eth0 inet dhcp\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_interface_stanza_keeps_inet6() {
+        let existing = "auto eth0\niface eth0 inet static\n    address 10.0.0.5\niface eth0 inet6 static\n    address 2001:db8::5/64\n";
+        let stanza = "auto eth0\niface eth0 inet dhcp\n";
+        
+        let once = replace_interface_stanza(existing, "eth0", stanza);
+        assert_eq!(
+            once,
+            "iface eth0 inet6 static\n    address 2001:db8::5/64\n\nauto eth0\niface eth0 inet dhcp\n"
+        );
+        assert_eq!(replace_interface_stanza(&once, "eth0", stanza), once);
+    }
+
+    #[test]
+    fn test_render_netplan() {
+        let module = NetworkModule::new();
+        let config = NetworkConfig {
+            interface: "eth0".to_string(),
+            dhcp: false,
+            address: Some("192.168.1.10".to_string()),
+            netmask: Some("255.255.192.0".to_string()),
+            gateway: Some("192.168.1.1".to_string()),
+            dns: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
+            mtu: None,
+            vlan_id: Some(10),
+        };
+
+        assert_eq!(
+            module.render_netplan(&config).unwrap(),
+            "network:\n  version: 2\n  renderer: networkd\n  vlans:\n    eth0.10:\n      id: 10\n      link: eth0\n      dhcp4: false\n      dhcp6: false\n      addresses:\n        - 192.168.1.10/18\n      routes:\n        - to: default\n          via: 192.168.1.1\n      nameservers:\n        addresses: [1.1.1.1, 8.8.8.8]\n"
+        );
+    }
+}
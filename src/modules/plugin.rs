@@ -0,0 +1,331 @@
+//! External plugin modules
+//!
+//! Any executable file in `general.modules_dir` is loaded as a module. Flux
+//! runs the executable once per request, writes a single JSON object to its
+//! stdin and reads a single JSON object from its stdout. Anything the plugin
+//! writes to stderr is passed through to the terminal.
+//!
+//! | Request | Response |
+//! |---------|----------|
+//! | `{"action": "describe"}` | module metadata, the same fields as [`ModuleInfo`] |
+//! | `{"action": "is_available"}` | `{"available": true}` |
+//! | `{"action": "help"}` | `{"help": "..."}` |
+//! | `{"action": "execute", "args": [...], "config": {...}, "dry_run": false}` | `{"success": true, "message": "..."}` |
+//!
+//! `config` is the plugin's `[modules.<name>]` table, or `null` when it has
+//! none. A plugin must honour `dry_run` itself.
+
+use crate::config::Config;
+use crate::error::{FluxError, Result};
+use crate::helpers::dry_run;
+use crate::helpers::logging::{log_debug, log_warn};
+use crate::modules::{Module, ModuleInfo};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use tokio::io::AsyncWriteExt;
+
+/// A request sent to a plugin on stdin
+#[derive(Debug, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Request<'a> {
+    Describe,
+    IsAvailable,
+    Help,
+    Execute {
+        args: &'a [String],
+        config: Option<&'a toml::Value>,
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct AvailableResponse {
+    available: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelpResponse {
+    help: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecuteResponse {
+    success: bool,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// A module implemented by an external executable
+pub struct PluginModule {
+    path: PathBuf,
+    info: ModuleInfo,
+    available: OnceLock<bool>,
+}
+
+impl PluginModule {
+    /// Load the plugin at `path` by asking it to describe itself
+    ///
+    /// A plugin that leaves `name` empty is named after its file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut info: ModuleInfo = call(path, &Request::Describe)?;
+
+        if info.name.is_empty() {
+            info.name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        if !info
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(FluxError::module(format!(
+                "Plugin {} has an invalid module name '{}'",
+                path.display(),
+                info.name
+            )));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            info,
+            available: OnceLock::new(),
+        })
+    }
+
+    /// Path of the plugin executable
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait]
+impl Module for PluginModule {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn version(&self) -> &str {
+        &self.info.version
+    }
+
+    fn is_available(&self) -> bool {
+        *self.available.get_or_init(|| {
+            match call::<AvailableResponse>(&self.path, &Request::IsAvailable) {
+                Ok(response) => response.available,
+                Err(e) => {
+                    log_debug(format!("Plugin '{}' availability check failed: {}", self.info.name, e));
+                    false
+                }
+            }
+        })
+    }
+
+    fn help(&self) -> String {
+        match call::<HelpResponse>(&self.path, &Request::Help) {
+            Ok(response) => response.help,
+            Err(e) => format!(
+                "{} ({})\n\nHelp unavailable: {}",
+                self.info.description,
+                self.path.display(),
+                e
+            ),
+        }
+    }
+
+    async fn execute(&self, args: Vec<String>, config: &Config) -> Result<()> {
+        let request = Request::Execute {
+            args: &args,
+            config: config.get_module_config(&self.info.name),
+            dry_run: dry_run::is_dry_run(),
+        };
+        let response: ExecuteResponse = call_async(&self.path, &request).await?;
+
+        if response.success {
+            Ok(())
+        } else {
+            Err(FluxError::module(format!(
+                "Plugin '{}' failed: {}",
+                self.info.name,
+                response.message.unwrap_or_else(|| "no message given".to_string())
+            )))
+        }
+    }
+
+    fn requires(&self) -> &[String] {
+        &self.info.requires
+    }
+
+    fn after(&self) -> &[String] {
+        &self.info.after
+    }
+}
+
+/// Load every executable in `dir` as a plugin
+///
+/// Plugins that cannot be loaded are reported and skipped so that one broken
+/// plugin does not take the rest of flux down with it.
+pub fn discover_plugins(dir: &Path) -> Result<Vec<PluginModule>> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        FluxError::config(format!("Failed to read modules_dir {}: {}", dir.display(), e))
+    })?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| is_plugin_executable(path))
+        .collect();
+    paths.sort();
+
+    let mut plugins = Vec::new();
+    for path in paths {
+        match PluginModule::load(&path) {
+            Ok(plugin) => plugins.push(plugin),
+            Err(e) => log_warn(format!("Skipping plugin {}: {}", path.display(), e)),
+        }
+    }
+
+    Ok(plugins)
+}
+
+fn is_plugin_executable(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+    !hidden
+        && fs::metadata(path)
+            .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+}
+
+/// Run the plugin for a single request and wait for its response
+fn call<T: DeserializeOwned>(path: &Path, request: &Request<'_>) -> Result<T> {
+    let input = encode_request(request)?;
+
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| spawn_failed(path, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A plugin may exit without reading its request; that is not an error here
+        let _ = stdin.write_all(&input);
+    }
+
+    let output = child.wait_with_output()?;
+    parse_response(path, output)
+}
+
+/// Async variant of [`call`] so long-running plugins do not block the runtime
+async fn call_async<T: DeserializeOwned>(path: &Path, request: &Request<'_>) -> Result<T> {
+    let input = encode_request(request)?;
+
+    let mut child = tokio::process::Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| spawn_failed(path, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(&input).await;
+    }
+
+    let output = child.wait_with_output().await?;
+    parse_response(path, output)
+}
+
+fn encode_request(request: &Request<'_>) -> Result<Vec<u8>> {
+    let mut input = serde_json::to_vec(request)
+        .map_err(|e| FluxError::parse(format!("Failed to encode plugin request: {}", e)))?;
+    // Terminate the line so plugins can read their request with `read`
+    input.push(b'\n');
+    Ok(input)
+}
+
+fn spawn_failed(path: &Path, e: std::io::Error) -> FluxError {
+    FluxError::command_failed(format!("Failed to run plugin {}: {}", path.display(), e))
+}
+
+fn parse_response<T: DeserializeOwned>(path: &Path, output: std::process::Output) -> Result<T> {
+    if !output.status.success() {
+        return Err(FluxError::command_failed(format!(
+            "Plugin {} exited with {}",
+            path.display(),
+            output.status
+        )));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| {
+        FluxError::parse(format!(
+            "Plugin {} returned an invalid response: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PLUGIN: &str = r#"#!/bin/sh
+read -r request
+case "$request" in
+  *'"describe"'*) echo '{"name": "site", "description": "Site tweaks", "version": "0.1.0", "after": ["user"]}' ;;
+  *'"is_available"'*) echo '{"available": true}' ;;
+  *'"help"'*) echo '{"help": "usage: site"}' ;;
+  *'"--fail"'*) echo '{"success": false, "message": "asked to fail"}' ;;
+  *'"execute"'*) echo '{"success": true}' ;;
+esac
+"#;
+
+    fn write_plugin(dir: &Path, name: &str, script: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_plugin_protocol() {
+        let dir = TempDir::new().unwrap();
+        write_plugin(dir.path(), "site", PLUGIN, 0o755);
+        write_plugin(dir.path(), "notes.txt", "not a plugin", 0o644);
+        write_plugin(dir.path(), "broken", "#!/bin/sh\necho nonsense\n", 0o755);
+
+        let plugins = discover_plugins(dir.path()).unwrap();
+        assert_eq!(plugins.len(), 1);
+
+        let plugin = &plugins[0];
+        assert_eq!(plugin.name(), "site");
+        assert_eq!(plugin.version(), "0.1.0");
+        assert_eq!(plugin.after(), ["user".to_string()]);
+        assert!(plugin.is_available());
+        assert_eq!(plugin.help(), "usage: site");
+
+        let config = Config::default();
+        assert!(plugin.execute(Vec::new(), &config).await.is_ok());
+
+        let err = plugin
+            .execute(vec!["--fail".to_string()], &config)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("asked to fail"));
+    }
+}
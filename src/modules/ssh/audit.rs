@@ -0,0 +1,480 @@
+//! Audit of the effective sshd configuration
+//!
+//! Rules are checked against `sshd -T`, which prints every setting sshd ends
+//! up with after includes and defaults, so a setting that is simply absent
+//! from sshd_config is judged by its default. Each finding carries a hint on
+//! how to fix it, usually a `flux load ssh -- set` command.
+
+use super::SSH_CONFIG_PATH;
+use crate::error::{FluxError, Result};
+use crate::helpers::{paths::resolve, system::command_output};
+use crate::outln;
+use colored::Colorize;
+use serde::Serialize;
+use std::fs;
+
+const MODULI_PATH: &str = "/etc/ssh/moduli";
+
+/// Diffie-Hellman groups below this many bits are considered weak
+const MIN_MODULUS_BITS: u32 = 3072;
+
+/// `MaxAuthTries` above this gives password guessing too many attempts
+const MAX_AUTH_TRIES: u32 = 4;
+
+/// Points taken off the score of 100 per finding
+const FAIL_PENALTY: u32 = 15;
+const WARN_PENALTY: u32 = 5;
+
+/// Outcome of a rule, from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Result of one rule
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    /// The directive or object checked, e.g. `PermitRootLogin`
+    pub check: String,
+    pub status: AuditStatus,
+    /// The value found, or what is wrong with it
+    pub detail: String,
+    pub remediation: Option<String>,
+}
+
+impl Finding {
+    fn new(check: impl Into<String>, status: AuditStatus, detail: impl Into<String>) -> Self {
+        Self {
+            check: check.into(),
+            status,
+            detail: detail.into(),
+            remediation: None,
+        }
+    }
+
+    fn fix(mut self, remediation: impl Into<String>) -> Self {
+        if self.status != AuditStatus::Pass {
+            self.remediation = Some(remediation.into());
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditReport {
+    /// 100 minus a penalty per warning and failure
+    pub score: u32,
+    /// A to F, from the score
+    pub grade: char,
+    pub findings: Vec<Finding>,
+}
+
+impl AuditReport {
+    fn new(findings: Vec<Finding>) -> Self {
+        let penalty: u32 = findings
+            .iter()
+            .map(|f| match f.status {
+                AuditStatus::Pass => 0,
+                AuditStatus::Warn => WARN_PENALTY,
+                AuditStatus::Fail => FAIL_PENALTY,
+            })
+            .sum();
+        let score = 100u32.saturating_sub(penalty);
+        let grade = match score {
+            90.. => 'A',
+            80..=89 => 'B',
+            70..=79 => 'C',
+            60..=69 => 'D',
+            _ => 'F',
+        };
+
+        Self { score, grade, findings }
+    }
+
+    fn count(&self, status: AuditStatus) -> usize {
+        self.findings.iter().filter(|f| f.status == status).count()
+    }
+
+    pub fn print(&self) {
+        outln!("{}", "=== SSH Audit ===".cyan());
+        for finding in &self.findings {
+            let status = match finding.status {
+                AuditStatus::Pass => format!("{:<5}", "pass").green(),
+                AuditStatus::Warn => format!("{:<5}", "warn").yellow(),
+                AuditStatus::Fail => format!("{:<5}", "fail").red(),
+            };
+            outln!("  {} {:<32} {}", status, finding.check, finding.detail);
+            if let Some(remediation) = &finding.remediation {
+                outln!("        {}", format!("→ {}", remediation).bright_black());
+            }
+        }
+
+        outln!();
+        let summary = format!(
+            "Score: {}/100 (grade {}), {} failed, {} warnings",
+            self.score,
+            self.grade,
+            self.count(AuditStatus::Fail),
+            self.count(AuditStatus::Warn)
+        );
+        match self.grade {
+            'A' | 'B' => outln!("{}", summary.green()),
+            'C' | 'D' => outln!("{}", summary.yellow()),
+            _ => outln!("{}", summary.red()),
+        }
+    }
+}
+
+/// Settings as printed by `sshd -T`: lowercase keywords, one value per line
+#[derive(Debug, Clone, Default)]
+pub struct EffectiveConfig {
+    settings: Vec<(String, String)>,
+}
+
+impl EffectiveConfig {
+    pub fn parse(text: &str) -> Self {
+        let settings = text
+            .lines()
+            .filter_map(|line| line.trim().split_once(' '))
+            .map(|(keyword, value)| (keyword.to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        Self { settings }
+    }
+
+    pub fn get(&self, keyword: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of a keyword that may repeat, such as `hostkey`
+    pub fn get_all(&self, keyword: &str) -> Vec<&str> {
+        self.settings
+            .iter()
+            .filter(|(k, _)| k == keyword)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// The comma-separated algorithms of a keyword such as `ciphers`
+    fn algorithms(&self, keyword: &str) -> Vec<&str> {
+        self.get(keyword)
+            .map(|value| value.split(',').filter(|a| !a.is_empty()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// A host key as described by `ssh-keygen -l`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostKey {
+    pub path: String,
+    pub bits: u32,
+    /// `RSA`, `ED25519`, `ECDSA` or `DSA`
+    pub kind: String,
+}
+
+impl HostKey {
+    /// Parse `ssh-keygen -l` output such as `3072 SHA256:... root@host (RSA)`
+    pub fn parse(path: &str, text: &str) -> Option<Self> {
+        let bits = text.split_whitespace().next()?.parse().ok()?;
+        let kind = text.trim().rsplit_once('(')?.1.strip_suffix(')')?.to_string();
+        Some(Self {
+            path: path.to_string(),
+            bits,
+            kind,
+        })
+    }
+}
+
+/// Run `sshd -T` and the checks that need files on the host
+pub fn run() -> Result<AuditReport> {
+    let config_path = resolve(SSH_CONFIG_PATH).to_string_lossy().into_owned();
+    let output = command_output("sshd", &["-T", "-f", &config_path])?;
+    if !output.success {
+        return Err(FluxError::command_failed(format!(
+            "sshd -T failed: {}",
+            output.stderr.trim()
+        )));
+    }
+    let config = EffectiveConfig::parse(&output.stdout);
+
+    let host_keys: Vec<HostKey> = config
+        .get_all("hostkey")
+        .into_iter()
+        .filter_map(|path| {
+            let key = resolve(path).to_string_lossy().into_owned();
+            let output = command_output("ssh-keygen", &["-l", "-f", &key]).ok()?;
+            HostKey::parse(path, &output.stdout)
+        })
+        .collect();
+    let moduli = fs::read_to_string(resolve(MODULI_PATH)).ok();
+
+    Ok(audit(&config, &host_keys, moduli.as_deref()))
+}
+
+/// Check the effective configuration against every rule
+pub fn audit(config: &EffectiveConfig, host_keys: &[HostKey], moduli: Option<&str>) -> AuditReport {
+    let mut findings = Vec::new();
+
+    findings.push(check_root_login(config));
+    findings.push(check_enabled(config, "PasswordAuthentication", AuditStatus::Fail));
+    findings.push(check_enabled(config, "PermitEmptyPasswords", AuditStatus::Fail));
+    // Renamed in OpenSSH 8.7
+    let keyboard_interactive = if config.get("kbdinteractiveauthentication").is_some() {
+        "KbdInteractiveAuthentication"
+    } else {
+        "ChallengeResponseAuthentication"
+    };
+    findings.push(check_enabled(config, keyboard_interactive, AuditStatus::Warn));
+    findings.push(check_max_auth_tries(config));
+
+    for keyword in ["X11Forwarding", "AllowTcpForwarding", "AllowAgentForwarding", "PermitTunnel", "GatewayPorts"] {
+        findings.push(check_enabled(config, keyword, AuditStatus::Warn));
+    }
+
+    findings.push(check_algorithms(config, "Ciphers", weak_cipher));
+    findings.push(check_algorithms(config, "MACs", weak_mac));
+    findings.push(check_algorithms(config, "KexAlgorithms", weak_kex));
+    findings.push(check_algorithms(config, "HostKeyAlgorithms", weak_host_key_algorithm));
+
+    findings.extend(host_keys.iter().map(check_host_key));
+
+    let group_exchange = config
+        .algorithms("kexalgorithms")
+        .iter()
+        .any(|kex| kex.starts_with("diffie-hellman-group-exchange"));
+    if let Some(moduli) = moduli.filter(|_| group_exchange) {
+        findings.push(check_moduli(moduli));
+    }
+
+    AuditReport::new(findings)
+}
+
+fn set_hint(keyword: &str, value: &str) -> String {
+    format!("flux load ssh -- set {} {}", keyword, value)
+}
+
+fn check_root_login(config: &EffectiveConfig) -> Finding {
+    let value = config.get("permitrootlogin").unwrap_or("yes");
+    let status = match value {
+        "no" => AuditStatus::Pass,
+        "yes" => AuditStatus::Fail,
+        // prohibit-password (shown as without-password by older releases) and forced-commands-only
+        _ => AuditStatus::Warn,
+    };
+    Finding::new("PermitRootLogin", status, value).fix(set_hint("PermitRootLogin", "no"))
+}
+
+/// A yes/no style setting that should be off; anything but `no` gets `status`
+fn check_enabled(config: &EffectiveConfig, keyword: &str, status: AuditStatus) -> Finding {
+    let value = config.get(&keyword.to_ascii_lowercase()).unwrap_or("no");
+    let status = if value == "no" { AuditStatus::Pass } else { status };
+    Finding::new(keyword, status, value).fix(set_hint(keyword, "no"))
+}
+
+fn check_max_auth_tries(config: &EffectiveConfig) -> Finding {
+    let value = config.get("maxauthtries").unwrap_or("6");
+    let status = match value.parse::<u32>() {
+        Ok(tries) if tries <= MAX_AUTH_TRIES => AuditStatus::Pass,
+        _ => AuditStatus::Warn,
+    };
+    Finding::new("MaxAuthTries", status, value).fix(set_hint("MaxAuthTries", &MAX_AUTH_TRIES.to_string()))
+}
+
+/// Flag the weak algorithms of a list; `weakness` tells how bad one is
+fn check_algorithms(
+    config: &EffectiveConfig,
+    keyword: &str,
+    weakness: fn(&str) -> Option<AuditStatus>,
+) -> Finding {
+    let algorithms = config.algorithms(&keyword.to_ascii_lowercase());
+    let weak: Vec<(&str, AuditStatus)> = algorithms
+        .iter()
+        .filter_map(|a| weakness(a).map(|status| (*a, status)))
+        .collect();
+
+    let Some(status) = weak.iter().map(|(_, status)| *status).max() else {
+        return Finding::new(keyword, AuditStatus::Pass, format!("{} algorithms, none weak", algorithms.len()));
+    };
+
+    let names: Vec<&str> = weak.iter().map(|(name, _)| *name).collect();
+    let strong: Vec<&str> = algorithms.iter().copied().filter(|a| !names.contains(a)).collect();
+    let finding = Finding::new(keyword, status, format!("weak: {}", names.join(", ")));
+    if strong.is_empty() {
+        finding.fix("Choose a crypto_profile under [modules.ssh] and run flux apply")
+    } else {
+        finding.fix(set_hint(keyword, &strong.join(",")))
+    }
+}
+
+fn weak_cipher(name: &str) -> Option<AuditStatus> {
+    ["cbc", "3des", "arcfour", "blowfish", "cast128", "none"]
+        .iter()
+        .any(|weak| name.contains(weak))
+        .then_some(AuditStatus::Fail)
+}
+
+fn weak_mac(name: &str) -> Option<AuditStatus> {
+    ["md5", "sha1", "umac-64", "ripemd"]
+        .iter()
+        .any(|weak| name.contains(weak))
+        .then_some(AuditStatus::Fail)
+}
+
+fn weak_kex(name: &str) -> Option<AuditStatus> {
+    (name.ends_with("-sha1") || name.starts_with("diffie-hellman-group1-")).then_some(AuditStatus::Fail)
+}
+
+fn weak_host_key_algorithm(name: &str) -> Option<AuditStatus> {
+    if name.starts_with("ssh-dss") {
+        Some(AuditStatus::Fail)
+    } else if name.starts_with("ssh-rsa") {
+        // ssh-rsa signs with SHA-1
+        Some(AuditStatus::Warn)
+    } else {
+        None
+    }
+}
+
+fn check_host_key(key: &HostKey) -> Finding {
+    let status = match key.kind.as_str() {
+        "DSA" => AuditStatus::Fail,
+        "RSA" if key.bits < 2048 => AuditStatus::Fail,
+        "RSA" if key.bits < 3072 => AuditStatus::Warn,
+        _ => AuditStatus::Pass,
+    };
+    Finding::new(
+        format!("HostKey {}", key.path),
+        status,
+        format!("{} {} bits", key.kind, key.bits),
+    )
+    .fix(match key.kind.as_str() {
+        "DSA" => format!("Remove the HostKey line for {} and the key", key.path),
+        _ => format!("ssh-keygen -t rsa -b 4096 -N '' -f {} (or flux load ssh -- --generate-keys)", key.path),
+    })
+}
+
+fn check_moduli(moduli: &str) -> Finding {
+    // The size column holds the modulus length minus one
+    let sizes: Vec<u32> = moduli
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_whitespace().nth(4)?.parse::<u32>().ok())
+        .map(|size| size + 1)
+        .collect();
+    let small = sizes.iter().filter(|&&bits| bits < MIN_MODULUS_BITS).count();
+
+    let (status, detail) = if sizes.is_empty() {
+        (AuditStatus::Warn, "no groups, sshd falls back to fixed groups".to_string())
+    } else if small > 0 {
+        (
+            AuditStatus::Warn,
+            format!("{} of {} groups smaller than {} bits", small, sizes.len(), MIN_MODULUS_BITS),
+        )
+    } else {
+        (AuditStatus::Pass, format!("{} groups, all at least {} bits", sizes.len(), MIN_MODULUS_BITS))
+    };
+
+    Finding::new("Moduli", status, detail).fix(format!(
+        "awk '$5 >= {}' {} > {}.safe && mv {}.safe {}",
+        MIN_MODULUS_BITS - 1,
+        MODULI_PATH,
+        MODULI_PATH,
+        MODULI_PATH,
+        MODULI_PATH
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `sshd -T` of a stock Debian install, abridged
+    const STOCK: &str = "port 22
+permitrootlogin without-password
+passwordauthentication yes
+permitemptypasswords no
+kbdinteractiveauthentication no
+maxauthtries 6
+x11forwarding yes
+allowtcpforwarding yes
+allowagentforwarding yes
+permittunnel no
+gatewayports no
+ciphers chacha20-poly1305@openssh.com,aes128-ctr,aes256-gcm@openssh.com
+macs umac-64-etm@openssh.com,hmac-sha2-256-etm@openssh.com,hmac-sha1
+kexalgorithms curve25519-sha256,diffie-hellman-group-exchange-sha256,diffie-hellman-group14-sha1
+hostkeyalgorithms ssh-ed25519,rsa-sha2-512,ssh-rsa
+hostkey /etc/ssh/ssh_host_rsa_key
+hostkey /etc/ssh/ssh_host_ed25519_key
+";
+
+    fn finding<'a>(report: &'a AuditReport, check: &str) -> &'a Finding {
+        report.findings.iter().find(|f| f.check == check).unwrap()
+    }
+
+    #[test]
+    fn test_audit_stock_config() {
+        let keys = vec![
+            HostKey::parse("/etc/ssh/ssh_host_rsa_key", "2048 SHA256:abc root@host (RSA)\n").unwrap(),
+            HostKey::parse("/etc/ssh/ssh_host_ed25519_key", "256 SHA256:def root@host (ED25519)\n").unwrap(),
+        ];
+        let moduli = "# Time Type Tests Tries Size Generator Modulus\n\
+                      20230101000000 2 6 100 2047 2 F1\n\
+                      20230101000000 2 6 100 3071 2 F2\n";
+
+        let report = audit(&EffectiveConfig::parse(STOCK), &keys, Some(moduli));
+
+        assert_eq!(finding(&report, "PermitRootLogin").status, AuditStatus::Warn);
+        let password = finding(&report, "PasswordAuthentication");
+        assert_eq!(password.status, AuditStatus::Fail);
+        assert_eq!(
+            password.remediation.as_deref(),
+            Some("flux load ssh -- set PasswordAuthentication no")
+        );
+        assert_eq!(finding(&report, "KbdInteractiveAuthentication").status, AuditStatus::Pass);
+        assert_eq!(finding(&report, "MaxAuthTries").status, AuditStatus::Warn);
+        assert_eq!(finding(&report, "X11Forwarding").status, AuditStatus::Warn);
+        assert_eq!(finding(&report, "PermitTunnel").status, AuditStatus::Pass);
+
+        assert_eq!(finding(&report, "Ciphers").status, AuditStatus::Pass);
+        let macs = finding(&report, "MACs");
+        assert_eq!(macs.detail, "weak: umac-64-etm@openssh.com, hmac-sha1");
+        assert_eq!(
+            macs.remediation.as_deref(),
+            Some("flux load ssh -- set MACs hmac-sha2-256-etm@openssh.com")
+        );
+        assert_eq!(finding(&report, "KexAlgorithms").status, AuditStatus::Fail);
+        assert_eq!(finding(&report, "HostKeyAlgorithms").status, AuditStatus::Warn);
+
+        assert_eq!(finding(&report, "HostKey /etc/ssh/ssh_host_rsa_key").status, AuditStatus::Warn);
+        assert_eq!(finding(&report, "HostKey /etc/ssh/ssh_host_ed25519_key").status, AuditStatus::Pass);
+        assert_eq!(finding(&report, "Moduli").detail, "1 of 2 groups smaller than 3072 bits");
+
+        // 3 failures and 8 warnings
+        assert_eq!(report.score, 15);
+        assert_eq!(report.grade, 'F');
+    }
+
+    #[test]
+    fn test_audit_hardened_config() {
+        let config = EffectiveConfig::parse(
+            "permitrootlogin no\npasswordauthentication no\nmaxauthtries 3\n\
+             x11forwarding no\nallowtcpforwarding no\nallowagentforwarding no\n\
+             ciphers aes256-gcm@openssh.com\nmacs hmac-sha2-512-etm@openssh.com\n\
+             kexalgorithms curve25519-sha256\nhostkeyalgorithms ssh-ed25519\n",
+        );
+
+        let report = audit(&config, &[], None);
+
+        assert!(report.findings.iter().all(|f| f.status == AuditStatus::Pass));
+        assert!(report.findings.iter().all(|f| f.remediation.is_none()));
+        // Moduli only matter with group exchange
+        assert!(!report.findings.iter().any(|f| f.check == "Moduli"));
+        assert_eq!((report.score, report.grade), (100, 'A'));
+    }
+}
@@ -0,0 +1,372 @@
+//! Crypto policy profiles
+//!
+//! A profile lists ciphers, MACs, key exchange and host key algorithms in
+//! order of preference. Resolving it against what the installed OpenSSH
+//! supports drops algorithms that would make `sshd -t` fail on older releases
+//! and keeps newer ones, such as post-quantum key exchange, where they exist.
+
+use super::OpensshVersion;
+use crate::error::{FluxError, Result};
+use crate::helpers::system::command_output;
+use serde::{Deserialize, Serialize};
+
+/// Named set of algorithms for `Ciphers`, `MACs`, `KexAlgorithms` and `HostKeyAlgorithms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CryptoProfile {
+    /// AEAD ciphers, encrypt-then-MAC and curve25519 or post-quantum key exchange only
+    Modern,
+    /// Modern plus CTR ciphers, non-ETM SHA-2 MACs and large DH groups
+    #[default]
+    Intermediate,
+    /// Intermediate plus CBC ciphers, SHA-1 and `ssh-rsa` for old clients
+    LegacyCompat,
+    /// FIPS 140 approved algorithms only
+    Fips,
+}
+
+/// The kinds of algorithm a profile sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlgorithmKind {
+    Cipher,
+    Mac,
+    Kex,
+    HostKey,
+}
+
+impl AlgorithmKind {
+    pub const ALL: [Self; 4] = [Self::Cipher, Self::Mac, Self::Kex, Self::HostKey];
+
+    /// Query name for `ssh -Q`
+    fn query(self) -> &'static str {
+        match self {
+            Self::Cipher => "cipher",
+            Self::Mac => "mac",
+            Self::Kex => "kex",
+            Self::HostKey => "key",
+        }
+    }
+
+    /// sshd_config directive taking algorithms of this kind
+    pub fn directive(self) -> &'static str {
+        match self {
+            Self::Cipher => "Ciphers",
+            Self::Mac => "MACs",
+            Self::Kex => "KexAlgorithms",
+            Self::HostKey => "HostKeyAlgorithms",
+        }
+    }
+}
+
+/// First OpenSSH release with each algorithm newer than 6.0
+///
+/// Used when the installed OpenSSH cannot list its algorithms.
+const INTRODUCED: &[(&str, OpensshVersion)] = &[
+    ("aes256-gcm@openssh.com", OpensshVersion::new(6, 2)),
+    ("aes128-gcm@openssh.com", OpensshVersion::new(6, 2)),
+    ("hmac-sha2-512-etm@openssh.com", OpensshVersion::new(6, 2)),
+    ("hmac-sha2-256-etm@openssh.com", OpensshVersion::new(6, 2)),
+    ("umac-128-etm@openssh.com", OpensshVersion::new(6, 2)),
+    ("hmac-sha1-etm@openssh.com", OpensshVersion::new(6, 2)),
+    ("chacha20-poly1305@openssh.com", OpensshVersion::new(6, 5)),
+    ("curve25519-sha256@libssh.org", OpensshVersion::new(6, 5)),
+    ("ssh-ed25519", OpensshVersion::new(6, 5)),
+    ("ssh-ed25519-cert-v01@openssh.com", OpensshVersion::new(6, 5)),
+    ("rsa-sha2-256", OpensshVersion::new(7, 2)),
+    ("rsa-sha2-512", OpensshVersion::new(7, 2)),
+    ("diffie-hellman-group14-sha256", OpensshVersion::new(7, 3)),
+    ("diffie-hellman-group16-sha512", OpensshVersion::new(7, 3)),
+    ("diffie-hellman-group18-sha512", OpensshVersion::new(7, 3)),
+    ("curve25519-sha256", OpensshVersion::new(7, 4)),
+    ("rsa-sha2-256-cert-v01@openssh.com", OpensshVersion::new(7, 8)),
+    ("rsa-sha2-512-cert-v01@openssh.com", OpensshVersion::new(7, 8)),
+    ("sntrup761x25519-sha512@openssh.com", OpensshVersion::new(8, 5)),
+    ("sntrup761x25519-sha512", OpensshVersion::new(9, 9)),
+    ("mlkem768x25519-sha256", OpensshVersion::new(9, 9)),
+];
+
+const MODERN_CIPHERS: &[&str] = &[
+    "chacha20-poly1305@openssh.com",
+    "aes256-gcm@openssh.com",
+    "aes128-gcm@openssh.com",
+];
+const CTR_CIPHERS: &[&str] = &["aes256-ctr", "aes192-ctr", "aes128-ctr"];
+const CBC_CIPHERS: &[&str] = &["aes256-cbc", "aes192-cbc", "aes128-cbc"];
+
+const ETM_MACS: &[&str] = &[
+    "hmac-sha2-512-etm@openssh.com",
+    "hmac-sha2-256-etm@openssh.com",
+    "umac-128-etm@openssh.com",
+];
+const SHA2_MACS: &[&str] = &["hmac-sha2-512", "hmac-sha2-256"];
+const SHA1_MACS: &[&str] = &["hmac-sha1-etm@openssh.com", "hmac-sha1"];
+
+const MODERN_KEX: &[&str] = &[
+    "mlkem768x25519-sha256",
+    "sntrup761x25519-sha512",
+    "sntrup761x25519-sha512@openssh.com",
+    "curve25519-sha256",
+    "curve25519-sha256@libssh.org",
+];
+const DH_KEX: &[&str] = &[
+    "diffie-hellman-group16-sha512",
+    "diffie-hellman-group18-sha512",
+    "diffie-hellman-group-exchange-sha256",
+];
+const ECDH_KEX: &[&str] = &["ecdh-sha2-nistp521", "ecdh-sha2-nistp384", "ecdh-sha2-nistp256"];
+const LEGACY_KEX: &[&str] = &["diffie-hellman-group14-sha256", "diffie-hellman-group14-sha1"];
+
+const MODERN_HOST_KEYS: &[&str] = &[
+    "ssh-ed25519-cert-v01@openssh.com",
+    "ssh-ed25519",
+    "rsa-sha2-512-cert-v01@openssh.com",
+    "rsa-sha2-256-cert-v01@openssh.com",
+    "rsa-sha2-512",
+    "rsa-sha2-256",
+];
+const ECDSA_HOST_KEYS: &[&str] = &["ecdsa-sha2-nistp521", "ecdsa-sha2-nistp384", "ecdsa-sha2-nistp256"];
+const SHA1_HOST_KEYS: &[&str] = &["ssh-rsa-cert-v01@openssh.com", "ssh-rsa"];
+
+impl CryptoProfile {
+    /// Algorithms of `kind` in order of preference, before checking what is installed
+    pub fn algorithms(self, kind: AlgorithmKind) -> Vec<&'static str> {
+        use AlgorithmKind::*;
+        use CryptoProfile::*;
+
+        let parts: &[&[&str]] = match (self, kind) {
+            (Modern, Cipher) => &[MODERN_CIPHERS],
+            (Modern, Mac) => &[ETM_MACS],
+            (Modern, Kex) => &[MODERN_KEX],
+            (Modern, HostKey) => &[MODERN_HOST_KEYS],
+            (Intermediate, Cipher) => &[MODERN_CIPHERS, CTR_CIPHERS],
+            (Intermediate, Mac) => &[ETM_MACS, SHA2_MACS],
+            (Intermediate, Kex) => &[MODERN_KEX, DH_KEX],
+            (Intermediate, HostKey) => &[MODERN_HOST_KEYS, ECDSA_HOST_KEYS],
+            (LegacyCompat, Cipher) => &[MODERN_CIPHERS, CTR_CIPHERS, CBC_CIPHERS],
+            (LegacyCompat, Mac) => &[ETM_MACS, SHA2_MACS, SHA1_MACS],
+            (LegacyCompat, Kex) => &[MODERN_KEX, DH_KEX, ECDH_KEX, LEGACY_KEX],
+            (LegacyCompat, HostKey) => &[MODERN_HOST_KEYS, ECDSA_HOST_KEYS, SHA1_HOST_KEYS],
+            (Fips, Cipher) => &[&MODERN_CIPHERS[1..], CTR_CIPHERS],
+            (Fips, Mac) => &[&ETM_MACS[..2], SHA2_MACS],
+            (Fips, Kex) => &[ECDH_KEX, DH_KEX, &LEGACY_KEX[..1]],
+            (Fips, HostKey) => &[ECDSA_HOST_KEYS, &MODERN_HOST_KEYS[4..]],
+        };
+        parts.concat()
+    }
+}
+
+/// Algorithms the installed OpenSSH lists with `ssh -Q`
+///
+/// sshd has no `-Q`, but it is built from the same sources as ssh and
+/// supports the same algorithms.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SupportedAlgorithms {
+    pub ciphers: Vec<String>,
+    pub macs: Vec<String>,
+    pub kex: Vec<String>,
+    /// Key types, which `ssh -Q key` lists without the `rsa-sha2-*` signature variants
+    pub keys: Vec<String>,
+}
+
+impl SupportedAlgorithms {
+    /// Query the installed OpenSSH, `None` when it cannot list its algorithms
+    pub fn installed() -> Option<Self> {
+        let mut supported = Self::default();
+        for kind in AlgorithmKind::ALL {
+            let output = command_output("ssh", &["-Q", kind.query()]).ok()?;
+            let names: Vec<String> = output.stdout.split_whitespace().map(str::to_string).collect();
+            if !output.success || names.is_empty() {
+                return None;
+            }
+            *supported.get_mut(kind) = names;
+        }
+        Some(supported)
+    }
+
+    fn get(&self, kind: AlgorithmKind) -> &[String] {
+        match kind {
+            AlgorithmKind::Cipher => &self.ciphers,
+            AlgorithmKind::Mac => &self.macs,
+            AlgorithmKind::Kex => &self.kex,
+            AlgorithmKind::HostKey => &self.keys,
+        }
+    }
+
+    fn get_mut(&mut self, kind: AlgorithmKind) -> &mut Vec<String> {
+        match kind {
+            AlgorithmKind::Cipher => &mut self.ciphers,
+            AlgorithmKind::Mac => &mut self.macs,
+            AlgorithmKind::Kex => &mut self.kex,
+            AlgorithmKind::HostKey => &mut self.keys,
+        }
+    }
+
+    pub fn supports(&self, kind: AlgorithmKind, name: &str) -> bool {
+        // rsa-sha2-* sign with ssh-rsa keys
+        let name = match name {
+            "rsa-sha2-256" | "rsa-sha2-512" if kind == AlgorithmKind::HostKey => "ssh-rsa",
+            "rsa-sha2-256-cert-v01@openssh.com" | "rsa-sha2-512-cert-v01@openssh.com"
+                if kind == AlgorithmKind::HostKey =>
+            {
+                "ssh-rsa-cert-v01@openssh.com"
+            }
+            name => name,
+        };
+        self.get(kind).iter().any(|supported| supported == name)
+    }
+}
+
+/// Algorithm lists ready to be written to sshd_config
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CryptoPolicy {
+    pub ciphers: Vec<String>,
+    pub macs: Vec<String>,
+    pub kex_algorithms: Vec<String>,
+    pub host_key_algorithms: Vec<String>,
+}
+
+impl CryptoPolicy {
+    /// A profile's preferences, to be narrowed down with [`CryptoPolicy::resolve`]
+    pub fn from_profile(profile: CryptoProfile) -> Self {
+        let list = |kind| profile.algorithms(kind).into_iter().map(str::to_string).collect();
+        Self {
+            ciphers: list(AlgorithmKind::Cipher),
+            macs: list(AlgorithmKind::Mac),
+            kex_algorithms: list(AlgorithmKind::Kex),
+            host_key_algorithms: list(AlgorithmKind::HostKey),
+        }
+    }
+
+    pub fn get(&self, kind: AlgorithmKind) -> &[String] {
+        match kind {
+            AlgorithmKind::Cipher => &self.ciphers,
+            AlgorithmKind::Mac => &self.macs,
+            AlgorithmKind::Kex => &self.kex_algorithms,
+            AlgorithmKind::HostKey => &self.host_key_algorithms,
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: AlgorithmKind) -> &mut Vec<String> {
+        match kind {
+            AlgorithmKind::Cipher => &mut self.ciphers,
+            AlgorithmKind::Mac => &mut self.macs,
+            AlgorithmKind::Kex => &mut self.kex_algorithms,
+            AlgorithmKind::HostKey => &mut self.host_key_algorithms,
+        }
+    }
+
+    /// Keep the algorithms the installed OpenSSH supports, in order of preference
+    ///
+    /// `supported` decides when known; otherwise algorithms newer than
+    /// `version` are dropped. Fails when nothing of a kind is left, since sshd
+    /// would reject the empty list.
+    pub fn resolve(
+        &self,
+        version: Option<OpensshVersion>,
+        supported: Option<&SupportedAlgorithms>,
+    ) -> Result<Self> {
+        let mut resolved = Self::default();
+
+        for kind in AlgorithmKind::ALL {
+            let available: Vec<String> = self
+                .get(kind)
+                .iter()
+                .filter(|name| supported.is_none_or(|s| s.supports(kind, name)))
+                .filter(|name| match (version, introduced(name)) {
+                    (Some(version), Some(introduced)) => version >= introduced,
+                    _ => true,
+                })
+                .cloned()
+                .collect();
+
+            if available.is_empty() && !self.get(kind).is_empty() {
+                return Err(FluxError::unsupported(format!(
+                    "The installed OpenSSH supports none of the {} {}",
+                    kind.directive(),
+                    self.get(kind).join(",")
+                )));
+            }
+            *resolved.get_mut(kind) = available;
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// First OpenSSH release with `name`, if known
+fn introduced(name: &str) -> Option<OpensshVersion> {
+    INTRODUCED
+        .iter()
+        .find(|(algorithm, _)| *algorithm == name)
+        .map(|(_, version)| *version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ssh -Q` of OpenSSH 7.4 on RHEL 7, abridged
+    fn openssh_7_4() -> SupportedAlgorithms {
+        let list = |names: &str| names.split_whitespace().map(str::to_string).collect();
+        SupportedAlgorithms {
+            ciphers: list("3des-cbc aes128-cbc aes192-cbc aes256-cbc aes128-ctr aes192-ctr aes256-ctr aes128-gcm@openssh.com aes256-gcm@openssh.com chacha20-poly1305@openssh.com"),
+            macs: list("hmac-sha1 hmac-sha2-256 hmac-sha2-512 umac-128@openssh.com hmac-sha1-etm@openssh.com hmac-sha2-256-etm@openssh.com hmac-sha2-512-etm@openssh.com umac-128-etm@openssh.com"),
+            kex: list("diffie-hellman-group1-sha1 diffie-hellman-group14-sha1 diffie-hellman-group14-sha256 diffie-hellman-group16-sha512 diffie-hellman-group18-sha512 diffie-hellman-group-exchange-sha256 ecdh-sha2-nistp256 ecdh-sha2-nistp384 ecdh-sha2-nistp521 curve25519-sha256 curve25519-sha256@libssh.org"),
+            keys: list("ssh-ed25519 ssh-ed25519-cert-v01@openssh.com ssh-rsa ssh-dss ecdsa-sha2-nistp256 ecdsa-sha2-nistp384 ecdsa-sha2-nistp521 ssh-rsa-cert-v01@openssh.com"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_against_installed_algorithms() {
+        let policy = CryptoPolicy::from_profile(CryptoProfile::Modern)
+            .resolve(Some(OpensshVersion::new(7, 4)), Some(&openssh_7_4()))
+            .unwrap();
+
+        // No post-quantum key exchange before 8.5
+        assert_eq!(policy.kex_algorithms, vec!["curve25519-sha256", "curve25519-sha256@libssh.org"]);
+        assert_eq!(policy.ciphers, MODERN_CIPHERS);
+        // rsa-sha2-* count as supported through ssh-rsa; cert variants need 7.8
+        assert_eq!(
+            policy.host_key_algorithms,
+            vec!["ssh-ed25519-cert-v01@openssh.com", "ssh-ed25519", "rsa-sha2-512", "rsa-sha2-256"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_by_version() {
+        let profile = CryptoPolicy::from_profile(CryptoProfile::Intermediate);
+
+        let new = profile.resolve(Some(OpensshVersion::new(9, 9)), None).unwrap();
+        assert_eq!(new.kex_algorithms[0], "mlkem768x25519-sha256");
+
+        let old = profile.resolve(Some(OpensshVersion::new(7, 2)), None).unwrap();
+        assert_eq!(old.kex_algorithms, vec!["curve25519-sha256@libssh.org", "diffie-hellman-group-exchange-sha256"]);
+
+        // Unknown version: trust the profile
+        assert_eq!(profile.resolve(None, None).unwrap(), profile);
+    }
+
+    #[test]
+    fn test_resolve_fails_when_nothing_is_left() {
+        let mut policy = CryptoPolicy::from_profile(CryptoProfile::Fips);
+        policy.kex_algorithms = vec!["sntrup761x25519-sha512@openssh.com".to_string()];
+
+        let err = policy.resolve(None, Some(&openssh_7_4())).unwrap_err();
+        assert!(err.to_string().contains("KexAlgorithms"));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let fips = CryptoPolicy::from_profile(CryptoProfile::Fips);
+        assert!(!fips.ciphers.iter().any(|c| c.starts_with("chacha20")));
+        assert!(!fips.kex_algorithms.iter().any(|k| k.contains("25519")));
+        assert!(!fips.host_key_algorithms.iter().any(|k| k.contains("ed25519")));
+
+        let legacy = CryptoPolicy::from_profile(CryptoProfile::LegacyCompat);
+        assert!(legacy.macs.contains(&"hmac-sha1".to_string()));
+        assert!(legacy.host_key_algorithms.contains(&"ssh-rsa".to_string()));
+
+        let profile: CryptoProfile = serde_json::from_str("\"legacy-compat\"").unwrap();
+        assert_eq!(profile, CryptoProfile::LegacyCompat);
+    }
+}
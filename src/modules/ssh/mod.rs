@@ -0,0 +1,1264 @@
+// src/modules/ssh/mod.rs
+// SSH hardening and configuration module
+
+mod audit;
+mod crypto;
+mod openssh;
+mod safe_apply;
+mod sshd_config;
+
+pub use audit::{AuditReport, AuditStatus, EffectiveConfig, Finding, HostKey};
+pub use crypto::{AlgorithmKind, CryptoPolicy, CryptoProfile, SupportedAlgorithms};
+pub use openssh::OpensshVersion;
+pub use safe_apply::{confirm_change, revert_change, PendingChange, SavedFile};
+use safe_apply::{restart_safely, restore, Snapshot, DEFAULT_CONFIRM_TIMEOUT};
+pub use sshd_config::{Directive, Scope, SshdConfig};
+use sshd_config::{directive_name, is_multi_value};
+
+use crate::config::Config;
+use crate::error::{FluxError, Result};
+use crate::helpers::{
+    facts,
+    file_ops::{backup_file, create_dir_all, safe_write_file},
+    logging::{log_error, log_info, log_success, log_warn},
+    output,
+    paths::{self, resolve},
+    schema::{self, ConfigSchema, SectionSchema},
+    system::{check_command, command_output, execute_command, restart_service},
+    template,
+    user_input::{prompt_with_default, prompt_yes_no, select_from_menu},
+};
+use crate::modules::{parse_spec, Drift, Module, ModuleBase, ModuleInfo};
+use crate::outln;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+const SSH_CONFIG_PATH: &str = "/etc/ssh/sshd_config";
+const SSH_CONFIG_DIR: &str = "/etc/ssh/sshd_config.d";
+const HARDENING_DROP_IN: &str = "/etc/ssh/sshd_config.d/50-flux-hardening.conf";
+const DEFAULT_BANNER_PATH: &str = "/etc/ssh/banner.txt";
+const FAIL2BAN_JAIL_PATH: &str = "/etc/fail2ban/jail.d/sshd.conf";
+
+/// Directives shown by `--status`
+const STATUS_DIRECTIVES: &[&str] = &[
+    "Port",
+    "PermitRootLogin",
+    "PasswordAuthentication",
+    "PubkeyAuthentication",
+];
+
+/// Desired SSH server state, read from `[modules.ssh]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SshSpec {
+    pub port: u16,
+    pub listen_addresses: Vec<String>,
+    pub disable_root_login: bool,
+    pub disable_password_auth: bool,
+    pub permit_empty_passwords: bool,
+    pub max_auth_tries: u32,
+    pub max_sessions: u32,
+    pub login_grace_time: u32,
+    pub client_alive_interval: u32,
+    pub client_alive_count_max: u32,
+    pub allowed_users: Vec<String>,
+    pub allowed_groups: Vec<String>,
+    pub denied_users: Vec<String>,
+    pub denied_groups: Vec<String>,
+    /// Algorithms used where the lists below are empty
+    pub crypto_profile: CryptoProfile,
+    pub ciphers: Vec<String>,
+    pub macs: Vec<String>,
+    pub kex_algorithms: Vec<String>,
+    pub host_key_algorithms: Vec<String>,
+    pub x11_forwarding: bool,
+    pub tcp_forwarding: bool,
+    pub agent_forwarding: bool,
+    pub permit_tunnel: bool,
+    pub banner_file: String,
+    pub log_level: String,
+    pub syslog_facility: String,
+    /// Configure fail2ban for sshd
+    pub fail2ban: bool,
+    /// Restart sshd after applying
+    pub restart: bool,
+    /// Test the changed configuration on a second sshd and revert it unless confirmed
+    pub safe_apply: bool,
+    /// Minutes to wait for `flux ssh confirm` before reverting a safe apply
+    pub confirm_timeout: u32,
+}
+
+impl Default for SshSpec {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+
+        Self {
+            port: 22,
+            listen_addresses: strings(&["0.0.0.0", "::"]),
+            disable_root_login: true,
+            disable_password_auth: true,
+            permit_empty_passwords: false,
+            max_auth_tries: 3,
+            max_sessions: 10,
+            login_grace_time: 30,
+            client_alive_interval: 300,
+            client_alive_count_max: 2,
+            allowed_users: Vec::new(),
+            allowed_groups: Vec::new(),
+            denied_users: Vec::new(),
+            denied_groups: Vec::new(),
+            crypto_profile: CryptoProfile::default(),
+            ciphers: Vec::new(),
+            macs: Vec::new(),
+            kex_algorithms: Vec::new(),
+            host_key_algorithms: Vec::new(),
+            x11_forwarding: false,
+            tcp_forwarding: false,
+            agent_forwarding: false,
+            permit_tunnel: false,
+            banner_file: DEFAULT_BANNER_PATH.to_string(),
+            log_level: "VERBOSE".to_string(),
+            syslog_facility: "AUTH".to_string(),
+            fail2ban: false,
+            restart: true,
+            safe_apply: false,
+            confirm_timeout: DEFAULT_CONFIRM_TIMEOUT,
+        }
+    }
+}
+
+impl ConfigSchema for SshSpec {
+    fn schema() -> serde_json::Value {
+        use schema::*;
+
+        let names = |description| array(description, string(""));
+        object(
+            "SSH server settings applied by `flux apply`",
+            vec![
+                ("port", port("Port sshd listens on")),
+                ("listen_addresses", names("Addresses sshd listens on")),
+                ("disable_root_login", boolean("Set PermitRootLogin no")),
+                ("disable_password_auth", boolean("Only allow public key authentication")),
+                ("permit_empty_passwords", boolean("Allow accounts with empty passwords to log in")),
+                ("max_auth_tries", unsigned("MaxAuthTries")),
+                ("max_sessions", unsigned("MaxSessions")),
+                ("login_grace_time", unsigned("LoginGraceTime in seconds")),
+                ("client_alive_interval", unsigned("ClientAliveInterval in seconds")),
+                ("client_alive_count_max", unsigned("ClientAliveCountMax")),
+                ("allowed_users", names("AllowUsers; empty allows every user")),
+                ("allowed_groups", names("AllowGroups; empty allows every group")),
+                ("denied_users", names("DenyUsers")),
+                ("denied_groups", names("DenyGroups")),
+                (
+                    "crypto_profile",
+                    one_of(
+                        "Algorithms for the lists left empty, narrowed down to what the installed OpenSSH supports",
+                        &["modern", "intermediate", "legacy-compat", "fips"],
+                    ),
+                ),
+                ("ciphers", names("Ciphers, in order of preference; empty uses crypto_profile")),
+                ("macs", names("MACs, in order of preference; empty uses crypto_profile")),
+                ("kex_algorithms", names("KexAlgorithms, in order of preference; empty uses crypto_profile")),
+                (
+                    "host_key_algorithms",
+                    names("HostKeyAlgorithms, in order of preference; empty uses crypto_profile"),
+                ),
+                ("x11_forwarding", boolean("X11Forwarding")),
+                ("tcp_forwarding", boolean("AllowTcpForwarding")),
+                ("agent_forwarding", boolean("AllowAgentForwarding")),
+                ("permit_tunnel", boolean("PermitTunnel")),
+                ("banner_file", string("Pre-login banner; written when missing")),
+                (
+                    "log_level",
+                    one_of(
+                        "sshd LogLevel",
+                        &["QUIET", "FATAL", "ERROR", "INFO", "VERBOSE", "DEBUG", "DEBUG1", "DEBUG2", "DEBUG3"],
+                    ),
+                ),
+                (
+                    "syslog_facility",
+                    one_of(
+                        "sshd SyslogFacility",
+                        &[
+                            "DAEMON", "USER", "AUTH", "AUTHPRIV", "LOCAL0", "LOCAL1", "LOCAL2", "LOCAL3",
+                            "LOCAL4", "LOCAL5", "LOCAL6", "LOCAL7",
+                        ],
+                    ),
+                ),
+                ("fail2ban", boolean("Configure fail2ban for sshd")),
+                ("restart", boolean("Restart sshd after applying")),
+                (
+                    "safe_apply",
+                    boolean("Test the changed configuration on a second sshd and revert it unless `flux ssh confirm` is run"),
+                ),
+                ("confirm_timeout", unsigned("Minutes to wait for `flux ssh confirm` before reverting")),
+            ],
+        )
+    }
+}
+
+impl SshSpec {
+    /// Render the hardening drop-in for this spec
+    pub fn render(&self) -> Result<String> {
+        template::render("ssh/50-flux-hardening.conf", &self.template_vars()?)
+    }
+
+    /// Variables of the `ssh/50-flux-hardening.conf` template: the spec with
+    /// its algorithms resolved, plus `generated`
+    fn template_vars(&self) -> Result<serde_json::Value> {
+        let policy = self.crypto_policy(OpensshVersion::installed(), SupportedAlgorithms::installed().as_ref())?;
+
+        let mut vars = serde_json::json!(self);
+        if let serde_json::Value::Object(lists) = serde_json::json!(policy) {
+            for (key, algorithms) in lists {
+                vars[key] = algorithms;
+            }
+        }
+        vars["generated"] = template::generated_by().into();
+        Ok(vars)
+    }
+
+    /// The algorithms to configure for the installed OpenSSH
+    ///
+    /// Explicit lists replace those of the profile. Algorithms the installed
+    /// OpenSSH lacks are left out, with a warning for explicit ones.
+    fn crypto_policy(
+        &self,
+        version: Option<OpensshVersion>,
+        supported: Option<&SupportedAlgorithms>,
+    ) -> Result<CryptoPolicy> {
+        let mut wanted = CryptoPolicy::from_profile(self.crypto_profile);
+        let explicit = CryptoPolicy {
+            ciphers: self.ciphers.clone(),
+            macs: self.macs.clone(),
+            kex_algorithms: self.kex_algorithms.clone(),
+            host_key_algorithms: self.host_key_algorithms.clone(),
+        };
+        for kind in AlgorithmKind::ALL {
+            if !explicit.get(kind).is_empty() {
+                *wanted.get_mut(kind) = explicit.get(kind).to_vec();
+            }
+        }
+
+        let policy = wanted.resolve(version, supported)?;
+        for kind in AlgorithmKind::ALL {
+            for name in explicit.get(kind) {
+                if !policy.get(kind).contains(name) {
+                    log_warn(format!(
+                        "{} {} is not supported by the installed OpenSSH, leaving it out",
+                        kind.directive(),
+                        name
+                    ));
+                }
+            }
+        }
+
+        Ok(policy)
+    }
+}
+
+/// Diff a live sshd configuration against the expected one, directive by directive
+///
+/// sshd uses the first occurrence of a directive, so only the first value is
+/// compared except for directives that accumulate.
+fn diff_sshd_config(expected: &SshdConfig, live: &SshdConfig) -> Vec<Drift> {
+    let mut seen = HashSet::new();
+    let mut drift = Vec::new();
+
+    for directive in expected.directives() {
+        let keyword = directive.keyword.to_ascii_lowercase();
+        if directive.scope != Scope::Global || !seen.insert(keyword.clone()) {
+            continue;
+        }
+
+        let want = expected.values(&keyword);
+        let have = live.values(&keyword);
+        let name = directive_name(&keyword);
+
+        if is_multi_value(&keyword) {
+            let mut want_sorted = want.clone();
+            let mut have_sorted = have.clone();
+            want_sorted.sort();
+            have_sorted.sort();
+            if want_sorted != have_sorted {
+                let actual = if have.is_empty() { None } else { Some(have.join(", ")) };
+                drift.push(Drift::new(name, want.join(", "), actual));
+            }
+        } else {
+            match have.first() {
+                Some(actual) if actual.eq_ignore_ascii_case(&want[0]) => {}
+                actual => drift.push(Drift::new(name, want[0].clone(), actual.cloned())),
+            }
+        }
+    }
+
+    drift
+}
+
+/// Directives outside `drop_in` that override or add to its settings
+///
+/// sshd keeps the first value it reads for most directives, so those read
+/// before the drop-in win over it. Directives that accumulate, such as `Port`,
+/// add to the drop-in's wherever they are.
+fn shadowing_directives(config: &SshdConfig, drop_in: &Path) -> Vec<Directive> {
+    let directives: Vec<Directive> = config
+        .directives()
+        .into_iter()
+        .filter(|d| d.scope == Scope::Global)
+        .collect();
+    let Some(start) = directives.iter().position(|d| d.file == drop_in) else {
+        return Vec::new();
+    };
+    let ours = |keyword: &str| {
+        directives[start..]
+            .iter()
+            .find(|d| d.file == drop_in && d.keyword.eq_ignore_ascii_case(keyword))
+    };
+
+    directives
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.file != drop_in)
+        .filter(|(index, d)| match ours(&d.keyword) {
+            None => false,
+            Some(_) if is_multi_value(&d.keyword) => true,
+            Some(own) => *index < start && !d.value.eq_ignore_ascii_case(&own.value),
+        })
+        .map(|(_, d)| d.clone())
+        .collect()
+}
+
+/// Set every global directive of `settings` in `config`
+fn set_directives(config: &mut SshdConfig, settings: &SshdConfig) {
+    let mut seen = HashSet::new();
+    for directive in settings.directives() {
+        if directive.scope == Scope::Global && seen.insert(directive.keyword.to_ascii_lowercase()) {
+            let values = settings.values(&directive.keyword);
+            config.set(&directive.keyword, &values.iter().map(String::as_str).collect::<Vec<_>>());
+        }
+    }
+}
+
+/// Save the SSH configuration before a safe apply changes it
+///
+/// `None` unless `safe` is set. Under `--root` there is no running sshd to
+/// test against, so the change is applied as usual. An error before the
+/// snapshot reaches `restart_safely` restores the saved files.
+fn safe_snapshot(safe: bool) -> Result<Option<Snapshot>> {
+    if !safe {
+        return Ok(None);
+    }
+    if paths::root().is_some() {
+        log_warn("Safe apply tests the running sshd and is skipped with --root");
+        return Ok(None);
+    }
+    Snapshot::take().map(Some)
+}
+
+/// Name of the sshd service unit
+fn ssh_service() -> &'static str {
+    if check_command("systemctl").is_ok() {
+        "sshd"
+    } else {
+        "ssh"
+    }
+}
+
+pub struct SshModule {
+    base: ModuleBase,
+}
+
+impl SshModule {
+    pub fn new() -> Self {
+        let info = ModuleInfo {
+            name: "ssh".to_string(),
+            description: "SSH server hardening and configuration".to_string(),
+            version: "1.0.0".to_string(),
+            author: "Flux Contributors".to_string(),
+            tags: vec!["security".to_string(), "network".to_string()],
+            requires_root: true,
+            supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: vec!["update".to_string(), "user".to_string()],
+        };
+        Self {
+            base: ModuleBase { info },
+        }
+    }
+
+    /// Apply SSH hardening configuration, returning whether anything changed
+    async fn harden_ssh(&self, port: Option<u16>) -> Result<bool> {
+        let spec = SshSpec {
+            port: port.unwrap_or(22),
+            ..SshSpec::default()
+        };
+        let changed = self.write_hardened_config(&spec, true).await?;
+
+        if changed {
+            log_info("SSH hardening complete. Remember to restart SSH service.");
+        } else {
+            log_info("SSH hardening already in place");
+        }
+        Ok(changed)
+    }
+
+    /// Write the hardening drop-in rendered from a spec
+    ///
+    /// The drop-in only takes effect when sshd_config includes it and nothing
+    /// read before it sets the same directives. A missing `Include` is added
+    /// at the top of sshd_config. Directives overriding the drop-in are
+    /// commented out, after asking when `interactive`. An OpenSSH too old for
+    /// `Include` gets the settings written into sshd_config itself.
+    ///
+    /// Returns whether any configuration file or the banner changed.
+    async fn write_hardened_config(&self, spec: &SshSpec, interactive: bool) -> Result<bool> {
+        log_info("Applying SSH hardening configuration");
+
+        let settings = spec.render()?;
+        let mut config = SshdConfig::load(SSH_CONFIG_PATH)?;
+
+        if !config.includes(HARDENING_DROP_IN) {
+            match OpensshVersion::installed() {
+                Some(version) if !version.supports_include() => {
+                    log_warn(format!(
+                        "OpenSSH {} does not support Include, hardening {} in place",
+                        version, SSH_CONFIG_PATH
+                    ));
+                    set_directives(&mut config, &SshdConfig::parse(HARDENING_DROP_IN, &settings));
+                }
+                _ => {
+                    log_info(format!("Including {}/*.conf from {}", SSH_CONFIG_DIR, SSH_CONFIG_PATH));
+                    config.prepend("Include", &format!("{}/*.conf", SSH_CONFIG_DIR));
+                }
+            }
+        }
+
+        if config.includes(HARDENING_DROP_IN) {
+            config.put_file(HARDENING_DROP_IN, &settings);
+            create_dir_all(SSH_CONFIG_DIR)?;
+
+            let shadowing = shadowing_directives(&config, Path::new(HARDENING_DROP_IN));
+            for directive in &shadowing {
+                log_warn(format!(
+                    "{} in {}:{} overrides the hardening drop-in",
+                    directive,
+                    directive.file.display(),
+                    directive.line
+                ));
+            }
+
+            let fix = !shadowing.is_empty()
+                && (!interactive
+                    || prompt_yes_no("Comment out the directives overriding the hardening?", true)?);
+            if fix {
+                for directive in &shadowing {
+                    config.comment_out(directive);
+                }
+            } else if !shadowing.is_empty() {
+                log_warn("sshd keeps using these values instead of the hardening drop-in");
+            }
+        }
+
+        // Create banner
+        let mut changed = false;
+        if spec.banner_file == DEFAULT_BANNER_PATH {
+            changed |= self.create_ssh_banner().await?;
+        }
+
+        // Write and validate the configuration, backing up the files it replaces
+        if self.save_validated(&mut config).await? {
+            log_success("SSH hardening configuration applied");
+            changed = true;
+        } else {
+            self.validate_ssh_config().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Converge SSH configuration to the given spec without prompting
+    async fn apply_spec(&self, spec: &SshSpec) -> Result<()> {
+        let snapshot = safe_snapshot(spec.safe_apply && spec.restart)?;
+        let changed = self.write_hardened_config(spec, false).await?;
+
+        if spec.fail2ban {
+            self.setup_fail2ban().await?;
+        }
+
+        if let Some(snapshot) = snapshot {
+            restart_safely(snapshot, spec.confirm_timeout)?;
+        } else if spec.restart && changed {
+            restart_service(ssh_service())?;
+        }
+
+        log_success("SSH configuration converged");
+        Ok(())
+    }
+
+    /// Diff the live SSH configuration against the given spec
+    async fn plan_spec(&self, spec: &SshSpec) -> Result<Vec<Drift>> {
+        let live = SshdConfig::load(SSH_CONFIG_PATH)?;
+        let expected = SshdConfig::parse(HARDENING_DROP_IN, &spec.render()?);
+
+        let mut drift = diff_sshd_config(&expected, &live);
+
+        if spec.fail2ban && !resolve(FAIL2BAN_JAIL_PATH).exists() {
+            drift.push(Drift::missing(FAIL2BAN_JAIL_PATH, "fail2ban sshd jail"));
+        }
+
+        Ok(drift)
+    }
+
+    /// Create SSH banner, returning whether it changed
+    async fn create_ssh_banner(&self) -> Result<bool> {
+        let banner_path = DEFAULT_BANNER_PATH;
+
+        let banner_content = r#"
+********************************************************************************
+*                             AUTHORIZED ACCESS ONLY                           *
+********************************************************************************
+*                                                                              *
+*  This system is for authorized users only. All activity is monitored and    *
+*  logged. Unauthorized access attempts will be prosecuted to the fullest     *
+*  extent of the law.                                                          *
+*                                                                              *
+*  By accessing this system, you consent to monitoring and recording.         *
+*                                                                              *
+********************************************************************************
+"#;
+
+        let changed = safe_write_file(banner_path, banner_content, true)?;
+        if changed {
+            log_success("SSH banner created");
+        }
+        Ok(changed)
+    }
+
+    /// Validate SSH configuration
+    async fn validate_ssh_config(&self) -> Result<()> {
+        log_info("Validating SSH configuration");
+
+        let config = resolve(SSH_CONFIG_PATH);
+        let output = command_output("sshd", &["-t", "-f", &config.to_string_lossy()])?;
+
+        if output.success {
+            log_success("SSH configuration is valid");
+            Ok(())
+        } else {
+            Err(FluxError::command_failed(format!(
+                "SSH configuration validation failed: {}",
+                output.stderr
+            )))
+        }
+    }
+
+    /// Save the edited configuration and check it with `sshd -t`
+    ///
+    /// Puts the previous files back when sshd rejects the result, so a bad
+    /// edit never stays on disk for the next restart. Returns whether any
+    /// file changed.
+    async fn save_validated(&self, config: &mut SshdConfig) -> Result<bool> {
+        let previous = config
+            .modified_files()
+            .map(SavedFile::read)
+            .collect::<Result<Vec<_>>>()?;
+        if !config.save()? {
+            return Ok(false);
+        }
+
+        if let Err(e) = self.validate_ssh_config().await {
+            log_warn("Restoring the previous SSH configuration");
+            restore(&previous)?;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Change SSH port
+    async fn change_port(&self, new_port: u16) -> Result<()> {
+        if let Err(e) = crate::helpers::validation::validate_port(&new_port.to_string()) {
+            return Err(e);
+        }
+
+        log_info(&format!("Changing SSH port to {}", new_port));
+
+        // Replace the Port sshd uses, wherever it is set
+        let mut config = SshdConfig::load(SSH_CONFIG_PATH)?;
+        config.set("Port", &[&new_port.to_string()]);
+
+        // Write and validate updated config, backing up the previous one
+        if !self.save_validated(&mut config).await? {
+            log_info(&format!("SSH port already is {}", new_port));
+            return Ok(());
+        }
+
+        log_success(&format!("SSH port changed to {}", new_port));
+        log_warn(&format!(
+            "Update your firewall rules to allow port {}",
+            new_port
+        ));
+        log_warn("Restart SSH service to apply changes");
+
+        Ok(())
+    }
+
+    /// Disable password authentication, returning whether the config changed
+    async fn disable_password_auth(&self) -> Result<bool> {
+        log_info("Disabling password authentication");
+
+        // Global settings only; Match blocks may still allow passwords deliberately
+        let mut config = SshdConfig::load(SSH_CONFIG_PATH)?;
+        for keyword in [
+            "PasswordAuthentication",
+            "ChallengeResponseAuthentication",
+            "PermitEmptyPasswords",
+        ] {
+            config.set(keyword, &["no"]);
+        }
+
+        // Write and validate updated config, backing up the previous one
+        if !self.save_validated(&mut config).await? {
+            log_info("Password authentication already disabled");
+            return Ok(false);
+        }
+
+        log_success("Password authentication disabled");
+        log_warn("Ensure you have SSH key access configured before restarting SSH!");
+
+        Ok(true)
+    }
+
+    /// Print the value sshd uses for a directive and where it is set
+    ///
+    /// `Match` blocks overriding it for some connections are listed as well.
+    async fn get_directive(&self, keyword: &str) -> Result<()> {
+        let config = SshdConfig::load(SSH_CONFIG_PATH)?;
+
+        let mut directives = config.get_all(keyword);
+        if !is_multi_value(keyword) {
+            directives.truncate(1);
+        }
+        directives.extend(config.directives().into_iter().filter(|d| {
+            d.scope != Scope::Global && d.keyword.eq_ignore_ascii_case(keyword)
+        }));
+
+        if output::is_json() {
+            return output::emit(&directives);
+        }
+
+        if directives.is_empty() {
+            log_info(format!("{} is not set, sshd uses its default", directive_name(keyword)));
+        }
+        for directive in &directives {
+            let location = format!("{}:{}", directive.file.display(), directive.line);
+            match &directive.scope {
+                Scope::Global => outln!("{}  ({})", directive, location),
+                Scope::Match(criteria) => outln!("{}  (Match {}, {})", directive, criteria, location),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set a global directive wherever sshd currently reads it from
+    async fn set_directive(&self, keyword: &str, value: &str) -> Result<()> {
+        if keyword.is_empty()
+            || !keyword.chars().all(|c| c.is_ascii_alphanumeric())
+            || keyword.eq_ignore_ascii_case("match")
+        {
+            return Err(FluxError::validation(format!("Invalid sshd directive: '{}'", keyword)));
+        }
+        if value.is_empty() {
+            return Err(FluxError::validation(format!(
+                "No value given for {}",
+                directive_name(keyword)
+            )));
+        }
+
+        let mut config = SshdConfig::load(SSH_CONFIG_PATH)?;
+        config.set(keyword, &[value]);
+
+        // Write and validate updated config, backing up the previous one
+        if !self.save_validated(&mut config).await? {
+            log_info(format!("{} already is {}", directive_name(keyword), value));
+            return Ok(());
+        }
+
+        log_success(format!("{} set to {}", directive_name(keyword), value));
+        log_warn("Restart SSH service to apply changes");
+
+        Ok(())
+    }
+
+    /// Grade the configuration sshd actually uses against the audit rules
+    async fn audit(&self) -> Result<()> {
+        let report = audit::run()?;
+
+        if output::is_json() {
+            return output::emit(&report);
+        }
+        report.print();
+        Ok(())
+    }
+
+    /// Setup fail2ban for SSH protection
+    async fn setup_fail2ban(&self) -> Result<()> {
+        log_info("Setting up fail2ban for SSH protection");
+
+        // Check if fail2ban is installed
+        if check_command("fail2ban-client").is_err() {
+            log_info("Installing fail2ban");
+            let distro = facts::get().os.distribution.clone();
+
+            if distro.is_debian_based() {
+                execute_command("apt-get", &["update"])?;
+                execute_command("apt-get", &["install", "-y", "fail2ban"])?;
+            } else if distro.is_redhat_based() {
+                execute_command("yum", &["install", "-y", "fail2ban"])?;
+            } else {
+                return Err(FluxError::Module(
+                    "Unsupported distribution for fail2ban installation".to_string(),
+                ));
+            }
+            facts::invalidate();
+        }
+
+        // Create fail2ban jail for SSH
+        let jail_config = r#"
+[sshd]
+enabled = true
+port = ssh
+filter = sshd
+logpath = /var/log/auth.log
+maxretry = 3
+findtime = 600
+bantime = 3600
+banaction = iptables-multiport
+
+[sshd-ddos]
+enabled = true
+port = ssh
+filter = sshd-ddos
+logpath = /var/log/auth.log
+maxretry = 2
+findtime = 600
+bantime = 86400
+"#;
+
+        // Restart fail2ban only when the jail changed
+        if safe_write_file(FAIL2BAN_JAIL_PATH, jail_config, true)? {
+            restart_service("fail2ban")?;
+            log_success("fail2ban configured for SSH protection");
+        } else {
+            log_info("fail2ban jail for SSH already configured");
+        }
+        Ok(())
+    }
+
+    /// Generate SSH host keys
+    async fn generate_host_keys(&self) -> Result<()> {
+        log_info("Generating SSH host keys");
+
+        let key_types = vec![
+            ("ed25519", "/etc/ssh/ssh_host_ed25519_key"),
+            ("rsa", "/etc/ssh/ssh_host_rsa_key"),
+            ("ecdsa", "/etc/ssh/ssh_host_ecdsa_key"),
+        ];
+
+        for (key_type, key_path) in key_types {
+            log_info(&format!("Generating {} key", key_type));
+
+            // Backup existing key
+            let key_path = resolve(key_path);
+            if key_path.exists() {
+                backup_file(&key_path)?;
+            }
+
+            // Generate new key (no passphrase)
+            let key_file = key_path.to_string_lossy();
+            let mut args = vec!["-t", key_type, "-f", &key_file, "-N", ""];
+
+            if key_type == "rsa" {
+                args.extend(["-b", "4096"]);
+            } else if key_type == "ecdsa" {
+                args.extend(["-b", "521"]);
+            }
+
+            match execute_command("ssh-keygen", &args) {
+                Ok(_) => log_success(&format!("{} host key generated", key_type)),
+                Err(_) => log_warn(&format!("Failed to generate {} host key", key_type)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Show SSH status and information
+    async fn show_status(&self) -> Result<()> {
+        log_info("SSH Server Status:");
+
+        // Check if SSH is running
+        if let Ok(output) = command_output("systemctl", &["status", ssh_service()]) {
+            if output.success {
+                outln!("\n{}", output.stdout);
+            }
+        }
+
+        // Show current SSH configuration
+        if let Ok(config) = SshdConfig::load(SSH_CONFIG_PATH) {
+            outln!("\nKey SSH Configuration Settings:");
+            outln!("{}", "=".repeat(70));
+
+            for keyword in STATUS_DIRECTIVES {
+                for value in config.values(keyword) {
+                    outln!("{} {}", keyword, value);
+                }
+            }
+        }
+
+        if let Ok(Some(pending)) = PendingChange::load() {
+            log_warn(format!(
+                "Unconfirmed change testing on port {}, reverted at {} unless 'flux ssh confirm' is run",
+                pending.test_port, pending.deadline
+            ));
+        }
+
+        // Show active connections
+        outln!("\nActive SSH Connections:");
+        outln!("{}", "=".repeat(70));
+
+        let filter = ["-tn", "state", "established", "'( dport = :ssh or sport = :ssh )'"];
+        if let Ok(output) = command_output("ss", &filter) {
+            outln!("{}", output.stdout);
+        }
+
+        Ok(())
+    }
+
+    /// Interactive hardening wizard
+    ///
+    /// With `safe` the restart is left to the safe apply that follows.
+    async fn hardening_wizard(&self, safe: bool) -> Result<()> {
+        log_info("SSH Hardening Wizard");
+
+        outln!("\nThis wizard will guide you through hardening your SSH configuration.");
+        outln!("Current configuration will be backed up before any changes.\n");
+
+        // Port configuration
+        let change_port = prompt_yes_no("Change SSH port from default (22)?", true)?;
+        let port = if change_port {
+            let port_str = prompt_with_default("Enter new SSH port (1024-65535)", "2222")?;
+            port_str.parse::<u16>().unwrap_or(2222)
+        } else {
+            22
+        };
+
+        // Password authentication
+        let disable_passwords = prompt_yes_no(
+            "Disable password authentication (key-only)?",
+            true,
+        )?;
+
+        if disable_passwords {
+            log_warn("Make sure you have SSH key access configured before proceeding!");
+            let confirm = prompt_yes_no("Are you sure you want to continue?", false)?;
+            if !confirm {
+                log_info("Aborting SSH hardening");
+                return Ok(());
+            }
+        }
+
+        // Fail2ban
+        let setup_fail2ban = prompt_yes_no("Setup fail2ban for SSH protection?", true)?;
+
+        // Apply hardening
+        log_info("Applying SSH hardening...");
+        let mut changed = self.harden_ssh(Some(port)).await?;
+
+        if disable_passwords {
+            changed |= self.disable_password_auth().await?;
+        }
+
+        if setup_fail2ban {
+            self.setup_fail2ban().await?;
+        }
+
+        // Restart SSH, unless its configuration is unchanged
+        if !changed {
+            log_info("SSH configuration unchanged, no restart needed");
+        } else if safe {
+            log_info("sshd is restarted once the new configuration is tested");
+        } else if prompt_yes_no("Restart SSH service now?", false)? {
+            log_warn("Restarting SSH service...");
+            restart_service(ssh_service())?;
+            log_success("SSH service restarted");
+        } else {
+            log_warn("Remember to restart SSH service to apply changes:");
+            log_warn("  sudo systemctl restart sshd");
+        }
+
+        log_success("SSH hardening complete!");
+
+        if change_port {
+            log_warn(&format!(
+                "\nIMPORTANT: Update your firewall to allow port {}",
+                port
+            ));
+            log_warn(&format!("Next SSH connection: ssh -p {} user@host", port));
+        }
+
+        Ok(())
+    }
+
+    /// Show interactive menu
+    async fn show_menu(&self) -> Result<()> {
+        loop {
+            let options = vec![
+                "Run hardening wizard",
+                "Change SSH port",
+                "Disable password authentication",
+                "Setup fail2ban",
+                "Generate host keys",
+                "Validate configuration",
+                "Show SSH status",
+                "Exit",
+            ];
+
+            let choice = select_from_menu("SSH Management", &options)?;
+
+            match choice {
+                0 => {
+                    self.hardening_wizard(false).await?;
+                }
+                1 => {
+                    let port_str = prompt_with_default("Enter new SSH port", "2222")?;
+                    if let Ok(port) = port_str.parse::<u16>() {
+                        self.change_port(port).await?;
+                    } else {
+                        log_error("Invalid port number");
+                    }
+                }
+                2 => {
+                    self.disable_password_auth().await?;
+                }
+                3 => {
+                    self.setup_fail2ban().await?;
+                }
+                4 => {
+                    self.generate_host_keys().await?;
+                }
+                5 => {
+                    self.validate_ssh_config().await?;
+                }
+                6 => {
+                    self.show_status().await?;
+                }
+                7 => {
+                    log_info("Exiting SSH management");
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Module for SshModule {
+    fn name(&self) -> &str {
+        &self.base.info.name
+    }
+
+    fn description(&self) -> &str {
+        &self.base.info.description
+    }
+
+    fn version(&self) -> &str {
+        &self.base.info.version
+    }
+
+    fn after(&self) -> &[String] {
+        &self.base.info.after
+    }
+
+    fn is_available(&self) -> bool {
+        check_command("sshd").is_ok() || check_command("ssh").is_ok()
+    }
+
+    fn help(&self) -> String {
+        format!(
+            r#"SSH Hardening Module v{}
+
+DESCRIPTION:
+    {}
+
+USAGE:
+    flux module {} [OPTIONS]
+
+OPTIONS:
+    --harden                     Apply full SSH hardening
+    --port <port>                Change SSH port
+    --disable-passwords          Disable password authentication
+    --fail2ban                   Setup fail2ban protection
+    --generate-keys              Generate new host keys
+    --status                     Show SSH status
+    --menu                       Show interactive menu
+    --safe                       Test changes on a second sshd, revert unless confirmed
+    --timeout <minutes>          Minutes to wait for 'flux ssh confirm' (default: {})
+    audit                        Grade the effective configuration (sshd -T)
+    get <Directive>              Show the value sshd uses and where it is set
+    set <Directive> <value>      Set a directive, keeping comments and Match blocks
+
+EXAMPLES:
+    flux module {} --menu
+    flux module {} --harden
+    flux module {} --port 2222
+    flux module {} --disable-passwords --fail2ban
+    flux module {} --safe --port 2222 --disable-passwords
+    flux module {} audit
+    flux module {} get PasswordAuthentication
+    flux module {} set MaxAuthTries 3
+"#,
+            self.version(),
+            self.description(),
+            self.name(),
+            DEFAULT_CONFIRM_TIMEOUT,
+            self.name(),
+            self.name(),
+            self.name(),
+            self.name(),
+            self.name(),
+            self.name(),
+            self.name(),
+            self.name()
+        )
+    }
+
+    fn supports_apply(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> Option<SectionSchema> {
+        Some(SectionSchema::of::<SshSpec>())
+    }
+
+    fn template_vars(&self, config: &Config) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        let spec: SshSpec = config.module_settings(self.name())?;
+        Ok(vec![("ssh/50-flux-hardening.conf", spec.template_vars()?)])
+    }
+
+    async fn apply(&self, spec: &toml::Value, _config: &Config) -> Result<()> {
+        let spec: SshSpec = parse_spec(self.name(), spec)?;
+        self.apply_spec(&spec).await
+    }
+
+    async fn plan(&self, spec: &toml::Value, _config: &Config) -> Result<Vec<Drift>> {
+        let spec: SshSpec = parse_spec(self.name(), spec)?;
+        self.plan_spec(&spec).await
+    }
+
+    async fn execute(&self, args: Vec<String>, _config: &Config) -> Result<()> {
+        if args.is_empty() || args.contains(&"--menu".to_string()) {
+            return self.show_menu().await;
+        }
+
+        // With --safe every change below is tested before sshd restarts
+        let timeout = match args.iter().position(|arg| arg == "--timeout") {
+            Some(i) => args
+                .get(i + 1)
+                .and_then(|minutes| minutes.parse::<u32>().ok())
+                .ok_or_else(|| FluxError::validation("--timeout takes a number of minutes"))?,
+            None => DEFAULT_CONFIRM_TIMEOUT,
+        };
+        let snapshot = safe_snapshot(args.iter().any(|arg| arg == "--safe"))?;
+
+        // Parse arguments
+        let mut i = 0;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--harden" => {
+                    self.hardening_wizard(snapshot.is_some()).await?;
+                    i += 1;
+                }
+                "--timeout" => {
+                    i += 2;
+                }
+                "--port" => {
+                    if i + 1 < args.len() {
+                        if let Ok(port) = args[i + 1].parse::<u16>() {
+                            self.change_port(port).await?;
+                        }
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                }
+                "--disable-passwords" => {
+                    self.disable_password_auth().await?;
+                    i += 1;
+                }
+                "--fail2ban" => {
+                    self.setup_fail2ban().await?;
+                    i += 1;
+                }
+                "--generate-keys" => {
+                    self.generate_host_keys().await?;
+                    i += 1;
+                }
+                "--status" => {
+                    self.show_status().await?;
+                    i += 1;
+                }
+                "audit" => {
+                    self.audit().await?;
+                    i += 1;
+                }
+                "get" => {
+                    let keyword = args.get(i + 1).ok_or_else(|| {
+                        FluxError::validation("Usage: flux load ssh -- get <Directive>")
+                    })?;
+                    self.get_directive(keyword).await?;
+                    i += 2;
+                }
+                "set" => {
+                    if args.len() < i + 3 {
+                        return Err(FluxError::validation(
+                            "Usage: flux load ssh -- set <Directive> <value>",
+                        ));
+                    }
+                    // The rest of the arguments form the value, e.g. `AllowUsers alice bob`
+                    self.set_directive(&args[i + 1], &args[i + 2..].join(" ")).await?;
+                    break;
+                }
+                _ => {
+                    i += 1;
+                }
+            }
+        }
+
+        if let Some(snapshot) = snapshot {
+            restart_safely(snapshot, timeout)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_spec_renders_hardened_config() {
+        let config = SshSpec::default().render().unwrap();
+
+        assert!(config.contains("\nPort 22\n"));
+        assert!(config.contains("ListenAddress 0.0.0.0\nListenAddress ::\n"));
+        assert!(config.contains("PermitRootLogin no\n"));
+        assert!(config.contains("PasswordAuthentication no\n"));
+        assert!(config.contains("MaxAuthTries 3\n"));
+        assert!(config.contains("Banner /etc/ssh/banner.txt\n"));
+        assert!(!config.contains("AllowUsers"));
+
+        // Distro defaults stay in sshd_config
+        assert!(!config.contains("Subsystem"));
+        assert!(!config.contains("Include"));
+    }
+
+    #[test]
+    fn test_shadowing_directives() {
+        let mut config = SshdConfig::parse(
+            SSH_CONFIG_PATH,
+            "PermitRootLogin yes\nMaxAuthTries 3\nInclude /etc/ssh/sshd_config.d/*.conf\nPort 22\nX11Forwarding yes\n",
+        );
+        config.put_file(
+            "/etc/ssh/sshd_config.d/10-cloud.conf",
+            "PasswordAuthentication yes\n",
+        );
+        config.put_file(
+            HARDENING_DROP_IN,
+            "Port 2222\nPermitRootLogin no\nMaxAuthTries 3\nPasswordAuthentication no\nX11Forwarding no\n",
+        );
+
+        let shadowing: Vec<String> = shadowing_directives(&config, Path::new(HARDENING_DROP_IN))
+            .iter()
+            .map(|d| format!("{}:{} {}", d.file.display(), d.line, d))
+            .collect();
+
+        // Equal values and directives read after the drop-in do not matter
+        assert_eq!(
+            shadowing,
+            vec![
+                "/etc/ssh/sshd_config:1 PermitRootLogin yes",
+                "/etc/ssh/sshd_config.d/10-cloud.conf:1 PasswordAuthentication yes",
+                "/etc/ssh/sshd_config:4 Port 22",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_directives_without_include() {
+        let mut config = SshdConfig::parse(SSH_CONFIG_PATH, "Port 22\nSubsystem sftp /usr/libexec/openssh/sftp-server\n");
+        let settings = SshdConfig::parse(HARDENING_DROP_IN, "Port 2222\nListenAddress ::\nListenAddress 0.0.0.0\n");
+
+        set_directives(&mut config, &settings);
+
+        assert_eq!(
+            config.text(SSH_CONFIG_PATH).unwrap(),
+            "Port 2222\nSubsystem sftp /usr/libexec/openssh/sftp-server\nListenAddress ::\nListenAddress 0.0.0.0\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_sshd_config() {
+        let expected = "Port 2222\nListenAddress 0.0.0.0\nListenAddress ::\nPermitRootLogin no\nMaxAuthTries 3\n";
+        let live = "# comment\nPort 22\nListenAddress ::\nListenAddress 0.0.0.0\npermitrootlogin NO\n\nMatch User backup\n    MaxAuthTries 3\n";
+
+        let drift = diff_sshd_config(
+            &SshdConfig::parse(SSH_CONFIG_PATH, expected),
+            &SshdConfig::parse(SSH_CONFIG_PATH, live),
+        );
+
+        assert_eq!(
+            drift,
+            vec![
+                Drift::new("Port", "2222", Some("22".to_string())),
+                Drift::missing("MaxAuthTries", "3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_sshd_config_first_match_wins() {
+        let drift = diff_sshd_config(
+            &SshdConfig::parse(SSH_CONFIG_PATH, "PasswordAuthentication no\n"),
+            &SshdConfig::parse(
+                SSH_CONFIG_PATH,
+                "PasswordAuthentication yes\nPasswordAuthentication no\n",
+            ),
+        );
+
+        assert_eq!(
+            drift,
+            vec![Drift::new("PasswordAuthentication", "no", Some("yes".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_spec_access_control() {
+        let spec = SshSpec {
+            port: 2222,
+            disable_password_auth: false,
+            allowed_users: vec!["alice".to_string(), "bob".to_string()],
+            ..SshSpec::default()
+        };
+        let config = spec.render().unwrap();
+
+        assert!(config.contains("\nPort 2222\n"));
+        assert!(config.contains("PasswordAuthentication yes\n"));
+        assert!(config.contains("AllowUsers alice bob\n"));
+    }
+}
@@ -0,0 +1,66 @@
+//! The installed OpenSSH release
+//!
+//! What sshd accepts depends on its version, so configuration that uses newer
+//! features checks [`OpensshVersion::installed`] first.
+
+use crate::helpers::system::command_output;
+use serde::Serialize;
+
+/// OpenSSH release, e.g. 8.9 for `OpenSSH_8.9p1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct OpensshVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl OpensshVersion {
+    /// First release whose sshd reads `Include`
+    pub const INCLUDE: Self = Self::new(8, 2);
+
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Find the version in `ssh -V` output such as `OpenSSH_8.9p1 Ubuntu-3, OpenSSL 3.0.2`
+    pub fn parse(text: &str) -> Option<Self> {
+        let version = &text[text.find("OpenSSH_")? + "OpenSSH_".len()..];
+        let (major, rest) = version.split_once('.')?;
+        let minor: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        Some(Self::new(major.parse().ok()?, minor.parse().ok()?))
+    }
+
+    /// Version of the installed OpenSSH, `None` when it cannot be told
+    pub fn installed() -> Option<Self> {
+        // ssh -V prints the version to stderr
+        let output = command_output("ssh", &["-V"]).ok()?;
+        Self::parse(&output.stderr).or_else(|| Self::parse(&output.stdout))
+    }
+
+    pub fn supports_include(&self) -> bool {
+        *self >= Self::INCLUDE
+    }
+}
+
+impl std::fmt::Display for OpensshVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let version = OpensshVersion::parse("OpenSSH_8.9p1 Ubuntu-3ubuntu0.10, OpenSSL 3.0.2 15 Mar 2022");
+        assert_eq!(version, Some(OpensshVersion::new(8, 9)));
+        assert!(version.unwrap().supports_include());
+
+        let old = OpensshVersion::parse("OpenSSH_7.4p1, OpenSSL 1.0.2k-fips  26 Jan 2017").unwrap();
+        assert!(!old.supports_include());
+        assert_eq!(old.to_string(), "7.4");
+
+        assert_eq!(OpensshVersion::parse("ssh: command not found"), None);
+    }
+}
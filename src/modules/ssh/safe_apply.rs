@@ -0,0 +1,654 @@
+//! Lockout-safe SSH changes
+//!
+//! A safe apply leaves the running sshd on its old configuration. After
+//! `sshd -t` passes and an admin user is found with a usable key, a second
+//! sshd is started with the new configuration so a login can be tried on it.
+//! `flux ssh confirm` then restarts sshd with the new configuration. Without
+//! confirmation a timer restores the saved files and restarts sshd.
+
+use super::{ssh_service, SshdConfig, HARDENING_DROP_IN, SSH_CONFIG_DIR, SSH_CONFIG_PATH};
+use crate::error::{FluxError, Result};
+use crate::helpers::{
+    dry_run,
+    file_ops::{remove_path, safe_write_file},
+    logging::{log_error, log_info, log_success, log_warn},
+    paths::resolve,
+    runner::{self, Invocation},
+    system::{command_output, execute_command, has_systemd, restart_service},
+    validation::validate_ssh_key,
+};
+use crate::remote::shell_quote;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The change waiting for confirmation, with the files it replaced
+const PENDING_PATH: &str = "/var/lib/flux/ssh-pending.json";
+
+/// Pid file of the sshd started for testing
+const TEST_PID_FILE: &str = "/run/flux-sshd-test.pid";
+
+/// Transient systemd unit that reverts an unconfirmed change
+const REVERT_UNIT: &str = "flux-ssh-revert";
+
+/// Port of the test sshd when the change keeps the port sshd listens on
+pub const TEST_PORT: u16 = 2022;
+
+/// Minutes to wait for `flux ssh confirm` unless configured otherwise
+pub const DEFAULT_CONFIRM_TIMEOUT: u32 = 5;
+
+/// Members of these groups administer the host
+const ADMIN_GROUPS: &[&str] = &["sudo", "wheel", "admin"];
+
+/// sshd's default `AuthorizedKeysFile`
+const DEFAULT_AUTHORIZED_KEYS: &str = ".ssh/authorized_keys .ssh/authorized_keys2";
+
+/// A configuration file as it was before the change, `None` if it did not exist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    pub content: Option<String>,
+}
+
+impl SavedFile {
+    /// The current content of `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        Ok(Self {
+            content: read_existing(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// Copies of the SSH configuration taken before a safe apply changes it
+///
+/// Dropping a snapshot restores the saved files unless the change was
+/// handed over to a pending change or found unchanged, so an error anywhere
+/// between taking it and `restart_safely` never leaves the new configuration
+/// on disk.
+pub struct Snapshot {
+    files: Vec<SavedFile>,
+    ports: Vec<String>,
+    armed: bool,
+}
+
+impl Snapshot {
+    /// Save sshd_config, the files it includes and everything in sshd_config.d
+    ///
+    /// Fails while an earlier change still waits for confirmation, since
+    /// reverting that one would undo this one as well.
+    pub fn take() -> Result<Self> {
+        if let Some(pending) = PendingChange::load()? {
+            return Err(FluxError::validation(format!(
+                "The SSH change from {} is waiting for confirmation; run 'flux ssh confirm' or 'flux ssh revert' first",
+                pending.started
+            )));
+        }
+
+        let config = SshdConfig::load(SSH_CONFIG_PATH)?;
+        let mut paths: Vec<PathBuf> = config.files().map(Path::to_path_buf).collect();
+        paths.push(PathBuf::from(HARDENING_DROP_IN));
+        if let Ok(entries) = fs::read_dir(resolve(SSH_CONFIG_DIR)) {
+            paths.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_file())
+                    .map(|entry| Path::new(SSH_CONFIG_DIR).join(entry.file_name())),
+            );
+        }
+        paths.sort();
+        paths.dedup();
+
+        let files = paths
+            .into_iter()
+            .map(|path| SavedFile::read(&path))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            files,
+            ports: config.values("Port"),
+            armed: true,
+        })
+    }
+
+    /// Keep the files as they are now when the snapshot is dropped
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+
+    /// Whether any saved file differs from what is on disk now
+    fn changed(&self) -> Result<bool> {
+        for file in &self.files {
+            if read_existing(&file.path)? != file.content {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        log_warn("Restoring the SSH configuration from before the change");
+        if let Err(e) = restore(&self.files) {
+            log_error(e.to_string());
+        }
+    }
+}
+
+fn read_existing(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(resolve(path)) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(FluxError::system(format!("Failed to read {}: {}", path.display(), e))),
+    }
+}
+
+/// Put the saved files back, removing those that did not exist
+///
+/// Keeps going when a file fails and reports the failures at the end.
+pub fn restore(files: &[SavedFile]) -> Result<()> {
+    let mut failures = 0;
+    for file in files {
+        let result = match &file.content {
+            Some(content) => safe_write_file(&file.path, content, false),
+            None => remove_path(&file.path),
+        };
+        if let Err(e) = result {
+            log_error(format!("Failed to restore {}: {}", file.path.display(), e));
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        return Err(FluxError::system(format!("{} SSH configuration file(s) could not be restored", failures)));
+    }
+    Ok(())
+}
+
+/// An SSH change that is tested but not yet confirmed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChange {
+    pub started: String,
+    /// When the change is reverted unless confirmed
+    pub deadline: String,
+    /// Port of the sshd running the new configuration
+    pub test_port: u16,
+    /// systemd unit of the revert timer, if systemd runs it
+    pub timer: Option<String>,
+    pub files: Vec<SavedFile>,
+}
+
+impl PendingChange {
+    /// The change waiting for confirmation, if any
+    pub fn load() -> Result<Option<Self>> {
+        let path = resolve(PENDING_PATH);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = fs::read_to_string(&path)?;
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| FluxError::parse(format!("Invalid pending SSH change in {}: {}", PENDING_PATH, e)))
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = resolve(PENDING_PATH);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| FluxError::parse(format!("Failed to serialize pending SSH change: {}", e)))?;
+        fs::write(&path, json)?;
+        Ok(())
+    }
+
+    fn clear() -> Result<()> {
+        if dry_run::is_dry_run() {
+            return Ok(());
+        }
+        match fs::remove_file(resolve(PENDING_PATH)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stop the revert timer and the test sshd
+    fn disarm(&self) {
+        if let Some(unit) = &self.timer {
+            let timer = format!("{}.timer", unit);
+            if let Err(e) = execute_command("systemctl", &["stop", &timer]) {
+                log_warn(format!("Could not stop {}: {}", timer, e));
+            }
+        }
+        stop_test_server();
+    }
+}
+
+/// Check that sshd will accept the new configuration before restarting it
+///
+/// Restarts nothing when the configuration is unchanged. Otherwise validates
+/// it, checks that an admin user can log in with a key, starts a second sshd
+/// with it and arms a timer that reverts the change after `minutes` unless
+/// `flux ssh confirm` is run. A failed check restores the saved files when
+/// the snapshot is dropped.
+pub fn restart_safely(mut snapshot: Snapshot, minutes: u32) -> Result<()> {
+    if minutes == 0 {
+        return Err(FluxError::validation("The confirmation timeout must be at least one minute"));
+    }
+    if !snapshot.changed()? {
+        snapshot.disarm();
+        log_info("SSH configuration unchanged, no restart needed");
+        return Ok(());
+    }
+
+    match start_pending(&snapshot, minutes) {
+        Ok(pending) => {
+            snapshot.disarm();
+            log_success(format!(
+                "sshd with the new configuration is listening on port {} for testing",
+                pending.test_port
+            ));
+            log_warn(format!(
+                "Log in with 'ssh -p {} <user>@<host>' from a new terminal, then run 'flux ssh confirm'",
+                pending.test_port
+            ));
+            log_warn(format!(
+                "Without confirmation the change is reverted at {}",
+                pending.deadline
+            ));
+            Ok(())
+        }
+        Err(e) => {
+            log_error(format!("Safe apply failed: {}", e));
+            Err(e)
+        }
+    }
+}
+
+fn start_pending(snapshot: &Snapshot, minutes: u32) -> Result<PendingChange> {
+    let config = SshdConfig::load(SSH_CONFIG_PATH)?;
+    let config_path = resolve(SSH_CONFIG_PATH).to_string_lossy().into_owned();
+
+    let output = command_output("sshd", &["-t", "-f", &config_path])?;
+    if !output.success {
+        return Err(FluxError::command_failed(format!(
+            "SSH configuration validation failed: {}",
+            output.stderr
+        )));
+    }
+
+    check_admin_keys(&config)?;
+
+    // A new port is free while the running sshd listens on the old one
+    let port = config
+        .values("Port")
+        .first()
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(22);
+    let test_port = if snapshot.ports.contains(&port.to_string()) || (snapshot.ports.is_empty() && port == 22) {
+        TEST_PORT
+    } else {
+        port
+    };
+
+    log_info(format!("Starting a test sshd on port {}", test_port));
+    execute_command(
+        "sshd",
+        &[
+            "-f",
+            &config_path,
+            "-p",
+            &test_port.to_string(),
+            "-o",
+            &format!("PidFile={}", TEST_PID_FILE),
+        ],
+    )?;
+
+    let now = Local::now();
+    let deadline = now + chrono::Duration::minutes(i64::from(minutes));
+    let timer = match arm_timer(minutes) {
+        Ok(timer) => timer,
+        Err(e) => {
+            stop_test_server();
+            return Err(e);
+        }
+    };
+
+    let pending = PendingChange {
+        started: now.to_rfc3339(),
+        deadline: deadline.to_rfc3339(),
+        test_port,
+        timer,
+        files: snapshot.files.clone(),
+    };
+    if !dry_run::is_dry_run() {
+        pending.save()?;
+    }
+    Ok(pending)
+}
+
+/// Run `flux ssh revert` after `minutes`, with systemd if it is there
+fn arm_timer(minutes: u32) -> Result<Option<String>> {
+    let flux = std::env::current_exe()?.to_string_lossy().into_owned();
+
+    if has_systemd() {
+        // A failed revert of an earlier change would keep the unit name taken
+        let service = format!("{}.service", REVERT_UNIT);
+        let _ = runner::run(&Invocation::new("systemctl", &["reset-failed", &service]));
+
+        execute_command(
+            "systemd-run",
+            &[
+                "--unit",
+                REVERT_UNIT,
+                &format!("--on-active={}min", minutes),
+                "--timer-property=AccuracySec=1s",
+                &flux,
+                "ssh",
+                "revert",
+            ],
+        )?;
+        return Ok(Some(REVERT_UNIT.to_string()));
+    }
+
+    // Confirming removes the pending change, which turns the revert into a no-op
+    execute_command("sh", &["-c", &revert_script(&flux, minutes)])?;
+    Ok(None)
+}
+
+/// Shell command running `flux ssh revert` in the background after `minutes`
+fn revert_script(flux: &str, minutes: u32) -> String {
+    let revert = format!("sleep {} && exec {} ssh revert", u64::from(minutes) * 60, shell_quote(flux));
+    format!("nohup sh -c {} >/dev/null 2>&1 &", shell_quote(&revert))
+}
+
+fn stop_test_server() {
+    let Ok(pid) = fs::read_to_string(resolve(TEST_PID_FILE)) else {
+        return;
+    };
+    if let Err(e) = execute_command("kill", &[pid.trim()]) {
+        log_warn(format!("Could not stop the test sshd: {}", e));
+    }
+}
+
+/// Restart sshd with the tested configuration and disarm the revert timer
+///
+/// Reverts the change if sshd fails to restart with it.
+pub fn confirm_change() -> Result<()> {
+    let Some(pending) = PendingChange::load()? else {
+        return Err(FluxError::not_found("No SSH change is waiting for confirmation"));
+    };
+
+    pending.disarm();
+    if let Err(e) = restart_service(ssh_service()) {
+        log_error(format!("sshd did not restart with the new configuration: {}", e));
+        revert_change()?;
+        return Err(e);
+    }
+
+    PendingChange::clear()?;
+    log_success("SSH change confirmed");
+    Ok(())
+}
+
+/// Restore the configuration from before the unconfirmed change and restart sshd
+///
+/// Does nothing when no change is waiting, e.g. after it was confirmed.
+pub fn revert_change() -> Result<()> {
+    let Some(pending) = PendingChange::load()? else {
+        log_info("No SSH change is waiting for confirmation, nothing to revert");
+        return Ok(());
+    };
+
+    log_warn(format!("Reverting the SSH change from {}", pending.started));
+    pending.disarm();
+    restore(&pending.files)?;
+    restart_service(ssh_service())?;
+
+    PendingChange::clear()?;
+    log_success("SSH configuration reverted");
+    Ok(())
+}
+
+/// Fail unless some admin user can log in with a key under `config`
+fn check_admin_keys(config: &SshdConfig) -> Result<()> {
+    if config
+        .get("PubkeyAuthentication")
+        .is_some_and(|d| d.value.eq_ignore_ascii_case("no"))
+    {
+        return Err(FluxError::validation("PubkeyAuthentication is disabled, nobody could log in with a key"));
+    }
+
+    let group = fs::read_to_string(resolve("/etc/group"))
+        .map_err(|e| FluxError::system(format!("Failed to read /etc/group: {}", e)))?;
+    let passwd = fs::read_to_string(resolve("/etc/passwd"))
+        .map_err(|e| FluxError::system(format!("Failed to read /etc/passwd: {}", e)))?;
+    let root_login = !config
+        .get("PermitRootLogin")
+        .is_some_and(|d| d.value.eq_ignore_ascii_case("no"));
+    let sudo_user = std::env::var("SUDO_USER").ok();
+
+    let mut users = admin_users(&group, &passwd, root_login);
+    if let Some(sudo_user) = sudo_user.filter(|name| !users.iter().any(|(user, _)| user == name)) {
+        users.extend(
+            passwd_entries(&passwd)
+                .filter(|(user, _, _)| *user == sudo_user)
+                .map(|(user, _, home)| (user.to_string(), PathBuf::from(home))),
+        );
+    }
+
+    for (user, home) in &users {
+        for path in authorized_keys_files(config, user, home) {
+            let Ok(keys) = fs::read_to_string(resolve(&path)) else {
+                continue;
+            };
+            if keys.lines().any(is_usable_key) {
+                log_success(format!("{} can log in with a key from {}", user, path.display()));
+                return Ok(());
+            }
+        }
+    }
+
+    Err(FluxError::validation(format!(
+        "No admin user ({}) has a valid key in authorized_keys; add one before changing sshd",
+        if users.is_empty() {
+            "none found".to_string()
+        } else {
+            users.iter().map(|(user, _)| user.as_str()).collect::<Vec<_>>().join(", ")
+        }
+    )))
+}
+
+/// Name, gid and home directory of every /etc/passwd entry
+fn passwd_entries(passwd: &str) -> impl Iterator<Item = (&str, &str, &str)> {
+    passwd.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.len() >= 6).then(|| (fields[0], fields[3], fields[5]))
+    })
+}
+
+/// Users in an admin group, with their home directories
+///
+/// `root` counts when it may log in over SSH.
+fn admin_users(group: &str, passwd: &str, root_login: bool) -> Vec<(String, PathBuf)> {
+    let mut names = Vec::new();
+    let mut gids = Vec::new();
+    for line in group.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() >= 4 && ADMIN_GROUPS.contains(&fields[0]) {
+            gids.push(fields[2]);
+            names.extend(fields[3].split(',').filter(|name| !name.is_empty()));
+        }
+    }
+
+    passwd_entries(passwd)
+        .filter(|(user, gid, _)| {
+            (*user == "root" && root_login) || names.contains(user) || (*user != "root" && gids.contains(gid))
+        })
+        .map(|(user, _, home)| (user.to_string(), PathBuf::from(home)))
+        .collect()
+}
+
+/// Files sshd reads a user's keys from, following `AuthorizedKeysFile`
+fn authorized_keys_files(config: &SshdConfig, user: &str, home: &Path) -> Vec<PathBuf> {
+    let setting = config
+        .get("AuthorizedKeysFile")
+        .map_or_else(|| DEFAULT_AUTHORIZED_KEYS.to_string(), |d| d.value);
+
+    setting
+        .split_whitespace()
+        .filter(|pattern| !pattern.eq_ignore_ascii_case("none"))
+        .map(|pattern| {
+            let path = pattern
+                .replace("%%", "\0")
+                .replace("%h", &home.to_string_lossy())
+                .replace("%u", user)
+                .replace('\0', "%");
+            if path.starts_with('/') {
+                PathBuf::from(path)
+            } else {
+                home.join(path)
+            }
+        })
+        .collect()
+}
+
+/// Whether an authorized_keys line holds a well-formed public key
+///
+/// The key may follow options such as `from="10.0.0.0/8"`.
+fn is_usable_key(line: &str) -> bool {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return false;
+    }
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    (0..fields.len()).any(|start| validate_ssh_key(&fields[start..].join(" ")).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::paths;
+    use serial_test::serial;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    const KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl alice@laptop";
+
+    #[test]
+    fn test_is_usable_key() {
+        assert!(is_usable_key(KEY));
+        assert!(is_usable_key(&format!("from=\"10.0.0.0/8\",no-pty {}", KEY)));
+        assert!(!is_usable_key(&format!("# {}", KEY)));
+        assert!(!is_usable_key("ssh-ed25519 not-base64!"));
+        assert!(!is_usable_key(""));
+    }
+
+    #[test]
+    fn test_revert_script_runs_a_quoted_path() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("reverted");
+        let flux = dir.path().join("it's \"flux\"");
+        fs::write(&flux, format!("#!/bin/sh\necho \"$@\" > '{}'\n", marker.display())).unwrap();
+        fs::set_permissions(&flux, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let status = std::process::Command::new("sh")
+            .args(["-c", &revert_script(&flux.to_string_lossy(), 0)])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        for _ in 0..50 {
+            if marker.exists() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "ssh revert\n");
+    }
+
+    #[test]
+    fn test_admin_users() {
+        let group = "root:x:0:\nsudo:x:27:alice,bob\nwheel:x:10:\nusers:x:100:carol\n";
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      alice:x:1000:1000::/home/alice:/bin/bash\n\
+                      carol:x:1002:100::/home/carol:/bin/bash\n\
+                      dave:x:1003:10::/home/dave:/bin/bash\n";
+
+        let users: Vec<String> = admin_users(group, passwd, false)
+            .into_iter()
+            .map(|(user, home)| format!("{} {}", user, home.display()))
+            .collect();
+        // bob has no passwd entry; dave is in wheel through his primary group
+        assert_eq!(users, vec!["alice /home/alice", "dave /home/dave"]);
+
+        assert_eq!(admin_users(group, passwd, true)[0].0, "root");
+    }
+
+    #[test]
+    fn test_authorized_keys_files() {
+        let home = Path::new("/home/alice");
+
+        let default = SshdConfig::parse(SSH_CONFIG_PATH, "Port 22\n");
+        assert_eq!(
+            authorized_keys_files(&default, "alice", home),
+            vec![
+                PathBuf::from("/home/alice/.ssh/authorized_keys"),
+                PathBuf::from("/home/alice/.ssh/authorized_keys2"),
+            ]
+        );
+
+        let custom = SshdConfig::parse(SSH_CONFIG_PATH, "AuthorizedKeysFile /etc/ssh/keys/%u %h/.ssh/keys none\n");
+        assert_eq!(
+            authorized_keys_files(&custom, "alice", home),
+            vec![
+                PathBuf::from("/etc/ssh/keys/alice"),
+                PathBuf::from("/home/alice/.ssh/keys"),
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_snapshot_restore() {
+        let root = TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join("etc/ssh/sshd_config.d")).unwrap();
+        fs::write(
+            root.path().join("etc/ssh/sshd_config"),
+            "Include /etc/ssh/sshd_config.d/*.conf\nPort 22\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("etc/ssh/sshd_config.d/10-cloud.conf"), "PasswordAuthentication yes\n").unwrap();
+        paths::set_root(Some(root.path().to_path_buf()));
+
+        let snapshot = Snapshot::take().unwrap();
+        let unchanged = snapshot.changed().unwrap();
+
+        fs::write(root.path().join("etc/ssh/sshd_config.d/10-cloud.conf"), "#PasswordAuthentication yes\n").unwrap();
+        fs::write(root.path().join("etc/ssh/sshd_config.d/50-flux-hardening.conf"), "Port 2222\n").unwrap();
+        let changed = snapshot.changed().unwrap();
+        let ports = snapshot.ports.clone();
+        // Dropped without being handed over, the snapshot restores the files
+        drop(snapshot);
+
+        paths::set_root(None);
+
+        assert!(!unchanged);
+        assert!(changed);
+        assert_eq!(ports, vec!["22"]);
+        assert_eq!(
+            fs::read_to_string(root.path().join("etc/ssh/sshd_config.d/10-cloud.conf")).unwrap(),
+            "PasswordAuthentication yes\n"
+        );
+        assert!(!root.path().join("etc/ssh/sshd_config.d/50-flux-hardening.conf").exists());
+    }
+}
@@ -0,0 +1,756 @@
+//! sshd_config model
+//!
+//! [`SshdConfig`] keeps every line of `sshd_config` and of the files it
+//! `Include`s, so directives can be queried in the order sshd reads them and
+//! edited without losing comments, blank lines or `Match` blocks.
+//!
+//! sshd reads an `Include`d file at the point of the `Include` and uses the
+//! first value it reads for most directives. Keywords are case-insensitive and
+//! may be separated from their value by `=`.
+
+use crate::error::{FluxError, Result};
+use crate::helpers::file_ops::safe_write_file;
+use crate::helpers::paths::{self, resolve};
+use glob::{MatchOptions, Pattern};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Relative `Include` paths are relative to this directory
+const SSH_DIR: &str = "/etc/ssh";
+
+/// sshd refuses deeper `Include` nesting
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// `*` matches neither `/` nor a leading `.`, as with glob(3)
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: true,
+};
+
+/// Directives that may legitimately appear more than once
+pub const MULTI_VALUE_DIRECTIVES: &[&str] = &[
+    "port",
+    "listenaddress",
+    "hostkey",
+    "include",
+    "subsystem",
+    "acceptenv",
+    "allowusers",
+    "denyusers",
+    "allowgroups",
+    "denygroups",
+];
+
+/// Part of the configuration a directive applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Every connection
+    Global,
+    /// Connections matching the criteria of a `Match` block
+    Match(String),
+}
+
+/// A directive as sshd reads it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Directive {
+    /// Keyword as written, e.g. `passwordauthentication`
+    pub keyword: String,
+    pub value: String,
+    pub scope: Scope,
+    /// File the directive is in, as sshd sees it
+    pub file: PathBuf,
+    /// Line number in `file`, starting at 1
+    pub line: usize,
+}
+
+impl std::fmt::Display for Directive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", directive_name(&self.keyword), self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    /// Blank line or comment
+    Other,
+    Directive {
+        keyword: String,
+        value: String,
+        /// Files an `Include` pulled in, as indices into [`SshdConfig::files`]
+        included: Vec<usize>,
+    },
+    Match(String),
+}
+
+#[derive(Debug, Clone)]
+struct ConfigLine {
+    /// The line as written, rebuilt when it is edited
+    text: String,
+    entry: Entry,
+}
+
+impl ConfigLine {
+    fn parse(text: &str) -> Self {
+        let trimmed = text.trim();
+        let entry = if trimmed.is_empty() || trimmed.starts_with('#') {
+            Entry::Other
+        } else {
+            let (keyword, value) = split_directive(trimmed);
+            if keyword.eq_ignore_ascii_case("match") {
+                Entry::Match(value)
+            } else {
+                Entry::Directive {
+                    keyword,
+                    value,
+                    included: Vec::new(),
+                }
+            }
+        };
+
+        Self {
+            text: text.to_string(),
+            entry,
+        }
+    }
+
+    fn directive(indent: &str, keyword: &str, value: &str) -> Self {
+        Self::parse(&format!("{}{} {}", indent, keyword, value))
+    }
+
+    fn indent(&self) -> &str {
+        let trimmed = self.text.trim_start();
+        &self.text[..self.text.len() - trimmed.len()]
+    }
+
+    /// Keyword of a directive commented out with `#`, as distro configs list defaults
+    fn commented_keyword(&self) -> Option<String> {
+        let rest = self.text.trim().strip_prefix('#')?.trim_start();
+        let (keyword, value) = split_directive(rest);
+        (!keyword.is_empty() && !value.is_empty()).then_some(keyword)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConfigFile {
+    /// Path as sshd sees it, i.e. without `--root`
+    path: PathBuf,
+    lines: Vec<ConfigLine>,
+    trailing_newline: bool,
+    modified: bool,
+}
+
+impl ConfigFile {
+    fn parse(path: &Path, text: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            lines: text.lines().map(ConfigLine::parse).collect(),
+            trailing_newline: text.is_empty() || text.ends_with('\n'),
+            modified: false,
+        }
+    }
+
+    fn text(&self) -> String {
+        let mut text = self
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.trailing_newline && !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Where a new global directive goes: after its commented-out default if
+    /// there is one, otherwise at the end of the global section
+    fn insert_position(&self, keyword: &str) -> usize {
+        let is_match = |line: &ConfigLine| {
+            matches!(line.entry, Entry::Match(_))
+                || line.commented_keyword().is_some_and(|k| k.eq_ignore_ascii_case("match"))
+        };
+        let global_end = self.lines.iter().position(is_match).unwrap_or(self.lines.len());
+
+        let commented = self.lines[..global_end]
+            .iter()
+            .position(|line| line.commented_keyword().is_some_and(|k| k.eq_ignore_ascii_case(keyword)));
+        if let Some(index) = commented {
+            return index + 1;
+        }
+
+        // Comments right above a `Match` block describe the block
+        let mut end = global_end;
+        while end > 0 && self.lines[end - 1].entry == Entry::Other {
+            if global_end == self.lines.len() && !self.lines[end - 1].text.trim().is_empty() {
+                break;
+            }
+            end -= 1;
+        }
+        end
+    }
+}
+
+/// sshd_config and every file it includes
+#[derive(Debug, Clone)]
+pub struct SshdConfig {
+    /// The main file first, then included files in the order they were loaded
+    files: Vec<ConfigFile>,
+}
+
+impl SshdConfig {
+    /// Read `path` and every file it includes
+    ///
+    /// Paths are resolved below `--root`; include patterns that match nothing
+    /// are skipped, as sshd does.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut config = Self { files: Vec::new() };
+        config.load_file(path.as_ref(), 0)?;
+        Ok(config)
+    }
+
+    /// Parse the text of a single file without resolving its includes
+    pub fn parse(path: impl AsRef<Path>, text: &str) -> Self {
+        Self {
+            files: vec![ConfigFile::parse(path.as_ref(), text)],
+        }
+    }
+
+    fn load_file(&mut self, path: &Path, depth: usize) -> Result<usize> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(FluxError::parse(format!(
+                "Include nested too deeply at {}",
+                path.display()
+            )));
+        }
+
+        let text = fs::read_to_string(resolve(path))
+            .map_err(|e| FluxError::system(format!("Failed to read {}: {}", path.display(), e)))?;
+        let index = self.files.len();
+        self.files.push(ConfigFile::parse(path, &text));
+
+        for line in 0..self.files[index].lines.len() {
+            let patterns = match &self.files[index].lines[line].entry {
+                Entry::Directive { keyword, value, .. } if keyword.eq_ignore_ascii_case("include") => {
+                    value.clone()
+                }
+                _ => continue,
+            };
+
+            let mut loaded = Vec::new();
+            for pattern in patterns.split_whitespace() {
+                for included in expand_include(pattern)? {
+                    loaded.push(self.load_file(&included, depth + 1)?);
+                }
+            }
+            if let Entry::Directive { included, .. } = &mut self.files[index].lines[line].entry {
+                *included = loaded;
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Path of the main file
+    pub fn path(&self) -> &Path {
+        &self.files[0].path
+    }
+
+    /// Paths of the main file and every file it includes
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+
+    /// Paths of the files with edits not yet saved
+    pub fn modified_files(&self) -> impl Iterator<Item = &Path> {
+        self.files
+            .iter()
+            .filter(|file| file.modified)
+            .map(|file| file.path.as_path())
+    }
+
+    /// Current text of a loaded file, including edits not yet saved
+    pub fn text(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.files
+            .iter()
+            .find(|file| file.path == path.as_ref())
+            .map(ConfigFile::text)
+    }
+
+    /// Every directive in the order sshd reads them, includes expanded
+    pub fn directives(&self) -> Vec<Directive> {
+        self.positions()
+            .into_iter()
+            .map(|(file, index, scope)| {
+                let Entry::Directive { keyword, value, .. } = &self.files[file].lines[index].entry else {
+                    unreachable!("positions only yields directives");
+                };
+                Directive {
+                    keyword: keyword.clone(),
+                    value: value.clone(),
+                    scope,
+                    file: self.files[file].path.clone(),
+                    line: index + 1,
+                }
+            })
+            .collect()
+    }
+
+    /// The global directive sshd uses for `keyword`
+    pub fn get(&self, keyword: &str) -> Option<Directive> {
+        self.get_all(keyword).into_iter().next()
+    }
+
+    /// Every global occurrence of `keyword`, in the order sshd reads them
+    pub fn get_all(&self, keyword: &str) -> Vec<Directive> {
+        self.directives()
+            .into_iter()
+            .filter(|d| d.scope == Scope::Global && d.keyword.eq_ignore_ascii_case(keyword))
+            .collect()
+    }
+
+    /// Global values sshd uses for `keyword`: all of them for directives that
+    /// accumulate, otherwise only the first
+    pub fn values(&self, keyword: &str) -> Vec<String> {
+        let mut values: Vec<String> = self.get_all(keyword).into_iter().map(|d| d.value).collect();
+        if !is_multi_value(keyword) {
+            values.truncate(1);
+        }
+        values
+    }
+
+    /// Set the global `keyword` to `values`, one line per value
+    ///
+    /// The directive sshd currently uses is replaced in whichever file it is
+    /// in; for directives that accumulate, every other global occurrence is
+    /// removed. A directive that is not set globally is added to the main
+    /// file, next to its commented-out default when there is one. `Match`
+    /// blocks are left alone.
+    pub fn set(&mut self, keyword: &str, values: &[&str]) {
+        let name = directive_name(keyword);
+        let found = self.global_positions(keyword);
+
+        let Some(&(file, index)) = found.first() else {
+            let main = &mut self.files[0];
+            let at = main.insert_position(keyword);
+            let lines = values.iter().map(|value| ConfigLine::directive("", &name, value));
+            main.lines.splice(at..at, lines);
+            main.modified = true;
+            return;
+        };
+
+        if is_multi_value(keyword) {
+            self.remove_positions(found[1..].to_vec());
+        }
+
+        let target = &mut self.files[file];
+        let indent = target.lines[index].indent().to_string();
+        let lines = values.iter().map(|value| ConfigLine::directive(&indent, &name, value));
+        target.lines.splice(index..=index, lines);
+        target.modified = true;
+    }
+
+    /// Remove every global occurrence of `keyword`, returning how many there were
+    pub fn remove(&mut self, keyword: &str) -> usize {
+        let found = self.global_positions(keyword);
+        let count = found.len();
+        self.remove_positions(found);
+        count
+    }
+
+    /// Add a global directive ahead of every other directive of the main file
+    ///
+    /// It goes below the comment block heading the file, as distributions
+    /// place `Include /etc/ssh/sshd_config.d/*.conf`.
+    pub fn prepend(&mut self, keyword: &str, value: &str) {
+        let main = &mut self.files[0];
+        let header = main
+            .lines
+            .iter()
+            .take_while(|line| line.entry == Entry::Other && !line.text.trim().is_empty())
+            .count();
+
+        let directive = ConfigLine::directive("", &directive_name(keyword), value);
+        let lines = if main.lines.is_empty() {
+            vec![directive]
+        } else if header == 0 {
+            vec![directive, ConfigLine::parse("")]
+        } else {
+            vec![ConfigLine::parse(""), directive]
+        };
+        main.lines.splice(header..header, lines);
+        main.modified = true;
+    }
+
+    /// Whether a global `Include` reads `path`, whether or not it exists yet
+    pub fn includes(&self, path: impl AsRef<Path>) -> bool {
+        self.directives().iter().any(|d| {
+            d.scope == Scope::Global
+                && d.keyword.eq_ignore_ascii_case("include")
+                && pattern_index(&d.value, path.as_ref()).is_some()
+        })
+    }
+
+    /// Replace the content of `path`, adding it where a global `Include`
+    /// reads it when it is not part of the configuration yet
+    ///
+    /// The file is written by [`SshdConfig::save`] like any other edit.
+    pub fn put_file(&mut self, path: impl AsRef<Path>, text: &str) {
+        let path = path.as_ref();
+        let mut file = ConfigFile::parse(path, text);
+        file.modified = true;
+
+        if let Some(existing) = self.files.iter_mut().find(|f| f.path == path) {
+            *existing = file;
+            return;
+        }
+
+        let index = self.files.len();
+        self.files.push(file);
+
+        let includes: Vec<(usize, usize, usize)> = self
+            .positions()
+            .into_iter()
+            .filter(|(_, _, scope)| *scope == Scope::Global)
+            .filter_map(|(file, line, _)| match &self.files[file].lines[line].entry {
+                Entry::Directive { keyword, value, .. } if keyword.eq_ignore_ascii_case("include") => {
+                    pattern_index(value, path).map(|pattern| (file, line, pattern))
+                }
+                _ => None,
+            })
+            .collect();
+
+        for (file, line, pattern) in includes {
+            let Entry::Directive { value, included, .. } = &self.files[file].lines[line].entry else {
+                continue;
+            };
+            // Included files are read pattern by pattern, each sorted by name
+            let key = |i: usize| (pattern_index(value, &self.files[i].path), &self.files[i].path);
+            let at = included
+                .iter()
+                .position(|&i| key(i) > (Some(pattern), &self.files[index].path))
+                .unwrap_or(included.len());
+
+            if let Entry::Directive { included, .. } = &mut self.files[file].lines[line].entry {
+                included.insert(at, index);
+            }
+        }
+    }
+
+    /// Comment out `directive`, returning whether it was found
+    pub fn comment_out(&mut self, directive: &Directive) -> bool {
+        let Some(file) = self.files.iter_mut().find(|f| f.path == directive.file) else {
+            return false;
+        };
+        let Some(line) = directive.line.checked_sub(1).and_then(|i| file.lines.get_mut(i)) else {
+            return false;
+        };
+        if !matches!(&line.entry, Entry::Directive { keyword, .. } if *keyword == directive.keyword) {
+            return false;
+        }
+
+        let indent = line.indent().to_string();
+        *line = ConfigLine::parse(&format!("{}#{}", indent, line.text.trim_start()));
+        file.modified = true;
+        true
+    }
+
+    /// Write every edited file, backing up its previous version
+    ///
+    /// Returns whether any file changed.
+    pub fn save(&mut self) -> Result<bool> {
+        let mut changed = false;
+        for file in self.files.iter_mut().filter(|file| file.modified) {
+            changed |= safe_write_file(&file.path, &file.text(), true)?;
+            file.modified = false;
+        }
+        Ok(changed)
+    }
+
+    fn remove_positions(&mut self, mut positions: Vec<(usize, usize)>) {
+        // Later lines first, so earlier indices stay valid
+        positions.sort_unstable_by(|a, b| b.cmp(a));
+        for (file, index) in positions {
+            self.files[file].lines.remove(index);
+            self.files[file].modified = true;
+        }
+    }
+
+    fn global_positions(&self, keyword: &str) -> Vec<(usize, usize)> {
+        self.positions()
+            .into_iter()
+            .filter(|(file, index, scope)| {
+                *scope == Scope::Global
+                    && matches!(
+                        &self.files[*file].lines[*index].entry,
+                        Entry::Directive { keyword: k, .. } if k.eq_ignore_ascii_case(keyword)
+                    )
+            })
+            .map(|(file, index, _)| (file, index))
+            .collect()
+    }
+
+    /// `(file, line index, scope)` of every directive in read order
+    fn positions(&self) -> Vec<(usize, usize, Scope)> {
+        let mut positions = Vec::new();
+        self.walk(0, Scope::Global, 0, &mut positions);
+        positions
+    }
+
+    fn walk(&self, file: usize, mut scope: Scope, depth: usize, out: &mut Vec<(usize, usize, Scope)>) {
+        for (index, line) in self.files[file].lines.iter().enumerate() {
+            match &line.entry {
+                Entry::Other => {}
+                Entry::Match(criteria) => scope = Scope::Match(criteria.clone()),
+                Entry::Directive { included, .. } => {
+                    out.push((file, index, scope.clone()));
+                    if depth < MAX_INCLUDE_DEPTH {
+                        for &included in included {
+                            self.walk(included, scope.clone(), depth + 1, out);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Split `Keyword value` or `Keyword=value`
+fn split_directive(line: &str) -> (String, String) {
+    let mut parts = line.splitn(2, |c: char| c.is_whitespace() || c == '=');
+    let keyword = parts.next().unwrap_or("");
+    let value = parts.next().unwrap_or("").trim().trim_start_matches('=').trim();
+    (keyword.to_string(), value.to_string())
+}
+
+/// Files matching an `Include` pattern, in the order sshd reads them
+fn expand_include(pattern: &str) -> Result<Vec<PathBuf>> {
+    let pattern = Path::new(SSH_DIR).join(pattern);
+    let resolved = resolve(&pattern);
+    let matches = glob::glob_with(&resolved.to_string_lossy(), GLOB_OPTIONS).map_err(|e| {
+        FluxError::parse(format!("Invalid Include pattern {}: {}", pattern.display(), e))
+    })?;
+
+    Ok(matches
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .map(system_path)
+        .collect())
+}
+
+/// Index of the first pattern of an `Include` value that matches `path`
+fn pattern_index(patterns: &str, path: &Path) -> Option<usize> {
+    patterns.split_whitespace().position(|pattern| {
+        let pattern = Path::new(SSH_DIR).join(pattern);
+        Pattern::new(&pattern.to_string_lossy())
+            .is_ok_and(|pattern| pattern.matches_path_with(path, GLOB_OPTIONS))
+    })
+}
+
+/// Path as sshd sees it for a path resolved below `--root`
+fn system_path(path: PathBuf) -> PathBuf {
+    match paths::root() {
+        Some(root) => match path.strip_prefix(&root) {
+            Ok(relative) => Path::new("/").join(relative),
+            Err(_) => path,
+        },
+        None => path,
+    }
+}
+
+/// Whether `keyword` may appear more than once, every occurrence counting
+pub fn is_multi_value(keyword: &str) -> bool {
+    MULTI_VALUE_DIRECTIVES.contains(&keyword.to_ascii_lowercase().as_str())
+}
+
+/// Restore the conventional capitalisation of a directive keyword
+pub fn directive_name(keyword: &str) -> String {
+    const NAMES: &[&str] = &[
+        "Port", "AddressFamily", "ListenAddress", "Protocol", "HostKey", "Ciphers", "MACs",
+        "KexAlgorithms", "HostKeyAlgorithms", "PubkeyAcceptedAlgorithms", "PermitRootLogin",
+        "PubkeyAuthentication", "PasswordAuthentication", "PermitEmptyPasswords",
+        "ChallengeResponseAuthentication", "KbdInteractiveAuthentication", "KerberosAuthentication",
+        "GSSAPIAuthentication", "HostbasedAuthentication", "UsePAM", "AuthorizedKeysFile",
+        "StrictModes", "MaxAuthTries", "MaxSessions", "MaxStartups", "LoginGraceTime",
+        "ClientAliveInterval", "ClientAliveCountMax", "AllowAgentForwarding", "AllowTcpForwarding",
+        "X11Forwarding", "PermitTunnel", "PermitUserEnvironment", "AllowUsers", "AllowGroups",
+        "DenyUsers", "DenyGroups", "AcceptEnv", "PrintMotd", "SyslogFacility", "LogLevel",
+        "Subsystem", "Banner", "Include", "Match",
+    ];
+
+    NAMES
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(keyword))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| keyword.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    const DEBIAN: &str = "\
+Include /etc/ssh/sshd_config.d/*.conf
+
+#Port 22
+#PermitRootLogin prohibit-password
+PasswordAuthentication=yes
+  port 2200
+
+# Example of overriding settings on a per-user basis
+Match User backup
+    PasswordAuthentication no
+";
+
+    #[test]
+    fn test_query_case_insensitive_and_scoped() {
+        let config = SshdConfig::parse("/etc/ssh/sshd_config", DEBIAN);
+
+        assert_eq!(config.get("PORT").unwrap().value, "2200");
+        assert_eq!(config.get("port").unwrap().line, 6);
+        assert_eq!(config.values("passwordauthentication"), vec!["yes"]);
+        assert!(config.get("PermitRootLogin").is_none());
+
+        let scoped: Vec<_> = config
+            .directives()
+            .into_iter()
+            .filter(|d| d.scope == Scope::Match("User backup".to_string()))
+            .collect();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].to_string(), "PasswordAuthentication no");
+    }
+
+    #[test]
+    fn test_set_preserves_comments_and_match_blocks() {
+        let mut config = SshdConfig::parse("/etc/ssh/sshd_config", DEBIAN);
+
+        config.set("port", &["2222"]);
+        config.set("PasswordAuthentication", &["no"]);
+        config.set("PermitRootLogin", &["no"]);
+        config.set("MaxAuthTries", &["3"]);
+
+        assert_eq!(
+            config.text("/etc/ssh/sshd_config").unwrap(),
+            "\
+Include /etc/ssh/sshd_config.d/*.conf
+
+#Port 22
+#PermitRootLogin prohibit-password
+PermitRootLogin no
+PasswordAuthentication no
+  Port 2222
+MaxAuthTries 3
+
+# Example of overriding settings on a per-user basis
+Match User backup
+    PasswordAuthentication no
+"
+        );
+    }
+
+    #[test]
+    fn test_set_multi_value_and_remove() {
+        let mut config = SshdConfig::parse(
+            "/etc/ssh/sshd_config",
+            "ListenAddress 0.0.0.0\nX11Forwarding yes\nListenAddress ::\n",
+        );
+
+        config.set("ListenAddress", &["10.0.0.1", "10.0.0.2"]);
+        assert_eq!(config.values("listenaddress"), vec!["10.0.0.1", "10.0.0.2"]);
+
+        assert_eq!(config.remove("x11forwarding"), 1);
+        assert_eq!(
+            config.text("/etc/ssh/sshd_config").unwrap(),
+            "ListenAddress 10.0.0.1\nListenAddress 10.0.0.2\n"
+        );
+    }
+
+    #[test]
+    fn test_allow_users_lines_accumulate() {
+        let text = "AllowUsers alice bob\nPort 22\nAllowUsers carol\n";
+        let mut config = SshdConfig::parse("/etc/ssh/sshd_config", text);
+        assert_eq!(config.values("allowusers"), vec!["alice bob", "carol"]);
+        assert_eq!(config.text("/etc/ssh/sshd_config").unwrap(), text);
+
+        // Every line is replaced, not just the last one
+        config.set("allowusers", &["alice", "dave"]);
+        assert_eq!(
+            config.text("/etc/ssh/sshd_config").unwrap(),
+            "AllowUsers alice\nAllowUsers dave\nPort 22\n"
+        );
+    }
+
+    #[test]
+    fn test_prepend_and_put_file() {
+        let mut config = SshdConfig::parse(
+            "/etc/ssh/sshd_config",
+            "# Header\n\nPort 22\nInclude sshd_config.d/90-*.conf\n",
+        );
+        assert!(!config.includes("/etc/ssh/sshd_config.d/50-flux-hardening.conf"));
+
+        config.prepend("include", "/etc/ssh/sshd_config.d/*.conf");
+        assert!(config.includes("/etc/ssh/sshd_config.d/50-flux-hardening.conf"));
+        assert!(!config.includes("/etc/ssh/sshd_config.d/nested/50.conf"));
+
+        config.put_file("/etc/ssh/sshd_config.d/90-late.conf", "Port 3\n");
+        config.put_file("/etc/ssh/sshd_config.d/50-flux-hardening.conf", "Port 2\n");
+
+        // Each Include reads the files it matches in name order
+        let ports: Vec<_> = config.get_all("Port").iter().map(|d| d.value.clone()).collect();
+        assert_eq!(ports, vec!["2", "3", "22", "3"]);
+        assert_eq!(
+            config.text("/etc/ssh/sshd_config").unwrap(),
+            "# Header\n\nInclude /etc/ssh/sshd_config.d/*.conf\n\nPort 22\nInclude sshd_config.d/90-*.conf\n"
+        );
+    }
+
+    #[test]
+    fn test_comment_out() {
+        let mut config = SshdConfig::parse("/etc/ssh/sshd_config", "  Port 22\nPort 2222\n");
+
+        let first = config.get("port").unwrap();
+        assert!(config.comment_out(&first));
+        assert!(!config.comment_out(&first));
+
+        assert_eq!(config.values("Port"), vec!["2222"]);
+        assert_eq!(config.text("/etc/ssh/sshd_config").unwrap(), "  #Port 22\nPort 2222\n");
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_resolves_includes() {
+        let dir = TempDir::new().unwrap();
+        let drop_ins = dir.path().join("sshd_config.d");
+        fs::create_dir(&drop_ins).unwrap();
+        fs::write(drop_ins.join("50-cloud-init.conf"), "PasswordAuthentication yes\n").unwrap();
+        fs::write(drop_ins.join("60-local.conf"), "PasswordAuthentication no\nPort 2222\n").unwrap();
+        fs::write(drop_ins.join("ignored.txt"), "Port 1\n").unwrap();
+
+        let main = dir.path().join("sshd_config");
+        fs::write(
+            &main,
+            format!("Include {}/*.conf\nPasswordAuthentication no\n", drop_ins.display()),
+        )
+        .unwrap();
+
+        let mut config = SshdConfig::load(&main).unwrap();
+
+        // The drop-in is read first, so it wins over the main file
+        let effective = config.get("PasswordAuthentication").unwrap();
+        assert_eq!(effective.value, "yes");
+        assert_eq!(effective.file, drop_ins.join("50-cloud-init.conf"));
+        assert_eq!(config.values("Port"), vec!["2222"]);
+
+        config.set("PasswordAuthentication", &["no"]);
+        assert!(config.save().unwrap());
+        assert_eq!(
+            fs::read_to_string(drop_ins.join("50-cloud-init.conf")).unwrap(),
+            "PasswordAuthentication no\n"
+        );
+        assert_eq!(SshdConfig::load(&main).unwrap().values("passwordauthentication"), vec!["no"]);
+    }
+}
@@ -4,20 +4,91 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    file_ops::safe_write_file,
-    logging::{log_info, log_success, log_warn},
-    system::check_command,
+    changes, dry_run,
+    file_ops::{copy_file_with_perms, create_dir_all, remove_path, safe_write_file},
+    logging::{log_debug, log_info, log_success, log_warn},
+    paths::{self, resolve},
+    runner::{self, Invocation},
+    schema::{self, ConfigSchema, SectionSchema},
+    system::{check_command, command_output, query_command},
+    template,
     user_input::{prompt_yes_no, select_from_menu},
 };
-use crate::modules::{Module, ModuleBase, ModuleInfo};
+use crate::modules::{parse_spec, Drift, Module, ModuleBase, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::process::Command;
 
 const SYSCTL_CONFIG_PATH: &str = "/etc/sysctl.d/99-flux-hardening.conf";
 const SYSCTL_BACKUP_DIR: &str = "/var/backups/flux/sysctl";
 
+/// Desired kernel parameters, read from `[modules.sysctl]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SysctlSpec {
+    /// Apply the built-in hardening parameters
+    #[serde(alias = "network_hardening")]
+    pub hardening: bool,
+    /// Also apply the performance tuning parameters
+    pub performance: bool,
+    /// Additional parameters, e.g. `"net.ipv4.ip_forward" = "0"`
+    pub custom: BTreeMap<String, String>,
+}
+
+impl Default for SysctlSpec {
+    fn default() -> Self {
+        Self {
+            hardening: true,
+            performance: false,
+            custom: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigSchema for SysctlSpec {
+    fn schema() -> serde_json::Value {
+        use schema::*;
+
+        object(
+            "Kernel parameters applied by `flux apply`",
+            vec![
+                ("hardening", boolean("Apply the built-in hardening parameters")),
+                ("performance", boolean("Also apply the performance tuning parameters")),
+                (
+                    "custom",
+                    map("Additional parameters, e.g. \"net.ipv4.ip_forward\" = \"0\"", string("")),
+                ),
+            ],
+        )
+    }
+}
+
+/// Collapse whitespace so multi-field values like `ip_local_port_range` compare equal
+fn normalize_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A titled template section of the parameters whose names start with `prefix`
+fn param_section(
+    title: &str,
+    params: &HashMap<String, (String, String)>,
+    prefix: &str,
+) -> serde_json::Value {
+    let mut params: Vec<_> = params.iter().filter(|(k, _)| k.starts_with(prefix)).collect();
+    params.sort_by_key(|(k, _)| k.as_str());
+
+    let params: Vec<_> = params
+        .into_iter()
+        .map(|(key, (value, description))| {
+            json!({ "key": key, "value": value, "description": description })
+        })
+        .collect();
+    json!({ "title": title, "params": params })
+}
+
 pub struct SysctlModule {
     base: ModuleBase,
 }
@@ -32,6 +103,8 @@ impl SysctlModule {
             tags: vec!["security".to_string(), "kernel".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
         Self {
             base: ModuleBase { info },
@@ -255,92 +328,45 @@ impl SysctlModule {
         params
     }
 
-    /// Generate sysctl configuration file
-    fn generate_config(&self, include_performance: bool) -> String {
-        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-        let mut config = format!(
-            r#"# Flux Framework - Sysctl Hardening Configuration
-# Generated: {}
-# This file contains kernel security hardening parameters
-
-"#,
-            timestamp
-        );
-
-        // Add hardening parameters
-        config.push_str("# =========================================\n");
-        config.push_str("# KERNEL HARDENING\n");
-        config.push_str("# =========================================\n\n");
-
-        let hardening_params = self.get_hardening_params();
-        let mut kernel_params: Vec<_> = hardening_params
-            .iter()
-            .filter(|(k, _)| k.starts_with("kernel."))
-            .collect();
-        kernel_params.sort_by_key(|(k, _)| k.as_str());
-
-        for (key, (value, description)) in kernel_params {
-            config.push_str(&format!("# {}\n", description));
-            config.push_str(&format!("{} = {}\n\n", key, value));
-        }
-
-        // Network hardening
-        config.push_str("# =========================================\n");
-        config.push_str("# NETWORK HARDENING\n");
-        config.push_str("# =========================================\n\n");
-
-        let mut net_params: Vec<_> = hardening_params
-            .iter()
-            .filter(|(k, _)| k.starts_with("net."))
-            .collect();
-        net_params.sort_by_key(|(k, _)| k.as_str());
-
-        for (key, (value, description)) in net_params {
-            config.push_str(&format!("# {}\n", description));
-            config.push_str(&format!("{} = {}\n\n", key, value));
+    /// Variables of the `sysctl/99-flux-hardening.conf` template
+    fn config_vars(&self, spec: &SysctlSpec) -> serde_json::Value {
+        let mut sections = Vec::new();
+        if spec.hardening {
+            let hardening_params = self.get_hardening_params();
+            sections.push(param_section("KERNEL HARDENING", &hardening_params, "kernel."));
+            sections.push(param_section("NETWORK HARDENING", &hardening_params, "net."));
+            sections.push(param_section("FILESYSTEM HARDENING", &hardening_params, "fs."));
         }
-
-        // Filesystem hardening
-        config.push_str("# =========================================\n");
-        config.push_str("# FILESYSTEM HARDENING\n");
-        config.push_str("# =========================================\n\n");
-
-        let mut fs_params: Vec<_> = hardening_params
-            .iter()
-            .filter(|(k, _)| k.starts_with("fs."))
-            .collect();
-        fs_params.sort_by_key(|(k, _)| k.as_str());
-
-        for (key, (value, description)) in fs_params {
-            config.push_str(&format!("# {}\n", description));
-            config.push_str(&format!("{} = {}\n\n", key, value));
+        if spec.performance {
+            sections.push(param_section("PERFORMANCE TUNING", &self.get_performance_params(), ""));
         }
 
-        // Performance tuning (optional)
-        if include_performance {
-            config.push_str("# =========================================\n");
-            config.push_str("# PERFORMANCE TUNING\n");
-            config.push_str("# =========================================\n\n");
-
-            let perf_params = self.get_performance_params();
-            let mut perf_vec: Vec<_> = perf_params.iter().collect();
-            perf_vec.sort_by_key(|(k, _)| k.as_str());
-
-            for (key, (value, description)) in perf_vec {
-                config.push_str(&format!("# {}\n", description));
-                config.push_str(&format!("{} = {}\n\n", key, value));
-            }
-        }
+        let mut vars = json!(spec);
+        vars["sections"] = json!(sections);
+        vars["generated"] = json!(template::generated_by());
+        vars
+    }
 
-        config
+    /// Generate sysctl configuration file
+    fn generate_config(&self, spec: &SysctlSpec) -> Result<String> {
+        template::render("sysctl/99-flux-hardening.conf", &self.config_vars(spec))
     }
 
     /// Apply sysctl hardening
     async fn apply_hardening(&self, include_performance: bool, force: bool) -> Result<()> {
         log_info("Applying sysctl hardening configuration");
 
-        // Check if config already exists
-        if fs::metadata(SYSCTL_CONFIG_PATH).is_ok() && !force {
+        let spec = SysctlSpec {
+            hardening: true,
+            performance: include_performance,
+            custom: BTreeMap::new(),
+        };
+
+        // Check if a different config already exists
+        let current = fs::read_to_string(resolve(SYSCTL_CONFIG_PATH)).ok();
+        let desired = self.generate_config(&spec)?;
+        let differs = current.is_some_and(|current| current != desired);
+        if differs && !force {
             let overwrite = prompt_yes_no(
                 "Sysctl configuration already exists. Overwrite?",
                 false,
@@ -351,37 +377,104 @@ impl SysctlModule {
             }
         }
 
+        self.install_config(&spec).await
+    }
+
+    /// Converge sysctl configuration to the given spec without prompting
+    async fn apply_spec(&self, spec: &SysctlSpec) -> Result<()> {
+        if !spec.hardening && !spec.performance && spec.custom.is_empty() {
+            log_info("No sysctl parameters configured, nothing to apply");
+            return Ok(());
+        }
+
+        self.install_config(spec).await
+    }
+
+    /// Parameters the given spec would set, keyed by sysctl name
+    fn desired_params(&self, spec: &SysctlSpec) -> BTreeMap<String, String> {
+        let mut params = BTreeMap::new();
+
+        if spec.hardening {
+            params.extend(self.get_hardening_params().into_iter().map(|(k, (v, _))| (k, v)));
+        }
+        if spec.performance {
+            params.extend(self.get_performance_params().into_iter().map(|(k, (v, _))| (k, v)));
+        }
+        params.extend(spec.custom.clone());
+
+        params
+    }
+
+    /// Compare live kernel parameters against the given spec
+    async fn plan_spec(&self, spec: &SysctlSpec) -> Result<Vec<Drift>> {
+        let mut drift = Vec::new();
+
+        for (key, expected) in self.desired_params(spec) {
+            let actual = match query_command("sysctl", &["-n", &key]) {
+                Ok(value) => value,
+                Err(_) => {
+                    log_debug(format!("Skipping {}: not supported by this kernel", key));
+                    continue;
+                }
+            };
+
+            if normalize_value(&actual) != normalize_value(&expected) {
+                drift.push(Drift::new(key, expected, Some(actual.trim().to_string())));
+            }
+        }
+
+        Ok(drift)
+    }
+
+    /// Back up, write and load the sysctl configuration file for `spec`
+    ///
+    /// Nothing is written or loaded when the file is current and the live
+    /// kernel parameters already match.
+    async fn install_config(&self, spec: &SysctlSpec) -> Result<()> {
+        let config = &self.generate_config(spec)?;
+        let current = fs::read_to_string(resolve(SYSCTL_CONFIG_PATH)).ok();
+
         // Backup existing configuration
-        if fs::metadata(SYSCTL_CONFIG_PATH).is_ok() {
-            fs::create_dir_all(SYSCTL_BACKUP_DIR)?;
+        if current.as_ref().is_some_and(|current| current != config) {
+            create_dir_all(SYSCTL_BACKUP_DIR)?;
             let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
             let backup_path = format!("{}/sysctl-{}.conf.bak", SYSCTL_BACKUP_DIR, timestamp);
-            fs::copy(SYSCTL_CONFIG_PATH, &backup_path)?;
+            copy_file_with_perms(resolve(SYSCTL_CONFIG_PATH), &backup_path)?;
             log_info(&format!("Backed up existing config to: {}", backup_path));
         }
 
-        // Generate and write configuration
-        let config = self.generate_config(include_performance);
-        safe_write_file(SYSCTL_CONFIG_PATH, &config, true)?;
+        // Write configuration
+        if safe_write_file(SYSCTL_CONFIG_PATH, config, true)? {
+            log_success(&format!(
+                "Sysctl configuration written to: {}",
+                SYSCTL_CONFIG_PATH
+            ));
+        } else if paths::root().is_some() || self.plan_spec(spec).await?.is_empty() {
+            changes::ok("kernel parameters");
+            log_info("Sysctl configuration already applied");
+            return Ok(());
+        }
 
-        log_success(&format!(
-            "Sysctl configuration written to: {}",
-            SYSCTL_CONFIG_PATH
-        ));
+        // Loading the file would change the host kernel, not the tree
+        if paths::root().is_some() {
+            log_info("Sysctl settings take effect when the system under --root boots");
+            return Ok(());
+        }
 
         // Apply the configuration
         log_info("Applying sysctl settings...");
-        let output = Command::new("sysctl")
-            .arg("-p")
-            .arg(SYSCTL_CONFIG_PATH)
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to apply sysctl: {}", e)))?;
+        if dry_run::intercept_command("sysctl", &["-p", SYSCTL_CONFIG_PATH]) {
+            changes::changed("kernel parameters", "would load");
+            return Ok(());
+        }
 
-        if output.status.success() {
+        let output = runner::run(&Invocation::new("sysctl", &["-p", SYSCTL_CONFIG_PATH]))?;
+        changes::changed("kernel parameters", format!("loaded {}", SYSCTL_CONFIG_PATH));
+
+        if output.success {
             log_success("Sysctl hardening applied successfully");
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            log_warn(&format!("Some sysctl parameters failed to apply: {}", stderr));
+            log_warn(format!("Some sysctl parameters failed to apply: {}", output.stderr));
             log_info("This is normal for parameters not supported by your kernel");
         }
 
@@ -390,20 +483,20 @@ impl SysctlModule {
 
     /// Show current sysctl configuration
     async fn show_config(&self) -> Result<()> {
-        if !fs::metadata(SYSCTL_CONFIG_PATH).is_ok() {
+        if fs::metadata(resolve(SYSCTL_CONFIG_PATH)).is_err() {
             log_warn("Flux sysctl configuration not found");
             log_info(&format!("Run 'flux module {} --apply' to create it", self.name()));
             return Ok(());
         }
 
         log_info("Current Flux sysctl configuration:");
-        println!("\n{}", "=".repeat(70));
+        outln!("\n{}", "=".repeat(70));
 
-        let config = fs::read_to_string(SYSCTL_CONFIG_PATH)
+        let config = fs::read_to_string(resolve(SYSCTL_CONFIG_PATH))
             .map_err(|e| FluxError::system(format!("Failed to read config: {}", e)))?;
 
-        println!("{}", config);
-        println!("{}", "=".repeat(70));
+        outln!("{}", config);
+        outln!("{}", "=".repeat(70));
 
         Ok(())
     }
@@ -412,7 +505,7 @@ impl SysctlModule {
     async fn verify_settings(&self) -> Result<()> {
         log_info("Verifying sysctl hardening settings");
 
-        if !fs::metadata(SYSCTL_CONFIG_PATH).is_ok() {
+        if fs::metadata(resolve(SYSCTL_CONFIG_PATH)).is_err() {
             return Err(FluxError::Module(
                 "Flux sysctl configuration not found. Apply hardening first.".to_string(),
             ));
@@ -422,34 +515,30 @@ impl SysctlModule {
         let mut success_count = 0;
         let mut fail_count = 0;
 
-        println!("\n{:<50} {:<15} {:<15} {}", "Parameter", "Expected", "Current", "Status");
-        println!("{}", "-".repeat(95));
+        outln!("\n{:<50} {:<15} {:<15} {}", "Parameter", "Expected", "Current", "Status");
+        outln!("{}", "-".repeat(95));
 
         for (key, (expected_value, _)) in params.iter() {
             // Get current value
-            let output = Command::new("sysctl")
-                .arg("-n")
-                .arg(key)
-                .output()
-                .ok();
+            let output = command_output("sysctl", &["-n", key]).ok();
 
             if let Some(out) = output {
-                let current_value = String::from_utf8_lossy(&out.stdout).trim().to_string();
+                let current_value = out.stdout.trim().to_string();
 
                 if current_value == *expected_value {
-                    println!("{:<50} {:<15} {:<15} ✓", key, expected_value, current_value);
+                    outln!("{:<50} {:<15} {:<15} ✓", key, expected_value, current_value);
                     success_count += 1;
                 } else {
-                    println!("{:<50} {:<15} {:<15} ✗", key, expected_value, current_value);
+                    outln!("{:<50} {:<15} {:<15} ✗", key, expected_value, current_value);
                     fail_count += 1;
                 }
             } else {
-                println!("{:<50} {:<15} {:<15} N/A", key, expected_value, "not available");
+                outln!("{:<50} {:<15} {:<15} N/A", key, expected_value, "not available");
             }
         }
 
-        println!("{}", "-".repeat(95));
-        println!(
+        outln!("{}", "-".repeat(95));
+        outln!(
             "\nVerification complete: {} passed, {} failed",
             success_count, fail_count
         );
@@ -468,7 +557,7 @@ impl SysctlModule {
     async fn remove_hardening(&self) -> Result<()> {
         log_info("Removing sysctl hardening configuration");
 
-        if !fs::metadata(SYSCTL_CONFIG_PATH).is_ok() {
+        if fs::metadata(resolve(SYSCTL_CONFIG_PATH)).is_err() {
             log_warn("Flux sysctl configuration not found");
             return Ok(());
         }
@@ -484,14 +573,14 @@ impl SysctlModule {
         }
 
         // Backup before removal
-        fs::create_dir_all(SYSCTL_BACKUP_DIR)?;
+        create_dir_all(SYSCTL_BACKUP_DIR)?;
         let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
         let backup_path = format!("{}/sysctl-removed-{}.conf", SYSCTL_BACKUP_DIR, timestamp);
-        fs::copy(SYSCTL_CONFIG_PATH, &backup_path)?;
+        copy_file_with_perms(resolve(SYSCTL_CONFIG_PATH), &backup_path)?;
         log_info(&format!("Backed up config to: {}", backup_path));
 
         // Remove configuration file
-        fs::remove_file(SYSCTL_CONFIG_PATH)?;
+        remove_path(SYSCTL_CONFIG_PATH)?;
         log_success("Sysctl hardening configuration removed");
 
         log_warn("System defaults will be used after reboot");
@@ -606,6 +695,29 @@ EXAMPLES:
         )
     }
 
+    fn supports_apply(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> Option<SectionSchema> {
+        Some(SectionSchema::of::<SysctlSpec>())
+    }
+
+    fn template_vars(&self, config: &Config) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        let spec: SysctlSpec = config.module_settings(self.name())?;
+        Ok(vec![("sysctl/99-flux-hardening.conf", self.config_vars(&spec))])
+    }
+
+    async fn apply(&self, spec: &toml::Value, _config: &Config) -> Result<()> {
+        let spec: SysctlSpec = parse_spec(self.name(), spec)?;
+        self.apply_spec(&spec).await
+    }
+
+    async fn plan(&self, spec: &toml::Value, _config: &Config) -> Result<Vec<Drift>> {
+        let spec: SysctlSpec = parse_spec(self.name(), spec)?;
+        self.plan_spec(&spec).await
+    }
+
     async fn execute(&self, args: Vec<String>, _config: &Config) -> Result<()> {
         if args.is_empty() || args.contains(&"--menu".to_string()) {
             return self.show_menu().await;
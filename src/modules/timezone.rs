@@ -1,11 +1,13 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
+    changes, dry_run,
     logging::{log_info, log_success},
-    system::execute_command,
+    system::{execute_command, query_command},
     user_input::{prompt_input, prompt_with_default},
 };
 use crate::modules::{Module, ModuleBase, ModuleContext, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::Colorize;
@@ -25,6 +27,8 @@ impl TimezoneModule {
             tags: vec!["system".to_string(), "timezone".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
 
         Self {
@@ -87,27 +91,27 @@ impl TimezoneModule {
     }
 
     async fn show_current_timezone(&self) -> Result<()> {
-        println!("{}", "=== Current Timezone ===".cyan());
-        println!();
+        outln!("{}", "=== Current Timezone ===".cyan());
+        outln!();
 
         // Get current timezone using timedatectl
-        let output = execute_command("timedatectl", &["show", "--property=Timezone", "--value"])?;
+        let output = query_command("timedatectl", &["show", "--property=Timezone", "--value"])?;
         let timezone = output.trim();
 
-        println!("Current timezone: {}", timezone.green());
+        outln!("Current timezone: {}", timezone.green());
 
         // Also show current date/time
-        let datetime_output = execute_command("date", &["+%Y-%m-%d %H:%M:%S %Z"])?;
-        println!("Current date/time: {}", datetime_output.trim());
+        let datetime_output = query_command("date", &["+%Y-%m-%d %H:%M:%S %Z"])?;
+        outln!("Current date/time: {}", datetime_output.trim());
 
         Ok(())
     }
 
     async fn list_timezones(&self) -> Result<()> {
-        println!("{}", "=== Available Timezones ===".cyan());
-        println!();
-        println!("Listing common timezones. Use 'timedatectl list-timezones' for a complete list.");
-        println!();
+        outln!("{}", "=== Available Timezones ===".cyan());
+        outln!();
+        outln!("Listing common timezones. Use 'timedatectl list-timezones' for a complete list.");
+        outln!();
 
         // Show common timezones by region
         let common_timezones = vec![
@@ -150,14 +154,14 @@ impl TimezoneModule {
         ];
 
         for (region, zones) in common_timezones {
-            println!("{}", format!("{}:", region).white().bold());
+            outln!("{}", format!("{}:", region).white().bold());
             for zone in zones {
-                println!("  {}", zone);
+                outln!("  {}", zone);
             }
-            println!();
+            outln!();
         }
 
-        println!("Use the full timezone name (e.g., America/Toronto) when setting.");
+        outln!("Use the full timezone name (e.g., America/Toronto) when setting.");
 
         Ok(())
     }
@@ -166,7 +170,7 @@ impl TimezoneModule {
         log_info(format!("Setting timezone to {}", timezone));
 
         // Validate timezone exists
-        let result = execute_command("timedatectl", &["list-timezones"]);
+        let result = query_command("timedatectl", &["list-timezones"]);
         if let Ok(output) = result {
             if !output.lines().any(|line| line.trim() == timezone) {
                 return Err(FluxError::validation(format!(
@@ -176,14 +180,25 @@ impl TimezoneModule {
             }
         }
 
+        let resource = format!("timezone {}", timezone);
+        let current = query_command("timedatectl", &["show", "--property=Timezone", "--value"])
+            .unwrap_or_default();
+        if current.trim() == timezone {
+            changes::ok(resource);
+            log_info(format!("Timezone already is {}", timezone));
+            return Ok(());
+        }
+
         // Set timezone using timedatectl
         execute_command("timedatectl", &["set-timezone", timezone])?;
+        let detail = if dry_run::is_dry_run() { "would set" } else { "set" };
+        changes::changed(resource, format!("{}, was {}", detail, current.trim()));
 
         log_success(format!("Timezone set to {}", timezone));
 
         // Show current time in new timezone
-        let datetime_output = execute_command("date", &["+%Y-%m-%d %H:%M:%S %Z"])?;
-        println!("Current date/time: {}", datetime_output.trim());
+        let datetime_output = query_command("date", &["+%Y-%m-%d %H:%M:%S %Z"])?;
+        outln!("Current date/time: {}", datetime_output.trim());
 
         Ok(())
     }
@@ -191,14 +206,14 @@ impl TimezoneModule {
     async fn configure_interactive(&self) -> Result<()> {
         log_info("Starting interactive timezone configuration");
 
-        println!("{}", "=== Timezone Configuration ===".cyan());
-        println!();
+        outln!("{}", "=== Timezone Configuration ===".cyan());
+        outln!();
 
         // Show current timezone
-        let current_tz = execute_command("timedatectl", &["show", "--property=Timezone", "--value"])?;
+        let current_tz = query_command("timedatectl", &["show", "--property=Timezone", "--value"])?;
         let current_tz = current_tz.trim();
-        println!("Current timezone: {}", current_tz.green());
-        println!();
+        outln!("Current timezone: {}", current_tz.green());
+        outln!();
 
         // Prompt for new timezone with default
         let timezone = prompt_with_default(
@@ -229,7 +244,7 @@ impl Module for TimezoneModule {
 
     fn is_available(&self) -> bool {
         // Check if timedatectl is available
-        execute_command("which", &["timedatectl"]).is_ok()
+        query_command("which", &["timedatectl"]).is_ok()
     }
 
     fn help(&self) -> String {
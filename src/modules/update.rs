@@ -1,11 +1,13 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
+    changes, dry_run, facts,
     logging::{log_info, log_warn, ProgressIndicator},
-    system::{detect_distro, Distribution, execute_command},
+    system::{Distribution, execute_command, query_command},
     user_input::prompt_yes_no,
 };
 use crate::modules::{Module, ModuleBase, ModuleContext, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::Colorize;
@@ -25,6 +27,8 @@ impl UpdateModule {
             tags: vec!["system".to_string(), "packages".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
         
         Self {
@@ -100,7 +104,7 @@ impl UpdateModule {
     }
     
     async fn execute_update(&self, matches: &ArgMatches, ctx: &ModuleContext<'_>) -> Result<()> {
-        let distro = detect_distro()?;
+        let distro = facts::get().os.distribution.clone();
         
         // Check what action to perform
         if matches.get_flag("check") {
@@ -142,7 +146,7 @@ impl UpdateModule {
         
         // Check disk space
         let disk_stats = crate::helpers::system::get_system_status()?;
-        println!("Disk usage: {}", disk_stats.disk_usage);
+        outln!("Disk usage: {}", disk_stats.disk_usage);
         
         // Check internet connectivity
         let connectivity = crate::helpers::network::test_connectivity();
@@ -150,7 +154,7 @@ impl UpdateModule {
             return Err(FluxError::network("No internet connectivity"));
         }
         
-        println!("{}", "✓ System requirements check passed".green());
+        outln!("{}", "✓ System requirements check passed".green());
         Ok(())
     }
     
@@ -182,11 +186,12 @@ impl UpdateModule {
         let upgradable_count = self.count_upgradable_packages(distro).await?;
         
         if upgradable_count == 0 {
-            println!("{}", "No packages available for upgrade".green());
+            outln!("{}", "No packages available for upgrade".green());
+            changes::ok("packages");
             return Ok(());
         }
         
-        println!("Packages available for upgrade: {}", upgradable_count);
+        outln!("Packages available for upgrade: {}", upgradable_count);
         
         if !prompt_yes_no("Proceed with package upgrade?", true)? {
             return Ok(());
@@ -198,7 +203,7 @@ impl UpdateModule {
             d if d.is_debian_based() => {
                 if security_only {
                     // Get security updates
-                    let output = execute_command("apt", &["list", "--upgradable"])?;
+                    let output = query_command("apt", &["list", "--upgradable"])?;
                     let security_packages: Vec<String> = output
                         .lines()
                         .filter(|line| line.contains("-security"))
@@ -226,8 +231,11 @@ impl UpdateModule {
             }
             _ => return Err(FluxError::unsupported("Unsupported distribution")),
         }
+        facts::invalidate();
         
         progress.finish_with_message("Packages upgraded successfully");
+        let detail = if dry_run::is_dry_run() { "would upgrade" } else { "upgraded" };
+        changes::changed("packages", format!("{} {}", detail, upgradable_count));
         
         // Check if reboot is required
         self.check_reboot_required(distro);
@@ -247,6 +255,12 @@ impl UpdateModule {
             progress.inc(1);
             progress.set_message(&format!("Installing {}", package));
             
+            let resource = format!("package {}", package);
+            if self.is_installed(distro, package) {
+                changes::ok(resource);
+                continue;
+            }
+            
             let result = match distro {
                 d if d.is_debian_based() => {
                     execute_command("apt-get", &["install", "-y", package])
@@ -258,8 +272,16 @@ impl UpdateModule {
                 _ => Err(FluxError::unsupported("Unsupported distribution")),
             };
             
-            if result.is_err() {
-                failed_packages.push(package.clone());
+            match result {
+                Ok(_) => {
+                    facts::invalidate();
+                    let detail = if dry_run::is_dry_run() { "would install" } else { "installed" };
+                    changes::changed(resource, detail);
+                }
+                Err(e) => {
+                    changes::failed(resource, e.to_string());
+                    failed_packages.push(package.clone());
+                }
             }
         }
         
@@ -288,8 +310,9 @@ impl UpdateModule {
             }
             _ => return Err(FluxError::unsupported("Unsupported distribution")),
         }
+        facts::invalidate();
         
-        println!("{}", "✓ Package cleanup completed".green());
+        outln!("{}", "✓ Package cleanup completed".green());
         Ok(())
     }
     
@@ -327,7 +350,7 @@ APT::Periodic::Unattended-Upgrade "1";
                     
                     // Configure dnf-automatic
                     let config_path = "/etc/dnf/automatic.conf";
-                    let content = std::fs::read_to_string(config_path)?;
+                    let content = crate::helpers::file_ops::read_file_to_string(config_path)?;
                     let updated = content
                         .replace("apply_updates = no", "apply_updates = yes")
                         .replace("upgrade_type = default", "upgrade_type = security");
@@ -342,8 +365,9 @@ APT::Periodic::Unattended-Upgrade "1";
             }
             _ => return Err(FluxError::unsupported("Unsupported distribution")),
         }
+        facts::invalidate();
         
-        println!("{}", "✓ Automatic updates configured".green());
+        outln!("{}", "✓ Automatic updates configured".green());
         Ok(())
     }
     
@@ -370,11 +394,11 @@ APT::Periodic::Unattended-Upgrade "1";
         // Cleanup
         self.cleanup_packages(distro).await?;
         
-        println!("\n{}", "=== Update Summary ===".cyan());
-        println!("{}", "✓ Package lists updated".white());
-        println!("{}", "✓ System packages upgraded".white());
-        println!("{}", "✓ Essential packages installed".white());
-        println!("{}", "✓ Package cache cleaned".white());
+        outln!("\n{}", "=== Update Summary ===".cyan());
+        outln!("{}", "✓ Package lists updated".white());
+        outln!("{}", "✓ System packages upgraded".white());
+        outln!("{}", "✓ Essential packages installed".white());
+        outln!("{}", "✓ Package cache cleaned".white());
         
         // Check if reboot is required
         self.check_reboot_required(distro);
@@ -417,10 +441,20 @@ APT::Periodic::Unattended-Upgrade "1";
         Ok(())
     }
     
+    /// Whether the package manager reports `package` as installed
+    fn is_installed(&self, distro: &Distribution, package: &str) -> bool {
+        match distro {
+            d if d.is_debian_based() => query_command("dpkg-query", &["-W", "-f=${Status}", package])
+                .is_ok_and(|status| status.contains("install ok installed")),
+            d if d.is_redhat_based() => query_command("rpm", &["-q", package]).is_ok(),
+            _ => false,
+        }
+    }
+    
     async fn count_upgradable_packages(&self, distro: &Distribution) -> Result<u32> {
         match distro {
             d if d.is_debian_based() => {
-                let output = execute_command("apt", &["list", "--upgradable"])?;
+                let output = query_command("apt", &["list", "--upgradable"])?;
                 let count = output.lines().filter(|line| line.contains("upgradable")).count();
                 Ok(count as u32)
             }
@@ -454,7 +488,7 @@ APT::Periodic::Unattended-Upgrade "1";
         if distro.is_debian_based() {
             if std::path::Path::new("/var/run/reboot-required").exists() {
                 log_warn("System reboot is required");
-                println!("{}", "⚠ System reboot recommended".yellow());
+                outln!("{}", "⚠ System reboot recommended".yellow());
             }
         }
     }
@@ -1,23 +1,191 @@
 // src/modules/user.rs
 // User and group management module
 
-use crate::config::Config;
+use crate::config::{Config, Secret};
 use crate::error::{FluxError, Result};
 use crate::helpers::{
+    changes,
+    dry_run::{self, ChangeKind},
+    facts,
+    journal,
+    file_ops::{create_dir_all, file_exists, read_file_to_string, safe_write_file, set_permissions},
     logging::{log_info, log_success, log_warn},
-    system::check_command,
+    paths::resolve,
+    runner::{self, Invocation},
+    schema::{self, ConfigSchema, SectionSchema},
+    system::{check_command, execute_command},
     user_input::{prompt_input, prompt_password, prompt_with_default, prompt_yes_no, select_from_menu},
-    validation::validate_username,
+    validation::{validate_ssh_key, validate_username},
 };
-use crate::modules::{Module, ModuleBase, ModuleInfo};
+use crate::modules::{parse_spec, Drift, Module, ModuleBase, ModuleInfo};
+use crate::outln;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
-use std::process::Command;
-use users::{get_user_by_name, get_group_by_name};
+use users::{get_group_by_name, get_user_by_name, get_user_groups};
 use users::os::unix::UserExt;
 
+/// Desired local accounts, read from `[modules.user]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserSpec {
+    pub create_admin_user: bool,
+    /// Defaults to `general.default_admin_user`
+    pub admin_username: String,
+    /// Defaults to the distro sudo group plus adm and systemd-journal
+    pub admin_groups: Vec<String>,
+    pub admin_shell: String,
+    /// Defaults to `general.github_user`
+    pub github_username: String,
+    pub authorized_keys: Vec<String>,
+    /// Initial password of the admin account
+    pub admin_password: Option<Secret>,
+    /// crypt(3) hash of the admin password, kept in sync with /etc/shadow
+    pub admin_password_hash: Option<Secret>,
+    pub additional_users: Vec<UserAccountSpec>,
+}
+
+impl Default for UserSpec {
+    fn default() -> Self {
+        Self {
+            create_admin_user: true,
+            admin_username: String::new(),
+            admin_groups: Vec::new(),
+            admin_shell: "/bin/bash".to_string(),
+            github_username: String::new(),
+            authorized_keys: Vec::new(),
+            admin_password: None,
+            admin_password_hash: None,
+            additional_users: Vec::new(),
+        }
+    }
+}
+
+impl ConfigSchema for UserSpec {
+    fn schema() -> serde_json::Value {
+        use schema::*;
+
+        let mut account = object(
+            "An extra account",
+            vec![
+                ("username", string("")),
+                ("groups", array("Supplementary groups", string(""))),
+                ("shell", string("Login shell")),
+                ("ssh_keys", array("Authorized SSH public keys", string(""))),
+                ("password", secret("Initial password, set while the account has none")),
+                ("password_hash", secret("crypt(3) password hash, kept in sync with /etc/shadow")),
+            ],
+        );
+        account["required"] = serde_json::json!(["username"]);
+
+        object(
+            "Local accounts applied by `flux apply`",
+            vec![
+                ("create_admin_user", boolean("Create the admin account")),
+                ("admin_username", string("Defaults to general.default_admin_user")),
+                (
+                    "admin_groups",
+                    array("Defaults to the distro sudo group plus adm and systemd-journal", string("")),
+                ),
+                ("admin_shell", string("Login shell of the admin account")),
+                ("github_username", string("Import the admin's SSH keys from GitHub; defaults to general.github_user")),
+                ("authorized_keys", array("SSH public keys for the admin account", string(""))),
+                ("admin_password", secret("Initial admin password, set while the account has none")),
+                (
+                    "admin_password_hash",
+                    secret("crypt(3) hash of the admin password, kept in sync with /etc/shadow"),
+                ),
+                ("additional_users", array("Extra accounts to create", account)),
+            ],
+        )
+    }
+}
+
+/// An extra account from `[[modules.user.additional_users]]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserAccountSpec {
+    pub username: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub ssh_keys: Vec<String>,
+    /// Initial password
+    #[serde(default)]
+    pub password: Option<Secret>,
+    /// crypt(3) password hash, kept in sync with /etc/shadow
+    #[serde(default)]
+    pub password_hash: Option<Secret>,
+}
+
+/// Configured password of an account
+#[derive(Clone, Copy)]
+enum Password<'a> {
+    /// Only set while the account has no password, since it cannot be
+    /// compared with the stored hash
+    Plain(&'a Secret),
+    /// A crypt(3) hash as stored in /etc/shadow
+    Hash(&'a Secret),
+}
+
+impl<'a> Password<'a> {
+    fn from_spec(username: &str, plain: Option<&'a Secret>, hash: Option<&'a Secret>) -> Result<Option<Self>> {
+        match (plain, hash) {
+            (Some(_), Some(_)) => Err(FluxError::validation(format!(
+                "Set either a password or a password hash for '{}', not both",
+                username
+            ))),
+            (Some(plain), None) => Ok(Some(Password::Plain(plain))),
+            (None, Some(hash)) => Ok(Some(Password::Hash(hash))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Password field of `username` in /etc/shadow, `None` if it cannot be read
+fn shadow_password(username: &str) -> Option<String> {
+    let shadow = fs::read_to_string(resolve("/etc/shadow")).ok()?;
+    shadow.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? == username {
+            fields.next().map(str::to_string)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether a shadow password field holds a password rather than only a lock
+fn has_password(field: &str) -> bool {
+    !field.trim_start_matches(['!', '*']).is_empty()
+}
+
+/// Names of all groups a user belongs to, empty if the user does not exist
+fn group_names(username: &str) -> Vec<String> {
+    get_user_by_name(username)
+        .and_then(|user| get_user_groups(username, user.primary_group_id()))
+        .unwrap_or_default()
+        .iter()
+        .map(|group| group.name().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Short human-readable label for an SSH public key: its type and comment
+fn key_label(key: &str) -> String {
+    let parts: Vec<&str> = key.split_whitespace().collect();
+    match parts.as_slice() {
+        [key_type, _, comment, ..] => format!("{} {}", key_type, comment),
+        [key_type, data] => {
+            let tail = &data[data.len().saturating_sub(8)..];
+            format!("{} ...{}", key_type, tail)
+        }
+        _ => key.trim().to_string(),
+    }
+}
+
 pub struct UserModule {
     base: ModuleBase,
 }
@@ -32,6 +200,8 @@ impl UserModule {
             tags: vec!["core".to_string(), "security".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: Vec::new(),
         };
         Self {
             base: ModuleBase { info },
@@ -63,46 +233,40 @@ impl UserModule {
             )));
         }
 
-        // Build useradd command
-        let mut cmd = Command::new("useradd");
+        // Build useradd arguments
+        let mut args = Vec::new();
 
         // Add flags
         if system_user {
-            cmd.arg("--system");
+            args.push("--system");
         } else {
-            cmd.arg("--create-home");
+            args.push("--create-home");
         }
 
         // Set shell
         if let Some(sh) = shell {
-            cmd.arg("--shell").arg(sh);
+            args.extend(["--shell", sh]);
         }
 
         // Set home directory
         if let Some(home) = home_dir {
-            cmd.arg("--home-dir").arg(home);
+            args.extend(["--home-dir", home]);
         }
 
         // Set full name (comment field)
         if let Some(name) = fullname {
-            cmd.arg("--comment").arg(name);
+            args.extend(["--comment", name]);
         }
 
         // Add username
-        cmd.arg(username);
+        args.push(username);
 
         // Execute command
-        let output = cmd.output().map_err(|e| {
-            FluxError::command_failed(format!("Failed to execute useradd: {}", e))
-        })?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(FluxError::command_failed(format!(
-                "Failed to create user: {}",
-                stderr
-            )));
-        }
+        execute_command("useradd", &args)
+            .map_err(|e| FluxError::command_failed(format!("Failed to create user: {}", e)))?;
+        journal::record_command("useradd", &args, &["userdel", "--remove", username])?;
+        let detail = if dry_run::is_dry_run() { "would create" } else { "created" };
+        changes::changed(format!("user {}", username), detail);
 
         log_success(&format!("User '{}' created successfully", username));
 
@@ -130,7 +294,7 @@ impl UserModule {
         .await?;
 
         // Determine sudo group based on distro
-        let distro = crate::helpers::system::detect_distro()?;
+        let distro = facts::get().os.distribution.clone();
         let sudo_group = if distro.is_debian_based() {
             "sudo"
         } else {
@@ -141,6 +305,17 @@ impl UserModule {
         let admin_groups = vec![sudo_group, "adm", "systemd-journal"];
         self.add_user_to_groups(username, &admin_groups).await?;
 
+        // In dry-run mode the account does not exist yet, so there is no home to set up
+        if dry_run::is_dry_run() && get_user_by_name(username).is_none() {
+            dry_run::record(
+                ChangeKind::CreateDir,
+                format!("~{}/.ssh", username),
+                Some("with authorized_keys".to_string()),
+            );
+            self.set_user_password(username).await?;
+            return Ok(());
+        }
+
         // Setup SSH directory
         self.setup_ssh_directory(username).await?;
 
@@ -162,6 +337,234 @@ impl UserModule {
         Ok(())
     }
 
+    /// Resolve the admin account name and groups, falling back to `[general]`
+    fn admin_account<'a>(&self, spec: &'a UserSpec, config: &'a Config) -> Result<(&'a str, Vec<&'a str>)> {
+        let username = if spec.admin_username.is_empty() {
+            config.general.default_admin_user.as_str()
+        } else {
+            spec.admin_username.as_str()
+        };
+
+        let groups: Vec<&str> = if spec.admin_groups.is_empty() {
+            let distro = facts::get().os.distribution.clone();
+            let sudo_group = if distro.is_debian_based() { "sudo" } else { "wheel" };
+            vec![sudo_group, "adm", "systemd-journal"]
+        } else {
+            spec.admin_groups.iter().map(String::as_str).collect()
+        };
+
+        Ok((username, groups))
+    }
+
+    /// Converge local accounts to the given spec without prompting
+    async fn apply_spec(&self, spec: &UserSpec, config: &Config) -> Result<()> {
+        if spec.create_admin_user {
+            let (username, groups) = self.admin_account(spec, config)?;
+
+            let github_user = if spec.github_username.is_empty() {
+                config.general.github_user.as_deref().filter(|u| !u.is_empty())
+            } else {
+                Some(spec.github_username.as_str())
+            };
+
+            self.ensure_account(username, &spec.admin_shell, &groups, &spec.authorized_keys, github_user)
+                .await?;
+
+            let password = Password::from_spec(
+                username,
+                spec.admin_password.as_ref(),
+                spec.admin_password_hash.as_ref(),
+            )?;
+            if let Some(password) = password {
+                self.ensure_password(username, password, config)?;
+            }
+        }
+
+        for account in &spec.additional_users {
+            // The sample configuration ships a blank entry
+            if account.username.is_empty() {
+                continue;
+            }
+
+            let groups: Vec<&str> = account.groups.iter().map(String::as_str).collect();
+            let shell = account.shell.as_deref().unwrap_or("/bin/bash");
+            self.ensure_account(&account.username, shell, &groups, &account.ssh_keys, None)
+                .await?;
+
+            let password = Password::from_spec(
+                &account.username,
+                account.password.as_ref(),
+                account.password_hash.as_ref(),
+            )?;
+            if let Some(password) = password {
+                self.ensure_password(&account.username, password, config)?;
+            }
+        }
+
+        log_success("User configuration converged");
+        Ok(())
+    }
+
+    /// Make sure an account exists with the given groups and SSH keys
+    async fn ensure_account(
+        &self,
+        username: &str,
+        shell: &str,
+        groups: &[&str],
+        keys: &[String],
+        github_user: Option<&str>,
+    ) -> Result<()> {
+        if get_user_by_name(username).is_none() {
+            self.create_user(username, None, Some(shell), None, false, None)
+                .await?;
+        } else {
+            changes::ok(format!("user {}", username));
+        }
+
+        if !groups.is_empty() {
+            self.add_user_to_groups(username, groups).await?;
+        }
+
+        // In dry-run mode a new account was only planned, so there is no home yet
+        if dry_run::is_dry_run() && get_user_by_name(username).is_none() {
+            dry_run::record(
+                ChangeKind::CreateDir,
+                format!("~{}/.ssh", username),
+                Some(format!("with {} authorized key(s)", keys.len())),
+            );
+            return Ok(());
+        }
+
+        self.setup_ssh_directory(username).await?;
+
+        for key in keys {
+            validate_ssh_key(key)?;
+            self.add_ssh_key(username, key).await?;
+        }
+
+        if let Some(gh_user) = github_user {
+            self.add_github_keys(username, gh_user).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Set a configured password
+    ///
+    /// A hash is applied whenever it differs from /etc/shadow; a plain password
+    /// only when the account has none yet.
+    fn ensure_password(&self, username: &str, password: Password<'_>, config: &Config) -> Result<()> {
+        let current = shadow_password(username);
+        match password {
+            Password::Hash(secret) => {
+                let hash = secret.resolve(config)?;
+                if current.as_deref() != Some(hash.as_str()) {
+                    return self.change_password(username, &hash, true);
+                }
+            }
+            Password::Plain(secret) => {
+                if !current.as_deref().is_some_and(has_password) {
+                    let password = secret.resolve(config)?;
+                    return self.change_password(username, &password, false);
+                }
+            }
+        }
+        changes::ok(format!("password {}", username));
+        Ok(())
+    }
+
+    /// Compare local accounts against the given spec
+    ///
+    /// Keys fetched from GitHub are not checked since that needs the network.
+    async fn plan_spec(&self, spec: &UserSpec, config: &Config) -> Result<Vec<Drift>> {
+        let mut drift = Vec::new();
+
+        if spec.create_admin_user {
+            let (username, groups) = self.admin_account(spec, config)?;
+            drift.extend(self.plan_account(username, &groups, &spec.authorized_keys));
+
+            let password = Password::from_spec(
+                username,
+                spec.admin_password.as_ref(),
+                spec.admin_password_hash.as_ref(),
+            )?;
+            if let Some(password) = password {
+                drift.extend(self.plan_password(username, password, config)?);
+            }
+        }
+
+        for account in &spec.additional_users {
+            if account.username.is_empty() {
+                continue;
+            }
+
+            let groups: Vec<&str> = account.groups.iter().map(String::as_str).collect();
+            drift.extend(self.plan_account(&account.username, &groups, &account.ssh_keys));
+
+            let password = Password::from_spec(
+                &account.username,
+                account.password.as_ref(),
+                account.password_hash.as_ref(),
+            )?;
+            if let Some(password) = password {
+                drift.extend(self.plan_password(&account.username, password, config)?);
+            }
+        }
+
+        Ok(drift)
+    }
+
+    /// Report a missing account, group memberships and authorized keys
+    fn plan_account(&self, username: &str, groups: &[&str], keys: &[String]) -> Vec<Drift> {
+        let resource = format!("user {}", username);
+
+        let Some(user) = get_user_by_name(username) else {
+            return vec![Drift::missing(resource, "present")];
+        };
+
+        let mut drift = Vec::new();
+
+        let member_of = group_names(username);
+        for group in groups {
+            if !member_of.iter().any(|g| g == group) {
+                drift.push(Drift::missing(&resource, format!("member of {}", group)));
+            }
+        }
+
+        let authorized = fs::read_to_string(resolve(user.home_dir().join(".ssh/authorized_keys")))
+            .unwrap_or_default();
+        for key in keys {
+            if !authorized.contains(key.trim()) {
+                drift.push(Drift::missing(&resource, format!("authorized key {}", key_label(key))));
+            }
+        }
+
+        drift
+    }
+
+    /// Report a password that `ensure_password` would set
+    ///
+    /// Nothing is reported for missing accounts or when /etc/shadow cannot be read.
+    fn plan_password(&self, username: &str, password: Password<'_>, config: &Config) -> Result<Option<Drift>> {
+        let resource = format!("user {}", username);
+        if get_user_by_name(username).is_none() {
+            return Ok(None);
+        }
+        let Some(current) = shadow_password(username) else {
+            return Ok(None);
+        };
+
+        let drift = match password {
+            Password::Hash(secret) if secret.resolve(config)? != current => {
+                let actual = if has_password(&current) { "other password" } else { "no password" };
+                Some(Drift::new(resource, "configured password", Some(actual.to_string())))
+            }
+            Password::Plain(_) if !has_password(&current) => Some(Drift::missing(resource, "password")),
+            _ => None,
+        };
+        Ok(drift)
+    }
+
     /// Add user to specified groups
     async fn add_user_to_groups(&self, username: &str, groups: &[&str]) -> Result<()> {
         for group in groups {
@@ -170,30 +573,32 @@ impl UserModule {
                 // Create group if it doesn't exist (for custom groups)
                 if !["sudo", "wheel", "adm", "docker", "systemd-journal"].contains(group) {
                     log_info(&format!("Creating group: {}", group));
-                    let output = Command::new("groupadd")
-                        .arg(group)
-                        .output()
-                        .map_err(|e| FluxError::command_failed(format!("Failed to create group: {}", e)))?;
-
-                    if !output.status.success() {
+                    if execute_command("groupadd", &[group]).is_err() {
                         log_warn(&format!("Group '{}' creation failed", group));
                         continue;
                     }
+                    journal::record_command("groupadd", &[group], &["groupdel", group])?;
                 }
             }
 
+            let resource = format!("user {} in group {}", username, group);
+            if group_names(username).iter().any(|g| g == group) {
+                changes::ok(resource);
+                continue;
+            }
+
             // Add user to group
-            let output = Command::new("usermod")
-                .arg("-a")
-                .arg("-G")
-                .arg(group)
-                .arg(username)
-                .output()
-                .map_err(|e| FluxError::command_failed(format!("Failed to add user to group: {}", e)))?;
-
-            if output.status.success() {
+            if execute_command("usermod", &["-a", "-G", group, username]).is_ok() {
+                journal::record_command(
+                    "usermod",
+                    &["-a", "-G", group, username],
+                    &["gpasswd", "-d", username, group],
+                )?;
+                let detail = if dry_run::is_dry_run() { "would add" } else { "added" };
+                changes::changed(resource, detail);
                 log_success(&format!("Added '{}' to group '{}'", username, group));
             } else {
+                changes::failed(resource, "usermod failed");
                 log_warn(&format!("Failed to add '{}' to group '{}'", username, group));
             }
         }
@@ -204,16 +609,7 @@ impl UserModule {
     /// Remove user from specified groups
     async fn remove_user_from_groups(&self, username: &str, groups: &[&str]) -> Result<()> {
         for group in groups {
-            let output = Command::new("gpasswd")
-                .arg("-d")
-                .arg(username)
-                .arg(group)
-                .output()
-                .map_err(|e| {
-                    FluxError::command_failed(format!("Failed to remove user from group: {}", e))
-                })?;
-
-            if output.status.success() {
+            if execute_command("gpasswd", &["-d", username, group]).is_ok() {
                 log_success(&format!("Removed '{}' from group '{}'", username, group));
             } else {
                 log_warn(&format!(
@@ -240,17 +636,13 @@ impl UserModule {
         let auth_keys = ssh_dir.join("authorized_keys");
 
         // Create .ssh directory
-        if !ssh_dir.exists() {
-            fs::create_dir_all(&ssh_dir).map_err(|e| {
+        if !resolve(&ssh_dir).exists() {
+            create_dir_all(&ssh_dir).map_err(|e| {
                 FluxError::system(format!("Failed to create .ssh directory: {}", e))
             })?;
 
             // Set permissions to 700
-            let mut perms = fs::metadata(&ssh_dir)
-                .map_err(|e| FluxError::system(format!("Failed to get .ssh metadata: {}", e)))?
-                .permissions();
-            perms.set_mode(0o700);
-            fs::set_permissions(&ssh_dir, perms).map_err(|e| {
+            set_permissions(&ssh_dir, 0o700).map_err(|e| {
                 FluxError::system(format!("Failed to set .ssh permissions: {}", e))
             })?;
 
@@ -258,17 +650,13 @@ impl UserModule {
         }
 
         // Create authorized_keys if it doesn't exist
-        if !auth_keys.exists() {
-            fs::write(&auth_keys, "").map_err(|e| {
+        if !resolve(&auth_keys).exists() {
+            safe_write_file(&auth_keys, "", false).map_err(|e| {
                 FluxError::system(format!("Failed to create authorized_keys: {}", e))
             })?;
 
             // Set permissions to 600
-            let mut perms = fs::metadata(&auth_keys)
-                .map_err(|e| FluxError::system(format!("Failed to get authorized_keys metadata: {}", e)))?
-                .permissions();
-            perms.set_mode(0o600);
-            fs::set_permissions(&auth_keys, perms).map_err(|e| {
+            set_permissions(&auth_keys, 0o600).map_err(|e| {
                 FluxError::system(format!("Failed to set authorized_keys permissions: {}", e))
             })?;
         }
@@ -277,11 +665,8 @@ impl UserModule {
         let uid = user.uid();
         let gid = user.primary_group_id();
 
-        Command::new("chown")
-            .arg("-R")
-            .arg(format!("{}:{}", uid, gid))
-            .arg(&ssh_dir)
-            .output()
+        let owner = format!("{}:{}", uid, gid);
+        execute_command("chown", &["-R", &owner, &ssh_dir.to_string_lossy()])
             .map_err(|e| FluxError::command_failed(format!("Failed to set ownership: {}", e)))?;
 
         log_success(&format!("SSH directory configured for '{}'", username));
@@ -347,15 +732,16 @@ impl UserModule {
         let auth_keys = home_dir.join(".ssh/authorized_keys");
 
         // Read existing keys
-        let existing_keys = if auth_keys.exists() {
-            fs::read_to_string(&auth_keys).unwrap_or_default()
+        let existing_keys = if file_exists(&auth_keys) {
+            read_file_to_string(&auth_keys).unwrap_or_default()
         } else {
             String::new()
         };
 
         // Check for duplicates
         if existing_keys.contains(key.trim()) {
-            log_warn("SSH key already exists in authorized_keys");
+            log_info("SSH key already exists in authorized_keys");
+            changes::ok(format!("{} key {}", auth_keys.display(), key_label(key)));
             return Ok(());
         }
 
@@ -368,7 +754,7 @@ impl UserModule {
         updated_keys.push('\n');
 
         // Write back
-        fs::write(&auth_keys, updated_keys).map_err(|e| {
+        safe_write_file(&auth_keys, &updated_keys, false).map_err(|e| {
             FluxError::system(format!("Failed to write authorized_keys: {}", e))
         })?;
 
@@ -378,29 +764,37 @@ impl UserModule {
 
     /// Set user password
     async fn set_user_password(&self, username: &str) -> Result<()> {
-        let password = prompt_password(&format!("Enter password for '{}'", username))?;
+        if dry_run::intercept_command("chpasswd", &[username]) {
+            return Ok(());
+        }
 
-        let mut child = Command::new("chpasswd")
-            .stdin(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| FluxError::command_failed(format!("Failed to start chpasswd: {}", e)))?;
+        let password = prompt_password(&format!("Enter password for '{}'", username))?;
+        self.change_password(username, &password, false)
+    }
 
-        if let Some(mut stdin) = child.stdin.take() {
-            use std::io::Write;
-            writeln!(stdin, "{}:{}", username, password).map_err(|e| {
-                FluxError::command_failed(format!("Failed to write to chpasswd: {}", e))
-            })?;
+    /// Set a password, or with `hashed` a crypt(3) hash, through chpasswd
+    fn change_password(&self, username: &str, password: &str, hashed: bool) -> Result<()> {
+        if password.contains('\n') || (hashed && password.contains(':')) {
+            return Err(FluxError::validation(format!("Invalid password for '{}'", username)));
+        }
+        let resource = format!("password {}", username);
+        if dry_run::intercept_command("chpasswd", &[username]) {
+            changes::changed(resource, "would set");
+            return Ok(());
         }
 
-        let output = child
-            .wait_with_output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to wait for chpasswd: {}", e)))?;
+        let args: &[&str] = if hashed { &["-e"] } else { &[] };
+        let output = runner::run(
+            &Invocation::new("chpasswd", args).stdin(format!("{}:{}\n", username, password)),
+        )?;
 
-        if !output.status.success() {
+        if !output.success {
+            changes::failed(resource, "chpasswd failed");
             return Err(FluxError::command_failed(
                 "Failed to set password".to_string(),
             ));
         }
+        changes::changed(resource, "set");
 
         log_success(&format!("Password set for '{}'", username));
         Ok(())
@@ -418,7 +812,7 @@ impl UserModule {
         if backup && remove_home {
             let home_dir = user.home_dir();
             let backup_dir = PathBuf::from("/var/backups/users");
-            fs::create_dir_all(&backup_dir).ok();
+            create_dir_all(&backup_dir).ok();
 
             let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
             let backup_path = backup_dir.join(format!("{}-{}.tar.gz", username, timestamp));
@@ -432,16 +826,18 @@ impl UserModule {
                 .file_name()
                 .ok_or_else(|| FluxError::system("Home directory has no file name"))?;
 
-            let output = Command::new("tar")
-                .arg("-czf")
-                .arg(&backup_path)
-                .arg("-C")
-                .arg(parent_dir)
-                .arg(dir_name)
-                .output()
-                .map_err(|e| FluxError::command_failed(format!("Failed to backup home directory: {}", e)))?;
-
-            if output.status.success() {
+            let result = execute_command(
+                "tar",
+                &[
+                    "-czf",
+                    &backup_path.to_string_lossy(),
+                    "-C",
+                    &parent_dir.to_string_lossy(),
+                    &dir_name.to_string_lossy(),
+                ],
+            );
+
+            if result.is_ok() {
                 log_success(&format!("Home directory backed up to {:?}", backup_path));
             } else {
                 log_warn("Failed to backup home directory");
@@ -449,23 +845,14 @@ impl UserModule {
         }
 
         // Delete user
-        let mut cmd = Command::new("userdel");
+        let mut args = Vec::new();
         if remove_home {
-            cmd.arg("-r");
+            args.push("-r");
         }
-        cmd.arg(username);
+        args.push(username);
 
-        let output = cmd
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to execute userdel: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(FluxError::command_failed(format!(
-                "Failed to delete user: {}",
-                stderr
-            )));
-        }
+        execute_command("userdel", &args)
+            .map_err(|e| FluxError::command_failed(format!("Failed to delete user: {}", e)))?;
 
         log_success(&format!("User '{}' deleted successfully", username));
         Ok(())
@@ -475,11 +862,11 @@ impl UserModule {
     async fn list_users(&self, min_uid: u32) -> Result<()> {
         log_info("Listing system users:");
 
-        let passwd = fs::read_to_string("/etc/passwd")
+        let passwd = fs::read_to_string(resolve("/etc/passwd"))
             .map_err(|e| FluxError::system(format!("Failed to read /etc/passwd: {}", e)))?;
 
-        println!("\n{:<20} {:<10} {:<10} {:<30}", "Username", "UID", "GID", "Full Name");
-        println!("{}", "-".repeat(70));
+        outln!("\n{:<20} {:<10} {:<10} {:<30}", "Username", "UID", "GID", "Full Name");
+        outln!("{}", "-".repeat(70));
 
         for line in passwd.lines() {
             let parts: Vec<&str> = line.split(':').collect();
@@ -490,7 +877,7 @@ impl UserModule {
                 let fullname = parts[4].split(',').next().unwrap_or("");
 
                 if uid >= min_uid {
-                    println!("{:<20} {:<10} {:<10} {:<30}", username, uid, gid, fullname);
+                    outln!("{:<20} {:<10} {:<10} {:<30}", username, uid, gid, fullname);
                 }
             }
         }
@@ -639,6 +1026,24 @@ EXAMPLES:
         )
     }
 
+    fn supports_apply(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> Option<SectionSchema> {
+        Some(SectionSchema::of::<UserSpec>())
+    }
+
+    async fn apply(&self, spec: &toml::Value, config: &Config) -> Result<()> {
+        let spec: UserSpec = parse_spec(self.name(), spec)?;
+        self.apply_spec(&spec, config).await
+    }
+
+    async fn plan(&self, spec: &toml::Value, config: &Config) -> Result<Vec<Drift>> {
+        let spec: UserSpec = parse_spec(self.name(), spec)?;
+        self.plan_spec(&spec, config).await
+    }
+
     async fn execute(&self, args: Vec<String>, _config: &Config) -> Result<()> {
         if args.is_empty() || args.contains(&"--menu".to_string()) {
             return self.show_menu().await;
@@ -683,3 +1088,78 @@ EXAMPLES:
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::runner::{set_runner, CommandOutput, RecordingRunner};
+    use serial_test::serial;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_create_user_commands() {
+        let recorder = Arc::new(RecordingRunner::new());
+        recorder.respond("usermod -a -G flux-missing-group", CommandOutput::failed(6, "no such group"));
+        let previous = set_runner(recorder.clone());
+
+        let result = UserModule::new()
+            .create_user(
+                "flux-test-user",
+                Some("Flux Test"),
+                Some("/bin/zsh"),
+                None,
+                false,
+                Some(vec!["sudo", "flux-missing-group"]),
+            )
+            .await;
+        set_runner(previous);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            recorder.command_lines(),
+            vec![
+                "useradd --create-home --shell /bin/zsh --comment Flux Test flux-test-user",
+                "usermod -a -G sudo flux-test-user",
+                "groupadd flux-missing-group",
+                "usermod -a -G flux-missing-group flux-test-user",
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_password_from_secret() {
+        std::env::set_var("FLUX_TEST_USER_HASH", "$6$salt$hash");
+        let hash = Secret::Env("FLUX_TEST_USER_HASH".to_string());
+        let plain = Secret::Plain("initial-password".to_string());
+        assert!(Password::from_spec("flux-test-user", Some(&plain), Some(&hash)).is_err());
+
+        let recorder = Arc::new(RecordingRunner::new());
+        let previous = set_runner(recorder.clone());
+
+        let module = UserModule::new();
+        let config = Config::default();
+        let result = module
+            .ensure_password("flux-test-user", Password::Hash(&hash), &config)
+            .and_then(|()| module.ensure_password("flux-test-user", Password::Plain(&plain), &config));
+        set_runner(previous);
+
+        assert!(result.is_ok());
+        let calls = recorder.calls();
+        assert_eq!(calls[0].command_line(), "chpasswd -e");
+        assert_eq!(calls[0].stdin.as_deref(), Some("flux-test-user:$6$salt$hash\n"));
+        assert_eq!(calls[1].command_line(), "chpasswd");
+        assert_eq!(calls[1].stdin.as_deref(), Some("flux-test-user:initial-password\n"));
+    }
+
+    #[test]
+    fn test_has_password() {
+        assert!(has_password("$6$salt$hash"));
+        assert!(has_password("!$6$salt$hash"));
+        assert!(!has_password("!"));
+        assert!(!has_password("!!"));
+        assert!(!has_password("*"));
+        assert!(!has_password(""));
+    }
+}
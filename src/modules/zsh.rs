@@ -4,21 +4,32 @@
 use crate::config::Config;
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    file_ops::safe_write_file,
+    changes, dry_run, facts,
+    file_ops::{copy_file_with_perms, create_dir_all, safe_write_file},
     logging::{log_debug, log_error, log_info, log_success, log_warn},
-    system::{check_command, execute_command},
+    paths::resolve,
+    runner::{self, Invocation},
+    system::{check_command, command_output, execute_command},
     user_input::{prompt_input, prompt_yes_no, select_from_menu},
 };
 use crate::modules::{Module, ModuleBase, ModuleInfo};
 use async_trait::async_trait;
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
 use users::{get_user_by_name};
 use users::os::unix::UserExt;
 
 const OH_MY_ZSH_INSTALL_URL: &str = "https://raw.githubusercontent.com/ohmyzsh/ohmyzsh/master/tools/install.sh";
 
+/// Detail for a resource installed by this module
+fn installed_detail() -> &'static str {
+    if dry_run::is_dry_run() {
+        "would install"
+    } else {
+        "installed"
+    }
+}
+
 pub struct ZshModule {
     base: ModuleBase,
 }
@@ -33,6 +44,8 @@ impl ZshModule {
             tags: vec!["shell".to_string(), "ux".to_string()],
             requires_root: true,
             supported_distros: vec!["all".to_string()],
+            requires: Vec::new(),
+            after: vec!["update".to_string(), "user".to_string()],
         };
         Self {
             base: ModuleBase { info },
@@ -43,11 +56,12 @@ impl ZshModule {
     async fn install_zsh(&self) -> Result<()> {
         if check_command("zsh").is_ok() {
             log_info("ZSH is already installed");
+            changes::ok("package zsh");
             return Ok(());
         }
 
         log_info("Installing ZSH");
-        let distro = crate::helpers::system::detect_distro()?;
+        let distro = facts::get().os.distribution.clone();
 
         if distro.is_debian_based() {
             execute_command("apt-get", &["update"])?;
@@ -60,6 +74,8 @@ impl ZshModule {
             ));
         }
 
+        facts::invalidate();
+        changes::changed("package zsh", installed_detail());
         log_success("ZSH installed successfully");
         Ok(())
     }
@@ -79,31 +95,30 @@ impl ZshModule {
         let oh_my_zsh_dir = home_dir.join(".oh-my-zsh");
 
         // Check if Oh-My-Zsh is already installed
-        if oh_my_zsh_dir.exists() {
+        let resource = oh_my_zsh_dir.display().to_string();
+        if resolve(&oh_my_zsh_dir).exists() {
             log_info("Oh-My-Zsh is already installed");
+            changes::ok(resource);
             return Ok(());
         }
 
         // Download and install Oh-My-Zsh
         log_info("Downloading Oh-My-Zsh installer");
 
-        let output = Command::new("su")
-            .arg("-")
-            .arg(username)
-            .arg("-c")
-            .arg(format!(
-                "sh -c \"$(curl -fsSL {})\" \"\" --unattended",
-                OH_MY_ZSH_INSTALL_URL
-            ))
-            .env("RUNZSH", "no")
-            .env("CHSH", "no")
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to install Oh-My-Zsh: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            log_warn(&format!("Oh-My-Zsh installation warning: {}", stderr));
+        let install = format!(
+            "sh -c \"$(curl -fsSL {})\" \"\" --unattended",
+            OH_MY_ZSH_INSTALL_URL
+        );
+        let output = runner::run(
+            &Invocation::new("su", &["-", username, "-c", &install])
+                .env("RUNZSH", "no")
+                .env("CHSH", "no"),
+        )?;
+
+        if !output.success {
+            log_warn(format!("Oh-My-Zsh installation warning: {}", output.stderr));
         }
+        changes::changed(resource, installed_detail());
 
         log_success("Oh-My-Zsh installed successfully");
         Ok(())
@@ -127,7 +142,7 @@ impl ZshModule {
             match plugin {
                 "zsh-autosuggestions" => {
                     let plugin_dir = custom_plugins_dir.join("zsh-autosuggestions");
-                    if !plugin_dir.exists() {
+                    if !resolve(&plugin_dir).exists() {
                         log_info("Installing zsh-autosuggestions");
                         let plugin_dir_str = plugin_dir
                             .to_str()
@@ -140,11 +155,14 @@ impl ZshModule {
                                 plugin_dir_str,
                             ],
                         )?;
+                        changes::changed(plugin_dir.display().to_string(), installed_detail());
+                    } else {
+                        changes::ok(plugin_dir.display().to_string());
                     }
                 }
                 "zsh-syntax-highlighting" => {
                     let plugin_dir = custom_plugins_dir.join("zsh-syntax-highlighting");
-                    if !plugin_dir.exists() {
+                    if !resolve(&plugin_dir).exists() {
                         log_info("Installing zsh-syntax-highlighting");
                         let plugin_dir_str = plugin_dir
                             .to_str()
@@ -157,11 +175,14 @@ impl ZshModule {
                                 plugin_dir_str,
                             ],
                         )?;
+                        changes::changed(plugin_dir.display().to_string(), installed_detail());
+                    } else {
+                        changes::ok(plugin_dir.display().to_string());
                     }
                 }
                 "zsh-completions" => {
                     let plugin_dir = custom_plugins_dir.join("zsh-completions");
-                    if !plugin_dir.exists() {
+                    if !resolve(&plugin_dir).exists() {
                         log_info("Installing zsh-completions");
                         let plugin_dir_str = plugin_dir
                             .to_str()
@@ -174,6 +195,9 @@ impl ZshModule {
                                 plugin_dir_str,
                             ],
                         )?;
+                        changes::changed(plugin_dir.display().to_string(), installed_detail());
+                    } else {
+                        changes::ok(plugin_dir.display().to_string());
                     }
                 }
                 _ => {
@@ -185,12 +209,8 @@ impl ZshModule {
         // Fix ownership
         let uid = user.uid();
         let gid = user.primary_group_id();
-        Command::new("chown")
-            .arg("-R")
-            .arg(format!("{}:{}", uid, gid))
-            .arg(&custom_plugins_dir)
-            .output()
-            .ok();
+        let owner = format!("{}:{}", uid, gid);
+        execute_command("chown", &["-R", &owner, &custom_plugins_dir.to_string_lossy()]).ok();
 
         log_success("ZSH plugins installed");
         Ok(())
@@ -210,7 +230,7 @@ impl ZshModule {
         let home_dir = PathBuf::from(home_dir_str);
         let theme_dir = home_dir.join(".oh-my-zsh/custom/themes/powerlevel10k");
 
-        if !theme_dir.exists() {
+        if !resolve(&theme_dir).exists() {
             let theme_dir_str = theme_dir
                 .to_str()
                 .ok_or_else(|| FluxError::system("Invalid UTF-8 in theme path"))?;
@@ -227,16 +247,14 @@ impl ZshModule {
             // Fix ownership
             let uid = user.uid();
             let gid = user.primary_group_id();
-            Command::new("chown")
-                .arg("-R")
-                .arg(format!("{}:{}", uid, gid))
-                .arg(&theme_dir)
-                .output()
-                .ok();
+            let owner = format!("{}:{}", uid, gid);
+            execute_command("chown", &["-R", &owner, theme_dir_str]).ok();
+            changes::changed(theme_dir.display().to_string(), installed_detail());
 
             log_success("Powerlevel10k installed");
         } else {
             log_info("Powerlevel10k already installed");
+            changes::ok(theme_dir.display().to_string());
         }
 
         Ok(())
@@ -247,7 +265,7 @@ impl ZshModule {
         let mut users = Vec::new();
 
         // Read /etc/passwd to get all users
-        let passwd_content = fs::read_to_string("/etc/passwd")?;
+        let passwd_content = fs::read_to_string(resolve("/etc/passwd"))?;
 
         for line in passwd_content.lines() {
             let parts: Vec<&str> = line.split(':').collect();
@@ -283,7 +301,7 @@ impl ZshModule {
         let themes_dir = home_dir.join(".oh-my-zsh/custom/themes");
 
         // Create themes directory if it doesn't exist
-        fs::create_dir_all(&themes_dir)?;
+        create_dir_all(&themes_dir)?;
 
         let theme_dest = themes_dir.join("fluxlab.zsh-theme");
 
@@ -293,16 +311,13 @@ impl ZshModule {
             .or_else(|_| PathBuf::from("config/fluxlab.zsh-theme").canonicalize())?;
 
         if theme_source.exists() {
-            fs::copy(&theme_source, &theme_dest)?;
+            copy_file_with_perms(&theme_source, &theme_dest)?;
 
             // Fix ownership
             let uid = user.uid();
             let gid = user.primary_group_id();
-            Command::new("chown")
-                .arg(format!("{}:{}", uid, gid))
-                .arg(&theme_dest)
-                .output()
-                .ok();
+            let owner = format!("{}:{}", uid, gid);
+            execute_command("chown", &[&owner, &theme_dest.to_string_lossy()]).ok();
 
             log_success("FluxLab theme installed");
         } else {
@@ -327,9 +342,9 @@ impl ZshModule {
         let zshrc_path = home_dir.join(".zshrc");
 
         // Backup existing .zshrc
-        if zshrc_path.exists() {
+        if resolve(&zshrc_path).exists() {
             let backup_path = home_dir.join(".zshrc.backup");
-            fs::copy(&zshrc_path, &backup_path)?;
+            copy_file_with_perms(resolve(&zshrc_path), &backup_path)?;
             log_info("Backed up existing .zshrc");
         }
 
@@ -339,16 +354,13 @@ impl ZshModule {
             .or_else(|_| PathBuf::from("config/.zshrc").canonicalize())?;
 
         if zshrc_source.exists() {
-            fs::copy(&zshrc_source, &zshrc_path)?;
+            copy_file_with_perms(&zshrc_source, &zshrc_path)?;
 
             // Fix ownership
             let uid = user.uid();
             let gid = user.primary_group_id();
-            Command::new("chown")
-                .arg(format!("{}:{}", uid, gid))
-                .arg(&zshrc_path)
-                .output()
-                .ok();
+            let owner = format!("{}:{}", uid, gid);
+            execute_command("chown", &[&owner, &zshrc_path.to_string_lossy()]).ok();
 
             log_success("ZSH configured successfully");
         } else {
@@ -365,19 +377,16 @@ impl ZshModule {
         log_info(&format!("Setting ZSH as default shell for: {}", username));
 
         // Get ZSH path
-        let output = Command::new("which")
-            .arg("zsh")
-            .output()
-            .map_err(|e| FluxError::command_failed(format!("Failed to find zsh: {}", e)))?;
+        let output = command_output("which", &["zsh"])?;
 
-        let zsh_path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let zsh_path = output.stdout.trim().to_string();
 
         if zsh_path.is_empty() {
             return Err(FluxError::Module("ZSH not found".to_string()));
         }
 
         // Ensure ZSH is in /etc/shells
-        let shells_content = fs::read_to_string("/etc/shells").unwrap_or_default();
+        let shells_content = fs::read_to_string(resolve("/etc/shells")).unwrap_or_default();
         if !shells_content.contains(&zsh_path) {
             log_info("Adding ZSH to /etc/shells");
             let mut shells = shells_content;
@@ -386,7 +395,16 @@ impl ZshModule {
         }
 
         // Change user shell
+        let resource = format!("shell of {}", username);
+        let current = get_user_by_name(username).map(|user| user.shell().to_path_buf());
+        if current.as_deref() == Some(std::path::Path::new(&zsh_path)) {
+            log_info(format!("ZSH already is the default shell for {}", username));
+            changes::ok(resource);
+            return Ok(());
+        }
         execute_command("chsh", &["-s", &zsh_path, username])?;
+        let detail = if dry_run::is_dry_run() { "would set" } else { "set" };
+        changes::changed(resource, format!("{} to {}", detail, zsh_path));
 
         log_success(&format!("ZSH set as default shell for {}", username));
         log_info("Logout and login again to use ZSH");
@@ -533,6 +551,10 @@ impl Module for ZshModule {
         &self.base.info.version
     }
 
+    fn after(&self) -> &[String] {
+        &self.base.info.after
+    }
+
     fn is_available(&self) -> bool {
         check_command("curl").is_ok() && check_command("git").is_ok()
     }
@@ -0,0 +1,310 @@
+//! Host inventory for remote execution
+//!
+//! ```toml
+//! [defaults]
+//! user = "root"
+//! identity_file = "~/.ssh/id_ed25519"
+//! concurrency = 5
+//!
+//! [groups.web]
+//! vars = { default_ssh_port = 2222 }
+//!
+//! [hosts.web1]
+//! address = "10.0.0.11"
+//! groups = ["web"]
+//!
+//! [hosts.db1]
+//! address = "10.0.0.21"
+//! user = "admin"
+//! vars = { default_admin_user = "dbadmin" }
+//! ```
+//!
+//! `vars` override keys of the `[general]` configuration on that host. Host
+//! vars take precedence over group vars, later groups over earlier ones. The
+//! host's groups become `general.groups`, which workflow `when` conditions read.
+
+use crate::config::Config;
+use crate::error::{FluxError, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Hosts connected to at the same time when the inventory does not say
+pub const DEFAULT_CONCURRENCY: usize = 5;
+
+/// Connection settings shared by all hosts
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostDefaults {
+    pub user: String,
+    pub port: u16,
+    /// Private key to authenticate with; the SSH agent is used when unset
+    pub identity_file: Option<String>,
+    /// Run flux with `sudo -n`; defaults to true for users other than root
+    pub sudo: Option<bool>,
+    /// Remote flux binary to run; the local binary is uploaded when unset
+    pub flux_path: Option<String>,
+    /// Reject hosts missing from `~/.ssh/known_hosts`
+    pub strict_host_keys: bool,
+    /// Maximum number of hosts to run on at the same time
+    pub concurrency: usize,
+    /// Seconds to wait for a host to accept the connection
+    pub connect_timeout: u64,
+}
+
+impl Default for HostDefaults {
+    fn default() -> Self {
+        Self {
+            user: "root".to_string(),
+            port: 22,
+            identity_file: None,
+            sudo: None,
+            flux_path: None,
+            strict_host_keys: true,
+            concurrency: DEFAULT_CONCURRENCY,
+            connect_timeout: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct HostEntry {
+    /// Defaults to the host name
+    address: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    identity_file: Option<String>,
+    sudo: Option<bool>,
+    flux_path: Option<String>,
+    groups: Vec<String>,
+    vars: toml::Table,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GroupEntry {
+    vars: toml::Table,
+}
+
+/// A parsed inventory file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Inventory {
+    pub defaults: HostDefaults,
+    hosts: BTreeMap<String, HostEntry>,
+    groups: BTreeMap<String, GroupEntry>,
+}
+
+/// A host with its connection settings and variables resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Host {
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    pub user: String,
+    pub identity_file: Option<PathBuf>,
+    pub sudo: bool,
+    pub flux_path: Option<String>,
+    pub groups: Vec<String>,
+    /// Overrides for `[general]`, group vars merged with host vars
+    pub vars: toml::Table,
+}
+
+impl Host {
+    /// The local configuration with this host's vars applied to `[general]`
+    pub fn config(&self, base: &Config) -> Result<Config> {
+        let mut value = toml::Value::try_from(base)
+            .map_err(|e| FluxError::config(format!("Failed to serialize config: {}", e)))?;
+
+        if let Some(general) = value.get_mut("general").and_then(|g| g.as_table_mut()) {
+            let groups = self.groups.iter().cloned().map(toml::Value::from).collect();
+            general.insert("groups".to_string(), toml::Value::Array(groups));
+            for (key, var) in &self.vars {
+                general.insert(key.clone(), var.clone());
+            }
+        }
+
+        value
+            .try_into()
+            .map_err(|e| FluxError::config(format!("Invalid vars for host '{}': {}", self.name, e)))
+    }
+}
+
+impl Inventory {
+    /// Load an inventory file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| {
+            FluxError::config(format!(
+                "Failed to read inventory {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Self::parse(&contents)
+            .map_err(|e| FluxError::config(format!("Invalid inventory {}: {}", path.display(), e)))
+    }
+
+    /// Parse an inventory from TOML
+    pub fn parse(contents: &str) -> Result<Self> {
+        let inventory: Inventory =
+            toml::from_str(contents).map_err(|e| FluxError::parse(e.to_string()))?;
+
+        // Groups without vars need no table of their own, so only clashes are checked
+        for name in inventory.hosts.keys() {
+            if inventory.groups.contains_key(name) {
+                return Err(FluxError::config(format!(
+                    "'{}' is both a host and a group",
+                    name
+                )));
+            }
+        }
+        if inventory.defaults.concurrency == 0 {
+            return Err(FluxError::config("defaults.concurrency must be at least 1"));
+        }
+
+        Ok(inventory)
+    }
+
+    /// Hosts matching `limit`, in inventory order
+    ///
+    /// `limit` holds host and group names; an empty limit selects every host.
+    pub fn select(&self, limit: &[String]) -> Result<Vec<Host>> {
+        for name in limit {
+            let known = self.hosts.contains_key(name)
+                || self.groups.contains_key(name)
+                || self.hosts.values().any(|h| h.groups.contains(name));
+            if !known {
+                return Err(FluxError::not_found(format!(
+                    "No host or group named '{}' in the inventory",
+                    name
+                )));
+            }
+        }
+
+        let hosts: Vec<Host> = self
+            .hosts
+            .iter()
+            .filter(|(name, entry)| {
+                limit.is_empty()
+                    || limit.contains(name)
+                    || entry.groups.iter().any(|g| limit.contains(g))
+            })
+            .map(|(name, entry)| self.resolve(name, entry))
+            .collect();
+
+        if hosts.is_empty() {
+            return Err(FluxError::config("The inventory selects no hosts"));
+        }
+        Ok(hosts)
+    }
+
+    fn resolve(&self, name: &str, entry: &HostEntry) -> Host {
+        let defaults = &self.defaults;
+        let user = entry.user.clone().unwrap_or_else(|| defaults.user.clone());
+
+        let mut vars = toml::Table::new();
+        for group in &entry.groups {
+            if let Some(group) = self.groups.get(group) {
+                vars.extend(group.vars.clone());
+            }
+        }
+        vars.extend(entry.vars.clone());
+
+        Host {
+            name: name.to_string(),
+            address: entry.address.clone().unwrap_or_else(|| name.to_string()),
+            port: entry.port.unwrap_or(defaults.port),
+            sudo: entry.sudo.or(defaults.sudo).unwrap_or(user != "root"),
+            user,
+            identity_file: entry
+                .identity_file
+                .as_ref()
+                .or(defaults.identity_file.as_ref())
+                .map(|p| expand_home(p)),
+            flux_path: entry
+                .flux_path
+                .clone()
+                .or_else(|| defaults.flux_path.clone()),
+            groups: entry.groups.clone(),
+            vars,
+        }
+    }
+}
+
+/// Expand a leading `~/` to the local home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVENTORY: &str = r#"
+[defaults]
+user = "deploy"
+concurrency = 2
+
+[groups.web]
+vars = { default_ssh_port = 2222, default_admin_user = "webadmin" }
+
+[hosts.web1]
+address = "10.0.0.11"
+groups = ["web"]
+
+[hosts.web2]
+groups = ["web"]
+vars = { default_ssh_port = 2200 }
+
+[hosts.db1]
+address = "10.0.0.21"
+user = "root"
+groups = ["db"]
+"#;
+
+    #[test]
+    fn test_select_by_group_and_host() {
+        let inventory = Inventory::parse(INVENTORY).unwrap();
+
+        let all = inventory.select(&[]).unwrap();
+        let names: Vec<&str> = all.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(names, vec!["db1", "web1", "web2"]);
+
+        let web = inventory.select(&["web".to_string()]).unwrap();
+        assert_eq!(web.len(), 2);
+        assert_eq!(web[1].address, "web2");
+        assert!(web[1].sudo);
+
+        let db = inventory.select(&["db".to_string()]).unwrap();
+        assert_eq!(db[0].user, "root");
+        assert!(!db[0].sudo);
+
+        assert!(inventory.select(&["mail".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_host_vars_override_general() {
+        let inventory = Inventory::parse(INVENTORY).unwrap();
+        let hosts = inventory.select(&["web".to_string()]).unwrap();
+        let base = Config::default();
+
+        let web1 = hosts[0].config(&base).unwrap();
+        let web2 = hosts[1].config(&base).unwrap();
+
+        assert_eq!(web1.general.default_ssh_port, 2222);
+        assert_eq!(web2.general.default_ssh_port, 2200);
+        assert_eq!(web2.general.default_admin_user, "webadmin");
+        assert_eq!(web1.general.groups, vec!["web".to_string()]);
+
+        let mut bad = hosts[0].clone();
+        bad.vars
+            .insert("default_ssh_port".to_string(), toml::Value::from("ssh"));
+        assert!(bad.config(&base).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+//! Remote execution against a host inventory
+//!
+//! `flux --hosts inventory.toml <command>` connects to every selected host over
+//! SSH, uploads the local flux binary unless the host has one configured, and
+//! runs the same command there with the local configuration plus the host's
+//! vars. Output is streamed back prefixed with the host name, followed by a
+//! summary table.
+
+pub mod inventory;
+pub mod session;
+
+pub use inventory::{Host, Inventory};
+
+use crate::config::Config;
+use crate::error::{FluxError, Result};
+use crate::helpers::logging::{log_error, log_info};
+use crate::helpers::output;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use session::Connection;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Global options that only make sense on the controlling machine
+///
+/// The profile is merged into the uploaded configuration already.
+const LOCAL_OPTIONS: &[&str] = &[
+    "--hosts",
+    "--limit",
+    "-c",
+    "--config",
+    "--profile",
+    "-o",
+    "--output",
+];
+
+/// Flags flux always passes to remote runs itself
+const REMOTE_FLAGS: &[&str] = &["-y", "--yes", "--non-interactive"];
+
+/// Outcome of a remote run on one host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostStatus {
+    Ok,
+    Failed,
+    /// Connecting, authenticating or uploading failed
+    Unreachable,
+}
+
+/// Result of a remote run on one host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostResult {
+    pub host: String,
+    pub address: String,
+    pub status: HostStatus,
+    /// Exit status of the remote flux process
+    pub exit_code: Option<i32>,
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Turn the local command line into the arguments for the remote flux
+///
+/// Options naming local files or controlling local output are dropped; the
+/// remote side always runs non-interactively. `--answers` is rejected since
+/// the answers file only exists locally.
+pub fn remote_args(args: &[String]) -> Result<Vec<String>> {
+    let mut remote = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let name = arg.split('=').next().unwrap_or(arg);
+        if name == "--answers" {
+            return Err(FluxError::validation(
+                "--answers cannot be used with --hosts; remote runs take defaults",
+            ));
+        }
+        if LOCAL_OPTIONS.contains(&name) {
+            if !arg.contains('=') {
+                iter.next();
+            }
+            continue;
+        }
+        if REMOTE_FLAGS.contains(&arg.as_str()) {
+            continue;
+        }
+        remote.push(arg.clone());
+    }
+
+    Ok(remote)
+}
+
+/// Quote `arg` for a POSIX shell
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Run flux with `args` on every host, at most `concurrency` at a time
+pub async fn run_on_hosts(
+    inventory: &Inventory,
+    hosts: Vec<Host>,
+    args: Vec<String>,
+    config: &Config,
+) -> Vec<HostResult> {
+    let semaphore = Arc::new(Semaphore::new(inventory.defaults.concurrency));
+    let args = Arc::new(args);
+    let strict = inventory.defaults.strict_host_keys;
+    let timeout = Duration::from_secs(inventory.defaults.connect_timeout);
+
+    let mut tasks = Vec::new();
+    for host in hosts {
+        let semaphore = Arc::clone(&semaphore);
+        let args = Arc::clone(&args);
+        let host_config = host.config(config);
+
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            tokio::task::spawn_blocking(move || {
+                let started = Instant::now();
+                let outcome = host_config
+                    .and_then(|config| run_on_host(&host, &config, &args, strict, timeout));
+                host_result(&host, outcome, started.elapsed())
+            })
+            .await
+        }));
+    }
+
+    let mut results = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok(Ok(result)) => results.push(result),
+            Ok(Err(e)) | Err(e) => log_error(format!("Remote task failed: {}", e)),
+        }
+    }
+    results
+}
+
+/// How far a remote run got
+enum Outcome {
+    Unreachable(FluxError),
+    Finished(i32),
+}
+
+fn run_on_host(
+    host: &Host,
+    config: &Config,
+    args: &[String],
+    strict: bool,
+    timeout: Duration,
+) -> Result<Outcome> {
+    let prefix = format!("[{}]", host.name).cyan().bold();
+
+    let connection = match Connection::open(host, strict, timeout) {
+        Ok(connection) => connection,
+        Err(e) => return Ok(Outcome::Unreachable(e)),
+    };
+    let dir = match make_private_dir(&connection) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(Outcome::Unreachable(e)),
+    };
+    let flux = match prepare_host(&connection, host, &dir) {
+        Ok(flux) => flux,
+        Err(e) => {
+            remove_private_dir(&connection, &dir);
+            return Ok(Outcome::Unreachable(e));
+        }
+    };
+
+    let config_path = format!("{}/flux.toml", dir);
+    let config_toml = toml::to_string_pretty(config)
+        .map_err(|e| FluxError::config(format!("Failed to serialize config: {}", e)))?;
+    if let Err(e) = connection.upload(config_toml.as_bytes(), &config_path, 0o600) {
+        remove_private_dir(&connection, &dir);
+        return Ok(Outcome::Unreachable(e));
+    }
+
+    let mut command = vec![
+        shell_quote(&flux),
+        "--config".to_string(),
+        shell_quote(&config_path),
+        "--yes".to_string(),
+    ];
+    command.extend(args.iter().map(|a| shell_quote(a)));
+    let sudo = if host.sudo { "sudo -n " } else { "" };
+    let script = format!(
+        "{}{}; rc=$?; rm -rf {}; exit $rc",
+        sudo,
+        command.join(" "),
+        shell_quote(&dir)
+    );
+
+    let code = connection.exec_streaming(&script, |line| println!("{} {}", prefix, line))?;
+    Ok(Outcome::Finished(code))
+}
+
+/// Create a directory only the connecting user can write to for this run
+///
+/// A fixed path under /tmp could be planted by any local user and would then
+/// be run with sudo.
+fn make_private_dir(connection: &Connection) -> Result<String> {
+    let (status, output) = connection.exec("umask 077 && mktemp -d /tmp/flux.XXXXXXXXXX")?;
+    let dir = output.trim();
+    if status != 0 || !dir.starts_with('/') {
+        return Err(FluxError::ssh(format!(
+            "Failed to create a temporary directory: {}",
+            dir
+        )));
+    }
+    Ok(dir.to_string())
+}
+
+fn remove_private_dir(connection: &Connection, dir: &str) {
+    if let Err(e) = connection.exec(&format!("rm -rf {}", shell_quote(dir))) {
+        log_error(format!("Failed to remove {}: {}", dir, e));
+    }
+}
+
+/// Make sure the host can run flux, returning the path of the binary
+///
+/// Unless the host has a flux configured, the local binary is uploaded into
+/// `dir` and its SHA-256 checked against the local one before it is used.
+fn prepare_host(connection: &Connection, host: &Host, dir: &str) -> Result<String> {
+    if let Some(path) = &host.flux_path {
+        return Ok(path.clone());
+    }
+
+    let remote_path = format!("{}/flux", dir);
+    let binary = std::env::current_exe()?;
+    let contents = std::fs::read(&binary)?;
+    log_info(format!("Uploading flux to {}:{}", host.name, remote_path));
+    connection.upload(&contents, &remote_path, 0o700)?;
+
+    let (status, output) = connection.exec(&format!("sha256sum {}", shell_quote(&remote_path)))?;
+    let remote_sum = output.split_whitespace().next().unwrap_or_default();
+    if status != 0 || remote_sum != sha256_hex(&contents) {
+        return Err(FluxError::ssh(format!(
+            "Checksum of the flux binary uploaded to {} does not match the local one",
+            host.name
+        )));
+    }
+    Ok(remote_path)
+}
+
+/// Lowercase hex SHA-256, as printed by `sha256sum`
+fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn host_result(host: &Host, outcome: Result<Outcome>, elapsed: Duration) -> HostResult {
+    let (status, exit_code, message) = match outcome {
+        Ok(Outcome::Finished(0)) => (HostStatus::Ok, Some(0), None),
+        Ok(Outcome::Finished(code)) => (
+            HostStatus::Failed,
+            Some(code),
+            Some(format!("flux exited with status {}", code)),
+        ),
+        Ok(Outcome::Unreachable(e)) => (HostStatus::Unreachable, None, Some(e.to_string())),
+        Err(e) => (HostStatus::Failed, None, Some(e.to_string())),
+    };
+
+    HostResult {
+        host: host.name.clone(),
+        address: host.address.clone(),
+        status,
+        exit_code,
+        message,
+        duration_ms: elapsed.as_millis() as u64,
+    }
+}
+
+/// Print the per-host results as a table, or as JSON with `--output json`
+pub fn print_summary(results: &[HostResult]) -> Result<()> {
+    if output::is_json() {
+        return output::emit(results);
+    }
+
+    let width = results
+        .iter()
+        .map(|r| r.host.len())
+        .max()
+        .unwrap_or(4)
+        .max(4);
+    println!("\n{}", "=== Remote Summary ===".cyan());
+    println!(
+        "{:<width$}  {:<12} {:>5} {:>9}  Message",
+        "Host", "Result", "Exit", "Duration"
+    );
+    for result in results {
+        let status = match result.status {
+            HostStatus::Ok => format!("{:<12}", "✓ ok").green(),
+            HostStatus::Failed => format!("{:<12}", "✗ failed").red(),
+            HostStatus::Unreachable => format!("{:<12}", "✗ unreachable").red(),
+        };
+        println!(
+            "{:<width$}  {} {:>5} {:>8.1}s  {}",
+            result.host,
+            status,
+            result
+                .exit_code
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            result.duration_ms as f64 / 1000.0,
+            result.message.as_deref().unwrap_or("")
+        );
+    }
+
+    let ok = results
+        .iter()
+        .filter(|r| r.status == HostStatus::Ok)
+        .count();
+    println!("{}", format!("✓ Ok: {}", ok).green());
+    if ok < results.len() {
+        println!("{}", format!("✗ Failed: {}", results.len() - ok).red());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_remote_args_drop_local_options() {
+        let args = strings(&[
+            "--hosts",
+            "inv.toml",
+            "--limit=web",
+            "-c",
+            "flux.toml",
+            "--profile",
+            "prod",
+            "--dry-run",
+            "-y",
+            "-o",
+            "json",
+            "workflow",
+            "security",
+        ]);
+
+        assert_eq!(
+            remote_args(&args).unwrap(),
+            strings(&["--dry-run", "workflow", "security"])
+        );
+        assert!(remote_args(&strings(&["--answers", "a.toml", "list"])).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("--port"), "--port");
+        assert_eq!(shell_quote("/tmp/flux-1.0"), "/tmp/flux-1.0");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! SSH connections to inventory hosts
+
+use crate::error::{FluxError, Result};
+use crate::remote::inventory::Host;
+use ssh2::{CheckResult, ExtendedData, KnownHostFileKind, Session};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+/// An authenticated SSH session to one host
+pub struct Connection {
+    session: Session,
+    name: String,
+}
+
+impl Connection {
+    /// Connect to `host`, verify its host key and authenticate
+    ///
+    /// With `strict_host_keys`, hosts missing from `~/.ssh/known_hosts` are
+    /// rejected; a changed host key is always rejected.
+    pub fn open(host: &Host, strict_host_keys: bool, connect_timeout: Duration) -> Result<Self> {
+        let addr = (host.address.as_str(), host.port)
+            .to_socket_addrs()
+            .map_err(|e| FluxError::network(format!("Cannot resolve {}: {}", host.address, e)))?
+            .next()
+            .ok_or_else(|| FluxError::network(format!("Cannot resolve {}", host.address)))?;
+        let tcp = TcpStream::connect_timeout(&addr, connect_timeout)
+            .map_err(|e| FluxError::network(format!("Cannot connect to {}: {}", addr, e)))?;
+
+        let mut session = Session::new().map_err(ssh_error("start session"))?;
+        session.set_tcp_stream(tcp);
+        session.handshake().map_err(ssh_error("handshake"))?;
+
+        let connection = Self {
+            session,
+            name: host.name.clone(),
+        };
+        connection.verify_host_key(host, strict_host_keys)?;
+        connection.authenticate(host)?;
+        Ok(connection)
+    }
+
+    fn verify_host_key(&self, host: &Host, strict: bool) -> Result<()> {
+        let (key, _) = self
+            .session
+            .host_key()
+            .ok_or_else(|| FluxError::ssh(format!("{} sent no host key", self.name)))?;
+
+        let mut known_hosts = self
+            .session
+            .known_hosts()
+            .map_err(ssh_error("read known hosts"))?;
+        if let Some(file) = dirs::home_dir().map(|h| h.join(".ssh/known_hosts")) {
+            if file.exists() {
+                known_hosts
+                    .read_file(&file, KnownHostFileKind::OpenSSH)
+                    .map_err(ssh_error("read known hosts"))?;
+            }
+        }
+
+        match known_hosts.check_port(&host.address, host.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::NotFound if !strict => Ok(()),
+            CheckResult::NotFound => Err(FluxError::ssh(format!(
+                "Host key of {} is not in ~/.ssh/known_hosts (connect once with ssh, or set strict_host_keys = false)",
+                host.address
+            ))),
+            CheckResult::Mismatch => Err(FluxError::ssh(format!(
+                "Host key of {} does not match ~/.ssh/known_hosts",
+                host.address
+            ))),
+            CheckResult::Failure => Err(FluxError::ssh(format!(
+                "Could not check the host key of {}",
+                host.address
+            ))),
+        }
+    }
+
+    fn authenticate(&self, host: &Host) -> Result<()> {
+        let result = match &host.identity_file {
+            Some(key) => self
+                .session
+                .userauth_pubkey_file(&host.user, None, key, None),
+            None => self.session.userauth_agent(&host.user),
+        };
+        result.map_err(|e| {
+            FluxError::ssh(format!("Authentication as {} failed: {}", host.user, e))
+        })?;
+
+        if !self.session.authenticated() {
+            return Err(FluxError::ssh(format!(
+                "Authentication as {} failed",
+                host.user
+            )));
+        }
+        Ok(())
+    }
+
+    /// Run a shell command, passing each line of output to `on_line`
+    ///
+    /// stderr is merged into stdout. Returns the command's exit status.
+    pub fn exec_streaming(&self, command: &str, mut on_line: impl FnMut(&str)) -> Result<i32> {
+        let mut channel = self
+            .session
+            .channel_session()
+            .map_err(ssh_error("open channel"))?;
+        channel
+            .handle_extended_data(ExtendedData::Merge)
+            .map_err(ssh_error("open channel"))?;
+        channel.exec(command).map_err(ssh_error("run command"))?;
+
+        for line in BufReader::new(&mut channel).lines() {
+            on_line(&line?);
+        }
+
+        channel.wait_close().map_err(ssh_error("close channel"))?;
+        channel.exit_status().map_err(ssh_error("read exit status"))
+    }
+
+    /// Run a shell command and collect its output
+    pub fn exec(&self, command: &str) -> Result<(i32, String)> {
+        let mut output = String::new();
+        let mut channel = self
+            .session
+            .channel_session()
+            .map_err(ssh_error("open channel"))?;
+        channel.exec(command).map_err(ssh_error("run command"))?;
+        channel.read_to_string(&mut output)?;
+        channel.wait_close().map_err(ssh_error("close channel"))?;
+        let status = channel
+            .exit_status()
+            .map_err(ssh_error("read exit status"))?;
+        Ok((status, output))
+    }
+
+    /// Copy `contents` to `remote_path` with the given mode
+    pub fn upload(&self, contents: &[u8], remote_path: &str, mode: i32) -> Result<()> {
+        let mut channel = self
+            .session
+            .scp_send(Path::new(remote_path), mode, contents.len() as u64, None)
+            .map_err(ssh_error("upload"))?;
+        channel.write_all(contents)?;
+        channel.send_eof().map_err(ssh_error("upload"))?;
+        channel.wait_eof().map_err(ssh_error("upload"))?;
+        channel.close().map_err(ssh_error("upload"))?;
+        channel.wait_close().map_err(ssh_error("upload"))?;
+        Ok(())
+    }
+}
+
+fn ssh_error(action: &'static str) -> impl Fn(ssh2::Error) -> FluxError {
+    move |e| FluxError::ssh(format!("Failed to {}: {}", action, e))
+}
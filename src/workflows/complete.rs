@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::error::Result;
-use crate::workflows::{BaseWorkflow, Workflow};
+use crate::workflows::{condition::Condition, BaseWorkflow, Workflow};
 use async_trait::async_trait;
 
 /// Complete system provisioning workflow
@@ -49,7 +49,10 @@ impl Workflow for CompleteWorkflow {
                 "motd",
                 "netdata",
             ],
-        );
+        )
+        // Containers get their network from the runtime
+        .with_condition("network", Condition::parse("facts.container == 'none'")?)
+        .with_condition("netdata", Condition::parse("facts.memory_mb > 1024")?);
 
         base.execute_modules(config).await
     }
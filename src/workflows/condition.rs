@@ -0,0 +1,118 @@
+//! `when` conditions of workflow steps
+//!
+//! A condition is a [MiniJinja](https://docs.rs/minijinja) expression. A step
+//! whose condition is false is skipped and reported as `skipped_by_condition`.
+//! Conditions see:
+//!
+//! - `facts`: the host facts, as shown by `flux facts --output json`
+//! - `groups`: the inventory groups of the host (`general.groups`)
+//! - `results`: the status of every module of the workflow so far —
+//!   `completed`, `failed`, `skipped`, `skipped_by_condition` or `pending`
+//!
+//! ```toml
+//! [workflows.web.when]
+//! netdata = "facts.memory_mb > 1024"
+//! network = "facts.container == 'none'"
+//! firewall = "'web' in groups"
+//! motd = "results.ssh == 'completed'"
+//! ```
+//!
+//! Reading a variable that does not exist is an error, so a misspelled fact
+//! fails the step instead of silently skipping it.
+
+use crate::error::{FluxError, Result};
+use crate::helpers::template;
+use minijinja::{context, Environment, UndefinedBehavior};
+use std::collections::BTreeMap;
+
+/// A parsed `when` expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+}
+
+impl Condition {
+    /// Parse `source`, rejecting invalid syntax
+    pub fn parse(source: &str) -> Result<Self> {
+        environment()
+            .compile_expression(source)
+            .map_err(|e| FluxError::config(format!("Invalid condition '{}': {}", source, e)))?;
+
+        Ok(Self {
+            source: source.to_string(),
+        })
+    }
+
+    /// The expression as written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the condition holds for `groups` and the `results` so far
+    ///
+    /// Facts are only gathered when the expression reads one.
+    pub fn evaluate(&self, groups: &[String], results: &BTreeMap<String, String>) -> Result<bool> {
+        let env = environment();
+        let expression = env
+            .compile_expression(&self.source)
+            .map_err(|e| FluxError::config(format!("Invalid condition '{}': {}", self.source, e)))?;
+
+        let value = expression
+            .eval(context! {
+                facts => template::facts_value(),
+                groups => groups,
+                results => results,
+            })
+            .map_err(|e| {
+                FluxError::validation(format!("Condition '{}' failed: {}", self.source, e))
+            })?;
+
+        Ok(value.is_true())
+    }
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(module, status)| (module.to_string(), status.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_syntax() {
+        assert!(Condition::parse("'web' in groups").is_ok());
+        assert!(Condition::parse("facts.memory_mb >").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_groups_and_results() {
+        let groups = vec!["web".to_string()];
+        let results = results(&[("ssh", "completed"), ("firewall", "pending")]);
+
+        let in_web = Condition::parse("'web' in groups").unwrap();
+        let in_db = Condition::parse("'db' in groups").unwrap();
+        let after_ssh = Condition::parse("results.ssh == 'completed'").unwrap();
+        let after_firewall = Condition::parse("results.firewall == 'completed'").unwrap();
+
+        assert!(in_web.evaluate(&groups, &results).unwrap());
+        assert!(!in_db.evaluate(&groups, &results).unwrap());
+        assert!(after_ssh.evaluate(&groups, &results).unwrap());
+        assert!(!after_firewall.evaluate(&groups, &results).unwrap());
+    }
+
+    #[test]
+    fn test_evaluate_unknown_variable_is_an_error() {
+        let typo = Condition::parse("results.shh == 'completed'").unwrap();
+        assert!(typo.evaluate(&[], &results(&[("ssh", "completed")])).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error::{FluxError, Result};
+use std::collections::HashMap;
+
+/// Ordering constraints declared by a module
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleDeps {
+    /// Must be part of the workflow and succeed before this module runs
+    pub requires: Vec<String>,
+    /// Must run before this module when both are part of the workflow
+    pub after: Vec<String>,
+}
+
+/// Group the modules of a workflow into waves that can run concurrently
+///
+/// Every module in a wave only depends on modules from earlier waves. Within a
+/// wave, modules keep the order in which the workflow lists them, so running
+/// the waves one module at a time gives a stable topological order.
+pub fn execution_waves<F>(workflow: &str, modules: &[String], deps_of: F) -> Result<Vec<Vec<String>>>
+where
+    F: Fn(&str) -> Result<ModuleDeps>,
+{
+    let mut predecessors: HashMap<&str, Vec<String>> = HashMap::new();
+
+    for module in modules {
+        let deps = deps_of(module)?;
+
+        for required in &deps.requires {
+            if !modules.contains(required) {
+                return Err(FluxError::config(format!(
+                    "Module '{}' requires '{}', which is not part of workflow '{}'",
+                    module, required, workflow
+                )));
+            }
+        }
+
+        // `after` only orders modules that are both present
+        let mut before: Vec<String> = deps.requires;
+        before.extend(deps.after.into_iter().filter(|m| modules.contains(m)));
+        before.retain(|m| m != module);
+        predecessors.insert(module.as_str(), before);
+    }
+
+    let mut remaining: Vec<&String> = Vec::new();
+    for module in modules {
+        if !remaining.contains(&module) {
+            remaining.push(module);
+        }
+    }
+    let mut done: Vec<&str> = Vec::new();
+    let mut waves = Vec::new();
+
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<&String>, Vec<&String>) = remaining
+            .into_iter()
+            .partition(|m| predecessors[m.as_str()].iter().all(|p| done.contains(&p.as_str())));
+
+        if ready.is_empty() {
+            let names: Vec<&str> = blocked.iter().map(|m| m.as_str()).collect();
+            return Err(FluxError::config(format!(
+                "Dependency cycle in workflow '{}' between modules: {}",
+                workflow,
+                names.join(", ")
+            )));
+        }
+
+        done.extend(ready.iter().map(|m| m.as_str()));
+        waves.push(ready.into_iter().cloned().collect());
+        remaining = blocked;
+    }
+
+    Ok(waves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Table<'a> = &'a [(&'a str, &'a [&'a str], &'a [&'a str])];
+
+    fn deps(table: Table<'_>) -> impl Fn(&str) -> Result<ModuleDeps> + '_ {
+        move |name| {
+            Ok(table
+                .iter()
+                .find(|(n, _, _)| *n == name)
+                .map(|(_, requires, after)| ModuleDeps {
+                    requires: requires.iter().map(|s| s.to_string()).collect(),
+                    after: after.iter().map(|s| s.to_string()).collect(),
+                })
+                .unwrap_or_default())
+        }
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_waves_follow_dependencies() {
+        let table: Table = &[
+            ("ssh", &[], &["user", "update"]),
+            ("firewall", &[], &["network"]),
+            ("netdata", &[], &["firewall"]),
+        ];
+        let modules = names(&["update", "ssh", "network", "firewall", "netdata", "user"]);
+
+        let waves = execution_waves("test", &modules, deps(table)).unwrap();
+
+        assert_eq!(
+            waves,
+            vec![
+                names(&["update", "network", "user"]),
+                names(&["ssh", "firewall"]),
+                names(&["netdata"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_after_ignores_absent_modules() {
+        let table: Table = &[("ssh", &[], &["user"])];
+
+        let waves = execution_waves("test", &names(&["ssh"]), deps(table)).unwrap();
+
+        assert_eq!(waves, vec![names(&["ssh"])]);
+    }
+
+    #[test]
+    fn test_missing_requirement_rejected() {
+        let table: Table = &[("netdata", &["firewall"], &[])];
+
+        let err = execution_waves("test", &names(&["netdata"]), deps(table)).unwrap_err();
+
+        assert!(err.to_string().contains("requires 'firewall'"));
+    }
+
+    #[test]
+    fn test_cycle_detected() {
+        let table: Table = &[
+            ("a", &[], &["b"]),
+            ("b", &["c"], &[]),
+            ("c", &[], &["a"]),
+        ];
+
+        let err = execution_waves("test", &names(&["a", "b", "c", "d"]), deps(table)).unwrap_err();
+
+        assert!(err.to_string().contains("cycle"));
+        assert!(err.to_string().contains("a, b, c"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::error::Result;
-use crate::workflows::{BaseWorkflow, Workflow};
+use crate::workflows::{condition::Condition, BaseWorkflow, Workflow};
 use async_trait::async_trait;
 
 /// Essential system setup workflow
@@ -31,7 +31,9 @@ impl Workflow for EssentialWorkflow {
             self.name(),
             self.description(),
             vec!["network", "certs", "update", "zsh", "motd"],
-        );
+        )
+        // Containers get their network from the runtime
+        .with_condition("network", Condition::parse("facts.container == 'none'")?);
 
         base.execute_modules(config).await
     }
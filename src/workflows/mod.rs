@@ -1,4 +1,6 @@
 pub mod complete;
+pub mod condition;
+pub mod dependency;
 pub mod development;
 pub mod essential;
 pub mod monitoring;
@@ -6,16 +8,23 @@ pub mod security;
 
 pub use crate::helpers::system::{check_reboot_needed, is_service_active_enhanced};
 
-use crate::config::Config;
+use crate::config::{Config, WorkflowConfig};
 use crate::error::{FluxError, Result};
 use crate::helpers::{
-    logging::{log_info, log_warn},
-    user_input::prompt_yes_no,
+    changes::{self, ResourceChange},
+    logging::{self, log_debug, log_info, log_warn},
+    output, report,
+    user_input::{is_non_interactive, prompt_yes_no},
 };
-use crate::modules::ModuleManager;
+use crate::modules::{ModuleManager, ModuleResult, ModuleStatus};
 use async_trait::async_trait;
 use colored::Colorize;
-use std::collections::HashMap;
+use condition::Condition;
+use dependency::ModuleDeps;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Workflow trait that all workflows must implement
 #[async_trait]
@@ -53,6 +62,46 @@ impl WorkflowManager {
         Ok(Self { workflows })
     }
     
+    /// Create a workflow manager including the `[workflows.*]` tables from `config`
+    ///
+    /// Configured workflows replace built-in ones of the same name. Every
+    /// configured workflow is validated up front, so a typo in any of them is
+    /// reported before anything runs.
+    pub fn with_config(config: &Config) -> Result<Self> {
+        let mut manager = Self::new()?;
+        
+        let modules = ModuleManager::with_config(config)?;
+        let known: Vec<String> = modules
+            .discover_modules()?
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        
+        let mut names: Vec<&String> = config.workflows.keys().collect();
+        names.sort();
+        
+        for name in names {
+            let workflow = &config.workflows[name];
+            validate_workflow(name, workflow, &known)?;
+            module_waves(&modules, name, &workflow.modules)?;
+            
+            if manager.workflows.contains_key(name) {
+                log_debug(format!("Workflow '{}' overridden by configuration", name));
+            }
+            manager.workflows.insert(
+                name.clone(),
+                Box::new(BaseWorkflow::from_config(name, workflow)?),
+            );
+        }
+        
+        Ok(manager)
+    }
+    
+    /// Get a workflow by name
+    pub fn get_workflow(&self, name: &str) -> Option<&dyn Workflow> {
+        self.workflows.get(name).map(|w| w.as_ref())
+    }
+    
     /// Execute a workflow by name
     pub async fn execute_workflow(&self, name: &str, config: &Config) -> Result<()> {
         let workflow = self.workflows
@@ -100,11 +149,88 @@ impl WorkflowManager {
     }
 }
 
+/// Order a workflow's modules by their declared dependencies
+pub fn module_waves(
+    manager: &ModuleManager,
+    workflow: &str,
+    modules: &[String],
+) -> Result<Vec<Vec<String>>> {
+    dependency::execution_waves(workflow, modules, |name| {
+        let module = manager.get_module(name)?;
+        Ok(ModuleDeps {
+            requires: module.requires().to_vec(),
+            after: module.after().to_vec(),
+        })
+    })
+}
+
+/// Result of a workflow run
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkflowSummary {
+    pub workflow: String,
+    pub completed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Modules whose `when` condition did not hold
+    pub skipped_by_condition: usize,
+    /// Module results in the order the modules ran
+    pub modules: Vec<ModuleResult>,
+}
+
+/// Check a configured workflow against the registered modules
+fn validate_workflow(name: &str, workflow: &WorkflowConfig, known: &[String]) -> Result<()> {
+    if workflow.modules.is_empty() {
+        return Err(FluxError::config(format!(
+            "Workflow '{}' does not list any modules",
+            name
+        )));
+    }
+    
+    for module in &workflow.modules {
+        if !known.contains(module) {
+            return Err(FluxError::config(format!(
+                "Workflow '{}' references unknown module '{}' (available: {})",
+                name,
+                module,
+                known.join(", ")
+            )));
+        }
+    }
+    
+    for module in workflow.args.keys() {
+        if !workflow.modules.contains(module) {
+            return Err(FluxError::config(format!(
+                "Workflow '{}' has args for '{}', which is not in its module list",
+                name, module
+            )));
+        }
+    }
+    
+    for module in workflow.when.keys() {
+        if !workflow.modules.contains(module) {
+            return Err(FluxError::config(format!(
+                "Workflow '{}' has a condition for '{}', which is not in its module list",
+                name, module
+            )));
+        }
+    }
+    
+    Ok(())
+}
+
 /// Base workflow implementation
 pub struct BaseWorkflow {
     name: String,
     description: String,
     modules: Vec<String>,
+    /// Extra arguments per module
+    args: HashMap<String, Vec<String>>,
+    /// `None` asks the user after a failure
+    continue_on_error: Option<bool>,
+    /// `None` asks the user before each module
+    confirm_modules: Option<bool>,
+    /// Modules only run when their condition holds
+    when: HashMap<String, Condition>,
 }
 
 impl BaseWorkflow {
@@ -113,61 +239,339 @@ impl BaseWorkflow {
             name: name.to_string(),
             description: description.to_string(),
             modules: modules.into_iter().map(String::from).collect(),
+            args: HashMap::new(),
+            continue_on_error: None,
+            confirm_modules: None,
+            when: HashMap::new(),
         }
     }
     
-    /// Execute modules in sequence
+    /// Only run `module` when `condition` holds
+    pub fn with_condition(mut self, module: &str, condition: Condition) -> Self {
+        self.when.insert(module.to_string(), condition);
+        self
+    }
+    
+    /// Build a workflow from a `[workflows.<name>]` table
+    pub fn from_config(name: &str, config: &WorkflowConfig) -> Result<Self> {
+        let mut when = HashMap::new();
+        for (module, source) in &config.when {
+            let condition = Condition::parse(source).map_err(|e| {
+                FluxError::config(format!("Workflow '{}', module '{}': {}", name, module, e))
+            })?;
+            when.insert(module.clone(), condition);
+        }
+        
+        Ok(Self {
+            name: name.to_string(),
+            description: config
+                .description
+                .clone()
+                .unwrap_or_else(|| "User-defined workflow".to_string()),
+            modules: config.modules.clone(),
+            args: config.args.clone(),
+            continue_on_error: config.continue_on_error,
+            confirm_modules: config.confirm_modules,
+            when,
+        })
+    }
+    
+    /// Execute modules in dependency order
+    ///
+    /// Modules are grouped into waves by their `requires`/`after` declarations.
+    /// In non-interactive mode the modules of a wave run concurrently, since no
+    /// prompt can interleave; otherwise they run one at a time. A module with a
+    /// `when` condition is checked just before its wave, so it sees the results
+    /// of every earlier wave.
     pub async fn execute_modules(&self, config: &Config) -> Result<()> {
-        let manager = ModuleManager::new()?;
+        let manager = Arc::new(ModuleManager::with_config(config)?);
+        let waves = module_waves(&manager, &self.name, &self.modules)?;
+        let parallel = is_non_interactive();
+        
+        println!("{}", "Execution plan:".white());
+        for (i, wave) in waves.iter().enumerate() {
+            println!("  {}. {}", i + 1, wave.join(", "));
+        }
         
-        let total = self.modules.len();
-        let mut completed = 0;
-        let mut failed = 0;
-        let mut skipped = 0;
+        let total: usize = waves.iter().map(Vec::len).sum();
+        let mut step = 0;
+        let mut outcomes: Vec<ModuleResult> = Vec::new();
+        let mut stopped_at = None;
         
-        for (i, module_name) in self.modules.iter().enumerate() {
-            println!("\n{}", format!("[{}/{}] Module: {}", i + 1, total, module_name).white());
+        'waves: for wave in &waves {
+            let mut runnable = Vec::new();
             
-            // Check if module is available
-            let module = manager.get_module(module_name)?;
-            if !module.is_available() {
-                log_warn(format!("Module {} is not available on this system", module_name));
-                skipped += 1;
-                continue;
+            for module_name in wave {
+                let module = manager.get_module(module_name)?;
+                
+                // A required module that did not complete blocks its dependents
+                let blocked_by = module.requires().iter().find(|required| {
+                    !outcomes
+                        .iter()
+                        .any(|r| &r.module == *required && r.status == ModuleStatus::Completed)
+                });
+                if let Some(required) = blocked_by {
+                    log_warn(format!(
+                        "Skipping {}: required module {} did not complete",
+                        module_name, required
+                    ));
+                    outcomes.push(ModuleResult::skipped(
+                        module_name,
+                        format!("required module {} did not complete", required),
+                    ));
+                    continue;
+                }
+                
+                if let Some(condition) = self.when.get(module_name) {
+                    let results = step_results(&self.modules, &outcomes);
+                    match condition.evaluate(&config.general.groups, &results) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            log_info(format!(
+                                "Skipping {}: condition not met ({})",
+                                module_name,
+                                condition.source()
+                            ));
+                            outcomes.push(ModuleResult::skipped_by_condition(
+                                module_name,
+                                condition.source(),
+                            ));
+                            continue;
+                        }
+                        Err(e) => {
+                            let result = Err(e);
+                            if !self.record_result(module_name, result, Duration::ZERO, Vec::new(), &mut outcomes)? {
+                                stopped_at = Some(module_name.clone());
+                                break 'waves;
+                            }
+                            continue;
+                        }
+                    }
+                }
+                
+                if !module.is_available() {
+                    log_warn(format!("Module {} is not available on this system", module_name));
+                    outcomes.push(ModuleResult::skipped(module_name, "not available on this system"));
+                    continue;
+                }
+                
+                runnable.push(module_name.clone());
             }
             
-            // Ask user if they want to execute this module
-            if prompt_yes_no(&format!("Execute {} module?", module_name), true)? {
-                match manager.load_module(module_name, vec![], config).await {
-                    Ok(_) => {
-                        completed += 1;
-                        log_info(format!("Module {} completed successfully", module_name));
-                    }
-                    Err(e) => {
-                        failed += 1;
-                        log_warn(format!("Module {} failed: {}", module_name, e));
-                        
-                        if !prompt_yes_no("Continue with remaining modules?", true)? {
-                            break;
-                        }
+            if parallel && runnable.len() > 1 {
+                step += runnable.len();
+                println!(
+                    "\n{}",
+                    format!("[{}/{}] Modules: {}", step, total, runnable.join(", ")).white()
+                );
+                
+                for (module_name, (result, resources), elapsed) in
+                    self.run_concurrently(&manager, config, runnable).await
+                {
+                    let keep_going =
+                        self.record_result(&module_name, result, elapsed, resources, &mut outcomes)?;
+                    if !keep_going {
+                        stopped_at = Some(module_name);
                     }
                 }
-            } else {
-                skipped += 1;
-                log_info(format!("Skipped module: {}", module_name));
+                if stopped_at.is_some() {
+                    break 'waves;
+                }
+                continue;
+            }
+            
+            for module_name in runnable {
+                step += 1;
+                println!("\n{}", format!("[{}/{}] Module: {}", step, total, module_name).white());
+                
+                // Ask user if they want to execute this module
+                let run = match self.confirm_modules {
+                    Some(false) => true,
+                    _ => prompt_yes_no(&format!("Execute {} module?", module_name), true)?,
+                };
+                
+                if !run {
+                    log_info(format!("Skipped module: {}", module_name));
+                    outcomes.push(ModuleResult::skipped(module_name, "declined by user"));
+                    continue;
+                }
+                
+                let args = self.args.get(&module_name).cloned().unwrap_or_default();
+                let started = Instant::now();
+                let (result, resources) = manager.run_module(&module_name, args, config).await;
+                let elapsed = started.elapsed();
+                if !self.record_result(&module_name, result, elapsed, resources, &mut outcomes)? {
+                    stopped_at = Some(module_name);
+                    break 'waves;
+                }
             }
         }
         
         // Summary
-        println!("\n{}", "=== Workflow Summary ===".cyan());
-        println!("{}", format!("✓ Completed: {}", completed).green());
-        if failed > 0 {
-            println!("{}", format!("✗ Failed: {}", failed).red());
+        let count = |status| outcomes.iter().filter(|r| r.status == status).count();
+        let summary = WorkflowSummary {
+            workflow: self.name.clone(),
+            completed: count(ModuleStatus::Completed),
+            failed: count(ModuleStatus::Failed),
+            skipped: count(ModuleStatus::Skipped),
+            skipped_by_condition: count(ModuleStatus::SkippedByCondition),
+            modules: outcomes,
+        };
+        report::record_modules(&summary.modules);
+        
+        if output::is_json() {
+            output::emit(&summary)?;
+        } else {
+            print_summary(&summary);
         }
-        if skipped > 0 {
-            println!("{}", format!("○ Skipped: {}", skipped).yellow());
+        
+        if let (Some(module), Some(false)) = (stopped_at, self.continue_on_error) {
+            return Err(FluxError::module(format!(
+                "Workflow '{}' stopped after module '{}' failed",
+                self.name, module
+            )));
         }
         
         Ok(())
     }
+    
+    /// Run the modules of one wave at the same time, returning results in wave order
+    ///
+    /// Each module's output is held back while it runs and printed as one block
+    /// once it finishes, in wave order.
+    async fn run_concurrently(
+        &self,
+        manager: &Arc<ModuleManager>,
+        config: &Config,
+        modules: Vec<String>,
+    ) -> Vec<(String, (Result<()>, Vec<ResourceChange>), Duration)> {
+        let config = Arc::new(config.clone());
+        let mut handles = Vec::new();
+        
+        for module_name in modules {
+            let manager = Arc::clone(manager);
+            let config = Arc::clone(&config);
+            let args = self.args.get(&module_name).cloned().unwrap_or_default();
+            let name = module_name.clone();
+            
+            let started = Instant::now();
+            let handle = tokio::spawn(async move {
+                logging::capture(manager.run_module(&name, args, &config)).await
+            });
+            handles.push((module_name, handle, started));
+        }
+        
+        let mut results = Vec::new();
+        for (module_name, handle, started) in handles {
+            let result = match handle.await {
+                Ok((result, captured)) => {
+                    // Print the module's output in one block so modules don't interleave
+                    println!("{}", format!("--- {} ---", module_name).cyan());
+                    captured.print();
+                    result
+                }
+                Err(e) => {
+                    let error = FluxError::module(format!("Module {} panicked: {}", module_name, e));
+                    (Err(error), Vec::new())
+                }
+            };
+            results.push((module_name, result, started.elapsed()));
+        }
+        
+        results
+    }
+    
+    /// Record a module result and decide whether the workflow keeps going
+    fn record_result(
+        &self,
+        module_name: &str,
+        result: Result<()>,
+        elapsed: Duration,
+        resources: Vec<ResourceChange>,
+        outcomes: &mut Vec<ModuleResult>,
+    ) -> Result<bool> {
+        outcomes.push(ModuleResult::new(module_name, &result, elapsed).with_resources(resources));
+        
+        match result {
+            Ok(()) => {
+                log_info(format!("Module {} completed successfully", module_name));
+                Ok(true)
+            }
+            Err(e) => {
+                log_warn(format!("Module {} failed: {}", module_name, e));
+                
+                match self.continue_on_error {
+                    Some(policy) => Ok(policy),
+                    None => prompt_yes_no("Continue with remaining modules?", true),
+                }
+            }
+        }
+    }
+}
+
+/// Status of every workflow module so far, `pending` for those not yet run
+fn step_results(modules: &[String], outcomes: &[ModuleResult]) -> BTreeMap<String, String> {
+    let mut results: BTreeMap<String, String> = modules
+        .iter()
+        .map(|module| (module.clone(), "pending".to_string()))
+        .collect();
+    for outcome in outcomes {
+        results.insert(outcome.module.clone(), outcome.status.as_str().to_string());
+    }
+    results
+}
+
+fn print_summary(summary: &WorkflowSummary) {
+    println!("\n{}", "=== Workflow Summary ===".cyan());
+    for result in &summary.modules {
+        let line = match result.status {
+            ModuleStatus::Completed => format!(
+                "  ✓ {} ({})",
+                result.module,
+                changes::summarize(&result.resources)
+            )
+            .green(),
+            ModuleStatus::Failed => format!("  ✗ {}", result.module).red(),
+            ModuleStatus::Skipped => format!("  ○ {}", result.module).yellow(),
+            ModuleStatus::SkippedByCondition => format!(
+                "  – {} ({})",
+                result.module,
+                result.message.as_deref().unwrap_or("condition not met")
+            )
+            .bright_black(),
+        };
+        println!("{}", line);
+    }
+    println!("{}", format!("✓ Completed: {}", summary.completed).green());
+    if summary.failed > 0 {
+        println!("{}", format!("✗ Failed: {}", summary.failed).red());
+    }
+    if summary.skipped > 0 {
+        println!("{}", format!("○ Skipped: {}", summary.skipped).yellow());
+    }
+    if summary.skipped_by_condition > 0 {
+        println!(
+            "{}",
+            format!("– Skipped by condition: {}", summary.skipped_by_condition).bright_black()
+        );
+    }
+}
+
+#[async_trait]
+impl Workflow for BaseWorkflow {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn modules(&self) -> Vec<String> {
+        self.modules.clone()
+    }
+    
+    async fn execute(&self, config: &Config) -> Result<()> {
+        self.execute_modules(config).await
+    }
 }
\ No newline at end of file
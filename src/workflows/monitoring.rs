@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::error::Result;
-use crate::workflows::{BaseWorkflow, Workflow};
+use crate::workflows::{condition::Condition, BaseWorkflow, Workflow};
 use async_trait::async_trait;
 
 /// Monitoring stack workflow
@@ -29,7 +29,8 @@ impl Workflow for MonitoringWorkflow {
             self.name(),
             self.description(),
             vec!["netdata", "certs", "motd"],
-        );
+        )
+        .with_condition("netdata", Condition::parse("facts.memory_mb > 1024")?);
 
         base.execute_modules(config).await
     }
@@ -1,6 +1,6 @@
 // Integration tests for configuration
 
-use flux_framework::config::{Config, GeneralConfig};
+use flux_framework::config::{Config, GeneralConfig, RunMode};
 use std::fs;
 use tempfile::TempDir;
 
@@ -141,3 +141,233 @@ fn test_config_module_config() {
     assert!(retrieved.is_some());
     assert_eq!(retrieved.unwrap(), &module_config);
 }
+
+#[test]
+fn test_config_mode() {
+    let mut config = Config::default();
+    assert_eq!(config.general.mode, RunMode::Interactive);
+    assert_eq!(config.get("mode").unwrap(), "interactive");
+
+    config.set("mode", "dry-run").unwrap();
+    assert_eq!(config.general.mode, RunMode::DryRun);
+    assert!(config.set("mode", "sometimes").is_err());
+
+    // Configs written before `mode` existed still load
+    let general: GeneralConfig = toml::from_str(
+        r#"
+default_ssh_port = 22
+default_admin_user = "fluxadmin"
+default_admin_groups = ["sudo"]
+log_level = "info"
+colored_output = true
+default_dns = []
+"#,
+    )
+    .unwrap();
+    assert_eq!(general.mode, RunMode::Interactive);
+
+    let general: GeneralConfig = toml::from_str(
+        r#"
+mode = "dry-run"
+default_ssh_port = 22
+default_admin_user = "fluxadmin"
+default_admin_groups = ["sudo"]
+log_level = "info"
+colored_output = true
+default_dns = []
+"#,
+    )
+    .unwrap();
+    assert_eq!(general.mode, RunMode::DryRun);
+}
+
+#[test]
+fn test_sample_config_module_specs() {
+    use flux_framework::modules::{
+        firewall::FirewallSpec, parse_spec, ssh::SshSpec, sysctl::SysctlSpec, user::UserSpec,
+    };
+
+    let config = Config::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/config/flux_default.toml"))
+        .unwrap();
+
+    let ssh: SshSpec = parse_spec("ssh", config.get_module_config("ssh").unwrap()).unwrap();
+    assert_eq!(ssh.port, 22);
+    assert!(ssh.disable_password_auth);
+    assert_eq!(ssh.denied_users, vec!["root"]);
+
+    let firewall: FirewallSpec =
+        parse_spec("firewall", config.get_module_config("firewall").unwrap()).unwrap();
+    let ports: Vec<u16> = firewall
+        .desired_ports()
+        .unwrap()
+        .into_iter()
+        .map(|(port, _, _)| port)
+        .collect();
+    assert_eq!(ports, vec![22, 80, 443]);
+
+    let sysctl: SysctlSpec = parse_spec("sysctl", config.get_module_config("sysctl").unwrap()).unwrap();
+    assert!(sysctl.hardening);
+
+    let user: UserSpec = parse_spec("user", config.get_module_config("user").unwrap()).unwrap();
+    assert_eq!(user.admin_username, "fluxadmin");
+    assert!(user.additional_users.is_empty());
+}
+
+#[test]
+fn test_invalid_module_spec() {
+    use flux_framework::modules::{parse_spec, ssh::SshSpec};
+
+    let value: toml::Value = toml::from_str("port = \"not-a-port\"").unwrap();
+    let result: flux_framework::Result<SshSpec> = parse_spec("ssh", &value);
+    assert!(result.unwrap_err().to_string().contains("[modules.ssh]"));
+}
+
+#[test]
+fn test_configured_workflows() {
+    use flux_framework::workflows::WorkflowManager;
+
+    let config = Config::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/config/flux_default.toml"))
+        .unwrap();
+    let baseline = &config.workflows["baseline"];
+    assert_eq!(baseline.modules, vec!["update", "ssh", "firewall", "sysctl"]);
+    assert_eq!(baseline.args["ssh"], vec!["--harden"]);
+    assert_eq!(baseline.continue_on_error, Some(false));
+
+    let manager = WorkflowManager::with_config(&config).unwrap();
+    let names: Vec<&str> = manager.list_workflows().into_iter().map(|(name, _)| name).collect();
+    assert!(names.contains(&"baseline"));
+    assert!(names.contains(&"essential"));
+    assert_eq!(manager.get_workflow("baseline").unwrap().modules().len(), 4);
+}
+
+#[test]
+fn test_invalid_workflow_rejected() {
+    use flux_framework::workflows::WorkflowManager;
+
+    let unknown_module: Config = toml::from_str(
+        r#"
+        [workflows.broken]
+        modules = ["ssh", "sshd"]
+        "#,
+    )
+    .unwrap();
+    let err = WorkflowManager::with_config(&unknown_module).err().unwrap();
+    assert!(err.to_string().contains("unknown module 'sshd'"));
+
+    let stray_args: Config = toml::from_str(
+        r#"
+        [workflows.broken]
+        modules = ["ssh"]
+        args.firewall = ["--enable"]
+        "#,
+    )
+    .unwrap();
+    assert!(WorkflowManager::with_config(&stray_args).is_err());
+
+    let stray_condition: Config = toml::from_str(
+        r#"
+        [workflows.broken]
+        modules = ["ssh"]
+        when.netdata = "facts.memory_mb > 1024"
+        "#,
+    )
+    .unwrap();
+    assert!(WorkflowManager::with_config(&stray_condition).is_err());
+
+    let bad_condition: Config = toml::from_str(
+        r#"
+        [workflows.broken]
+        modules = ["ssh"]
+        when.ssh = "'web' in"
+        "#,
+    )
+    .unwrap();
+    let err = WorkflowManager::with_config(&bad_condition).err().unwrap();
+    assert!(err.to_string().contains("module 'ssh'"));
+}
+
+#[test]
+fn test_module_keys() {
+    use flux_framework::modules::ssh::SshSpec;
+
+    let mut config = Config::default();
+    config.set("modules.ssh.port", "2222").unwrap();
+    config.set("modules.ssh.allowed_users", r#"["alice", "bob"]"#).unwrap();
+    config.set("modules.ssh.banner_file", "/etc/issue.net").unwrap();
+
+    assert_eq!(config.get("modules.ssh.port").unwrap(), "2222");
+    assert_eq!(config.get("modules.ssh.banner_file").unwrap(), "/etc/issue.net");
+    assert!(config.get("modules.ssh.missing").is_none());
+    assert!(config.custom.is_empty());
+
+    let spec: SshSpec = config.module_settings("ssh").unwrap();
+    assert_eq!(spec.port, 2222);
+    assert_eq!(spec.allowed_users, vec!["alice", "bob"]);
+    assert!(spec.disable_root_login);
+
+    let missing: SshSpec = config.module_settings("nothing").unwrap();
+    assert_eq!(missing, SshSpec::default());
+
+    config.set("modules.ssh.prot", "22").unwrap();
+    assert!(config.module_settings::<SshSpec>("ssh").is_err());
+}
+
+#[test]
+fn test_unknown_general_key_rejected() {
+    let result: Result<Config, _> = toml::from_str("[general]\ndefault_ssh_prot = 22\n");
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_validate_config_reports_lines() {
+    use flux_framework::modules::ModuleManager;
+
+    let manager = ModuleManager::new().unwrap();
+    let default = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/config/flux_default.toml"
+    ))
+    .unwrap();
+    assert_eq!(manager.validate_config(&default), vec![]);
+
+    let contents = r#"[general]
+mode = "auto"
+
+[modules.ssh]
+port = "2222"
+
+[modules.firewall]
+backend = "ufw"
+
+[[modules.firewall.rules]]
+port = 8080
+proto = "tcp"
+
+[modules.nope]
+enabled = true
+"#;
+    let issues = manager.validate_config(contents);
+    let found: Vec<(usize, usize)> = issues.iter().map(|i| (i.line, i.column)).collect();
+    assert_eq!(found, vec![(5, 8), (12, 1), (14, 1)]);
+    assert!(issues[0].message.starts_with("[modules.ssh] invalid type"));
+    assert!(issues[1].message.contains("unknown field `proto`"));
+    assert_eq!(issues[2].message, "[modules.nope] unknown module 'nope'");
+
+    let syntax = manager.validate_config("[general]\nmode = \n");
+    assert_eq!(syntax.len(), 1);
+    assert_eq!(syntax[0].line, 2);
+}
+
+#[test]
+fn test_config_schema_covers_modules() {
+    use flux_framework::modules::ModuleManager;
+
+    let schema = ModuleManager::new().unwrap().config_schema();
+    let modules = &schema["properties"]["modules"]["properties"];
+
+    assert_eq!(modules["ssh"]["properties"]["port"]["default"], 22);
+    assert_eq!(modules["firewall"]["properties"]["rules"]["items"]["required"][0], "port");
+    assert!(modules["sysctl"]["properties"]["custom"].is_object());
+    assert!(modules["update"].is_null());
+    assert_eq!(schema["properties"]["general"]["properties"]["mode"]["enum"][1], "auto");
+}
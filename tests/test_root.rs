@@ -0,0 +1,113 @@
+// Integration tests for running modules against a --root tree
+
+use flux_framework::config::Config;
+use flux_framework::helpers::paths;
+use flux_framework::helpers::runner::{set_runner, CommandOutput, RecordingRunner};
+use flux_framework::modules::ssh::SshModule;
+use flux_framework::modules::sysctl::SysctlModule;
+use flux_framework::modules::Module;
+use serial_test::serial;
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// A bare image tree with the given directories
+fn image_tree(dirs: &[&str]) -> TempDir {
+    let root = TempDir::new().unwrap();
+    for dir in dirs {
+        fs::create_dir_all(root.path().join(dir)).unwrap();
+    }
+    root
+}
+
+#[tokio::test]
+#[serial]
+async fn test_sysctl_apply_writes_into_root() {
+    let root = image_tree(&["etc/sysctl.d"]);
+    let recorder = Arc::new(RecordingRunner::new());
+    let previous = set_runner(recorder.clone());
+    paths::set_root(Some(root.path().to_path_buf()));
+
+    let spec: toml::Value = toml::from_str("hardening = true").unwrap();
+    let result = SysctlModule::new().apply(&spec, &Config::default()).await;
+
+    paths::set_root(None);
+    set_runner(previous);
+    result.unwrap();
+
+    let written = fs::read_to_string(root.path().join("etc/sysctl.d/99-flux-hardening.conf")).unwrap();
+    assert!(written.contains("net.ipv4.tcp_syncookies = 1"));
+    // Loading the settings would change the host kernel
+    assert!(recorder.calls().is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ssh_apply_writes_into_root() {
+    let root = image_tree(&["etc/ssh"]);
+    let sshd_config = root.path().join("etc/ssh/sshd_config");
+    fs::write(&sshd_config, "Port 22\n").unwrap();
+
+    let recorder = Arc::new(RecordingRunner::new());
+    let previous = set_runner(recorder.clone());
+    paths::set_root(Some(root.path().to_path_buf()));
+
+    let spec: toml::Value = toml::from_str("port = 2222\nfail2ban = false\nrestart = true").unwrap();
+    let result = SshModule::new().apply(&spec, &Config::default()).await;
+
+    paths::set_root(None);
+    set_runner(previous);
+    result.unwrap();
+
+    // Hardening goes into a drop-in that sshd_config now includes first
+    let drop_in = fs::read_to_string(root.path().join("etc/ssh/sshd_config.d/50-flux-hardening.conf")).unwrap();
+    assert!(drop_in.contains("Port 2222"));
+    assert_eq!(
+        fs::read_to_string(&sshd_config).unwrap(),
+        "Include /etc/ssh/sshd_config.d/*.conf\n\n#Port 22\n"
+    );
+    assert!(root.path().join("etc/ssh/banner.txt").exists());
+
+    // The original was backed up next to it, inside the tree
+    let backups = fs::read_dir(root.path().join("etc/ssh"))
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("sshd_config.backup_"))
+        .count();
+    assert!(backups >= 1);
+
+    // Resolving the crypto profile, the version check and validation inspect
+    // the tree; the restart is not run on the host
+    let lines = recorder.command_lines();
+    assert_eq!(
+        lines,
+        vec![
+            "ssh -V".to_string(),
+            "ssh -Q cipher".to_string(),
+            "ssh -V".to_string(),
+            format!("sshd -t -f {}", sshd_config.display()),
+        ]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ssh_set_rejected_by_sshd_is_restored() {
+    let root = image_tree(&["etc/ssh"]);
+    let sshd_config = root.path().join("etc/ssh/sshd_config");
+    fs::write(&sshd_config, "PermitRootLogin no\n").unwrap();
+
+    let recorder = Arc::new(RecordingRunner::new());
+    recorder.respond("sshd -t", CommandOutput::failed(255, "Bad yes/without-password/no argument: maybe"));
+    let previous = set_runner(recorder.clone());
+    paths::set_root(Some(root.path().to_path_buf()));
+
+    let args = ["set", "PermitRootLogin", "maybe"].map(String::from).to_vec();
+    let result = SshModule::new().execute(args, &Config::default()).await;
+
+    paths::set_root(None);
+    set_runner(previous);
+
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&sshd_config).unwrap(), "PermitRootLogin no\n");
+}